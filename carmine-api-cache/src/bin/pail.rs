@@ -7,8 +7,9 @@ use dotenvy::dotenv;
 async fn main() {
     dotenv().ok();
 
-    let pail_events =
-        transform_pail_events(&get_protocol_events(&Network::Mainnet, &Protocol::Pail));
+    let events = get_protocol_events(&Network::Mainnet, &Protocol::Pail)
+        .expect("Failed getting Pail events");
+    let pail_events = transform_pail_events(&events);
 
     println!("{:#?}", pail_events);
 }
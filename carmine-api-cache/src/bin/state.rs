@@ -1,14 +1,17 @@
 use carmine_api_cache::Cache;
 use carmine_api_core::network::Network;
+use carmine_api_db::PgStore;
 use dotenvy::dotenv;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    let cache = Cache::new(Network::Mainnet).await;
+    let cache = Cache::new(PgStore::new(Network::Mainnet))
+        .await
+        .expect("Failed creating cache");
 
-    let app_data = cache.get_app_data();
+    let app_data = cache.get_app_data().expect("Failed getting app data");
 
     // let eth_usdc_call_state = app_data.state.get("eth-usdc-call").unwrap().first();
     // let eth_usdc_put_state = app_data.state.get("eth-usdc-put").unwrap().first();
@@ -1,62 +1,148 @@
-use std::time::UNIX_EPOCH;
+use std::{collections::HashMap, fmt, time::UNIX_EPOCH};
 
 use carmine_api_core::{
-    types::{InsuranceData, InsuranceEventQueryable},
-    utils::{math_64_to_decimal, strike_from_hex},
+    insurance::{decode_insurance_calldata, matching_trade, InsuranceCalldataError},
+    types::{InsuranceData, InsuranceEventQueryable, TradeEvent},
 };
-use carmine_api_prices::HistoricalPrices;
+use carmine_api_prices::{graph::PriceError, BlockId, HistoricalPrices, USD};
 
+#[derive(Debug)]
+pub enum InsuranceError {
+    Calldata(InsuranceCalldataError),
+    Price(PriceError),
+}
+
+impl fmt::Display for InsuranceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InsuranceError::Calldata(e) => write!(f, "invalid calldata: {}", e),
+            InsuranceError::Price(e) => write!(f, "failed pricing: {}", e),
+        }
+    }
+}
+
+/// `trades` are TradeEvents by pool id, the event is verified when
+/// the user opened the insured option around the time of submission
 pub fn compose_insurance_event(
     event: &InsuranceEventQueryable,
+    trades: &HashMap<String, Vec<TradeEvent>>,
     prices: &HistoricalPrices,
-) -> InsuranceData {
-    let base_token_address = event
-        .calldata
-        .get(7)
-        .expect("failed getting base token for insurance event");
-    let premia = event
-        .calldata
-        .get(8)
-        .expect("failed getting premia for insurance event");
-    let strike = event
-        .calldata
-        .get(1)
-        .expect("failed getting strike for insurance event");
-    let size = event
-        .calldata
-        .get(5)
-        .expect("failed getting premia for insurance event");
-    let pool_id = match base_token_address.as_str() {
-        "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7" => "eth-usdc-call",
-        "0x03fe2b97c1fd336e750087d68b9b867997fd64a2661ff3ca5a7c771641e8e7ac" => "btc-usdc-call",
-        "0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d" => "strk-usdc-call",
-        "0x075afe6402ad5a5c20dd25e10ec3b3986acaa647b77e4ae24b0cbc9a54a27a87" => "ekubo-usdc-call",
-        _ => unreachable!("invalid base token"),
-    };
+) -> Result<InsuranceData, InsuranceError> {
+    let calldata: Vec<&str> = event.calldata.iter().map(String::as_str).collect();
+    let decoded = decode_insurance_calldata(&calldata).map_err(InsuranceError::Calldata)?;
+
     let timestamp = event
         .timestamp
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_secs() as i64;
-    let price = prices.get_price(&pool_id, carmine_api_prices::BlockId::Timestamp(timestamp));
+    // the insured asset is the base token, also for put pools denominated in the quote token
+    let price = prices
+        .get_cross_price(&decoded.base_symbol, USD, BlockId::Timestamp(timestamp))
+        .map_err(InsuranceError::Price)? as f32;
+
+    let trade = trades.get(decoded.pool_id).and_then(|pool_trades| {
+        matching_trade(&decoded, &event.user_address, timestamp, pool_trades)
+    });
 
-    InsuranceData {
+    Ok(InsuranceData {
         user_address: event.user_address.to_string(),
         base_token_price: price,
         timestamp,
-        base_token_address: base_token_address.to_string(),
-        premia: math_64_to_decimal(&premia),
-        strike: strike_from_hex(&strike),
-        size: size.to_string(),
-    }
+        base_token_address: calldata[7].to_string(),
+        premia: decoded.premia,
+        strike: decoded.strike,
+        size: decoded.size.to_string(),
+        pool_id: decoded.pool_id.to_string(),
+        maturity: decoded.maturity,
+        verified: trade.is_some(),
+        trade_timestamp: trade.map(|trade| trade.timestamp),
+    })
 }
 
 pub fn get_insurace_data(
     events: Vec<InsuranceEventQueryable>,
+    trades: &HashMap<String, Vec<TradeEvent>>,
     prices: &HistoricalPrices,
 ) -> Vec<InsuranceData> {
     events
         .iter()
-        .map(|e| compose_insurance_event(e, prices))
+        .filter_map(|e| match compose_insurance_event(e, trades, prices) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                println!("Failed to compose insurance event {}: {}", e.id, err);
+                None
+            }
+        })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use carmine_api_core::{
+        network::{Network, NEW_AMM_GENESIS_BLOCK_NUMBER},
+        types::{DbBlock, OraclePriceConcise},
+    };
+    use carmine_api_db::{Fixtures, MemoryStore};
+
+    use super::*;
+
+    const ETH: &str = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+    const USDC: &str = "0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8";
+    const BLOCK_NUMBER: i64 = NEW_AMM_GENESIS_BLOCK_NUMBER + 1;
+    const TIMESTAMP: i64 = 1737000000;
+
+    fn prices() -> HistoricalPrices {
+        let store = MemoryStore::new(
+            Network::Mainnet,
+            Fixtures {
+                blocks: vec![DbBlock {
+                    block_number: BLOCK_NUMBER,
+                    timestamp: TIMESTAMP,
+                }],
+                ..Default::default()
+            },
+        );
+        let oracle_prices = HashMap::from([(
+            "eth-usdc".to_string(),
+            vec![OraclePriceConcise {
+                price: 310000,
+                decimals: 2,
+                last_updated_timestamp: TIMESTAMP,
+                block_number: BLOCK_NUMBER,
+            }],
+        )]);
+        HistoricalPrices::new(&store, &oracle_prices).unwrap()
+    }
+
+    #[test]
+    fn put_pool_is_priced_in_base_token() {
+        let calldata = [
+            "0x1",
+            "0xc1c0000000000000000",
+            "0x0",
+            "0x6789f8c0",
+            "0x0",
+            "1000000000000000000",
+            USDC,
+            ETH,
+            "18446744073709551616",
+            "0x0",
+            "0x6789f8c0",
+        ];
+        let event = InsuranceEventQueryable {
+            id: 1,
+            user_address: "0x1".to_string(),
+            calldata: calldata.iter().map(|field| field.to_string()).collect(),
+            timestamp: UNIX_EPOCH + Duration::from_secs(TIMESTAMP as u64 + 60),
+        };
+
+        let data = compose_insurance_event(&event, &HashMap::new(), &prices()).unwrap();
+
+        assert_eq!(data.pool_id, "eth-usdc-put");
+        assert_eq!(data.base_token_price, 3100.0);
+        assert!(!data.verified);
+    }
+}
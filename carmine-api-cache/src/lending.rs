@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use carmine_api_core::{
+    lending::{market_stats, protocol_markets, LendingProtocol},
+    types::LendingMarketStats,
+};
+use carmine_api_db::{DbError, Store};
+
+/// Stats of every lending market with decoded events by protocol and market
+pub fn generate_lending_stats(
+    store: &dyn Store,
+) -> Result<HashMap<String, HashMap<String, LendingMarketStats>>, DbError> {
+    let mut map = HashMap::new();
+
+    for protocol in LendingProtocol::ALL {
+        let mut markets = HashMap::new();
+
+        for market in protocol_markets(protocol) {
+            let events = store.get_lending_events(protocol.id(), market)?;
+            if let Some(stats) = market_stats(protocol.id(), market, &events) {
+                markets.insert(market.to_string(), stats);
+            }
+        }
+
+        map.insert(protocol.id().to_string(), markets);
+    }
+
+    Ok(map)
+}
@@ -1,26 +1,33 @@
 use carmine_api_core::{
-    network::{Network, Protocol, LEGACY_AMM_CONTRACT_ADDRESS},
+    governance::tally_proposals,
+    network::{Network, Protocol, LEGACY_AMM_CONTRACT_ADDRESS, NEW_AMM_GENESIS_BLOCK_NUMBER},
+    pail::pail_stats,
     pool::{get_all_pools, Pool},
+    referral::{referral_points_factor, referrer_stats, RewardSchedule},
+    referral_abuse::{detect_referral_abuse, flagged_wallets},
     telegram_bot::TelegramBot,
     types::{
-        AppData, DefispringInfo, IOption, Messenger, OraclePrice, OraclePriceConcise,
+        AppData, DefispringInfo, IOption, Messenger, OracleHealth, OraclePrice, OraclePriceConcise,
         PoolStateWithTimestamp, ReferralEventDigest, StarkScanEventSettled, TokenPair, TradeEvent,
         TradeHistory, UserPointsWithPosition, Vote, APY,
     },
-    utils::{normalize_address, strike_from_hex},
+    utils::{canonical_address, normalize_address, strike_from_hex},
 };
-use carmine_api_db::{
-    get_all_user_points, get_braavos_users_proscore_80_with_timestamp, get_events_by_address,
-    get_insurance_events, get_legacy_options, get_options, get_oracle_prices_since_new_amm,
-    get_pool_state, get_protocol_events, get_protocol_events_from_block, get_referral_events,
-    get_user_points_lastest_timestamp, get_votes,
+use carmine_api_db::{DbError, Store};
+use carmine_api_prices::{
+    history::candle_series,
+    monitor::get_oracle_health,
+    sources::{now, CoinGeckoSource, PriceSource},
+    volatility::AmmVolatility,
+    HistoricalPrices,
 };
-use carmine_api_prices::HistoricalPrices;
 use carmine_api_starknet::carmine::Carmine;
 use defispring::get_defispring_stats;
 use insurance_events::get_insurace_data;
+use lending::generate_lending_stats;
 use live_options_tracker::LiveOptionsUpdateTracker;
-use pail_events::transform_pail_events;
+use pail_events::{generate_hedges, transform_pail_events};
+use state_history::generate_rollups;
 use std::{
     collections::HashMap,
     sync::Arc,
@@ -28,13 +35,17 @@ use std::{
     vec,
 };
 use trade_data::get_trades;
+use volatility_premium::{generate_volatility_premium, update_amm_volatility};
 
 mod apy;
 pub mod defispring;
 pub mod insurance_events;
+pub mod lending;
 pub mod live_options_tracker;
 pub mod pail_events;
+pub mod state_history;
 pub mod trade_data;
+pub mod volatility_premium;
 
 // Only store Events we know and not ExpireOptionTokenForPool and Upgrade
 const ALLOWED_METHODS: &'static [&'static str; 12] = &[
@@ -54,7 +65,11 @@ const ALLOWED_METHODS: &'static [&'static str; 12] = &[
     "synthetic::WithdrawLiquidity",
 ];
 
-pub struct Cache {
+// users in the user points leaderboard
+const TOP_USER_POINTS: usize = 20;
+
+pub struct Cache<S: Store> {
+    store: S,
     network: Network,
     carmine: Carmine,
     events: Vec<StarkScanEventSettled>,
@@ -65,53 +80,67 @@ pub struct Cache {
     legacy_trade_history: Vec<TradeHistory>,
     pools: Vec<Pool>,
     referrals: Vec<ReferralEventDigest>,
+    referral_rewards: RewardSchedule,
     user_points_timestamp: SystemTime,
     user_points: HashMap<String, UserPointsWithPosition>,
     top_user_points: Vec<UserPointsWithPosition>,
     defispring: DefispringInfo,
     oracle_prices: HashMap<String, Vec<OraclePriceConcise>>,
     historical_prices: HistoricalPrices,
+    amm_volatility: HashMap<String, AmmVolatility>,
+    oracle_health: Vec<OracleHealth>,
     live_options_tracking: LiveOptionsUpdateTracker<Arc<TelegramBot>>,
     telegram_messenger: Arc<TelegramBot>,
 }
 
-impl Cache {
-    pub async fn new(network: Network) -> Self {
-        let network = network;
+impl<S: Store> Cache<S> {
+    /// Cache of the network of the store, data fetched from the network
+    /// is left empty when the fetch fails and filled by later updates
+    pub async fn new(store: S) -> Result<Self, DbError> {
+        let mut cache = Cache::from_store(store)?;
+
+        cache.update_all_non_expired().await;
+        if matches!(cache.network, Network::Mainnet) {
+            cache.update_defispring().await;
+        }
+        cache.update_oracle_health().await;
+
+        Ok(cache)
+    }
+
+    /// Cache of the data in the store, nothing is fetched from the network
+    pub fn from_store(store: S) -> Result<Self, DbError> {
+        let network = store.network();
         let carmine = Carmine::new(network);
-        let events = get_protocol_events(&network, &Protocol::CarmineOptions);
+        let events = store.get_protocol_events(&Protocol::CarmineOptions)?;
         let legacy_events = match network {
-            Network::Mainnet => {
-                get_events_by_address(&Network::Mainnet, LEGACY_AMM_CONTRACT_ADDRESS)
-            }
+            Network::Mainnet => store.get_events_by_address(LEGACY_AMM_CONTRACT_ADDRESS)?,
             Network::Testnet => vec![],
         };
 
-        let options_vec = get_options(&network);
-        let options = Cache::options_vec_to_hashmap(options_vec);
+        let options_vec = store.get_options()?;
+        let options = Self::options_vec_to_hashmap(options_vec);
         let all_non_expired = vec![];
         let pools = get_all_pools(&network);
         let referrals = match network {
-            Network::Mainnet => get_referral_events(),
+            Network::Mainnet => store.get_referral_events()?,
             // no referral events on Testnet
             Network::Testnet => vec![],
         };
-        let defispring = match network {
-            Network::Mainnet => get_defispring_stats()
-                .await
-                .expect("Failed getting defispring!"),
-            // do not bother generating for testnet
-            Network::Testnet => DefispringInfo {
-                allocation: 0.0,
-                apy: 0.0,
-                tvl: 0.0,
-            },
+        // fetched by `new` on Mainnet, not generated for testnet
+        let defispring = DefispringInfo {
+            allocation: 0.0,
+            apy: 0.0,
+            tvl: 0.0,
         };
-        let oracle_prices = generate_oracle_prices_hash_map();
-        let historical_prices = HistoricalPrices::new(&oracle_prices);
+        let referral_rewards =
+            RewardSchedule::load().expect("Failed loading referral reward schedule");
+        let oracle_prices = generate_oracle_prices_hash_map(&store)?;
+        let historical_prices = HistoricalPrices::new(&store, &oracle_prices)?;
         let telegram_messenger = Arc::new(TelegramBot::new());
 
         let mut cache = Cache {
+            store,
             network,
             carmine,
             events,
@@ -122,25 +151,28 @@ impl Cache {
             legacy_trade_history: vec![],
             pools,
             referrals,
+            referral_rewards,
             user_points_timestamp: SystemTime::UNIX_EPOCH,
             user_points: HashMap::new(), // initialize empty
             top_user_points: vec![],     // initialize empty
             defispring,
             oracle_prices,
             historical_prices,
+            amm_volatility: HashMap::new(),
+            oracle_health: vec![],
             live_options_tracking: LiveOptionsUpdateTracker::new(telegram_messenger.clone()),
             telegram_messenger,
         };
 
         cache.trade_history = Cache::generate_trade_history(&mut cache);
-        cache.legacy_trade_history = Cache::generate_legacy_trade_history(&mut cache);
-        cache.update_all_non_expired().await;
-        cache.update_user_points();
+        cache.legacy_trade_history = Cache::generate_legacy_trade_history(&mut cache)?;
+        cache.update_user_points()?;
+        cache.update_amm_volatility()?;
 
-        cache
+        Ok(cache)
     }
 
-    pub fn get_app_data(&self) -> AppData {
+    pub fn get_app_data(&self) -> Result<AppData, DbError> {
         let t0 = Instant::now();
         let all_non_expired = self.get_all_non_expired();
         println!("all non expired: {:?}", t0.elapsed());
@@ -151,27 +183,76 @@ impl Cache {
         let option_volatility = vec![];
         // let option_volatility = get_options_volatility(&self.network);
         println!("option volatility: {:?}", t0.elapsed());
-        let state = self.generate_state_hashmap();
+        let state = self.generate_state_hashmap()?;
         println!("state: {:?}", t0.elapsed());
-        let apy = self.generate_apy_hashmap();
+        let state_rollups = generate_rollups(&state);
+        println!("state rollups: {:?}", t0.elapsed());
+        let apy = self.generate_apy_hashmap()?;
         println!("apy: {:?}", t0.elapsed());
         let oracle_prices = self.oracle_prices.clone();
         println!("oracle prices: {:?}", t0.elapsed());
+        let price_series = self.historical_prices.all_series().clone();
+        println!("price series: {:?}", t0.elapsed());
+        let price_candles = price_series
+            .iter()
+            .map(|(pair, series)| (pair.to_string(), candle_series(series)))
+            .collect();
+        println!("price candles: {:?}", t0.elapsed());
+        let oracle_health = self.oracle_health.clone();
+        println!("oracle health: {:?}", t0.elapsed());
+        let volatility_premium = match &self.network {
+            Network::Mainnet => {
+                generate_volatility_premium(&self.amm_volatility, &self.historical_prices)
+            }
+            Network::Testnet => HashMap::new(),
+        };
+        println!("volatility premium: {:?}", t0.elapsed());
         let referrals = self.referrals.clone();
         println!("referrals: {:?}", t0.elapsed());
-        let user_points = self.user_points.clone();
-        println!("user points: {:?}", t0.elapsed());
-        let top_user_points = self.top_user_points.clone();
-        println!("top user points: {:?}", t0.elapsed());
         let trades = self.generate_trades_hashmap();
         println!("trades: {:?}", t0.elapsed());
-        let votes = get_votes();
+        let votes = self.store.get_votes()?;
         println!("votes: {:?}", t0.elapsed());
+        let proposals = tally_proposals(
+            &self.store.get_proposals()?,
+            &votes,
+            &self.store.get_voting_power()?,
+            now(),
+        );
+        println!("proposals: {:?}", t0.elapsed());
         let mut votes_map: HashMap<String, Vec<Vote>> = HashMap::new();
         println!("votes map: {:?}", t0.elapsed());
         let trades_with_prices = get_trades(&trades, &self.historical_prices);
         println!("trades with prices: {:?}", t0.elapsed());
-        let insurance_events = get_insurace_data(get_insurance_events(), &self.historical_prices);
+        let referral_abuse = detect_referral_abuse(&referrals, &trades_with_prices.user_trades);
+        println!("referral abuse: {:?}", t0.elapsed());
+        let referrers = referrer_stats(
+            &referrals,
+            &trades_with_prices.user_trades,
+            &self.referral_rewards,
+            &flagged_wallets(&referral_abuse),
+        );
+        println!("referrers: {:?}", t0.elapsed());
+        let flagged_referrers: HashMap<String, f64> = referrers
+            .iter()
+            .filter(|referrer| referrer.summary.flagged_wallets > 0)
+            .map(|referrer| {
+                (
+                    referrer.summary.address.to_string(),
+                    referral_points_factor(referrer),
+                )
+            })
+            .collect();
+        let (top_user_points, user_points) = match flagged_referrers.is_empty() {
+            true => (self.top_user_points.clone(), self.user_points.clone()),
+            false => exclude_flagged_referral_points(&self.user_points, &flagged_referrers),
+        };
+        println!("user points: {:?}", t0.elapsed());
+        let insurance_events = get_insurace_data(
+            self.store.get_insurance_events()?,
+            &trades,
+            &self.historical_prices,
+        );
         println!("insurance events: {:?}", t0.elapsed());
 
         for vote in votes.iter() {
@@ -183,25 +264,37 @@ impl Cache {
 
         let defispring = self.defispring;
         println!("defispring: {:?}", t0.elapsed());
-        let braavos_proscore = get_braavos_users_proscore_80_with_timestamp();
+        let braavos_proscore = self.store.get_braavos_users_proscore_80_with_timestamp()?;
         println!("braavos proscore: {:?}", t0.elapsed());
-        let pail_events = match &self.network {
-            Network::Mainnet => {
-                transform_pail_events(&get_protocol_events(&Network::Mainnet, &Protocol::Pail))
-            }
-            Network::Testnet => HashMap::new(),
+        let pail_raw_events = match &self.network {
+            Network::Mainnet => self.store.get_protocol_events(&Protocol::Pail)?,
+            Network::Testnet => vec![],
         };
+        let pail_events = transform_pail_events(&pail_raw_events);
         println!("pail events: {:?}", t0.elapsed());
+        let hedges = generate_hedges(&pail_raw_events, &self.historical_prices);
+        let pail_stats = pail_stats(&hedges);
+        println!("hedges: {:?}", t0.elapsed());
+        let lending = match &self.network {
+            Network::Mainnet => generate_lending_stats(&self.store)?,
+            Network::Testnet => HashMap::new(),
+        };
+        println!("lending: {:?}", t0.elapsed());
 
-        AppData {
+        Ok(AppData {
             all_non_expired,
             trade_history,
             legacy_trade_history,
             trades,
             option_volatility,
             state,
+            state_rollups,
             apy,
             oracle_prices,
+            price_series,
+            price_candles,
+            oracle_health,
+            volatility_premium,
             referrals,
             user_points,
             top_user_points,
@@ -212,7 +305,13 @@ impl Cache {
             trades_with_prices,
             insurance_events,
             pail_events,
-        }
+            hedges,
+            pail_stats,
+            lending,
+            proposals,
+            referrers,
+            referral_abuse,
+        })
     }
 
     pub fn get_all_non_expired(&self) -> Vec<String> {
@@ -234,24 +333,26 @@ impl Cache {
         })
     }
 
-    fn generate_state_hashmap(&self) -> HashMap<String, Vec<PoolStateWithTimestamp>> {
-        self.pools.iter().fold(HashMap::new(), |mut acc, pool| {
-            acc.insert(
-                pool.id.to_string(),
-                get_pool_state(&pool.address, &self.network),
-            );
-            acc
-        })
+    fn generate_state_hashmap(
+        &self,
+    ) -> Result<HashMap<String, Vec<PoolStateWithTimestamp>>, DbError> {
+        self.pools
+            .iter()
+            .map(|pool| {
+                let state = self.store.get_pool_state(pool.address)?;
+                Ok((pool.id.to_string(), state))
+            })
+            .collect()
     }
 
-    fn generate_apy_hashmap(&self) -> HashMap<String, APY> {
-        self.pools.iter().fold(HashMap::new(), |mut acc, pool| {
-            acc.insert(
-                pool.id.to_string(),
-                self.calculate_apy_for_pool(&pool.address),
-            );
-            acc
-        })
+    fn generate_apy_hashmap(&self) -> Result<HashMap<String, APY>, DbError> {
+        self.pools
+            .iter()
+            .map(|pool| {
+                let apy = self.calculate_apy_for_pool(&pool.address)?;
+                Ok((pool.id.to_string(), apy))
+            })
+            .collect()
     }
 
     fn generate_trades_hashmap(&self) -> HashMap<String, Vec<TradeEvent>> {
@@ -291,30 +392,32 @@ impl Cache {
         map
     }
 
-    fn update_user_points<'a>(&mut self) {
+    fn update_user_points<'a>(&mut self) -> Result<(), DbError> {
         if !matches!(self.network, Network::Mainnet) {
             // only do Mainnet
-            return;
+            return Ok(());
         }
 
-        let timestamp_result = get_user_points_lastest_timestamp();
-
-        let timestamp = match timestamp_result {
+        let timestamp = match self.store.get_user_points_lastest_timestamp()? {
             Some(v) => v,
-            _ => panic!("Failed getting UserPoints timestamp"),
+            None => {
+                return Err(DbError::InvalidData(
+                    "Failed getting UserPoints timestamp".to_string(),
+                ))
+            }
         };
 
         if self.user_points_timestamp == timestamp {
             // no new points, keep the previous
-            return;
+            return Ok(());
         }
 
+        let user_points = self.store.get_all_user_points(timestamp)?;
+
         // update timestamp for the next update cycle
         self.user_points_timestamp = timestamp;
 
-        let user_points = get_all_user_points(timestamp);
-
-        let mut user_points_with_total: Vec<UserPointsWithPosition> = user_points
+        let user_points_with_total: Vec<UserPointsWithPosition> = user_points
             .into_iter()
             .map(|u| UserPointsWithPosition {
                 address: u.address,
@@ -330,36 +433,12 @@ impl Cache {
             })
             .collect();
 
-        user_points_with_total.sort_by_key(|u| -u.total_points); // negative for descending order
-
-        let mut last_points = user_points_with_total
-            .get(0)
-            .expect("Zero user points")
-            .total_points;
-        let mut current_position = 1;
-
-        let mut user_points_with_position = vec![];
-
-        for mut user in user_points_with_total.into_iter() {
-            if user.total_points < last_points {
-                last_points = user.total_points;
-                current_position += 1;
-            }
-            user.position = current_position;
-            user_points_with_position.push(user);
-        }
-
-        let top: Vec<UserPointsWithPosition> = user_points_with_position[..20].to_vec();
-
-        let mut map = HashMap::new();
-
-        for user in user_points_with_position {
-            let address = user.address.to_string();
-            map.insert(address, user);
-        }
+        let (top, map) = rank_user_points(user_points_with_total);
 
         self.user_points = map;
         self.top_user_points = top;
+
+        Ok(())
     }
 
     fn generate_trade_history(&self) -> Vec<TradeHistory> {
@@ -406,7 +485,7 @@ impl Cache {
         trade_history
     }
 
-    fn generate_legacy_trade_history(&self) -> Vec<TradeHistory> {
+    fn generate_legacy_trade_history(&self) -> Result<Vec<TradeHistory>, DbError> {
         let legacy_pool_hash_map = HashMap::from([
             (
                 "0x7aba50fdb4e024c1ba63e2c60565d0fd32566ff4b18aa5818fc80c30e749024",
@@ -418,7 +497,7 @@ impl Cache {
             ),
         ]);
 
-        let legacy_options = get_legacy_options(&Network::Mainnet);
+        let legacy_options = self.store.get_legacy_options()?;
 
         let legacy_options_map =
             legacy_options
@@ -467,36 +546,36 @@ impl Cache {
             .collect::<Vec<TradeHistory>>();
 
         trade_history.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-        trade_history
+        Ok(trade_history)
     }
 
-    fn calculate_apy_for_pool(&self, pool_address: &str) -> APY {
-        let state = get_pool_state(pool_address, &self.network);
-        apy::calculate_apy(&state)
+    fn calculate_apy_for_pool(&self, pool_address: &str) -> Result<APY, DbError> {
+        let state = self.store.get_pool_state(pool_address)?;
+        Ok(apy::calculate_apy(&state))
     }
 
-    pub fn update_options(&mut self) {
-        let options_vec = get_options(&self.network);
-        let options = Cache::options_vec_to_hashmap(options_vec);
+    pub fn update_options(&mut self) -> Result<(), DbError> {
+        let options_vec = self.store.get_options()?;
+        let options = Self::options_vec_to_hashmap(options_vec);
         self.options = options;
+        Ok(())
     }
 
-    pub fn update_events(&mut self) {
+    pub fn update_events(&mut self) -> Result<(), DbError> {
         let max_block_number_option = self.events.iter().max_by_key(|event| event.block_number);
         let max_block_number = match max_block_number_option {
             Some(event) => event.block_number,
             // did not find max block number, get all events
             None => {
-                self.events = get_protocol_events(&self.network, &Protocol::CarmineOptions);
-                return;
+                self.events = self.store.get_protocol_events(&Protocol::CarmineOptions)?;
+                return Ok(());
             }
         };
-        let new_events = get_protocol_events_from_block(
-            &self.network,
-            &Protocol::CarmineOptions,
-            max_block_number,
-        );
-        self.events.extend(new_events)
+        let new_events = self
+            .store
+            .get_protocol_events_from_block(&Protocol::CarmineOptions, max_block_number)?;
+        self.events.extend(new_events);
+        Ok(())
     }
 
     pub async fn update_all_non_expired(&mut self) {
@@ -537,32 +616,70 @@ impl Cache {
         self.trade_history = Cache::generate_trade_history(self);
     }
 
-    pub fn update_referral_events(&mut self) {
+    pub fn update_referral_events(&mut self) -> Result<(), DbError> {
         self.referrals = match self.network {
-            Network::Mainnet => get_referral_events(),
+            Network::Mainnet => self.store.get_referral_events()?,
             // no referral events on Testnet
             Network::Testnet => vec![],
         };
+        Ok(())
     }
 
-    fn update_prices(&mut self) {
+    fn update_prices(&mut self) -> Result<(), DbError> {
         if matches!(self.network, Network::Testnet) {
-            return;
+            return Ok(());
+        }
+        // only load prices stored since the last update
+        let new_prices = match self.historical_prices.last_price_block_number() {
+            Some(last) => group_oracle_prices(&self.store.get_oracle_prices_from_block(last + 1)?),
+            None => generate_oracle_prices_hash_map(&self.store)?,
+        };
+
+        self.historical_prices.update(&self.store, &new_prices)?;
+
+        for (pair_id, prices) in new_prices {
+            self.oracle_prices
+                .entry(pair_id)
+                .or_insert_with(Vec::new)
+                .extend(prices);
         }
-        let oracle_prices = generate_oracle_prices_hash_map();
-        let historical_prices = HistoricalPrices::new(&oracle_prices);
 
-        self.oracle_prices = oracle_prices;
-        self.historical_prices = historical_prices;
+        Ok(())
+    }
+
+    fn update_amm_volatility(&mut self) -> Result<(), DbError> {
+        if matches!(self.network, Network::Testnet) {
+            return Ok(());
+        }
+        update_amm_volatility(&self.store, &mut self.amm_volatility)
     }
 
+    pub async fn update_oracle_health(&mut self) {
+        if matches!(self.network, Network::Testnet) {
+            return;
+        }
+        let reference = match CoinGeckoSource.fetch_prices().await {
+            Ok(prices) => prices,
+            Err(e) => {
+                println!("Failed getting oracle reference prices: {}", e);
+                HashMap::new()
+            }
+        };
+        self.oracle_health = get_oracle_health(&self.store, &reference);
+    }
+
+    /// Failed steps keep the previous data
     pub async fn update(&mut self) {
         let t0 = Instant::now();
-        self.update_options();
+        if let Err(e) = self.update_options() {
+            println!("Failed updating options: {}", e);
+        }
         println!("Update options in: {}", t0.elapsed().as_secs());
 
         let t1 = Instant::now();
-        self.update_events();
+        if let Err(e) = self.update_events() {
+            println!("Failed updating events: {}", e);
+        }
         println!("Update events in: {}", t1.elapsed().as_secs());
 
         let t2 = Instant::now();
@@ -578,22 +695,36 @@ impl Cache {
         println!("Update trade history in: {}", t4.elapsed().as_secs());
 
         let t5 = Instant::now();
-        self.update_user_points();
+        if let Err(e) = self.update_user_points() {
+            println!("Failed updating user points: {}", e);
+        }
         println!("Update user points in: {}", t5.elapsed().as_secs());
 
         let t6 = Instant::now();
-        self.update_prices();
+        if let Err(e) = self.update_prices() {
+            println!("Failed updating prices: {}", e);
+        }
         println!("Update prices in: {}", t6.elapsed().as_secs());
+
+        let t7 = Instant::now();
+        self.update_oracle_health().await;
+        println!("Update oracle health in: {}", t7.elapsed().as_secs());
+
+        let t8 = Instant::now();
+        if let Err(e) = self.update_amm_volatility() {
+            println!("Failed updating AMM volatility: {}", e);
+        }
+        println!("Update AMM volatility in: {}", t8.elapsed().as_secs());
     }
 }
 
 fn set_oracle_prices_pair(
     prices_map: &mut HashMap<String, Vec<OraclePriceConcise>>,
     pair_id: String,
-    prices: Vec<OraclePrice>,
+    prices: &[OraclePrice],
 ) {
     let data = prices
-        .into_iter()
+        .iter()
         .filter(|oracle_price| &oracle_price.token_pair == &pair_id)
         .map(|full_price| OraclePriceConcise {
             price: full_price.price,
@@ -606,15 +737,147 @@ fn set_oracle_prices_pair(
     prices_map.insert(pair_id, data);
 }
 
-fn generate_oracle_prices_hash_map() -> HashMap<String, Vec<OraclePriceConcise>> {
+fn group_oracle_prices(oracle_prices: &[OraclePrice]) -> HashMap<String, Vec<OraclePriceConcise>> {
     let mut map: HashMap<String, Vec<OraclePriceConcise>> = HashMap::new();
-    let oracle_prices = get_oracle_prices_since_new_amm();
 
-    // TODO: optimize this
-    set_oracle_prices_pair(&mut map, TokenPair::EthUsdc.id(), oracle_prices.clone());
-    set_oracle_prices_pair(&mut map, TokenPair::BtcUsdc.id(), oracle_prices.clone());
-    set_oracle_prices_pair(&mut map, TokenPair::StrkUsdc.id(), oracle_prices.clone());
-    set_oracle_prices_pair(&mut map, TokenPair::EkuboUsdc.id(), oracle_prices.clone());
+    set_oracle_prices_pair(&mut map, TokenPair::EthUsdc.id(), oracle_prices);
+    set_oracle_prices_pair(&mut map, TokenPair::BtcUsdc.id(), oracle_prices);
+    set_oracle_prices_pair(&mut map, TokenPair::StrkUsdc.id(), oracle_prices);
+    set_oracle_prices_pair(&mut map, TokenPair::EkuboUsdc.id(), oracle_prices);
 
     map
 }
+
+/// Positions by total points, top users and points by address
+fn rank_user_points(
+    mut users: Vec<UserPointsWithPosition>,
+) -> (
+    Vec<UserPointsWithPosition>,
+    HashMap<String, UserPointsWithPosition>,
+) {
+    users.sort_by_key(|u| -u.total_points); // negative for descending order
+
+    let mut last_points = users.first().map_or(0, |user| user.total_points);
+    let mut current_position = 1;
+
+    let mut user_points_with_position = vec![];
+
+    for mut user in users.into_iter() {
+        if user.total_points < last_points {
+            last_points = user.total_points;
+            current_position += 1;
+        }
+        user.position = current_position;
+        user_points_with_position.push(user);
+    }
+
+    let top: Vec<UserPointsWithPosition> = user_points_with_position
+        .iter()
+        .take(TOP_USER_POINTS)
+        .cloned()
+        .collect();
+
+    let mut map = HashMap::new();
+
+    for user in user_points_with_position {
+        let address = user.address.to_string();
+        map.insert(address, user);
+    }
+
+    (top, map)
+}
+
+/// Referral points of referrers with flagged wallets scaled by `factors`
+fn exclude_flagged_referral_points(
+    user_points: &HashMap<String, UserPointsWithPosition>,
+    factors: &HashMap<String, f64>,
+) -> (
+    Vec<UserPointsWithPosition>,
+    HashMap<String, UserPointsWithPosition>,
+) {
+    let mut users: Vec<UserPointsWithPosition> = user_points
+        .values()
+        .map(|user| {
+            let mut user = user.clone();
+            if let Some(factor) = factors.get(&canonical_address(&user.address)) {
+                let referral_points = (user.referral_points as f64 * factor).round() as i64;
+                user.total_points -= user.referral_points - referral_points;
+                user.referral_points = referral_points;
+            }
+            user
+        })
+        .collect();
+    // ties keep the same order on every update
+    users.sort_by(|a, b| a.address.cmp(&b.address));
+
+    rank_user_points(users)
+}
+
+fn generate_oracle_prices_hash_map(
+    store: &dyn Store,
+) -> Result<HashMap<String, Vec<OraclePriceConcise>>, DbError> {
+    Ok(group_oracle_prices(&store.get_oracle_prices_from_block(
+        NEW_AMM_GENESIS_BLOCK_NUMBER,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use carmine_api_db::MemoryStore;
+    use std::env;
+
+    use super::*;
+
+    fn user(address: &str, total_points: i64) -> UserPointsWithPosition {
+        UserPointsWithPosition {
+            address: address.to_string(),
+            trading_points: total_points,
+            liquidity_points: 0,
+            referral_points: 0,
+            vote_points: 0,
+            total_points,
+            position: 0,
+        }
+    }
+
+    fn positions(users: &[UserPointsWithPosition]) -> Vec<(&str, i64)> {
+        users
+            .iter()
+            .map(|user| (user.address.as_str(), user.position))
+            .collect()
+    }
+
+    #[test]
+    fn ranks_fewer_users_than_the_top() {
+        let (top, map) = rank_user_points(vec![user("0x1", 10), user("0x2", 30), user("0x3", 10)]);
+        assert_eq!(positions(&top), vec![("0x2", 1), ("0x1", 2), ("0x3", 2)]);
+        assert_eq!(map["0x3"].position, 2);
+
+        let (top, map) = rank_user_points(vec![]);
+        assert!(top.is_empty());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn serves_data_of_memory_store() {
+        // the messenger is created but nothing is sent
+        env::set_var("BOT_TOKEN", "test");
+        env::set_var("CHAT_ID", "test");
+        let store = MemoryStore::from_json(
+            Network::Mainnet,
+            include_str!("../../carmine-api-db/fixtures/mainnet.json"),
+        )
+        .unwrap();
+
+        let data = Cache::from_store(store).unwrap().get_app_data().unwrap();
+
+        assert_eq!(data.trade_history.len(), 1);
+        assert_eq!(data.votes.len(), 1);
+        // only the latest points count
+        assert_eq!(
+            positions(&data.top_user_points),
+            vec![("0xabc", 1), ("0x123", 1), ("0xdef", 2)]
+        );
+        assert_eq!(data.user_points["0xabc"].total_points, 70);
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::HashMap;
 
-use carmine_api_core::types::{
-    PailEvents, PailHedgeFinalized, PailHedgeOpen, StarkScanEventSettled,
+use carmine_api_core::{
+    pail::hedge_ledger,
+    types::{Hedge, PailEvents, PailHedgeFinalized, PailHedgeOpen, StarkScanEventSettled},
 };
+use carmine_api_prices::{sources::now, BlockId, HistoricalPrices, USD};
+use carmine_api_starknet::pail::decode_hedge_events;
 
 fn transform_event(event: &StarkScanEventSettled) -> Option<PailEvents> {
     match event.key_name.as_str() {
@@ -117,3 +120,12 @@ pub fn transform_pail_events(
 
     res
 }
+
+/// Hedges of the Pail events, open hedges are valued at the latest oracle prices
+pub fn generate_hedges(events: &[StarkScanEventSettled], prices: &HistoricalPrices) -> Vec<Hedge> {
+    hedge_ledger(&decode_hedge_events(events), now(), |symbol| {
+        prices
+            .get_cross_price(symbol, USD, BlockId::BlockNumber(i64::MAX))
+            .ok()
+    })
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use carmine_api_core::types::{
+    Ohlc, PoolStateBucket, PoolStateRollups, PoolStateWithTimestamp, StateResolution,
+};
+
+fn update_ohlc(ohlc: &mut Option<Ohlc>, value: Option<f64>) {
+    let value = match value {
+        Some(v) => v,
+        None => return,
+    };
+
+    match ohlc {
+        Some(current) => {
+            current.high = current.high.max(value);
+            current.low = current.low.min(value);
+            current.close = value;
+        }
+        None => {
+            *ohlc = Some(Ohlc {
+                open: value,
+                high: value,
+                low: value,
+                close: value,
+            })
+        }
+    }
+}
+
+/// Aggregates pool states into buckets of the resolution.
+/// `states` must be sorted by block number ascending.
+fn bucket_states(
+    states: &[&PoolStateWithTimestamp],
+    resolution: StateResolution,
+) -> Vec<PoolStateBucket> {
+    let mut buckets: Vec<PoolStateBucket> = vec![];
+
+    for state in states {
+        let bucket_start = resolution
+            .bucket_start(state.timestamp)
+            .expect("Block resolution is not bucketed");
+
+        match buckets.last_mut() {
+            Some(bucket) if bucket.timestamp == bucket_start => {
+                bucket.last_block_number = state.block_number;
+                bucket.samples += 1;
+                update_ohlc(&mut bucket.lp_token_value_usd, state.lp_token_value_usd);
+                update_ohlc(
+                    &mut bucket.underlying_asset_price,
+                    state.underlying_asset_price,
+                );
+                bucket.last = (*state).clone();
+            }
+            _ => {
+                let mut bucket = PoolStateBucket {
+                    timestamp: bucket_start,
+                    first_block_number: state.block_number,
+                    last_block_number: state.block_number,
+                    samples: 1,
+                    lp_token_value_usd: None,
+                    underlying_asset_price: None,
+                    last: (*state).clone(),
+                };
+                update_ohlc(&mut bucket.lp_token_value_usd, state.lp_token_value_usd);
+                update_ohlc(
+                    &mut bucket.underlying_asset_price,
+                    state.underlying_asset_price,
+                );
+                buckets.push(bucket);
+            }
+        }
+    }
+
+    buckets
+}
+
+pub fn calculate_rollups(state: &[PoolStateWithTimestamp]) -> PoolStateRollups {
+    // DB returns state sorted descending, rollups are built ascending
+    let mut sorted: Vec<&PoolStateWithTimestamp> = state.iter().collect();
+    sorted.sort_by_key(|s| s.block_number);
+
+    PoolStateRollups {
+        hour: bucket_states(&sorted, StateResolution::Hour),
+        day: bucket_states(&sorted, StateResolution::Day),
+        week: bucket_states(&sorted, StateResolution::Week),
+    }
+}
+
+pub fn generate_rollups(
+    state: &HashMap<String, Vec<PoolStateWithTimestamp>>,
+) -> HashMap<String, PoolStateRollups> {
+    state
+        .iter()
+        .map(|(pool_id, pool_state)| (pool_id.to_string(), calculate_rollups(pool_state)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Monday 2024-01-01 00:00:00 UTC
+    const MONDAY: i64 = 1704067200;
+
+    fn state(block_number: i64, timestamp: i64, price: f64) -> PoolStateWithTimestamp {
+        PoolStateWithTimestamp {
+            unlocked_cap: "0x0".to_string(),
+            locked_cap: "0x0".to_string(),
+            lp_balance: "0x0".to_string(),
+            pool_position: None,
+            lp_token_value: None,
+            lp_token_value_usd: None,
+            underlying_asset_price: Some(price),
+            block_number,
+            lp_address: "0x1".to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let week = StateResolution::Week;
+        assert_eq!(week.bucket_start(MONDAY), Some(MONDAY));
+        assert_eq!(week.bucket_start(MONDAY + 6 * 86400 + 3599), Some(MONDAY));
+        assert_eq!(week.bucket_start(MONDAY - 1), Some(MONDAY - 7 * 86400));
+        assert_eq!(StateResolution::Block.bucket_start(MONDAY), None);
+    }
+
+    #[test]
+    fn rollups_aggregate_states_per_bucket() {
+        // DB order, descending
+        let states = vec![
+            state(4, MONDAY + 7 * 86400, 5.0),
+            state(3, MONDAY + 3600 + 10, 4.0),
+            state(2, MONDAY + 20, 1.0),
+            state(1, MONDAY + 10, 2.0),
+        ];
+
+        let rollups = calculate_rollups(&states);
+        assert_eq!(rollups.hour.len(), 3);
+        assert_eq!(rollups.day.len(), 2);
+        assert_eq!(rollups.week.len(), 2);
+
+        let hour = &rollups.hour[0];
+        assert_eq!(hour.timestamp, MONDAY);
+        assert_eq!(hour.samples, 2);
+        assert_eq!(hour.first_block_number, 1);
+        assert_eq!(hour.last_block_number, 2);
+        let price = hour.underlying_asset_price.unwrap();
+        assert_eq!(
+            (price.open, price.high, price.low, price.close),
+            (2.0, 2.0, 1.0, 1.0)
+        );
+        assert!(hour.lp_token_value_usd.is_none());
+
+        let week = &rollups.week[0];
+        assert_eq!(week.timestamp, MONDAY);
+        assert_eq!(week.samples, 3);
+        assert_eq!(week.last.block_number, 3);
+        assert_eq!(rollups.week[1].timestamp, MONDAY + 7 * 86400);
+    }
+}
@@ -5,7 +5,7 @@ use carmine_api_core::{
     types::{TradeEvent, TradeEventWithPrice, Trades},
     utils::string_to_float,
 };
-use carmine_api_prices::HistoricalPrices;
+use carmine_api_prices::{graph::PriceError, HistoricalPrices};
 
 fn calculate_premia(
     side: i16,
@@ -32,11 +32,11 @@ fn transform_trade_event(
     pool_id: &str,
     underlying_decimals: usize,
     base_decimals: usize,
-) -> TradeEventWithPrice {
+) -> Result<TradeEventWithPrice, PriceError> {
     let price = prices.get_price(
-        &pool_id,
+        pool_id,
         carmine_api_prices::BlockId::Timestamp(event.timestamp),
-    );
+    )?;
     let capital_transfered_string = event.capital_transfered.to_string();
     let capital_transfered_human_readable =
         string_to_float(&capital_transfered_string, underlying_decimals);
@@ -53,7 +53,7 @@ fn transform_trade_event(
     );
     let premia_usd = (premia as f32) * price;
 
-    TradeEventWithPrice {
+    Ok(TradeEventWithPrice {
         timestamp: event.timestamp,
         action: event.action.to_string(),
         caller: event.caller.to_string(),
@@ -68,7 +68,7 @@ fn transform_trade_event(
         maturity: event.maturity,
         strike_price: event.strike_price,
         pool_id: pool_id.to_string(),
-    }
+    })
 }
 
 pub fn get_trades(
@@ -82,7 +82,16 @@ pub fn get_trades(
 
         let pool_trades_with_price: Vec<TradeEventWithPrice> = trades
             .iter()
-            .map(|t| transform_trade_event(t, prices, pool_id, underlying_decimals, base_decimals))
+            .filter_map(|t| {
+                match transform_trade_event(t, prices, pool_id, underlying_decimals, base_decimals)
+                {
+                    Ok(trade) => Some(trade),
+                    Err(e) => {
+                        println!("Failed to price trade in {}: {}", pool_id, e);
+                        None
+                    }
+                }
+            })
             .collect();
 
         all_trades.extend(pool_trades_with_price);
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use carmine_api_core::{network::Network, pool::get_all_pools, types::VolatilityPremiumSeries};
+use carmine_api_db::{DbError, Store};
+use carmine_api_prices::{
+    volatility::{volatility_premium, AmmVolatility, RealizedVolatility},
+    HistoricalPrices,
+};
+
+/// Loads AMM volatilities stored since the last update of each pool
+pub fn update_amm_volatility(
+    store: &dyn Store,
+    amm_volatility: &mut HashMap<String, AmmVolatility>,
+) -> Result<(), DbError> {
+    for pool in get_all_pools(&Network::Mainnet) {
+        let amm = amm_volatility.entry(pool.id.to_string()).or_default();
+        let rows = store.get_pool_volatilities(pool.address, amm.last_block.unwrap_or(-1))?;
+        amm.extend(&rows);
+    }
+
+    Ok(())
+}
+
+pub fn generate_volatility_premium(
+    amm_volatility: &HashMap<String, AmmVolatility>,
+    prices: &HistoricalPrices,
+) -> HashMap<String, Vec<VolatilityPremiumSeries>> {
+    let mut map = HashMap::new();
+
+    for pool in get_all_pools(&Network::Mainnet) {
+        let amm = match amm_volatility.get(pool.id) {
+            Some(amm) => amm,
+            None => continue,
+        };
+        // options are priced on base/quote, realized volatility uses the same rate
+        let series = match prices.get_cross_series(
+            &pool.base.symbol.to_lowercase(),
+            &pool.quote.symbol.to_lowercase(),
+        ) {
+            Ok(series) => series,
+            Err(e) => {
+                println!("No price series for {}: {}", pool.id, e);
+                continue;
+            }
+        };
+        let realized = RealizedVolatility::new(&series);
+
+        let premium = amm
+            .series
+            .iter()
+            .map(|(maturity, points)| VolatilityPremiumSeries {
+                maturity: *maturity,
+                points: volatility_premium(points, &realized),
+            })
+            .collect();
+        map.insert(pool.id.to_string(), premium);
+    }
+
+    map
+}
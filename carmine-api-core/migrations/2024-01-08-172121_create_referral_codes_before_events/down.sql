@@ -0,0 +1,3 @@
+DROP SCHEMA IF EXISTS referral_placeholder CASCADE;
+
+RESET search_path;
@@ -0,0 +1,11 @@
+-- create_referral_codes references referral_codes before creating it. On a database set up
+-- from scratch the reference resolves to this placeholder, searched after public, while
+-- create_referral_codes still creates public.referral_codes. restore_referral_events_fk
+-- points the reference to public.referral_codes and drops the placeholder
+CREATE SCHEMA IF NOT EXISTS referral_placeholder;
+
+CREATE TABLE IF NOT EXISTS referral_placeholder.referral_codes (
+  referral_code TEXT PRIMARY KEY
+);
+
+SET search_path TO public, referral_placeholder;
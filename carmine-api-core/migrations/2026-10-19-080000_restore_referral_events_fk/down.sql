@@ -0,0 +1,3 @@
+-- create_referral_codes drops referral_codes before referral_events
+ALTER TABLE
+  referral_events DROP CONSTRAINT IF EXISTS referral_events_referral_code_fkey;
@@ -0,0 +1,13 @@
+-- on a database set up from scratch referral_events references the placeholder
+-- of create_referral_codes_before_events
+ALTER TABLE
+  referral_events DROP CONSTRAINT IF EXISTS referral_events_referral_code_fkey;
+
+ALTER TABLE
+  referral_events
+ADD
+  CONSTRAINT referral_events_referral_code_fkey FOREIGN KEY (referral_code) REFERENCES public.referral_codes (referral_code);
+
+DROP SCHEMA IF EXISTS referral_placeholder CASCADE;
+
+RESET search_path;
@@ -0,0 +1,26 @@
+DROP INDEX IF EXISTS oracle_prices_token_pair_block_number_idx;
+
+DROP INDEX IF EXISTS starkscan_events_from_address_block_number_idx;
+
+ALTER TABLE
+  starkscan_events
+ALTER COLUMN
+  from_address DROP NOT NULL,
+ALTER COLUMN
+  keys DROP NOT NULL,
+ALTER COLUMN
+  data DROP NOT NULL,
+ALTER COLUMN
+  timestamp DROP NOT NULL,
+ALTER COLUMN
+  key_name DROP NOT NULL;
+
+ALTER TABLE
+  oracle_prices
+ALTER COLUMN
+  num_sources_aggregated DROP NOT NULL;
+
+ALTER TABLE
+  options
+ALTER COLUMN
+  lp_address DROP NOT NULL;
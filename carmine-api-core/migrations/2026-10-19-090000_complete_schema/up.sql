@@ -0,0 +1,97 @@
+-- rows with NULL in the columns declared NOT NULL below would abort the migration,
+-- backfill what can be derived
+UPDATE
+  oracle_prices
+SET
+  num_sources_aggregated = 0
+WHERE
+  num_sources_aggregated IS NULL;
+
+UPDATE
+  starkscan_events
+SET
+  timestamp = blocks.timestamp
+FROM
+  blocks
+WHERE
+  starkscan_events.timestamp IS NULL
+  AND blocks.block_number = starkscan_events.block_number;
+
+-- events without name are skipped by the parsers
+UPDATE
+  starkscan_events
+SET
+  key_name = ''
+WHERE
+  key_name IS NULL;
+
+-- rows the backfill cannot complete are not deleted here, they are listed and deleted
+-- with `cargo run -p carmine-api-db --bin incomplete_rows [delete]`
+DO $$ BEGIN
+  IF EXISTS (
+    SELECT
+      1
+    FROM
+      options
+    WHERE
+      lp_address IS NULL
+  )
+  OR EXISTS (
+    SELECT
+      1
+    FROM
+      starkscan_events
+    WHERE
+      from_address IS NULL
+      OR keys IS NULL
+      OR data IS NULL
+      OR timestamp IS NULL
+  ) THEN RAISE EXCEPTION 'Incomplete rows in options or starkscan_events, run the incomplete_rows bin of carmine-api-db';
+  END IF;
+END $$;
+
+-- columns declared NOT NULL in schema.rs
+ALTER TABLE
+  options
+ALTER COLUMN
+  lp_address
+SET
+  NOT NULL;
+
+ALTER TABLE
+  oracle_prices
+ALTER COLUMN
+  num_sources_aggregated
+SET
+  NOT NULL;
+
+ALTER TABLE
+  starkscan_events
+ALTER COLUMN
+  from_address
+SET
+  NOT NULL,
+ALTER COLUMN
+  keys
+SET
+  NOT NULL,
+ALTER COLUMN
+  data
+SET
+  NOT NULL,
+ALTER COLUMN
+  timestamp
+SET
+  NOT NULL,
+ALTER COLUMN
+  key_name
+SET
+  NOT NULL;
+
+-- events of a contract from a block
+CREATE INDEX IF NOT EXISTS starkscan_events_from_address_block_number_idx ON starkscan_events (from_address, block_number);
+
+-- state of a pool over time is served by the primary key (lp_address, block_number)
+
+-- prices of a pair over time
+CREATE INDEX IF NOT EXISTS oracle_prices_token_pair_block_number_idx ON oracle_prices (token_pair, block_number);
@@ -0,0 +1,9 @@
+CREATE TABLE ingestion_checkpoints (
+  -- "events:<protocol>", "amm_state", "oracle_prices" or "volatility"
+  stream TEXT NOT NULL PRIMARY KEY,
+  -- last fully processed block
+  last_block Int8 NOT NULL,
+  -- "ok" or "failed"
+  status TEXT NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1 @@
+DROP TABLE raw_events_unparsed;
@@ -0,0 +1,21 @@
+CREATE TABLE raw_events_unparsed (
+  -- same as starkscan_events.id
+  id TEXT NOT NULL PRIMARY KEY,
+  block_hash TEXT NOT NULL,
+  block_number Int8 NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  event_index Int8 NOT NULL,
+  from_address TEXT NOT NULL,
+  keys TEXT[] NOT NULL,
+  data TEXT[] NOT NULL,
+  timestamp Int8 NOT NULL,
+  key_name TEXT,
+  -- first key, NULL if the event has no keys
+  selector TEXT,
+  -- "unknown_selector", "unexpected_key_count", "unexpected_data_length" or "disallowed_action"
+  reason TEXT NOT NULL,
+  -- set once the event was decoded and stored
+  reprocessed_at TIMESTAMP
+);
+
+CREATE INDEX raw_events_unparsed_from_address_selector_idx ON raw_events_unparsed (from_address, selector);
@@ -0,0 +1 @@
+DROP TABLE lending_events;
@@ -0,0 +1,23 @@
+CREATE TABLE lending_events (
+  -- same as starkscan_events.id
+  id TEXT NOT NULL PRIMARY KEY,
+  -- "zklend", "nostra-alpha", "nostra-mainnet" or "hashstack"
+  protocol TEXT NOT NULL,
+  -- lowercase symbol of the underlying token
+  market TEXT NOT NULL,
+  -- "deposit", "withdraw", "borrow", "repay", "liquidation" or "rate_update"
+  action TEXT NOT NULL,
+  -- NULL for rate updates
+  user_address TEXT,
+  -- in tokens of the market, NULL for rate updates
+  amount DOUBLE PRECISION,
+  -- yearly rates, eg. 0.05 for 5 %, only set for rate updates
+  lending_rate DOUBLE PRECISION,
+  borrowing_rate DOUBLE PRECISION,
+  block_number Int8 NOT NULL,
+  event_index Int8 NOT NULL,
+  timestamp Int8 NOT NULL,
+  transaction_hash TEXT NOT NULL
+);
+
+CREATE INDEX lending_events_protocol_market_idx ON lending_events (protocol, market, block_number);
@@ -0,0 +1,15 @@
+CREATE TABLE proposals (
+  prop_id Int8 NOT NULL PRIMARY KEY,
+  payload TEXT NOT NULL,
+  to_upgrade Int8 NOT NULL,
+  -- set by hand with the proposal_metadata binary
+  title TEXT,
+  description TEXT,
+  -- block and transaction of the Proposed event
+  block_number Int8 NOT NULL,
+  timestamp Int8 NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  -- set once the proposal was applied
+  executed_block_number Int8,
+  executed_timestamp Int8
+);
@@ -0,0 +1,2 @@
+ALTER TABLE proposals DROP COLUMN total_supply;
+DROP TABLE voting_power;
@@ -0,0 +1,12 @@
+CREATE TABLE voting_power (
+  prop_id Int8 NOT NULL,
+  user_address TEXT NOT NULL,
+  -- governance token balance at the snapshot block, in whole tokens
+  voting_power DOUBLE PRECISION NOT NULL,
+  -- snapshot block the balance was read at
+  block_number Int8 NOT NULL,
+  PRIMARY KEY (prop_id, user_address)
+);
+
+-- governance token supply at the snapshot block, used for quorum
+ALTER TABLE proposals ADD COLUMN total_supply DOUBLE PRECISION;
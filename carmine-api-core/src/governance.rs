@@ -0,0 +1,313 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{Proposal, ProposalDetail, ProposalSummary, ProposalVoter, Vote, VotingPower};
+
+/// Proposals can be voted on for a week after they were submitted
+pub const PROPOSAL_VOTING_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+/// Share of the token supply at the snapshot that has to vote for a proposal to pass
+pub const QUORUM_PERCENT: f64 = 10.0;
+
+/// Number of voters listed as top voters of a proposal
+pub const TOP_VOTERS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProposalStatus {
+    Live,
+    /// voting ended, voting power of some voters is not fetched yet
+    Pending,
+    Passed,
+    Rejected,
+    Executed,
+}
+
+impl ProposalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProposalStatus::Live => "live",
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Passed => "passed",
+            ProposalStatus::Rejected => "rejected",
+            ProposalStatus::Executed => "executed",
+        }
+    }
+}
+
+/// Only the first vote of a voter counts, the contract rejects revotes
+fn first_votes<'a>(votes: impl Iterator<Item = &'a Vote>) -> Vec<Vote> {
+    let mut votes: Vec<&Vote> = votes.collect();
+    votes.sort_by_key(|vote| vote.timestamp);
+
+    let mut voted = HashSet::new();
+    votes
+        .into_iter()
+        .filter(|vote| voted.insert(vote.user_address.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Proposals with their voters and tallies, newest first
+///
+/// Votes are weighted by the voting power at the snapshot block of the proposal,
+/// the outcome is decided once the token supply and the power of every voter are fetched
+pub fn tally_proposals(
+    proposals: &[Proposal],
+    votes: &[Vote],
+    voting_power: &[VotingPower],
+    now: i64,
+) -> Vec<ProposalDetail> {
+    let powers: HashMap<(i64, &str), f64> = voting_power
+        .iter()
+        .map(|p| ((p.prop_id, p.user_address.as_str()), p.voting_power))
+        .collect();
+
+    let mut votes_by_proposal: HashMap<i64, Vec<&Vote>> = HashMap::new();
+    for vote in votes {
+        votes_by_proposal
+            .entry(vote.prop_id as i64)
+            .or_default()
+            .push(vote);
+    }
+
+    let mut details: Vec<ProposalDetail> = proposals
+        .iter()
+        .map(|proposal| {
+            let voters = first_votes(
+                votes_by_proposal
+                    .get(&proposal.prop_id)
+                    .into_iter()
+                    .flatten()
+                    .copied(),
+            );
+            let voters: Vec<ProposalVoter> = voters
+                .into_iter()
+                .map(|vote| ProposalVoter {
+                    voting_power: powers
+                        .get(&(proposal.prop_id, vote.user_address.as_str()))
+                        .copied(),
+                    user_address: vote.user_address,
+                    opinion: vote.opinion,
+                    timestamp: vote.timestamp,
+                })
+                .collect();
+
+            let yes_votes = voters.iter().filter(|vote| vote.opinion == 1).count();
+            let no_votes = voters.len() - yes_votes;
+            let power = |opinion: usize| -> f64 {
+                voters
+                    .iter()
+                    .filter(|vote| vote.opinion == opinion)
+                    .filter_map(|vote| vote.voting_power)
+                    .sum()
+            };
+            let (yes_power, no_power) = (power(1), power(0));
+
+            let quorum = proposal
+                .total_supply
+                .map(|supply| supply * QUORUM_PERCENT / 100.0);
+            // voters without fetched power would count as 0
+            let powers_loaded = voters.iter().all(|vote| vote.voting_power.is_some());
+            let quorum_reached = quorum
+                .filter(|_| powers_loaded)
+                .map(|quorum| yes_power + no_power >= quorum);
+
+            let voting_ends = proposal.timestamp + PROPOSAL_VOTING_SECONDS;
+            let status = match (proposal.executed_timestamp, now < voting_ends) {
+                (Some(_), _) => ProposalStatus::Executed,
+                (None, true) => ProposalStatus::Live,
+                (None, false) => match quorum_reached {
+                    Some(true) if yes_power > no_power => ProposalStatus::Passed,
+                    Some(_) => ProposalStatus::Rejected,
+                    None => ProposalStatus::Pending,
+                },
+            };
+
+            let turnout = proposal
+                .total_supply
+                .filter(|supply| powers_loaded && *supply > 0.0)
+                .map(|supply| (yes_power + no_power) / supply);
+
+            let mut top_voters: Vec<ProposalVoter> = voters
+                .iter()
+                .filter(|vote| vote.voting_power.is_some())
+                .cloned()
+                .collect();
+            top_voters.sort_by(|a, b| {
+                let weight = |voter: &ProposalVoter| voter.voting_power.unwrap_or_default();
+                weight(b).total_cmp(&weight(a))
+            });
+            top_voters.truncate(TOP_VOTERS);
+
+            ProposalDetail {
+                summary: ProposalSummary {
+                    prop_id: proposal.prop_id,
+                    title: proposal.title.clone(),
+                    description: proposal.description.clone(),
+                    payload: proposal.payload.clone(),
+                    to_upgrade: proposal.to_upgrade,
+                    block_number: proposal.block_number,
+                    timestamp: proposal.timestamp,
+                    voting_ends,
+                    executed_timestamp: proposal.executed_timestamp,
+                    yes_votes,
+                    no_votes,
+                    voters_count: voters.len(),
+                    turnout,
+                    yes_power,
+                    no_power,
+                    total_supply: proposal.total_supply,
+                    quorum,
+                    quorum_reached,
+                    status: status.as_str().to_string(),
+                },
+                top_voters,
+                voters,
+            }
+        })
+        .collect();
+
+    details.sort_by_key(|detail| Reverse(detail.summary.prop_id));
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal(prop_id: i64, timestamp: i64, executed_timestamp: Option<i64>) -> Proposal {
+        Proposal {
+            prop_id,
+            payload: "0x1".to_string(),
+            to_upgrade: 1,
+            title: None,
+            description: None,
+            block_number: 600000,
+            timestamp,
+            transaction_hash: "0x2".to_string(),
+            executed_block_number: executed_timestamp.map(|_| 600100),
+            executed_timestamp,
+            total_supply: None,
+        }
+    }
+
+    fn power(prop_id: i64, user_address: &str, voting_power: f64) -> VotingPower {
+        VotingPower {
+            prop_id,
+            user_address: user_address.to_string(),
+            voting_power,
+            block_number: 600000,
+        }
+    }
+
+    fn vote(prop_id: usize, user_address: &str, opinion: usize, timestamp: i64) -> Vote {
+        Vote {
+            user_address: user_address.to_string(),
+            prop_id,
+            opinion,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn tallies_proposals() {
+        let now = 10 * PROPOSAL_VOTING_SECONDS;
+        let proposals = vec![
+            proposal(1, 0, None),
+            proposal(2, 0, Some(PROPOSAL_VOTING_SECONDS + 1)),
+            proposal(3, now - 1, None),
+            proposal(4, 0, None),
+        ];
+        let votes = vec![
+            vote(1, "0xa", 1, 1),
+            vote(1, "0xb", 0, 2),
+            vote(1, "0xc", 1, 3),
+            // revote is ignored
+            vote(1, "0xb", 1, 4),
+            vote(4, "0xa", 0, 5),
+        ];
+
+        let details = tally_proposals(&proposals, &votes, &[], now);
+        let ids: Vec<i64> = details.iter().map(|d| d.summary.prop_id).collect();
+        assert_eq!(ids, vec![4, 3, 2, 1]);
+
+        let first = &details[3];
+        assert_eq!((first.summary.yes_votes, first.summary.no_votes), (2, 1));
+        assert_eq!(first.summary.turnout, None);
+        assert_eq!(first.summary.status, "pending");
+        assert_eq!(first.voters.len(), 3);
+
+        let statuses: Vec<&str> = details.iter().map(|d| d.summary.status.as_str()).collect();
+        assert_eq!(statuses, vec!["pending", "live", "executed", "pending"]);
+        assert_eq!(details[1].summary.voters_count, 0);
+        assert_eq!(first.summary.quorum_reached, None);
+        assert!(first.top_voters.is_empty());
+    }
+
+    #[test]
+    fn weights_votes_by_voting_power() {
+        let now = 10 * PROPOSAL_VOTING_SECONDS;
+        let mut whale_against = proposal(1, 0, None);
+        whale_against.total_supply = Some(1000.0);
+        let mut below_quorum = proposal(2, 0, None);
+        below_quorum.total_supply = Some(1000.0);
+
+        let votes = vec![
+            vote(1, "0xa", 1, 1),
+            vote(1, "0xb", 1, 2),
+            vote(1, "0xc", 0, 3),
+            vote(1, "0xd", 1, 4),
+            vote(2, "0xa", 1, 5),
+        ];
+        let mut powers = vec![
+            power(1, "0xa", 10.0),
+            power(1, "0xb", 20.0),
+            power(1, "0xc", 100.0),
+            power(2, "0xa", 50.0),
+        ];
+
+        let proposals = vec![whale_against, below_quorum];
+        let details = tally_proposals(&proposals, &votes, &powers, now);
+
+        let second = &details[0];
+        assert_eq!(
+            (second.summary.yes_power, second.summary.no_power),
+            (50.0, 0.0)
+        );
+        assert_eq!(second.summary.quorum, Some(100.0));
+        assert_eq!(second.summary.quorum_reached, Some(false));
+        assert_eq!(second.summary.turnout, Some(0.05));
+        assert_eq!(second.summary.status, "rejected");
+
+        let first = &details[1];
+        assert_eq!((first.summary.yes_votes, first.summary.no_votes), (3, 1));
+        assert_eq!(
+            (first.summary.yes_power, first.summary.no_power),
+            (30.0, 100.0)
+        );
+        // power of 0xd is not fetched yet
+        assert_eq!(first.summary.quorum_reached, None);
+        assert_eq!(first.summary.turnout, None);
+        assert_eq!(first.summary.status, "pending");
+
+        // voters without fetched power are not ranked
+        let top: Vec<&str> = first
+            .top_voters
+            .iter()
+            .map(|v| v.user_address.as_str())
+            .collect();
+        assert_eq!(top, vec!["0xc", "0xb", "0xa"]);
+        assert_eq!(first.voters[3].voting_power, None);
+
+        powers.push(power(1, "0xd", 20.0));
+        let details = tally_proposals(&proposals, &votes, &powers, now);
+        let first = &details[1];
+        assert_eq!(
+            (first.summary.yes_power, first.summary.no_power),
+            (50.0, 100.0)
+        );
+        assert_eq!(first.summary.quorum_reached, Some(true));
+        assert_eq!(first.summary.turnout, Some(0.15));
+        assert_eq!(first.summary.status, "rejected");
+    }
+}
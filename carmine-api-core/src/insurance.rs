@@ -0,0 +1,282 @@
+use std::fmt;
+
+use crate::{
+    constants::MATH_64,
+    network::Network,
+    pool::{get_all_pools, Pool, Type},
+    types::TradeEvent,
+    utils::{is_hex_address, normalize_address},
+};
+
+/// On-chain trade matches a submission made at most this many seconds before or after it
+pub const VERIFICATION_WINDOW_SECONDS: i64 = 60 * 60;
+
+/// Arguments of the AMM `trade_open` call the insurance was bought with
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsuranceCalldata {
+    /// 0 call, 1 put
+    pub option_type: u8,
+    pub strike: f64,
+    pub maturity: i64,
+    /// 0 long, 1 short
+    pub option_side: u8,
+    /// raw units of the base token
+    pub size: u128,
+    pub quote_token: String,
+    pub base_token: String,
+    /// limit of the total premia
+    pub premia: f64,
+    pub pool_id: &'static str,
+    /// lowercase symbol of the base token
+    pub base_symbol: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsuranceCalldataError {
+    /// calldata has fewer fields than the decoded arguments
+    TooShort(usize),
+    /// field is not a hex or decimal number of the expected size
+    InvalidField(&'static str, String),
+    /// no pool trades the tokens with the option type, (base, quote, option type)
+    UnknownPool(String, String, u8),
+}
+
+impl fmt::Display for InsuranceCalldataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InsuranceCalldataError::TooShort(len) => write!(
+                f,
+                "calldata has {} fields, expected at least {}",
+                len, CALLDATA_FIELDS
+            ),
+            InsuranceCalldataError::InvalidField(field, value) => {
+                write!(f, "invalid {} \"{}\"", field, value)
+            }
+            InsuranceCalldataError::UnknownPool(base, quote, option_type) => write!(
+                f,
+                "no pool for base token {}, quote token {} and option type {}",
+                base, quote, option_type
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InsuranceCalldataError {}
+
+// option_type, strike (mag, sign), maturity, option_side, option_size,
+// quote_token, base_token, limit_total_premia mag; sign and deadline may follow
+const CALLDATA_FIELDS: usize = 9;
+
+/// Calldata numbers are sent both as hex and decimal strings
+fn parse_number(value: &str) -> Option<u128> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16).ok(),
+        None => value.parse::<u128>().ok(),
+    }
+}
+
+fn field<T: TryFrom<u128>>(
+    calldata: &[&str],
+    index: usize,
+    name: &'static str,
+) -> Result<T, InsuranceCalldataError> {
+    let value = calldata[index];
+    parse_number(value)
+        .and_then(|number| T::try_from(number).ok())
+        .ok_or_else(|| InsuranceCalldataError::InvalidField(name, value.to_string()))
+}
+
+fn address_field(
+    calldata: &[&str],
+    index: usize,
+    name: &'static str,
+) -> Result<String, InsuranceCalldataError> {
+    let value = calldata[index];
+    match is_hex_address(value) {
+        true => Ok(normalize_address(&value.to_lowercase())),
+        false => Err(InsuranceCalldataError::InvalidField(
+            name,
+            value.to_string(),
+        )),
+    }
+}
+
+fn pool_type(pool: &Pool) -> u8 {
+    match pool.type_ {
+        Type::Call => 0,
+        Type::Put => 1,
+    }
+}
+
+/// Decodes the submitted calldata, fails on missing or malformed
+/// fields and on tokens no pool trades
+pub fn decode_insurance_calldata(
+    calldata: &[&str],
+) -> Result<InsuranceCalldata, InsuranceCalldataError> {
+    if calldata.len() < CALLDATA_FIELDS {
+        return Err(InsuranceCalldataError::TooShort(calldata.len()));
+    }
+
+    let option_type: u8 = field(calldata, 0, "option_type")?;
+    let strike_mag: u128 = field(calldata, 1, "strike_price")?;
+    let strike_sign: u8 = field(calldata, 2, "strike_price sign")?;
+    let maturity: i64 = field(calldata, 3, "maturity")?;
+    let option_side: u8 = field(calldata, 4, "option_side")?;
+    let size: u128 = field(calldata, 5, "option_size")?;
+    let quote_token = address_field(calldata, 6, "quote_token_address")?;
+    let base_token = address_field(calldata, 7, "base_token_address")?;
+    let premia_mag: u128 = field(calldata, 8, "limit_total_premia")?;
+
+    if option_type > 1 {
+        return Err(InsuranceCalldataError::InvalidField(
+            "option_type",
+            calldata[0].to_string(),
+        ));
+    }
+    if option_side > 1 {
+        return Err(InsuranceCalldataError::InvalidField(
+            "option_side",
+            calldata[4].to_string(),
+        ));
+    }
+    // strike is a positive Cubit fixed point number
+    if strike_sign != 0 || strike_mag == 0 {
+        return Err(InsuranceCalldataError::InvalidField(
+            "strike_price",
+            calldata[1].to_string(),
+        ));
+    }
+
+    let pool = get_all_pools(&Network::Mainnet)
+        .into_iter()
+        .find(|pool| {
+            pool_type(pool) == option_type
+                && normalize_address(pool.base.address) == base_token
+                && normalize_address(pool.quote.address) == quote_token
+        })
+        .ok_or_else(|| {
+            InsuranceCalldataError::UnknownPool(
+                base_token.to_string(),
+                quote_token.to_string(),
+                option_type,
+            )
+        })?;
+
+    Ok(InsuranceCalldata {
+        option_type,
+        strike: strike_mag as f64 / MATH_64,
+        maturity,
+        option_side,
+        size,
+        quote_token,
+        base_token,
+        premia: premia_mag as f64 / MATH_64,
+        pool_id: pool.id,
+        base_symbol: pool.base.symbol.to_lowercase(),
+    })
+}
+
+/// TradeOpen of the user with the option of the insurance made within
+/// the verification window of the submission, `trades` are the trades of its pool
+pub fn matching_trade<'a>(
+    calldata: &InsuranceCalldata,
+    user_address: &str,
+    submitted: i64,
+    trades: &'a [TradeEvent],
+) -> Option<&'a TradeEvent> {
+    let user = normalize_address(&user_address.to_lowercase());
+
+    trades
+        .iter()
+        .filter(|trade| {
+            trade.action == "TradeOpen"
+                && normalize_address(&trade.caller.to_lowercase()) == user
+                && trade.option_type == calldata.option_type as i16
+                && trade.option_side == calldata.option_side as i16
+                && trade.maturity == calldata.maturity
+                && (trade.strike_price - calldata.strike).abs() < 1e-6
+                && (trade.timestamp - submitted).abs() <= VERIFICATION_WINDOW_SECONDS
+        })
+        .min_by_key(|trade| (trade.timestamp - submitted).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETH: &str = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+    const USDC: &str = "0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8";
+
+    fn calldata<'a>(strike: &'a str, base: &'a str) -> Vec<&'a str> {
+        vec![
+            "0x0",
+            strike,
+            "0x0",
+            "0x6789f8c0",
+            "0x0",
+            "1000000000000000000",
+            USDC,
+            base,
+            "18446744073709551616",
+            "0x0",
+            "0x6789f8c0",
+        ]
+    }
+
+    fn trade_open(caller: &str, timestamp: i64, strike_price: f64) -> TradeEvent {
+        TradeEvent {
+            timestamp,
+            action: "TradeOpen".to_string(),
+            caller: caller.to_string(),
+            capital_transfered: "0x1".to_string(),
+            tokens_minted: "0x1".to_string(),
+            option_side: 0,
+            option_type: 0,
+            maturity: 0x6789f8c0,
+            strike_price,
+        }
+    }
+
+    #[test]
+    fn decodes_insurance_calldata() {
+        // 3100 as Math64
+        let decoded = decode_insurance_calldata(&calldata("0xc1c0000000000000000", ETH)).unwrap();
+        assert_eq!(decoded.strike, 3100.0);
+        assert_eq!(decoded.premia, 1.0);
+        assert_eq!(decoded.size, 1_000_000_000_000_000_000);
+        assert_eq!(decoded.pool_id, "eth-usdc-call");
+        assert_eq!(decoded.base_symbol, "eth");
+
+        assert_eq!(
+            decode_insurance_calldata(&calldata("0xc1c0000000000000000", ETH)[..5]),
+            Err(InsuranceCalldataError::TooShort(5))
+        );
+        assert_eq!(
+            decode_insurance_calldata(&calldata("abc", ETH)),
+            Err(InsuranceCalldataError::InvalidField(
+                "strike_price",
+                "abc".to_string()
+            ))
+        );
+        assert!(matches!(
+            decode_insurance_calldata(&calldata("0x1", "0x123")),
+            Err(InsuranceCalldataError::UnknownPool(_, _, 0))
+        ));
+    }
+
+    #[test]
+    fn matches_trade_of_the_user() {
+        let decoded = decode_insurance_calldata(&calldata("0xc1c0000000000000000", ETH)).unwrap();
+        let trades = vec![
+            trade_open("0xb", 1000, 3100.0),
+            trade_open("0x0A", 1000 + 2 * VERIFICATION_WINDOW_SECONDS, 3100.0),
+            trade_open("0x0A", 1000, 3200.0),
+            trade_open("0x0A", 1100, 3100.0),
+            trade_open("0x0A", 990, 3100.0),
+        ];
+
+        let found = matching_trade(&decoded, "0xa", 1000, &trades).unwrap();
+        assert_eq!(found.timestamp, 990);
+        assert!(matching_trade(&decoded, "0xc", 1000, &trades).is_none());
+    }
+}
@@ -0,0 +1,688 @@
+use std::fmt;
+
+use crate::network::{protocol_address, Network, Protocol};
+use crate::types::{LendingEvent, LendingMarketPoint, LendingMarketStats};
+use crate::utils::normalize_address;
+
+const DAY_SECS: i64 = 86400;
+
+/// Lending protocols whose events are fetched from Starkscan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LendingProtocol {
+    ZkLend,
+    NostraAlpha,
+    NostraMainnet,
+    Hashstack,
+}
+
+impl LendingProtocol {
+    pub const ALL: [LendingProtocol; 4] = [
+        LendingProtocol::ZkLend,
+        LendingProtocol::NostraAlpha,
+        LendingProtocol::NostraMainnet,
+        LendingProtocol::Hashstack,
+    ];
+
+    /// Id used in the DB and in the API path
+    pub fn id(&self) -> &'static str {
+        match self {
+            LendingProtocol::ZkLend => "zklend",
+            LendingProtocol::NostraAlpha => "nostra-alpha",
+            LendingProtocol::NostraMainnet => "nostra-mainnet",
+            LendingProtocol::Hashstack => "hashstack",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        LendingProtocol::ALL
+            .into_iter()
+            .find(|protocol| protocol.id() == id)
+    }
+}
+
+impl fmt::Display for LendingProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LendingAction {
+    Deposit,
+    Withdraw,
+    Borrow,
+    Repay,
+    Liquidation,
+    RateUpdate,
+}
+
+impl LendingAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LendingAction::Deposit => "deposit",
+            LendingAction::Withdraw => "withdraw",
+            LendingAction::Borrow => "borrow",
+            LendingAction::Repay => "repay",
+            LendingAction::Liquidation => "liquidation",
+            LendingAction::RateUpdate => "rate_update",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "deposit" => Some(LendingAction::Deposit),
+            "withdraw" => Some(LendingAction::Withdraw),
+            "borrow" => Some(LendingAction::Borrow),
+            "repay" => Some(LendingAction::Repay),
+            "liquidation" => Some(LendingAction::Liquidation),
+            "rate_update" => Some(LendingAction::RateUpdate),
+            _ => None,
+        }
+    }
+}
+
+/// What a lending contract emits, markets are lowercase token symbols
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractKind {
+    /// zkLend market, emits events of all markets, market is the token of the event
+    Market,
+    /// token minted on deposit and burned on withdrawal
+    SupplyToken(&'static str),
+    /// token minted on borrow and burned on repay
+    DebtToken(&'static str),
+    /// Nostra interest rate model, market is the debt token of the event
+    InterestModel,
+}
+
+/// Every lending contract the events are fetched for
+pub const LENDING_PROTOCOLS: [Protocol; 106] = [
+    Protocol::ZkLendMarket,
+    Protocol::ZkLendETH,
+    Protocol::ZkLendUSDC,
+    Protocol::ZkLendUSDT,
+    Protocol::ZkLendWBTC,
+    Protocol::ZkLendDAI,
+    Protocol::NostraAlphaInterestModel,
+    Protocol::NostraAlphaETH,
+    Protocol::NostraAlphaETHCollateral,
+    Protocol::NostraAlphaETHInterest,
+    Protocol::NostraAlphaETHDebt,
+    Protocol::NostraAlphaETHInterestCollateral,
+    Protocol::NostraAlphaUSDC,
+    Protocol::NostraAlphaUSDCCollateral,
+    Protocol::NostraAlphaUSDCInterest,
+    Protocol::NostraAlphaUSDCDebt,
+    Protocol::NostraAlphaUSDCInterestCollateral,
+    Protocol::NostraAlphaUSDT,
+    Protocol::NostraAlphaUSDTCollateral,
+    Protocol::NostraAlphaUSDTInterest,
+    Protocol::NostraAlphaUSDTDebt,
+    Protocol::NostraAlphaUSDTInterestCollateral,
+    Protocol::NostraAlphaDAI,
+    Protocol::NostraAlphaDAICollateral,
+    Protocol::NostraAlphaDAIInterest,
+    Protocol::NostraAlphaDAIDebt,
+    Protocol::NostraAlphaDAIInterestCollateral,
+    Protocol::NostraAlphaWBTC,
+    Protocol::NostraAlphaWBTCCollateral,
+    Protocol::NostraAlphaWBTCInterest,
+    Protocol::NostraAlphaWBTCDebt,
+    Protocol::NostraAlphaWBTCInterestCollateral,
+    Protocol::NostraMainnetInterestModel,
+    Protocol::NostraMainnetETH,
+    Protocol::NostraMainnetETHCollateral,
+    Protocol::NostraMainnetETHInterest,
+    Protocol::NostraMainnetETHDebt,
+    Protocol::NostraMainnetETHInterestCollateral,
+    Protocol::NostraMainnetUSDC,
+    Protocol::NostraMainnetUSDCCollateral,
+    Protocol::NostraMainnetUSDCInterest,
+    Protocol::NostraMainnetUSDCDebt,
+    Protocol::NostraMainnetUSDCInterestCollateral,
+    Protocol::NostraMainnetUSDT,
+    Protocol::NostraMainnetUSDTCollateral,
+    Protocol::NostraMainnetUSDTInterest,
+    Protocol::NostraMainnetUSDTDebt,
+    Protocol::NostraMainnetUSDTInterestCollateral,
+    Protocol::NostraMainnetDAI,
+    Protocol::NostraMainnetDAICollateral,
+    Protocol::NostraMainnetDAIInterest,
+    Protocol::NostraMainnetDAIDebt,
+    Protocol::NostraMainnetDAIInterestCollateral,
+    Protocol::NostraMainnetWBTC,
+    Protocol::NostraMainnetWBTCCollateral,
+    Protocol::NostraMainnetWBTCInterest,
+    Protocol::NostraMainnetWBTCDebt,
+    Protocol::NostraMainnetWBTCInterestCollateral,
+    Protocol::NostraMainnetWSTETH,
+    Protocol::NostraMainnetWSTETHCollateral,
+    Protocol::NostraMainnetWSTETHInterest,
+    Protocol::NostraMainnetWSTETHDebt,
+    Protocol::NostraMainnetWSTETHInterestCollateral,
+    Protocol::NostraMainnetLORDS,
+    Protocol::NostraMainnetLORDSCollateral,
+    Protocol::NostraMainnetLORDSInterest,
+    Protocol::NostraMainnetLORDSDebt,
+    Protocol::NostraMainnetLORDSInterestCollateral,
+    Protocol::NostraMainnetSTRK,
+    Protocol::NostraMainnetSTRKCollateral,
+    Protocol::NostraMainnetSTRKInterest,
+    Protocol::NostraMainnetSTRKDebt,
+    Protocol::NostraMainnetSTRKInterestCollateral,
+    Protocol::NostraMainnetNSTSTRK,
+    Protocol::NostraMainnetNSTSTRKCollateral,
+    Protocol::NostraMainnetNSTSTRKInterest,
+    Protocol::NostraMainnetNSTSTRKDebt,
+    Protocol::NostraMainnetNSTSTRKInterestCollateral,
+    Protocol::NostraMainnetUNO,
+    Protocol::NostraMainnetUNOCollateral,
+    Protocol::NostraMainnetUNOInterest,
+    Protocol::NostraMainnetUNODebt,
+    Protocol::NostraMainnetUNOInterestCollateral,
+    Protocol::NostraMainnetNSTR,
+    Protocol::NostraMainnetNSTRCollateral,
+    Protocol::NostraMainnetNSTRInterest,
+    Protocol::NostraMainnetNSTRDebt,
+    Protocol::NostraMainnetNSTRInterestCollateral,
+    Protocol::NostraMainnetDAIV2,
+    Protocol::NostraMainnetDAIV2Interest,
+    Protocol::NostraMainnetDAIV2Debt,
+    Protocol::Hashstack,
+    Protocol::Hashstack2,
+    Protocol::HashstackBTCRToken,
+    Protocol::HashstackBTCDToken,
+    Protocol::HashstackETHRToken,
+    Protocol::HashstackETHDToken,
+    Protocol::HashstackUSDTRToken,
+    Protocol::HashstackUSDTDToken,
+    Protocol::HashstackUSDCRToken,
+    Protocol::HashstackUSDCDToken,
+    Protocol::HashstackDAIRToken,
+    Protocol::HashstackDAIDToken,
+    Protocol::HashstackStaking,
+    Protocol::HashstackDiamond,
+    Protocol::HashstackL3Diamond,
+];
+
+/// Protocol and kind of the contract, None for contracts whose events
+/// are not decoded - zkLend zTokens only mirror the market events and
+/// Hashstack loans of the diamond and staking contracts are not indexed
+pub fn lending_contract(protocol: &Protocol) -> Option<(LendingProtocol, ContractKind)> {
+    let contract = match protocol {
+        Protocol::ZkLendMarket => (LendingProtocol::ZkLend, ContractKind::Market),
+        Protocol::NostraAlphaInterestModel => {
+            (LendingProtocol::NostraAlpha, ContractKind::InterestModel)
+        }
+        Protocol::NostraAlphaETH
+        | Protocol::NostraAlphaETHCollateral
+        | Protocol::NostraAlphaETHInterest
+        | Protocol::NostraAlphaETHInterestCollateral => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::SupplyToken("eth"),
+        ),
+        Protocol::NostraAlphaETHDebt => {
+            (LendingProtocol::NostraAlpha, ContractKind::DebtToken("eth"))
+        }
+        Protocol::NostraAlphaUSDC
+        | Protocol::NostraAlphaUSDCCollateral
+        | Protocol::NostraAlphaUSDCInterest
+        | Protocol::NostraAlphaUSDCInterestCollateral => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::SupplyToken("usdc"),
+        ),
+        Protocol::NostraAlphaUSDCDebt => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::DebtToken("usdc"),
+        ),
+        Protocol::NostraAlphaUSDT
+        | Protocol::NostraAlphaUSDTCollateral
+        | Protocol::NostraAlphaUSDTInterest
+        | Protocol::NostraAlphaUSDTInterestCollateral => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::SupplyToken("usdt"),
+        ),
+        Protocol::NostraAlphaUSDTDebt => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::DebtToken("usdt"),
+        ),
+        Protocol::NostraAlphaDAI
+        | Protocol::NostraAlphaDAICollateral
+        | Protocol::NostraAlphaDAIInterest
+        | Protocol::NostraAlphaDAIInterestCollateral => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::SupplyToken("dai"),
+        ),
+        Protocol::NostraAlphaDAIDebt => {
+            (LendingProtocol::NostraAlpha, ContractKind::DebtToken("dai"))
+        }
+        Protocol::NostraAlphaWBTC
+        | Protocol::NostraAlphaWBTCCollateral
+        | Protocol::NostraAlphaWBTCInterest
+        | Protocol::NostraAlphaWBTCInterestCollateral => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::SupplyToken("wbtc"),
+        ),
+        Protocol::NostraAlphaWBTCDebt => (
+            LendingProtocol::NostraAlpha,
+            ContractKind::DebtToken("wbtc"),
+        ),
+        Protocol::NostraMainnetInterestModel => {
+            (LendingProtocol::NostraMainnet, ContractKind::InterestModel)
+        }
+        Protocol::NostraMainnetETH
+        | Protocol::NostraMainnetETHCollateral
+        | Protocol::NostraMainnetETHInterest
+        | Protocol::NostraMainnetETHInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("eth"),
+        ),
+        Protocol::NostraMainnetETHDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("eth"),
+        ),
+        Protocol::NostraMainnetUSDC
+        | Protocol::NostraMainnetUSDCCollateral
+        | Protocol::NostraMainnetUSDCInterest
+        | Protocol::NostraMainnetUSDCInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("usdc"),
+        ),
+        Protocol::NostraMainnetUSDCDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("usdc"),
+        ),
+        Protocol::NostraMainnetUSDT
+        | Protocol::NostraMainnetUSDTCollateral
+        | Protocol::NostraMainnetUSDTInterest
+        | Protocol::NostraMainnetUSDTInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("usdt"),
+        ),
+        Protocol::NostraMainnetUSDTDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("usdt"),
+        ),
+        Protocol::NostraMainnetDAI
+        | Protocol::NostraMainnetDAICollateral
+        | Protocol::NostraMainnetDAIInterest
+        | Protocol::NostraMainnetDAIInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("dai"),
+        ),
+        Protocol::NostraMainnetDAIDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("dai"),
+        ),
+        Protocol::NostraMainnetWBTC
+        | Protocol::NostraMainnetWBTCCollateral
+        | Protocol::NostraMainnetWBTCInterest
+        | Protocol::NostraMainnetWBTCInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("wbtc"),
+        ),
+        Protocol::NostraMainnetWBTCDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("wbtc"),
+        ),
+        Protocol::NostraMainnetWSTETH
+        | Protocol::NostraMainnetWSTETHCollateral
+        | Protocol::NostraMainnetWSTETHInterest
+        | Protocol::NostraMainnetWSTETHInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("wsteth"),
+        ),
+        Protocol::NostraMainnetWSTETHDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("wsteth"),
+        ),
+        Protocol::NostraMainnetLORDS
+        | Protocol::NostraMainnetLORDSCollateral
+        | Protocol::NostraMainnetLORDSInterest
+        | Protocol::NostraMainnetLORDSInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("lords"),
+        ),
+        Protocol::NostraMainnetLORDSDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("lords"),
+        ),
+        Protocol::NostraMainnetSTRK
+        | Protocol::NostraMainnetSTRKCollateral
+        | Protocol::NostraMainnetSTRKInterest
+        | Protocol::NostraMainnetSTRKInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("strk"),
+        ),
+        Protocol::NostraMainnetSTRKDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("strk"),
+        ),
+        Protocol::NostraMainnetNSTSTRK
+        | Protocol::NostraMainnetNSTSTRKCollateral
+        | Protocol::NostraMainnetNSTSTRKInterest
+        | Protocol::NostraMainnetNSTSTRKInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("nststrk"),
+        ),
+        Protocol::NostraMainnetNSTSTRKDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("nststrk"),
+        ),
+        Protocol::NostraMainnetUNO
+        | Protocol::NostraMainnetUNOCollateral
+        | Protocol::NostraMainnetUNOInterest
+        | Protocol::NostraMainnetUNOInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("uno"),
+        ),
+        Protocol::NostraMainnetUNODebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("uno"),
+        ),
+        Protocol::NostraMainnetNSTR
+        | Protocol::NostraMainnetNSTRCollateral
+        | Protocol::NostraMainnetNSTRInterest
+        | Protocol::NostraMainnetNSTRInterestCollateral => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("nstr"),
+        ),
+        Protocol::NostraMainnetNSTRDebt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("nstr"),
+        ),
+        Protocol::NostraMainnetDAIV2 | Protocol::NostraMainnetDAIV2Interest => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::SupplyToken("daiv2"),
+        ),
+        Protocol::NostraMainnetDAIV2Debt => (
+            LendingProtocol::NostraMainnet,
+            ContractKind::DebtToken("daiv2"),
+        ),
+        Protocol::HashstackBTCRToken => (
+            LendingProtocol::Hashstack,
+            ContractKind::SupplyToken("wbtc"),
+        ),
+        Protocol::HashstackBTCDToken => {
+            (LendingProtocol::Hashstack, ContractKind::DebtToken("wbtc"))
+        }
+        Protocol::HashstackETHRToken => {
+            (LendingProtocol::Hashstack, ContractKind::SupplyToken("eth"))
+        }
+        Protocol::HashstackETHDToken => {
+            (LendingProtocol::Hashstack, ContractKind::DebtToken("eth"))
+        }
+        Protocol::HashstackUSDTRToken => (
+            LendingProtocol::Hashstack,
+            ContractKind::SupplyToken("usdt"),
+        ),
+        Protocol::HashstackUSDTDToken => {
+            (LendingProtocol::Hashstack, ContractKind::DebtToken("usdt"))
+        }
+        Protocol::HashstackUSDCRToken => (
+            LendingProtocol::Hashstack,
+            ContractKind::SupplyToken("usdc"),
+        ),
+        Protocol::HashstackUSDCDToken => {
+            (LendingProtocol::Hashstack, ContractKind::DebtToken("usdc"))
+        }
+        Protocol::HashstackDAIRToken => {
+            (LendingProtocol::Hashstack, ContractKind::SupplyToken("dai"))
+        }
+        Protocol::HashstackDAIDToken => {
+            (LendingProtocol::Hashstack, ContractKind::DebtToken("dai"))
+        }
+        _ => return None,
+    };
+    Some(contract)
+}
+
+// underlying tokens of the zkLend markets
+const ZKLEND_TOKENS: [(&str, &str); 6] = [
+    (
+        "0x49d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7",
+        "eth",
+    ),
+    (
+        "0x53c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8",
+        "usdc",
+    ),
+    (
+        "0x68f5c6a61780768455de69077e07e89787839bf8166decfbf92b645209c0fb8",
+        "usdt",
+    ),
+    (
+        "0x3fe2b97c1fd336e750087d68b9b867997fd64a2661ff3ca5a7c771641e8e7ac",
+        "wbtc",
+    ),
+    (
+        "0xda114221cb83fa859dbdb4c44beeaa0bb37c7537ad5ae66fe5e0efd20e6eb3",
+        "dai",
+    ),
+    (
+        "0x4718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d",
+        "strk",
+    ),
+];
+
+/// Market of the zkLend event by its token
+pub fn zklend_market(token: &str) -> Option<&'static str> {
+    let token = normalize_address(token);
+    ZKLEND_TOKENS
+        .iter()
+        .find(|(address, _)| *address == token)
+        .map(|(_, market)| *market)
+}
+
+/// Market of the debt token, used for the rates of the Nostra interest model
+pub fn debt_token_market(lending_protocol: LendingProtocol, token: &str) -> Option<&'static str> {
+    let token = normalize_address(token);
+    LENDING_PROTOCOLS
+        .iter()
+        .find_map(|protocol| match lending_contract(protocol) {
+            Some((p, ContractKind::DebtToken(market)))
+                if p == lending_protocol
+                    && normalize_address(protocol_address(&Network::Mainnet, protocol))
+                        == token =>
+            {
+                Some(market)
+            }
+            _ => None,
+        })
+}
+
+/// Decimals of the underlying token of the market
+pub fn market_decimals(market: &str) -> Option<u32> {
+    match market {
+        "usdc" | "usdt" => Some(6),
+        "wbtc" => Some(8),
+        "eth" | "dai" | "daiv2" | "strk" | "wsteth" | "lords" | "nststrk" | "uno" | "nstr" => {
+            Some(18)
+        }
+        _ => None,
+    }
+}
+
+/// Markets of the protocol, sorted
+pub fn protocol_markets(lending_protocol: LendingProtocol) -> Vec<&'static str> {
+    let mut markets: Vec<&'static str> = match lending_protocol {
+        LendingProtocol::ZkLend => ZKLEND_TOKENS.iter().map(|(_, market)| *market).collect(),
+        _ => LENDING_PROTOCOLS
+            .iter()
+            .filter_map(|protocol| match lending_contract(protocol) {
+                Some((p, ContractKind::SupplyToken(market) | ContractKind::DebtToken(market)))
+                    if p == lending_protocol =>
+                {
+                    Some(market)
+                }
+                _ => None,
+            })
+            .collect(),
+    };
+    markets.sort();
+    markets.dedup();
+    markets
+}
+
+/// Totals and rates of the market with history of the days it had events,
+/// None without events.
+/// Totals are net flows - accrued interest is not included, so repaying or
+/// withdrawing interest lowers them below the actual market size
+pub fn market_stats(
+    protocol: &str,
+    market: &str,
+    events: &[LendingEvent],
+) -> Option<LendingMarketStats> {
+    let last_block = events.last()?.block_number;
+
+    let mut point = LendingMarketPoint {
+        timestamp: events[0].timestamp / DAY_SECS * DAY_SECS,
+        total_supply: 0.0,
+        total_borrow: 0.0,
+        lending_rate: None,
+        borrowing_rate: None,
+    };
+    let mut history = vec![];
+
+    for event in events {
+        let day = event.timestamp / DAY_SECS * DAY_SECS;
+        if day != point.timestamp {
+            history.push(point);
+            point.timestamp = day;
+        }
+
+        let amount = event.amount.unwrap_or(0.0);
+        match LendingAction::parse(&event.action) {
+            Some(LendingAction::Deposit) => point.total_supply += amount,
+            Some(LendingAction::Withdraw) => point.total_supply -= amount,
+            Some(LendingAction::Borrow) => point.total_borrow += amount,
+            Some(LendingAction::Repay | LendingAction::Liquidation) => point.total_borrow -= amount,
+            Some(LendingAction::RateUpdate) => {
+                point.lending_rate = event.lending_rate;
+                point.borrowing_rate = event.borrowing_rate;
+            }
+            None => {}
+        }
+    }
+    history.push(point);
+
+    let utilization = match point.total_supply > 0.0 {
+        true => Some(point.total_borrow / point.total_supply),
+        false => None,
+    };
+
+    Some(LendingMarketStats {
+        protocol: protocol.to_string(),
+        market: market.to_string(),
+        total_supply: point.total_supply,
+        total_borrow: point.total_borrow,
+        utilization,
+        lending_rate: point.lending_rate,
+        borrowing_rate: point.borrowing_rate,
+        last_block,
+        history,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lending_event(action: LendingAction, timestamp: i64, amount: f64) -> LendingEvent {
+        let rate = match action {
+            LendingAction::RateUpdate => Some(amount),
+            _ => None,
+        };
+        LendingEvent {
+            id: format!("{}_{}", timestamp, action.as_str()),
+            protocol: "zklend".to_string(),
+            market: "eth".to_string(),
+            action: action.as_str().to_string(),
+            user_address: None,
+            amount: rate.is_none().then_some(amount),
+            lending_rate: rate,
+            borrowing_rate: rate.map(|r| r * 2.0),
+            block_number: timestamp / 10,
+            event_index: 0,
+            timestamp,
+            transaction_hash: "0x1".to_string(),
+        }
+    }
+
+    #[test]
+    fn aggregates_market_by_day() {
+        let events = vec![
+            lending_event(LendingAction::Deposit, 100, 10.0),
+            lending_event(LendingAction::Borrow, 200, 4.0),
+            lending_event(LendingAction::RateUpdate, 300, 0.02),
+            lending_event(LendingAction::Withdraw, DAY_SECS + 100, 2.0),
+            lending_event(LendingAction::Repay, 3 * DAY_SECS, 1.0),
+            lending_event(LendingAction::Liquidation, 3 * DAY_SECS + 1, 1.0),
+        ];
+
+        let stats = market_stats("zklend", "eth", &events).unwrap();
+        assert_eq!(stats.total_supply, 8.0);
+        assert_eq!(stats.total_borrow, 2.0);
+        assert_eq!(stats.utilization, Some(0.25));
+        assert_eq!(stats.lending_rate, Some(0.02));
+        assert_eq!(stats.borrowing_rate, Some(0.04));
+        assert_eq!(stats.last_block, (3 * DAY_SECS + 1) / 10);
+
+        let days: Vec<(i64, f64, f64)> = stats
+            .history
+            .iter()
+            .map(|p| (p.timestamp, p.total_supply, p.total_borrow))
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                (0, 10.0, 4.0),
+                (DAY_SECS, 8.0, 4.0),
+                (3 * DAY_SECS, 8.0, 2.0)
+            ]
+        );
+        assert_eq!(stats.history[1].lending_rate, Some(0.02));
+
+        assert_eq!(market_stats("zklend", "eth", &[]), None);
+    }
+
+    #[test]
+    fn markets_have_decimals() {
+        for protocol in LendingProtocol::ALL {
+            let markets = protocol_markets(protocol);
+            assert!(!markets.is_empty());
+            for market in markets {
+                assert!(market_decimals(market).is_some(), "{}", market);
+            }
+        }
+        assert_eq!(protocol_markets(LendingProtocol::NostraAlpha).len(), 5);
+        assert_eq!(protocol_markets(LendingProtocol::NostraMainnet).len(), 12);
+    }
+
+    #[test]
+    fn finds_markets_by_address() {
+        assert_eq!(
+            zklend_market("0x068f5c6a61780768455de69077e07e89787839bf8166decfbf92b645209c0fb8"),
+            Some("usdt")
+        );
+        assert_eq!(zklend_market("0x123"), None);
+
+        let debt = protocol_address(&Network::Mainnet, &Protocol::NostraMainnetUSDCDebt);
+        assert_eq!(
+            debt_token_market(LendingProtocol::NostraMainnet, debt),
+            Some("usdc")
+        );
+        assert_eq!(debt_token_market(LendingProtocol::NostraAlpha, debt), None);
+    }
+
+    #[test]
+    fn protocol_ids_round_trip() {
+        for protocol in LendingProtocol::ALL {
+            assert_eq!(LendingProtocol::from_id(protocol.id()), Some(protocol));
+        }
+        assert_eq!(
+            lending_contract(&Protocol::HashstackBTCDToken),
+            Some((LendingProtocol::Hashstack, ContractKind::DebtToken("wbtc")))
+        );
+        assert_eq!(lending_contract(&Protocol::ZkLendETH), None);
+    }
+}
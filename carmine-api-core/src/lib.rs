@@ -1,7 +1,17 @@
 pub mod constants;
+pub mod governance;
+pub mod insurance;
+pub mod lending;
 pub mod network;
+pub mod pail;
 pub mod pool;
+pub mod referral;
+pub mod referral_abuse;
 pub mod schema;
+pub mod selectors;
+pub mod signature;
 pub mod telegram_bot;
+#[cfg(test)]
+mod test_utils;
 pub mod types;
 pub mod utils;
@@ -23,7 +23,7 @@ pub const NEW_AMM_GENESIS_TIMESTAMP: i64 = 1705078858;
 const MAINNET_STARKSCAN_API_BASE_URL: &str = "https://api.starkscan.co/api/v0/events";
 const TESTNET_STARKSCAN_API_BASE_URL: &str = "https://api-testnet.starkscan.co/api/v0/events";
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Protocol {
     CarmineOptions,
     CarmineGovernance,
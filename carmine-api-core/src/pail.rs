@@ -0,0 +1,419 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    network::Network,
+    pool::token_by_address,
+    types::{Hedge, PailStats, PailToken, TokenAttribute},
+    utils::{canonical_address, normalize_address},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HedgeStatus {
+    Open,
+    Closed,
+    Settled,
+}
+
+impl HedgeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HedgeStatus::Open => "open",
+            HedgeStatus::Closed => "closed",
+            HedgeStatus::Settled => "settled",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "open" => Some(HedgeStatus::Open),
+            "closed" => Some(HedgeStatus::Closed),
+            "settled" => Some(HedgeStatus::Settled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgeOpened {
+    pub token_id: u64,
+    pub user: String,
+    /// in base token
+    pub amount: f64,
+    pub quote_token: String,
+    pub base_token: String,
+    pub maturity: i64,
+    pub at_price: f64,
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub transaction_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgeFinalized {
+    pub token_id: u64,
+    pub user: String,
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub transaction_hash: String,
+}
+
+/// Decoded event of the Pail contract
+#[derive(Debug, Clone, PartialEq)]
+pub enum HedgeEvent {
+    Opened(HedgeOpened),
+    Closed(HedgeFinalized),
+    Settled(HedgeFinalized),
+}
+
+/// Lowercase symbol of the pool token, as used by the oracle pairs
+fn symbol(address: &str) -> Option<String> {
+    token_by_address(&Network::Mainnet, address).map(|token| token.symbol.to_lowercase())
+}
+
+/// Joins opened hedges with the event that finalized them by token id, newest first.
+/// `usd_price` gives the current USD price of a token by its lowercase symbol
+/// and values the open hedges, finalizing events without an open are skipped
+pub fn hedge_ledger(
+    events: &[HedgeEvent],
+    now: i64,
+    usd_price: impl Fn(&str) -> Option<f64>,
+) -> Vec<Hedge> {
+    let mut finalized: HashMap<u64, (HedgeStatus, &HedgeFinalized)> = HashMap::new();
+    for event in events {
+        let (status, data) = match event {
+            HedgeEvent::Closed(data) => (HedgeStatus::Closed, data),
+            HedgeEvent::Settled(data) => (HedgeStatus::Settled, data),
+            HedgeEvent::Opened(_) => continue,
+        };
+        // a token is finalized once, keep the earliest event
+        finalized
+            .entry(data.token_id)
+            .and_modify(|current| {
+                if data.block_number < current.1.block_number {
+                    *current = (status, data);
+                }
+            })
+            .or_insert((status, data));
+    }
+
+    let mut hedges: Vec<Hedge> = events
+        .iter()
+        .filter_map(|event| match event {
+            HedgeEvent::Opened(opened) => Some(opened),
+            _ => None,
+        })
+        .map(|opened| {
+            let base_symbol = symbol(&opened.base_token);
+            let quote_symbol = symbol(&opened.quote_token);
+            let end = finalized.get(&opened.token_id);
+
+            let (status, duration) = match end {
+                Some((status, data)) => (*status, data.timestamp - opened.timestamp),
+                None => (HedgeStatus::Open, now - opened.timestamp),
+            };
+
+            let base_usd = base_symbol.as_deref().and_then(&usd_price);
+            let quote_usd = quote_symbol.as_deref().and_then(&usd_price);
+            let (current_price, value_usd) = match status {
+                HedgeStatus::Open => (
+                    base_usd
+                        .zip(quote_usd)
+                        .filter(|(_, quote)| *quote > 0.0)
+                        .map(|(base, quote)| base / quote),
+                    base_usd.map(|base| base * opened.amount),
+                ),
+                _ => (None, None),
+            };
+
+            Hedge {
+                token_id: opened.token_id,
+                // joined with addresses from requests
+                user: canonical_address(&opened.user),
+                status: status.as_str().to_string(),
+                base_token: normalize_address(&opened.base_token),
+                quote_token: normalize_address(&opened.quote_token),
+                base_symbol,
+                quote_symbol,
+                amount: opened.amount,
+                at_price: opened.at_price,
+                maturity: opened.maturity,
+                opened_block: opened.block_number,
+                opened_timestamp: opened.timestamp,
+                opened_transaction_hash: opened.transaction_hash.to_string(),
+                finalized_block: end.map(|(_, data)| data.block_number),
+                finalized_timestamp: end.map(|(_, data)| data.timestamp),
+                finalized_transaction_hash: end.map(|(_, data)| data.transaction_hash.to_string()),
+                duration,
+                current_price,
+                value_usd,
+            }
+        })
+        .collect();
+
+    hedges.sort_by_key(|hedge| Reverse(hedge.token_id));
+    hedges
+}
+
+pub fn pail_stats(hedges: &[Hedge]) -> PailStats {
+    let count = |status: HedgeStatus| {
+        hedges
+            .iter()
+            .filter(|hedge| hedge.status == status.as_str())
+            .count()
+    };
+
+    let finalized_durations: Vec<i64> = hedges
+        .iter()
+        .filter(|hedge| hedge.finalized_timestamp.is_some())
+        .map(|hedge| hedge.duration)
+        .collect();
+    let average_duration = match finalized_durations.len() {
+        0 => None,
+        n => Some(finalized_durations.iter().sum::<i64>() as f64 / n as f64),
+    };
+
+    PailStats {
+        hedges: hedges.len(),
+        open: count(HedgeStatus::Open),
+        closed: count(HedgeStatus::Closed),
+        settled: count(HedgeStatus::Settled),
+        users: hedges
+            .iter()
+            .map(|hedge| hedge.user.as_str())
+            .collect::<HashSet<&str>>()
+            .len(),
+        open_value_usd: hedges.iter().filter_map(|hedge| hedge.value_usd).sum(),
+        average_duration,
+    }
+}
+
+/// Uppercase symbol of the token, its address when it is not a pool token
+fn display_symbol(symbol: &Option<String>, address: &str) -> String {
+    symbol
+        .as_ref()
+        .map(|symbol| symbol.to_uppercase())
+        .unwrap_or_else(|| address.to_string())
+}
+
+/// Number with at most `decimals` decimal places, without trailing zeros
+fn format_number(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    match formatted.contains('.') {
+        true => formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+        false => formatted,
+    }
+}
+
+/// "YYYY-MM-DD" of the unix timestamp in UTC
+fn utc_date(timestamp: i64) -> String {
+    // days to civil date, http://howardhinnant.github.io/date_algorithms.html
+    let z = timestamp.div_euclid(24 * 60 * 60) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn attribute(
+    trait_type: &str,
+    value: serde_json::Value,
+    display_type: Option<&str>,
+) -> TokenAttribute {
+    TokenAttribute {
+        trait_type: trait_type.to_string(),
+        value,
+        display_type: display_type.map(String::from),
+    }
+}
+
+/// ERC-721 metadata of the hedge token with OpenSea attributes
+pub fn hedge_token_metadata(hedge: &Hedge, image: String) -> PailToken {
+    let base = display_symbol(&hedge.base_symbol, &hedge.base_token);
+    let quote = display_symbol(&hedge.quote_symbol, &hedge.quote_token);
+
+    PailToken {
+        name: format!("Carmine Pail hedge #{}", hedge.token_id),
+        description: format!(
+            "Hedge of {} {} at {} {}, matures {}.",
+            format_number(hedge.amount, 4),
+            base,
+            format_number(hedge.at_price, 2),
+            quote,
+            utc_date(hedge.maturity)
+        ),
+        token_id: hedge.token_id,
+        image,
+        attributes: vec![
+            attribute("Base token", base.into(), None),
+            attribute("Quote token", quote.into(), None),
+            attribute("Amount", hedge.amount.into(), Some("number")),
+            attribute("Hedged price", hedge.at_price.into(), Some("number")),
+            attribute("Maturity", hedge.maturity.into(), Some("date")),
+            attribute("Status", hedge.status.as_str().into(), None),
+        ],
+    }
+}
+
+/// Image of the hedge token, the same hedge always renders the same SVG
+pub fn hedge_svg(hedge: &Hedge) -> String {
+    let base = display_symbol(&hedge.base_symbol, &hedge.base_token);
+    let quote = display_symbol(&hedge.quote_symbol, &hedge.quote_token);
+
+    // token id picks the colors, neighbouring tokens get distant hues
+    let hue = (hedge.token_id % 360 * 137) % 360;
+    let accent = (hue + 40) % 360;
+
+    let (pair, amount) = match (hedge.base_symbol.is_some(), hedge.quote_symbol.is_some()) {
+        (true, true) => (
+            format!("{} / {}", base, quote),
+            format!("{} {}", format_number(hedge.amount, 4), base),
+        ),
+        _ => ("Unknown pair".to_string(), format_number(hedge.amount, 4)),
+    };
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400" viewBox="0 0 400 400">
+<defs><linearGradient id="bg" x1="0" y1="0" x2="1" y2="1">
+<stop offset="0" stop-color="hsl({hue},70%,22%)"/><stop offset="1" stop-color="hsl({accent},70%,38%)"/>
+</linearGradient></defs>
+<rect width="400" height="400" rx="24" fill="url(#bg)"/>
+<g fill="#ffffff" font-family="Helvetica, Arial, sans-serif">
+<text x="32" y="56" font-size="18" letter-spacing="4" opacity="0.8">CARMINE PAIL</text>
+<text x="32" y="112" font-size="40" font-weight="bold">#{token_id}</text>
+<text x="32" y="184" font-size="28">{pair}</text>
+<text x="32" y="236" font-size="22">{amount}</text>
+<text x="32" y="272" font-size="22">at {price} {quote}</text>
+<text x="32" y="308" font-size="18" opacity="0.8">matures {maturity}</text>
+<rect x="32" y="332" width="120" height="36" rx="18" fill="#ffffff" fill-opacity="0.2"/>
+<text x="92" y="356" font-size="16" text-anchor="middle">{status}</text>
+</g>
+</svg>"##,
+        hue = hue,
+        accent = accent,
+        token_id = hedge.token_id,
+        pair = pair,
+        amount = amount,
+        price = format_number(hedge.at_price, 2),
+        quote = quote,
+        maturity = utc_date(hedge.maturity),
+        status = hedge.status.to_uppercase(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETH: &str = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+    const USDC: &str = "0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8";
+
+    fn opened(token_id: u64, user: &str, timestamp: i64) -> HedgeEvent {
+        HedgeEvent::Opened(HedgeOpened {
+            token_id,
+            user: user.to_string(),
+            amount: 2.0,
+            quote_token: USDC.to_string(),
+            base_token: ETH.to_string(),
+            maturity: 1800000000,
+            at_price: 3000.0,
+            block_number: timestamp,
+            timestamp,
+            transaction_hash: "0x1".to_string(),
+        })
+    }
+
+    fn finalized(token_id: u64, timestamp: i64) -> HedgeFinalized {
+        HedgeFinalized {
+            token_id,
+            user: "0xa".to_string(),
+            block_number: timestamp,
+            timestamp,
+            transaction_hash: "0x2".to_string(),
+        }
+    }
+
+    #[test]
+    fn joins_hedges_by_token_id() {
+        let events = vec![
+            opened(1, "0x0a", 100),
+            opened(2, "0xa", 200),
+            opened(3, "0xb", 300),
+            HedgeEvent::Closed(finalized(1, 150)),
+            HedgeEvent::Settled(finalized(2, 1200)),
+            // finalized without open is skipped
+            HedgeEvent::Closed(finalized(9, 500)),
+        ];
+        let price = |symbol: &str| match symbol {
+            "eth" => Some(3300.0),
+            "usdc" => Some(1.0),
+            _ => None,
+        };
+
+        let hedges = hedge_ledger(&events, 1000, price);
+        let ids: Vec<u64> = hedges.iter().map(|h| h.token_id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        let open = &hedges[0];
+        assert_eq!(open.status, "open");
+        assert_eq!(open.duration, 700);
+        assert_eq!(open.base_symbol.as_deref(), Some("eth"));
+        assert_eq!(open.current_price, Some(3300.0));
+        assert_eq!(open.value_usd, Some(6600.0));
+
+        assert_eq!(hedges[1].status, "settled");
+        assert_eq!(hedges[1].duration, 1000);
+        assert_eq!(hedges[1].value_usd, None);
+        assert_eq!(hedges[2].status, "closed");
+        assert_eq!(hedges[2].user, "0xa");
+        assert_eq!(hedges[2].finalized_transaction_hash.as_deref(), Some("0x2"));
+
+        let stats = pail_stats(&hedges);
+        assert_eq!((stats.open, stats.closed, stats.settled), (1, 1, 1));
+        assert_eq!(stats.users, 2);
+        assert_eq!(stats.open_value_usd, 6600.0);
+        assert_eq!(stats.average_duration, Some(525.0));
+    }
+
+    #[test]
+    fn renders_token_metadata_and_image() {
+        let hedges = hedge_ledger(&[opened(12, "0xa", 100)], 200, |_| None);
+        let hedge = &hedges[0];
+
+        assert_eq!(utc_date(hedge.maturity), "2027-01-15");
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(format_number(2.50, 4), "2.5");
+        assert_eq!(format_number(3000.0, 2), "3000");
+
+        let metadata = hedge_token_metadata(hedge, "https://x/12.svg".to_string());
+        assert_eq!(metadata.name, "Carmine Pail hedge #12");
+        assert_eq!(
+            metadata.description,
+            "Hedge of 2 ETH at 3000 USDC, matures 2027-01-15."
+        );
+        let traits: Vec<(&str, String)> = metadata
+            .attributes
+            .iter()
+            .map(|a| (a.trait_type.as_str(), a.value.to_string()))
+            .collect();
+        assert_eq!(traits[0], ("Base token", "\"ETH\"".to_string()));
+        assert_eq!(traits[4], ("Maturity", "1800000000".to_string()));
+        assert_eq!(metadata.attributes[4].display_type.as_deref(), Some("date"));
+        assert_eq!(traits[5], ("Status", "\"open\"".to_string()));
+
+        let svg = hedge_svg(hedge);
+        assert_eq!(svg, hedge_svg(hedge));
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">ETH / USDC<"));
+        assert!(svg.contains(">at 3000 USDC<"));
+        assert!(svg.contains(">OPEN<"));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{network::Network, utils::normalize_address};
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Token {
     pub address: &'static str,
     pub symbol: &'static str,
@@ -338,6 +338,15 @@ pub fn get_all_pools(network: &Network) -> Vec<Pool> {
     }
 }
 
+/// Base or quote token of the pools with the address
+pub fn token_by_address(network: &Network, address: &str) -> Option<Token> {
+    let address = normalize_address(address);
+    get_all_pools(network)
+        .into_iter()
+        .flat_map(|pool| [pool.base, pool.quote])
+        .find(|token| normalize_address(token.address) == address)
+}
+
 pub fn get_all_pool_addresses(network: &Network) -> Vec<&'static str> {
     get_all_pools(network)
         .iter()
@@ -0,0 +1,338 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    env, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    types::{
+        ReferralEventDigest, ReferredWallet, ReferrerDetail, ReferrerSummary, TradeEventWithPrice,
+    },
+    utils::canonical_address,
+};
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RewardTier {
+    /// volume of all wallets of the referrer needed for the tier
+    pub min_volume_usd: f64,
+    /// part of the premia paid by the referred wallets owed to the referrer
+    pub premia_share: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RewardSchedule {
+    /// premia of trades made this many days after the referral are rewarded, None for all
+    pub window_days: Option<i64>,
+    pub tiers: Vec<RewardTier>,
+}
+
+impl RewardSchedule {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let schedule: RewardSchedule =
+            serde_json::from_str(json).map_err(|e| format!("Invalid reward schedule: {}", e))?;
+
+        if schedule
+            .tiers
+            .iter()
+            .any(|tier| !(0.0..=1.0).contains(&tier.premia_share))
+        {
+            return Err("Reward share must be between 0 and 1".to_string());
+        }
+        if schedule
+            .tiers
+            .windows(2)
+            .any(|pair| pair[0].min_volume_usd >= pair[1].min_volume_usd)
+        {
+            return Err("Reward tiers must be sorted by min_volume_usd".to_string());
+        }
+        Ok(schedule)
+    }
+
+    /// Schedule from the file in REFERRAL_REWARDS_CONFIG, bundled referral_rewards.json if not set
+    pub fn load() -> Result<Self, String> {
+        match env::var("REFERRAL_REWARDS_CONFIG") {
+            Ok(path) => {
+                let json = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed reading {}: {}", path, e))?;
+                RewardSchedule::parse(&json)
+            }
+            Err(_) => RewardSchedule::parse(include_str!("../referral_rewards.json")),
+        }
+    }
+
+    /// Share of the highest tier the volume reached, 0 below all tiers
+    pub fn share(&self, volume_usd: f64) -> f64 {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| volume_usd >= tier.min_volume_usd)
+            .map_or(0.0, |tier| tier.premia_share)
+    }
+
+    fn rewarded(&self, referred_at: i64, timestamp: i64) -> bool {
+        match self.window_days {
+            Some(days) => timestamp - referred_at <= days * DAY_SECONDS,
+            None => true,
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// (referrer, referral code, referred at) by referred wallet,
+/// wallets referred more than once count for the first referral
+pub fn first_referrals(
+    referrals: &[ReferralEventDigest],
+) -> HashMap<String, (String, String, i64)> {
+    let mut sorted: Vec<&ReferralEventDigest> = referrals.iter().collect();
+    sorted.sort_by_key(|referral| referral.timestamp);
+
+    let mut first: HashMap<String, (String, String, i64)> = HashMap::new();
+    for referral in sorted {
+        first
+            .entry(canonical_address(&referral.referred_wallet_address))
+            .or_insert_with(|| {
+                (
+                    canonical_address(&referral.referee_wallet_address),
+                    referral.referral_code.to_string(),
+                    unix_seconds(referral.timestamp),
+                )
+            });
+    }
+    first
+}
+
+fn referred_wallet(
+    address: String,
+    referred_at: i64,
+    trades: &[&TradeEventWithPrice],
+    schedule: &RewardSchedule,
+    flagged: bool,
+) -> ReferredWallet {
+    let trades: Vec<&&TradeEventWithPrice> = trades
+        .iter()
+        .filter(|trade| trade.timestamp >= referred_at)
+        .collect();
+
+    ReferredWallet {
+        address,
+        referred_at,
+        first_trade: trades.iter().map(|trade| trade.timestamp).min(),
+        trades: trades.len(),
+        volume_usd: trades
+            .iter()
+            .map(|trade| trade.capital_transfered_usd as f64)
+            .sum(),
+        premia_usd: trades.iter().map(|trade| trade.premia_usd as f64).sum(),
+        rewarded_premia_usd: trades
+            .iter()
+            .filter(|trade| schedule.rewarded(referred_at, trade.timestamp))
+            .map(|trade| trade.premia_usd as f64)
+            .sum(),
+        flagged,
+    }
+}
+
+/// Trades of each wallet by its canonical address, trades of the same
+/// wallet stored padded and unpadded are merged
+pub(crate) fn trades_by_user(
+    user_trades: &HashMap<String, Vec<TradeEventWithPrice>>,
+) -> HashMap<String, Vec<&TradeEventWithPrice>> {
+    let mut trades_by_user: HashMap<String, Vec<&TradeEventWithPrice>> = HashMap::new();
+    for (user, trades) in user_trades {
+        trades_by_user
+            .entry(canonical_address(user))
+            .or_default()
+            .extend(trades.iter());
+    }
+    trades_by_user
+}
+
+/// Wallets referred by each referrer with the volume and premia of their trades
+/// since the referral and the rewards owed under the schedule, highest rewards first;
+/// `flagged` wallets are listed but not counted
+pub fn referrer_stats(
+    referrals: &[ReferralEventDigest],
+    user_trades: &HashMap<String, Vec<TradeEventWithPrice>>,
+    schedule: &RewardSchedule,
+    flagged: &HashSet<String>,
+) -> Vec<ReferrerDetail> {
+    let trades_by_user = trades_by_user(user_trades);
+
+    let mut by_referrer: HashMap<String, (String, Vec<ReferredWallet>)> = HashMap::new();
+    for (wallet, (referrer, code, referred_at)) in first_referrals(referrals) {
+        let trades = trades_by_user
+            .get(&wallet)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let is_flagged = flagged.contains(&wallet);
+        let stats = referred_wallet(wallet, referred_at, trades, schedule, is_flagged);
+        by_referrer
+            .entry(referrer)
+            .or_insert_with(|| (code, vec![]))
+            .1
+            .push(stats);
+    }
+
+    let mut referrers: Vec<ReferrerDetail> = by_referrer
+        .into_iter()
+        .map(|(address, (referral_code, mut wallets))| {
+            wallets.sort_by(|a, b| {
+                a.referred_at
+                    .cmp(&b.referred_at)
+                    .then_with(|| a.address.cmp(&b.address))
+            });
+            let counted: Vec<&ReferredWallet> =
+                wallets.iter().filter(|wallet| !wallet.flagged).collect();
+            let volume_usd: f64 = counted.iter().map(|wallet| wallet.volume_usd).sum();
+            let rewarded_premia: f64 = counted
+                .iter()
+                .map(|wallet| wallet.rewarded_premia_usd)
+                .sum();
+            let reward_share = schedule.share(volume_usd);
+
+            ReferrerDetail {
+                summary: ReferrerSummary {
+                    position: 0,
+                    address,
+                    referral_code,
+                    referred_wallets: counted.len(),
+                    flagged_wallets: wallets.len() - counted.len(),
+                    trading_wallets: counted.iter().filter(|wallet| wallet.trades > 0).count(),
+                    volume_usd,
+                    premia_usd: counted.iter().map(|wallet| wallet.premia_usd).sum(),
+                    reward_share,
+                    rewards_usd: reward_share * rewarded_premia,
+                },
+                wallets,
+            }
+        })
+        .collect();
+
+    referrers.sort_by(|a, b| {
+        b.summary
+            .rewards_usd
+            .partial_cmp(&a.summary.rewards_usd)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                b.summary
+                    .volume_usd
+                    .partial_cmp(&a.summary.volume_usd)
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| a.summary.address.cmp(&b.summary.address))
+    });
+    for (index, referrer) in referrers.iter_mut().enumerate() {
+        referrer.summary.position = index + 1;
+    }
+
+    referrers
+}
+
+/// Part of the referral points of the referrer kept, points of flagged wallets
+/// are removed in proportion to the volume they traded since the referral
+pub fn referral_points_factor(referrer: &ReferrerDetail) -> f64 {
+    let volume: f64 = referrer
+        .wallets
+        .iter()
+        .map(|wallet| wallet.volume_usd)
+        .sum();
+    let flagged_volume: f64 = referrer
+        .wallets
+        .iter()
+        .filter(|wallet| wallet.flagged)
+        .map(|wallet| wallet.volume_usd)
+        .sum();
+
+    match (volume > 0.0, referrer.summary.referred_wallets) {
+        (true, _) => 1.0 - flagged_volume / volume,
+        // nothing traded, keep the points unless every wallet is flagged
+        (false, 0) => 0.0,
+        (false, _) => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{referral, trade};
+
+    #[test]
+    fn reward_schedule() {
+        let schedule = RewardSchedule::parse(include_str!("../referral_rewards.json")).unwrap();
+        assert_eq!(schedule.share(5_000.0), 0.05);
+        assert_eq!(schedule.share(10_000.0), 0.075);
+        assert_eq!(schedule.share(1_000_000.0), 0.1);
+
+        assert!(RewardSchedule::parse(
+            r#"{"window_days":null,"tiers":[{"min_volume_usd":10,"premia_share":0.1},{"min_volume_usd":0,"premia_share":0.2}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn referrer_rewards() {
+        let schedule = RewardSchedule {
+            window_days: Some(1),
+            tiers: vec![
+                RewardTier {
+                    min_volume_usd: 0.0,
+                    premia_share: 0.1,
+                },
+                RewardTier {
+                    min_volume_usd: 1000.0,
+                    premia_share: 0.2,
+                },
+            ],
+        };
+        let referrals = vec![
+            referral("0xa", "0x01", 1000),
+            // second referral of the same wallet is ignored
+            referral("0xb", "0x1", 2000),
+            referral("0xa", "0x2", 3000),
+            referral("0xb", "0x3", 3000),
+        ];
+        let user_trades = HashMap::from([
+            (
+                "0x0001".to_string(),
+                vec![
+                    // before the referral
+                    trade("0x0001", 500, 5000.0, 50.0),
+                    trade("0x0001", 1500, 800.0, 10.0),
+                    // out of the reward window
+                    trade("0x0001", 1000 + 2 * DAY_SECONDS, 400.0, 20.0),
+                ],
+            ),
+            ("0x3".to_string(), vec![trade("0x3", 4000, 100.0, 5.0)]),
+        ]);
+
+        let referrers = referrer_stats(&referrals, &user_trades, &schedule, &HashSet::new());
+        assert_eq!(referrers.len(), 2);
+
+        let a = &referrers[0];
+        assert_eq!(a.summary.address, "0xa");
+        assert_eq!(a.summary.position, 1);
+        assert_eq!(a.summary.referred_wallets, 2);
+        assert_eq!(a.summary.trading_wallets, 1);
+        assert_eq!(a.summary.volume_usd, 1200.0);
+        assert_eq!(a.summary.premia_usd, 30.0);
+        assert_eq!(a.summary.reward_share, 0.2);
+        assert_eq!(a.summary.rewards_usd, 2.0);
+        assert_eq!(a.wallets[0].address, "0x1");
+        assert_eq!(a.wallets[0].first_trade, Some(1500));
+
+        let b = &referrers[1];
+        assert_eq!(b.summary.address, "0xb");
+        assert_eq!(b.summary.referred_wallets, 1);
+        assert_eq!(b.summary.rewards_usd, 0.5);
+    }
+}
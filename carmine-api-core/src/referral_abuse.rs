@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    referral::{first_referrals, trades_by_user},
+    types::{ReferralAbuseReport, ReferralEventDigest, ReferralFlag, TradeEventWithPrice},
+};
+
+/// Trades of the same option made at most this many seconds apart are in lockstep
+pub const LOCKSTEP_WINDOW_SECONDS: i64 = 120;
+/// Lockstep trades two wallets need to be linked
+pub const LOCKSTEP_MIN_TRADES: usize = 3;
+
+const SELF_REFERRAL: &str = "self_referral";
+const CYCLE: &str = "cycle";
+const LOCKSTEP: &str = "lockstep";
+
+/// Rings in the referred by graph, every wallet has at most one referrer
+/// so each wallet is in at most one ring; self-referrals are rings of one
+fn referral_cycles(referrer_of: &HashMap<String, String>) -> Vec<Vec<String>> {
+    // 1 on the current path, 2 done
+    let mut state: HashMap<&str, u8> = HashMap::new();
+    let mut cycles = vec![];
+
+    let mut wallets: Vec<&String> = referrer_of.keys().collect();
+    wallets.sort();
+
+    for start in wallets {
+        let mut path: Vec<&str> = vec![];
+        let mut current = start.as_str();
+        loop {
+            match state.get(current) {
+                None => {
+                    state.insert(current, 1);
+                    path.push(current);
+                    match referrer_of.get(current) {
+                        Some(referrer) => current = referrer,
+                        None => break,
+                    }
+                }
+                Some(1) => {
+                    let position = path
+                        .iter()
+                        .position(|wallet| *wallet == current)
+                        .expect("wallet on the path");
+                    // path follows referrers, reverse for referral order
+                    let mut cycle: Vec<String> =
+                        path[position..].iter().map(|w| w.to_string()).collect();
+                    cycle.reverse();
+                    cycles.push(cycle);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        for wallet in path {
+            state.insert(wallet, 2);
+        }
+    }
+
+    cycles
+}
+
+/// pool, action, option type, side, maturity and strike bits
+type OptionKey<'a> = (&'a str, &'a str, i16, i16, i64, u64);
+
+/// option and position of the trade among the sorted trades of the option
+type TradeRef = (usize, usize);
+
+/// distinct trades of each wallet of a pair matched by a trade of the other
+type MatchedTrades = (HashSet<TradeRef>, HashSet<TradeRef>);
+
+fn find(parents: &mut HashMap<String, String>, wallet: &str) -> String {
+    let parent = parents
+        .get(wallet)
+        .cloned()
+        .unwrap_or_else(|| wallet.to_string());
+    if parent == wallet {
+        return parent;
+    }
+    let root = find(parents, &parent);
+    parents.insert(wallet.to_string(), root.clone());
+    root
+}
+
+/// Groups of wallets linked by at least LOCKSTEP_MIN_TRADES trades of the same option
+/// made within LOCKSTEP_WINDOW_SECONDS of a trade of the other wallet, counted
+/// for both wallets - a burst of one wallet next to a single trade of the other is one
+fn lockstep_clusters(
+    wallets: &BTreeSet<String>,
+    trades_by_user: &HashMap<String, Vec<&TradeEventWithPrice>>,
+) -> Vec<Vec<String>> {
+    let mut by_option: HashMap<OptionKey, Vec<(i64, &str)>> = HashMap::new();
+    for wallet in wallets {
+        for trade in trades_by_user.get(wallet).into_iter().flatten() {
+            by_option
+                .entry((
+                    trade.pool_id.as_str(),
+                    trade.action.as_str(),
+                    trade.option_type,
+                    trade.option_side,
+                    trade.maturity,
+                    trade.strike_price.to_bits(),
+                ))
+                .or_default()
+                .push((trade.timestamp, wallet.as_str()));
+        }
+    }
+
+    let mut matched: HashMap<(&str, &str), MatchedTrades> = HashMap::new();
+    for (option, trades) in by_option.values_mut().enumerate() {
+        trades.sort();
+        for (i, (timestamp, wallet)) in trades.iter().enumerate() {
+            for (j, (other_timestamp, other)) in trades.iter().enumerate().skip(i + 1) {
+                if other_timestamp - timestamp > LOCKSTEP_WINDOW_SECONDS {
+                    break;
+                }
+                if wallet == other {
+                    continue;
+                }
+                let ((a, trade_a), (b, trade_b)) = match wallet < other {
+                    true => ((*wallet, i), (*other, j)),
+                    false => ((*other, j), (*wallet, i)),
+                };
+                let (trades_a, trades_b) = matched.entry((a, b)).or_default();
+                trades_a.insert((option, trade_a));
+                trades_b.insert((option, trade_b));
+            }
+        }
+    }
+
+    let mut parents: HashMap<String, String> = HashMap::new();
+    for ((a, b), (trades_a, trades_b)) in matched {
+        if trades_a.len().min(trades_b.len()) >= LOCKSTEP_MIN_TRADES {
+            for wallet in [a, b] {
+                parents
+                    .entry(wallet.to_string())
+                    .or_insert_with(|| wallet.to_string());
+            }
+            let (root_a, root_b) = (find(&mut parents, a), find(&mut parents, b));
+            parents.insert(root_a, root_b);
+        }
+    }
+
+    let mut clusters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for wallet in wallets {
+        if parents.contains_key(wallet) {
+            let root = find(&mut parents, wallet);
+            clusters.entry(root).or_default().push(wallet.to_string());
+        }
+    }
+
+    clusters
+        .into_values()
+        .filter(|cluster| cluster.len() > 1)
+        .collect()
+}
+
+fn flag<'a>(
+    flags: &'a mut BTreeMap<String, ReferralFlag>,
+    first: &HashMap<String, (String, String, i64)>,
+    wallet: &str,
+) -> &'a mut ReferralFlag {
+    let (referrer, code, referred_at) = &first[wallet];
+    flags
+        .entry(wallet.to_string())
+        .or_insert_with(|| ReferralFlag {
+            referrer: referrer.clone(),
+            referred_wallet: wallet.to_string(),
+            referral_code: code.clone(),
+            referred_at: *referred_at,
+            reasons: vec![],
+            cycle: None,
+            cluster: None,
+        })
+}
+
+/// Flags referrals of wallets referring themselves, wallets referring each other
+/// in rings and wallets of a referrer trading in lockstep with each other or the referrer
+pub fn detect_referral_abuse(
+    referrals: &[ReferralEventDigest],
+    user_trades: &HashMap<String, Vec<TradeEventWithPrice>>,
+) -> ReferralAbuseReport {
+    let first = first_referrals(referrals);
+    let referrer_of: HashMap<String, String> = first
+        .iter()
+        .map(|(wallet, (referrer, _, _))| (wallet.clone(), referrer.clone()))
+        .collect();
+
+    let trades_by_user = trades_by_user(user_trades);
+
+    let mut flags: BTreeMap<String, ReferralFlag> = BTreeMap::new();
+
+    let mut report = ReferralAbuseReport {
+        referrals: first.len(),
+        ..Default::default()
+    };
+
+    for cycle in referral_cycles(&referrer_of) {
+        match cycle.as_slice() {
+            [wallet] => {
+                report.self_referrals += 1;
+                flag(&mut flags, &first, wallet)
+                    .reasons
+                    .push(SELF_REFERRAL.to_string());
+            }
+            _ => {
+                report.cycles += 1;
+                for wallet in &cycle {
+                    let flagged = flag(&mut flags, &first, wallet);
+                    flagged.reasons.push(CYCLE.to_string());
+                    flagged.cycle = Some(cycle.clone());
+                }
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for (wallet, referrer) in &referrer_of {
+        groups
+            .entry(referrer.as_str())
+            .or_insert_with(|| BTreeSet::from([referrer.clone()]))
+            .insert(wallet.clone());
+    }
+    for (referrer, wallets) in groups {
+        for cluster in lockstep_clusters(&wallets, &trades_by_user) {
+            report.lockstep_clusters += 1;
+            let referred: Vec<&String> = cluster
+                .iter()
+                .filter(|wallet| referrer_of.get(*wallet).map(String::as_str) == Some(referrer))
+                .collect();
+            for wallet in referred {
+                let flagged = flag(&mut flags, &first, wallet);
+                flagged.reasons.push(LOCKSTEP.to_string());
+                flagged.cluster = Some(cluster.clone());
+            }
+        }
+    }
+
+    report.flags = flags.into_values().collect();
+    report.flags.sort_by_key(|flag| flag.referred_at);
+    report.flagged = report.flags.len();
+    report
+}
+
+/// Referred wallets excluded from rewards and points
+pub fn flagged_wallets(report: &ReferralAbuseReport) -> HashSet<String> {
+    report
+        .flags
+        .iter()
+        .map(|flag| flag.referred_wallet.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{referral, trade};
+
+    fn option_trade(caller: &str, timestamp: i64, maturity: i64) -> TradeEventWithPrice {
+        TradeEventWithPrice {
+            maturity,
+            ..trade(caller, timestamp, 0.0, 0.0)
+        }
+    }
+
+    fn reasons(report: &ReferralAbuseReport, wallet: &str) -> Vec<String> {
+        report
+            .flags
+            .iter()
+            .find(|flag| flag.referred_wallet == wallet)
+            .map(|flag| flag.reasons.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn flags_self_referrals_and_cycles() {
+        let referrals = vec![
+            referral("0xa", "0x0a", 1),
+            referral("0xb", "0xc", 2),
+            referral("0xc", "0xd", 3),
+            referral("0xd", "0xb", 4),
+            referral("0xb", "0xe", 5),
+        ];
+
+        let report = detect_referral_abuse(&referrals, &HashMap::new());
+        assert_eq!(report.referrals, 5);
+        assert_eq!(report.self_referrals, 1);
+        assert_eq!(report.cycles, 1);
+        assert_eq!(report.flagged, 4);
+        assert_eq!(reasons(&report, "0xa"), vec![SELF_REFERRAL]);
+        assert_eq!(reasons(&report, "0xc"), vec![CYCLE]);
+        // referred by a wallet of the ring, not part of it
+        assert!(reasons(&report, "0xe").is_empty());
+
+        let ring = report.flags[1].cycle.clone().unwrap();
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    fn flags_lockstep_clusters() {
+        let referrals = vec![
+            referral("0xa", "0x1", 1),
+            referral("0xa", "0x2", 2),
+            referral("0xa", "0x3", 3),
+        ];
+        let mut user_trades: HashMap<String, Vec<TradeEventWithPrice>> = HashMap::new();
+        for (i, maturity) in [100, 200, 300].into_iter().enumerate() {
+            let timestamp = 1000 * i as i64;
+            for (wallet, delay) in [("0x1", 0), ("0x0002", 30), ("0xa", 60)] {
+                user_trades
+                    .entry(wallet.to_string())
+                    .or_default()
+                    .push(option_trade(wallet, timestamp + delay, maturity));
+            }
+            // same option, too late
+            user_trades
+                .entry("0x3".to_string())
+                .or_default()
+                .push(option_trade("0x3", timestamp + 600, maturity));
+        }
+
+        let report = detect_referral_abuse(&referrals, &user_trades);
+        assert_eq!(report.lockstep_clusters, 1);
+        assert_eq!(reasons(&report, "0x1"), vec![LOCKSTEP]);
+        assert_eq!(reasons(&report, "0x2"), vec![LOCKSTEP]);
+        assert!(reasons(&report, "0x3").is_empty());
+        assert_eq!(
+            report.flags[0].cluster,
+            Some(vec![
+                "0x1".to_string(),
+                "0x2".to_string(),
+                "0xa".to_string()
+            ])
+        );
+        assert_eq!(
+            flagged_wallets(&report),
+            HashSet::from(["0x1".to_string(), "0x2".to_string()])
+        );
+    }
+
+    #[test]
+    fn single_trade_next_to_a_burst_is_not_lockstep() {
+        let referrals = vec![referral("0xa", "0x1", 1), referral("0xa", "0x2", 2)];
+        let user_trades = HashMap::from([
+            (
+                "0x1".to_string(),
+                vec![
+                    option_trade("0x1", 1000, 100),
+                    option_trade("0x1", 1010, 100),
+                    option_trade("0x1", 1020, 100),
+                ],
+            ),
+            ("0x2".to_string(), vec![option_trade("0x2", 1030, 100)]),
+        ]);
+
+        let report = detect_referral_abuse(&referrals, &user_trades);
+        assert_eq!(report.lockstep_clusters, 0);
+        assert_eq!(report.flagged, 0);
+    }
+}
@@ -139,6 +139,75 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ingestion_checkpoints (stream) {
+        stream -> Text,
+        last_block -> Int8,
+        status -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    raw_events_unparsed (id) {
+        id -> Text,
+        block_hash -> Text,
+        block_number -> Int8,
+        transaction_hash -> Text,
+        event_index -> Int8,
+        from_address -> Text,
+        keys -> Array<Text>,
+        data -> Array<Text>,
+        timestamp -> Int8,
+        key_name -> Nullable<Text>,
+        selector -> Nullable<Text>,
+        reason -> Text,
+        reprocessed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    lending_events (id) {
+        id -> Text,
+        protocol -> Text,
+        market -> Text,
+        action -> Text,
+        user_address -> Nullable<Text>,
+        amount -> Nullable<Double>,
+        lending_rate -> Nullable<Double>,
+        borrowing_rate -> Nullable<Double>,
+        block_number -> Int8,
+        event_index -> Int8,
+        timestamp -> Int8,
+        transaction_hash -> Text,
+    }
+}
+
+diesel::table! {
+    proposals (prop_id) {
+        prop_id -> Int8,
+        payload -> Text,
+        to_upgrade -> Int8,
+        title -> Nullable<Text>,
+        description -> Nullable<Text>,
+        block_number -> Int8,
+        timestamp -> Int8,
+        transaction_hash -> Text,
+        executed_block_number -> Nullable<Int8>,
+        executed_timestamp -> Nullable<Int8>,
+        total_supply -> Nullable<Double>,
+    }
+}
+
+diesel::table! {
+    voting_power (prop_id, user_address) {
+        prop_id -> Int8,
+        user_address -> Text,
+        voting_power -> Double,
+        block_number -> Int8,
+    }
+}
+
 diesel::allow_tables_to_appear_in_same_query!(
     events,
     options,
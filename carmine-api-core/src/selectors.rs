@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+use starknet::core::types::FieldElement;
+use starknet::core::utils::starknet_keccak;
+
+/// Selector of an entrypoint or event, starknet_keccak of its name
+pub fn selector(name: &str) -> String {
+    format!("{:#x}", starknet_keccak(name.as_bytes()))
+}
+
+/// Hex without leading zeroes, "0x0230b..." and "0x230b..." are the same selector
+pub fn normalize_selector(selector: &str) -> Option<String> {
+    FieldElement::from_hex_be(selector)
+        .ok()
+        .map(|felt| format!("{:#x}", felt))
+}
+
+/// Maps selectors back to entrypoint and event names
+#[derive(Debug, Default, Clone)]
+pub struct SelectorRegistry {
+    names: HashMap<String, String>,
+}
+
+impl SelectorRegistry {
+    pub fn new() -> Self {
+        SelectorRegistry::default()
+    }
+
+    pub fn from_names(names: &[&str]) -> Self {
+        let mut registry = SelectorRegistry::new();
+        for name in names {
+            registry.register(name);
+        }
+        registry
+    }
+
+    /// Registers the name and returns its selector
+    pub fn register(&mut self, name: &str) -> String {
+        let selector = selector(name);
+        self.names.insert(selector.clone(), name.to_string());
+        selector
+    }
+
+    /// Registers functions and events of a Cairo 0 or Cairo 1 ABI, either the ABI
+    /// itself or a contract class containing it, returns number of registered names
+    pub fn register_abi(&mut self, abi_json: &str) -> Result<usize, String> {
+        let value: Value =
+            serde_json::from_str(abi_json).map_err(|e| format!("Invalid ABI JSON: {}", e))?;
+
+        let abi = match value {
+            Value::Array(_) => value,
+            Value::Object(mut class) => match class.remove("abi") {
+                // Sierra classes keep the ABI as a string
+                Some(Value::String(abi)) => serde_json::from_str(&abi)
+                    .map_err(|e| format!("Invalid ABI JSON in contract class: {}", e))?,
+                Some(abi) => abi,
+                None => return Err("Contract class has no ABI".to_string()),
+            },
+            _ => return Err("ABI must be an array or a contract class".to_string()),
+        };
+
+        let entries = abi
+            .as_array()
+            .ok_or_else(|| "ABI must be an array".to_string())?;
+
+        let mut names: Vec<String> = vec![];
+        collect_abi_names(entries, &mut names);
+
+        for name in names.iter() {
+            self.register(name);
+        }
+
+        Ok(names.len())
+    }
+
+    pub fn from_abi_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let json = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed reading {}: {}", path.as_ref().display(), e))?;
+        let mut registry = SelectorRegistry::new();
+        registry.register_abi(&json)?;
+        Ok(registry)
+    }
+
+    /// Name registered for the selector
+    pub fn name(&self, selector: &str) -> Option<&str> {
+        normalize_selector(selector)
+            .and_then(|normalized| self.names.get(&normalized))
+            .map(|name| name.as_str())
+    }
+}
+
+fn collect_abi_names(entries: &[Value], names: &mut Vec<String>) {
+    for entry in entries {
+        let name = entry.get("name").and_then(Value::as_str);
+
+        match (entry.get("type").and_then(Value::as_str), name) {
+            (Some("function") | Some("l1_handler"), Some(name)) => names.push(name.to_string()),
+            (Some("event"), Some(name)) => {
+                // Cairo 1 events are named by path, the key is the last segment
+                let short_name = name.rsplit("::").next().unwrap_or(name);
+                names.push(short_name.to_string());
+
+                // variants of the event enum are keys of the emitted events
+                if let Some(variants) = entry.get("variants").and_then(Value::as_array) {
+                    for variant in variants {
+                        if let Some(variant_name) = variant.get("name").and_then(Value::as_str) {
+                            names.push(variant_name.to_string());
+                        }
+                    }
+                }
+            }
+            (Some("interface"), _) => {
+                if let Some(items) = entry.get("items").and_then(Value::as_array) {
+                    collect_abi_names(items, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    names.sort();
+    names.dedup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_selector, selector, SelectorRegistry};
+
+    #[test]
+    fn selector_from_name() {
+        assert_eq!(
+            selector("get_all_options"),
+            "0x230b3b6ebadc35ebd0b91e93d39824daff6574cbe99bb7882037547cbb75197"
+        );
+        assert_eq!(
+            normalize_selector(
+                "0x0230b3b6ebadc35ebd0b91e93d39824daff6574cbe99bb7882037547cbb75197"
+            ),
+            Some(selector("get_all_options"))
+        );
+    }
+
+    #[test]
+    fn names_from_abi() {
+        let abi = r#"[
+            {"type": "interface", "name": "IPail", "items": [
+                {"type": "function", "name": "hedge_open", "inputs": [], "outputs": []}
+            ]},
+            {"type": "event", "name": "pail::Pail::Event", "kind": "enum", "variants": [
+                {"name": "HedgeOpened", "type": "pail::Pail::HedgeOpened", "kind": "nested"}
+            ]},
+            {"type": "event", "name": "pail::Pail::HedgeOpened", "kind": "struct", "members": []}
+        ]"#;
+
+        let mut registry = SelectorRegistry::new();
+        assert_eq!(registry.register_abi(abi), Ok(3));
+        assert_eq!(
+            registry.name("0x2e770a5d835a0fc0bf1f36b9b91399b8216f234b49647eea957ce5808318568"),
+            Some("HedgeOpened")
+        );
+        assert_eq!(registry.name(&selector("hedge_open")), Some("hedge_open"));
+        assert_eq!(registry.name("0x1"), None);
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+
+use starknet::core::utils::{cairo_short_string_to_felt, starknet_keccak};
+use starknet_crypto::{pedersen_hash, verify, FieldElement};
+
+/// Signed requests are accepted at most this many seconds before they expire
+pub const MAX_SIGNATURE_VALIDITY_SECONDS: i64 = 10 * 60;
+
+const DOMAIN_NAME: &str = "Carmine Finance";
+const DOMAIN_VERSION: &str = "1";
+const CHAIN_ID: &str = "SN_MAIN";
+const DOMAIN_TYPE: &str = "StarkNetDomain(name:felt,version:felt,chainId:felt)";
+const MESSAGE_TYPE: &str = "Authentication(action:felt,payload:felt,nonce:felt,expiry:felt)";
+const MESSAGE_PREFIX: &str = "StarkNet Message";
+
+/// SNIP-12 (revision 0) message the account signs to authenticate a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthMessage {
+    /// short string naming the endpoint, eg. "referral_event"
+    pub action: String,
+    /// starknet_keccak of the request body
+    pub payload: FieldElement,
+    pub nonce: FieldElement,
+    /// unix timestamp
+    pub expiry: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    Malformed(String),
+    Expired,
+    /// expiry is further than MAX_SIGNATURE_VALIDITY_SECONDS
+    ExpiryTooFar,
+    Invalid,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::Malformed(msg) => write!(f, "malformed signature: {}", msg),
+            SignatureError::Expired => write!(f, "signature expired"),
+            SignatureError::ExpiryTooFar => write!(
+                f,
+                "signature expiry is more than {} seconds away",
+                MAX_SIGNATURE_VALIDITY_SECONDS
+            ),
+            SignatureError::Invalid => write!(f, "invalid signature"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Hex or decimal felt
+pub fn parse_felt(value: &str) -> Result<FieldElement, SignatureError> {
+    let parsed = match value.starts_with("0x") {
+        true => FieldElement::from_hex_be(value).ok(),
+        false => FieldElement::from_dec_str(value).ok(),
+    };
+    parsed.ok_or_else(|| SignatureError::Malformed(format!("invalid felt \"{}\"", value)))
+}
+
+/// Comma separated felts returned by the wallet, accounts with guardians
+/// append the guardian signature after the (r, s) of the owner
+pub fn parse_signature(value: &str) -> Result<(FieldElement, FieldElement), SignatureError> {
+    let felts = value
+        .split(',')
+        .map(|felt| parse_felt(felt.trim()))
+        .collect::<Result<Vec<FieldElement>, SignatureError>>()?;
+
+    match felts.as_slice() {
+        [r, s, ..] => Ok((*r, *s)),
+        _ => Err(SignatureError::Malformed(
+            "expected r and s of the signature".to_string(),
+        )),
+    }
+}
+
+/// Body hash the client signs as the payload of the message
+pub fn payload_hash(body: &[u8]) -> FieldElement {
+    starknet_keccak(body)
+}
+
+fn short_string(value: &str) -> Result<FieldElement, SignatureError> {
+    cairo_short_string_to_felt(value)
+        .map_err(|_| SignatureError::Malformed(format!("\"{}\" is not a short string", value)))
+}
+
+/// Pedersen hash chain of the elements followed by their count
+fn hash_array(elements: &[FieldElement]) -> FieldElement {
+    let hash = elements.iter().fold(FieldElement::ZERO, |acc, element| {
+        pedersen_hash(&acc, element)
+    });
+    pedersen_hash(&hash, &FieldElement::from(elements.len()))
+}
+
+fn domain_hash() -> Result<FieldElement, SignatureError> {
+    Ok(hash_array(&[
+        starknet_keccak(DOMAIN_TYPE.as_bytes()),
+        short_string(DOMAIN_NAME)?,
+        short_string(DOMAIN_VERSION)?,
+        short_string(CHAIN_ID)?,
+    ]))
+}
+
+/// Hash the account signs, same as `account.hashMessage(typedData)` in starknet.js
+pub fn auth_message_hash(
+    account: FieldElement,
+    message: &AuthMessage,
+) -> Result<FieldElement, SignatureError> {
+    let expiry = u64::try_from(message.expiry)
+        .map_err(|_| SignatureError::Malformed("negative expiry".to_string()))?;
+    let message_hash = hash_array(&[
+        starknet_keccak(MESSAGE_TYPE.as_bytes()),
+        short_string(&message.action)?,
+        message.payload,
+        message.nonce,
+        FieldElement::from(expiry),
+    ]);
+
+    Ok(hash_array(&[
+        short_string(MESSAGE_PREFIX)?,
+        domain_hash()?,
+        account,
+        message_hash,
+    ]))
+}
+
+pub fn check_expiry(expiry: i64, now: i64) -> Result<(), SignatureError> {
+    if expiry < now {
+        return Err(SignatureError::Expired);
+    }
+    if expiry - now > MAX_SIGNATURE_VALIDITY_SECONDS {
+        return Err(SignatureError::ExpiryTooFar);
+    }
+    Ok(())
+}
+
+/// Checks the expiry and the signature of the message against the public key of the account
+pub fn verify_auth_signature(
+    public_key: FieldElement,
+    account: FieldElement,
+    message: &AuthMessage,
+    (r, s): (FieldElement, FieldElement),
+    now: i64,
+) -> Result<(), SignatureError> {
+    check_expiry(message.expiry, now)?;
+    let hash = auth_message_hash(account, message)?;
+
+    match verify(&public_key, &hash, &r, &s) {
+        Ok(true) => Ok(()),
+        _ => Err(SignatureError::Invalid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_crypto::{get_public_key, sign};
+
+    use super::*;
+
+    fn message(payload: &[u8]) -> AuthMessage {
+        AuthMessage {
+            action: "referral_event".to_string(),
+            payload: payload_hash(payload),
+            nonce: FieldElement::from(42u64),
+            expiry: 1_700_000_300,
+        }
+    }
+
+    #[test]
+    fn domain_type_hash() {
+        assert_eq!(
+            starknet_keccak(DOMAIN_TYPE.as_bytes()),
+            FieldElement::from_hex_be(
+                "0x1bfc207425a47a5dfa1a50a4f5241203f50624ca5fdf5e18755765416b8e288"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn verifies_signed_message() {
+        let private_key = FieldElement::from(123456789u64);
+        let public_key = get_public_key(&private_key);
+        let account = FieldElement::from_hex_be("0x123").unwrap();
+        let now = 1_700_000_000;
+
+        let signed = message(b"{\"referral_code\":\"abc\"}");
+        let hash = auth_message_hash(account, &signed).unwrap();
+        let signature = sign(&private_key, &hash, &FieldElement::from(7u64)).unwrap();
+        let rs = (signature.r, signature.s);
+
+        assert_eq!(
+            verify_auth_signature(public_key, account, &signed, rs, now),
+            Ok(())
+        );
+        // different body
+        assert_eq!(
+            verify_auth_signature(public_key, account, &message(b"{}"), rs, now),
+            Err(SignatureError::Invalid)
+        );
+        // different account
+        assert_eq!(
+            verify_auth_signature(public_key, FieldElement::ONE, &signed, rs, now),
+            Err(SignatureError::Invalid)
+        );
+        assert_eq!(
+            verify_auth_signature(public_key, account, &signed, rs, signed.expiry + 1),
+            Err(SignatureError::Expired)
+        );
+        assert_eq!(
+            verify_auth_signature(public_key, account, &signed, rs, now - 3600),
+            Err(SignatureError::ExpiryTooFar)
+        );
+    }
+
+    #[test]
+    fn parses_signature() {
+        let (r, s) = parse_signature("0x1, 2,0x3,0x4").unwrap();
+        assert_eq!(r, FieldElement::ONE);
+        assert_eq!(s, FieldElement::TWO);
+        assert!(parse_signature("0x1").is_err());
+        assert!(parse_signature("0x1,xyz").is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::types::{ReferralEventDigest, TradeEventWithPrice};
+
+/// `referred` joining with the code of `referrer` at the unix timestamp
+pub fn referral(referrer: &str, referred: &str, timestamp: u64) -> ReferralEventDigest {
+    ReferralEventDigest {
+        referred_wallet_address: referred.to_string(),
+        referee_wallet_address: referrer.to_string(),
+        referral_code: format!("code-{}", referrer),
+        timestamp: UNIX_EPOCH + Duration::from_secs(timestamp),
+    }
+}
+
+/// Opened ETH/USDC call with the volume and premia in USD
+pub fn trade(caller: &str, timestamp: i64, volume: f32, premia: f32) -> TradeEventWithPrice {
+    TradeEventWithPrice {
+        timestamp,
+        action: "TradeOpen".to_string(),
+        caller: caller.to_string(),
+        capital_transfered: 0.0,
+        capital_transfered_usd: volume,
+        underlying_asset_price_usd: 1.0,
+        tokens_minted: 0.0,
+        premia: 0.0,
+        premia_usd: premia,
+        option_side: 0,
+        option_type: 0,
+        maturity: 0,
+        strike_price: 0.0,
+        pool_id: "eth-usdc-call".to_string(),
+    }
+}
@@ -1,9 +1,11 @@
 use core::fmt;
-use std::{collections::HashMap, time::SystemTime};
+use std::{collections::HashMap, str::FromStr, time::SystemTime};
 
+use crate::network::Protocol;
 use crate::schema::{
-    blocks, braavos_bonus, events, insurance_events, options, options_volatility, oracle_prices,
-    pool_state, pools, referral_codes, referral_events, starkscan_events,
+    blocks, braavos_bonus, events, ingestion_checkpoints, insurance_events, lending_events,
+    options, options_volatility, oracle_prices, pool_state, pools, proposals, raw_events_unparsed,
+    referral_codes, referral_events, starkscan_events, voting_power,
 };
 use carmine_api_airdrop::merkle_tree::MerkleTree;
 use diesel::prelude::*;
@@ -93,6 +95,10 @@ pub enum OracleName {
     Pragma,
 }
 
+/// Oracle whose prices are used for USD values, prices of other
+/// oracles are stored next to it under their own oracle_name
+pub const PRIMARY_ORACLE: &str = "pragma";
+
 pub struct Trades {
     pub all_trades: Vec<TradeEventWithPrice>,
     pub user_trades: HashMap<String, Vec<TradeEventWithPrice>>,
@@ -105,7 +111,13 @@ pub struct AppData {
     pub trades: HashMap<String, Vec<TradeEvent>>,
     pub option_volatility: Vec<OptionWithVolatility>,
     pub state: HashMap<String, Vec<PoolStateWithTimestamp>>,
+    pub state_rollups: HashMap<String, PoolStateRollups>,
     pub oracle_prices: HashMap<String, Vec<OraclePriceConcise>>,
+    pub price_series: HashMap<String, Vec<PricePoint>>,
+    /// candles by pair and interval
+    pub price_candles: HashMap<String, HashMap<i64, Vec<PriceCandle>>>,
+    pub oracle_health: Vec<OracleHealth>,
+    pub volatility_premium: HashMap<String, Vec<VolatilityPremiumSeries>>,
     pub apy: HashMap<String, APY>,
     pub referrals: Vec<ReferralEventDigest>,
     pub top_user_points: Vec<UserPointsWithPosition>,
@@ -117,12 +129,23 @@ pub struct AppData {
     pub trades_with_prices: Trades,
     pub insurance_events: Vec<InsuranceData>,
     pub pail_events: HashMap<String, Vec<PailEvents>>,
+    /// newest first
+    pub hedges: Vec<Hedge>,
+    pub pail_stats: PailStats,
+    /// lending protocol -> market -> stats
+    pub lending: HashMap<String, HashMap<String, LendingMarketStats>>,
+    /// newest first
+    pub proposals: Vec<ProposalDetail>,
+    /// highest rewards first
+    pub referrers: Vec<ReferrerDetail>,
+    pub referral_abuse: ReferralAbuseReport,
 }
 
 pub struct AppState {
     pub mainnet: AppData,
     pub airdrop: MerkleTree,
     pub token_prices: TokenPrices,
+    pub token_price_sources: HashMap<String, AggregatedPrice>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -145,7 +168,7 @@ pub struct StarkScanEvent {
     pub key_name: Option<String>,
 }
 
-#[derive(Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable)]
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq, Selectable)]
 #[diesel(table_name = starkscan_events)]
 pub struct StarkScanEventSettled {
     pub id: String,
@@ -160,6 +183,77 @@ pub struct StarkScanEventSettled {
     pub key_name: String,
 }
 
+/// Why an event could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnparsedReason {
+    /// no key_name and the selector is not known
+    UnknownSelector,
+    /// LP token event with other than 4 keys
+    UnexpectedKeyCount,
+    /// not enough data to decode the event
+    UnexpectedDataLength,
+    /// Carmine AMM action the events table does not handle, eg "ExpireOptionTokenForPool"
+    DisallowedAction,
+    /// event of a lending contract its decoder failed on
+    LendingDecode,
+}
+
+impl UnparsedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnparsedReason::UnknownSelector => "unknown_selector",
+            UnparsedReason::UnexpectedKeyCount => "unexpected_key_count",
+            UnparsedReason::UnexpectedDataLength => "unexpected_data_length",
+            UnparsedReason::DisallowedAction => "disallowed_action",
+            UnparsedReason::LendingDecode => "lending_decode",
+        }
+    }
+}
+
+/// Event that failed to decode, kept to be reprocessed once a decoder exists
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq)]
+#[diesel(table_name = raw_events_unparsed)]
+pub struct UnparsedEvent {
+    pub id: String,
+    pub block_hash: String,
+    pub block_number: i64,
+    pub transaction_hash: String,
+    pub event_index: i64,
+    pub from_address: String,
+    pub keys: Vec<String>,
+    pub data: Vec<String>,
+    pub timestamp: i64,
+    pub key_name: Option<String>,
+    pub selector: Option<String>,
+    pub reason: String,
+    pub reprocessed_at: Option<SystemTime>,
+}
+
+impl UnparsedEvent {
+    pub fn to_starkscan_event(&self) -> StarkScanEvent {
+        StarkScanEvent {
+            block_hash: Some(self.block_hash.clone()),
+            block_number: Some(self.block_number),
+            transaction_hash: self.transaction_hash.clone(),
+            event_index: self.event_index,
+            from_address: self.from_address.clone(),
+            keys: self.keys.clone(),
+            data: self.data.clone(),
+            timestamp: self.timestamp,
+            key_name: self.key_name.clone(),
+        }
+    }
+}
+
+/// Unparsed events of a contract with the same selector and reason
+#[derive(Debug, Clone, Serialize)]
+pub struct UnparsedSelectorCount {
+    pub from_address: String,
+    pub selector: Option<String>,
+    pub reason: String,
+    pub count: i64,
+}
+
 #[derive(Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable)]
 #[diesel(table_name = events)]
 pub struct Event {
@@ -176,7 +270,9 @@ pub struct Event {
     pub tokens_minted: String,
 }
 
-#[derive(Associations, Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable)]
+#[derive(
+    Associations, Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq, Selectable,
+)]
 #[diesel(belongs_to(Pool, foreign_key = lp_address))]
 #[diesel(table_name = options)]
 pub struct IOption {
@@ -220,7 +316,9 @@ pub struct PoolStats {
     pub lp_token_value: String,
 }
 
-#[derive(Associations, Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable)]
+#[derive(
+    Associations, Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq, Selectable,
+)]
 #[diesel(belongs_to(Pool, foreign_key = lp_address))]
 #[diesel(belongs_to(DbBlock, foreign_key = block_number))]
 #[diesel(table_name = pool_state)]
@@ -244,7 +342,7 @@ pub struct PoolStatePriceUpdate {
     pub lp_address: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PoolStateWithTimestamp {
     pub unlocked_cap: String,
     pub locked_cap: String,
@@ -258,6 +356,101 @@ pub struct PoolStateWithTimestamp {
     pub timestamp: i64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateResolution {
+    Block,
+    Hour,
+    Day,
+    Week,
+}
+
+impl FromStr for StateResolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(StateResolution::Block),
+            "hour" => Ok(StateResolution::Hour),
+            "day" => Ok(StateResolution::Day),
+            "week" => Ok(StateResolution::Week),
+            other => Err(format!("Unknown resolution {}", other)),
+        }
+    }
+}
+
+impl StateResolution {
+    /// Bucket size in seconds, `None` for per-block resolution
+    pub fn seconds(&self) -> Option<i64> {
+        match self {
+            StateResolution::Block => None,
+            StateResolution::Hour => Some(3600),
+            StateResolution::Day => Some(86400),
+            StateResolution::Week => Some(604800),
+        }
+    }
+
+    /// Start of the bucket containing the timestamp, weeks start on Monday 00:00 UTC
+    pub fn bucket_start(&self, timestamp: i64) -> Option<i64> {
+        // Unix epoch was a Thursday, 1970-01-05 is the first Monday
+        let offset = match self {
+            StateResolution::Week => 4 * 86400,
+            _ => 0,
+        };
+        self.seconds()
+            .map(|size| timestamp - (timestamp - offset).rem_euclid(size))
+    }
+}
+
+impl fmt::Display for StateResolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateResolution::Block => write!(f, "block"),
+            StateResolution::Hour => write!(f, "hour"),
+            StateResolution::Day => write!(f, "day"),
+            StateResolution::Week => write!(f, "week"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Ohlc {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStateBucket {
+    /// start of the bucket
+    pub timestamp: i64,
+    pub first_block_number: i64,
+    pub last_block_number: i64,
+    pub samples: usize,
+    pub lp_token_value_usd: Option<Ohlc>,
+    pub underlying_asset_price: Option<Ohlc>,
+    /// last stored state in the bucket
+    pub last: PoolStateWithTimestamp,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PoolStateRollups {
+    pub hour: Vec<PoolStateBucket>,
+    pub day: Vec<PoolStateBucket>,
+    pub week: Vec<PoolStateBucket>,
+}
+
+impl PoolStateRollups {
+    pub fn get(&self, resolution: &StateResolution) -> Option<&Vec<PoolStateBucket>> {
+        match resolution {
+            StateResolution::Block => None,
+            StateResolution::Hour => Some(&self.hour),
+            StateResolution::Day => Some(&self.day),
+            StateResolution::Week => Some(&self.week),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq, Selectable)]
 #[diesel(table_name = blocks)]
 pub struct DbBlock {
@@ -272,7 +465,16 @@ pub struct Pool {
 }
 
 #[derive(
-    AsChangeset, Associations, Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable,
+    AsChangeset,
+    Associations,
+    Debug,
+    Clone,
+    Queryable,
+    Insertable,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Selectable,
 )]
 #[diesel(belongs_to(IOption, foreign_key = option_address))]
 #[diesel(belongs_to(DbBlock, foreign_key = block_number))]
@@ -284,7 +486,9 @@ pub struct OptionVolatility {
     pub option_position: Option<String>,
 }
 
-#[derive(Associations, Debug, Clone, Queryable, Insertable, Serialize, PartialEq, Selectable)]
+#[derive(
+    Associations, Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq, Selectable,
+)]
 #[diesel(belongs_to(DbBlock, foreign_key = block_number))]
 #[diesel(table_name = oracle_prices)]
 pub struct OraclePrice {
@@ -298,6 +502,57 @@ pub struct OraclePrice {
     pub block_number: i64,
 }
 
+/// AMM volatility against realized volatility of the underlying, all in percent
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct VolatilityPremiumPoint {
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub amm_volatility: f64,
+    pub realized_volatility_1d: Option<f64>,
+    pub realized_volatility_7d: Option<f64>,
+    pub realized_volatility_30d: Option<f64>,
+    pub premium_1d: Option<f64>,
+    pub premium_7d: Option<f64>,
+    pub premium_30d: Option<f64>,
+}
+
+/// Volatility premium of the options of one maturity of a pool
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VolatilityPremiumSeries {
+    pub maturity: i64,
+    pub points: Vec<VolatilityPremiumPoint>,
+}
+
+/// Failed oracle check, unlike the issue messages it does not change between checks
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleIssue {
+    PriceChange,
+    ReferenceDeviation,
+    StaleUpdate,
+    SourcesDropped,
+}
+
+/// Result of the oracle deviation and staleness checks for a pair
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OracleHealth {
+    pub token_pair: String,
+    pub oracle_name: String,
+    pub healthy: bool,
+    /// failed checks, in the order of `issues`
+    pub issue_kinds: Vec<OracleIssue>,
+    pub issues: Vec<String>,
+    pub block_number: i64,
+    pub price: f64,
+    pub previous_price: Option<f64>,
+    pub price_change: Option<f64>,
+    pub reference_price: Option<f64>,
+    pub reference_deviation: Option<f64>,
+    pub lag_secs: i64,
+    pub num_sources_aggregated: i16,
+    pub previous_num_sources_aggregated: Option<i16>,
+}
+
 #[derive(Serialize, Debug, Clone, Copy)]
 pub struct OraclePriceConcise {
     pub price: i64,
@@ -306,14 +561,34 @@ pub struct OraclePriceConcise {
     pub block_number: i64,
 }
 
-#[derive(Queryable, Insertable)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub price: f64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PriceCandle {
+    /// start of the candle interval
+    pub timestamp: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub first_block_number: i64,
+    pub last_block_number: i64,
+    pub samples: usize,
+}
+
+#[derive(Queryable, Insertable, Deserialize)]
 #[diesel(table_name = referral_codes)]
 pub struct ReferralCode {
     pub wallet_address: String,
     pub referral_code: String,
 }
 
-#[derive(Debug, Clone, Queryable, Serialize, Selectable)]
+#[derive(Debug, Clone, Queryable, Serialize, Deserialize, Selectable)]
 #[diesel(table_name = referral_events)]
 pub struct ReferralEvent {
     pub id: i32,
@@ -354,7 +629,7 @@ pub struct InsuranceEvent<'a> {
     pub calldata: Vec<&'a str>,
 }
 
-#[derive(Serialize, Deserialize, Queryable, Debug)]
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
 pub struct InsuranceEventQueryable {
     pub id: i32,
     pub user_address: String,
@@ -371,6 +646,11 @@ pub struct InsuranceData {
     pub premia: f64,
     pub strike: f64,
     pub size: String,
+    pub pool_id: String,
+    pub maturity: i64,
+    /// matching TradeOpen of the user was indexed
+    pub verified: bool,
+    pub trade_timestamp: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Queryable)]
@@ -404,7 +684,7 @@ pub struct UserPointsWithPosition {
     pub position: i64,
 }
 
-#[derive(Serialize, Debug, Deserialize, Clone)]
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq)]
 pub struct Vote {
     pub user_address: String,
     pub prop_id: usize,
@@ -412,6 +692,80 @@ pub struct Vote {
     pub timestamp: i64,
 }
 
+/// Governance proposal, title and description are set by hand
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq)]
+#[diesel(table_name = proposals)]
+pub struct Proposal {
+    pub prop_id: i64,
+    pub payload: String,
+    pub to_upgrade: i64,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub transaction_hash: String,
+    pub executed_block_number: Option<i64>,
+    pub executed_timestamp: Option<i64>,
+    /// governance token supply at the snapshot block, None until fetched
+    pub total_supply: Option<f64>,
+}
+
+/// Governance token balance of a voter at the snapshot block of the proposal
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq)]
+#[diesel(table_name = voting_power)]
+pub struct VotingPower {
+    pub prop_id: i64,
+    pub user_address: String,
+    pub voting_power: f64,
+    pub block_number: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProposalSummary {
+    pub prop_id: i64,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub payload: String,
+    pub to_upgrade: i64,
+    pub block_number: i64,
+    pub timestamp: i64,
+    pub voting_ends: i64,
+    pub executed_timestamp: Option<i64>,
+    pub yes_votes: usize,
+    pub no_votes: usize,
+    pub voters_count: usize,
+    /// voting power of the voters out of the token supply at the snapshot,
+    /// None until the supply is known
+    pub turnout: Option<f64>,
+    /// summed voting power of the voters, in governance tokens
+    pub yes_power: f64,
+    pub no_power: f64,
+    pub total_supply: Option<f64>,
+    /// voting power needed for the proposal to pass, None until the supply is known
+    pub quorum: Option<f64>,
+    pub quorum_reached: Option<bool>,
+    /// "live", "pending", "passed", "rejected" or "executed"
+    pub status: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProposalVoter {
+    pub user_address: String,
+    pub opinion: usize,
+    pub timestamp: i64,
+    /// None until the balance at the snapshot block is fetched
+    pub voting_power: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProposalDetail {
+    #[serde(flatten)]
+    pub summary: ProposalSummary,
+    /// voters with the most voting power first
+    pub top_voters: Vec<ProposalVoter>,
+    pub voters: Vec<ProposalVoter>,
+}
+
 #[derive(Queryable, Debug)]
 pub struct PoolTvlInfo {
     pub block_number: i64,
@@ -438,13 +792,24 @@ pub struct PriceResponse {
     pub ekubo: CoinGeckoPrice,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// USD prices, `None` until some source priced the token
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
 pub struct TokenPrices {
-    pub eth: f64,
-    pub usdc: f64,
-    pub strk: f64,
-    pub btc: f64,
-    pub ekubo: f64,
+    pub eth: Option<f64>,
+    pub usdc: Option<f64>,
+    pub strk: Option<f64>,
+    pub btc: Option<f64>,
+    pub ekubo: Option<f64>,
+}
+
+/// Token price aggregated from multiple sources
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AggregatedPrice {
+    pub price: f64,
+    pub sources: Vec<String>,
+    pub rejected_sources: Vec<String>,
+    pub stale_sources: Vec<String>,
+    pub timestamp: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -471,7 +836,7 @@ pub struct OpenblockResponse {
     pub carmine: Vec<OpenblockData>,
 }
 
-#[derive(Queryable, Insertable, AsChangeset)]
+#[derive(Queryable, Insertable, AsChangeset, Deserialize)]
 #[diesel(table_name = braavos_bonus)]
 pub struct BraavosBonus {
     pub user_address: String,
@@ -485,12 +850,124 @@ pub struct BraavosBonusValues {
     pub braavos_referral: Option<i64>,
 }
 
-#[derive(Serialize)]
+/// Data stream written by the fetcher, each keeps its own checkpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IngestionStream {
+    ProtocolEvents(Protocol),
+    AmmState,
+    OraclePrices,
+    Volatility,
+    /// decoding of stored lending events into lending_events
+    LendingEvents(Protocol),
+    /// decoding of stored governance events into proposals
+    Proposals,
+}
+
+impl IngestionStream {
+    pub fn id(&self) -> String {
+        match self {
+            IngestionStream::ProtocolEvents(protocol) => format!("events:{}", protocol),
+            IngestionStream::AmmState => "amm_state".to_string(),
+            IngestionStream::OraclePrices => "oracle_prices".to_string(),
+            IngestionStream::Volatility => "volatility".to_string(),
+            IngestionStream::LendingEvents(protocol) => format!("lending:{}", protocol),
+            IngestionStream::Proposals => "proposals".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckpointStatus {
+    Ok,
+    Failed,
+}
+
+impl CheckpointStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointStatus::Ok => "ok",
+            CheckpointStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Last fully processed block of a stream
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = ingestion_checkpoints)]
+pub struct IngestionCheckpoint {
+    pub stream: String,
+    pub last_block: i64,
+    pub status: String,
+    pub updated_at: SystemTime,
+}
+
+/// Everything fetched for a single block by the AMM state observer
+pub struct BlockState {
+    pub block: DbBlock,
+    pub volatilities: Vec<OptionVolatility>,
+    pub pool_states: Vec<PoolState>,
+    pub oracle_prices: Vec<OraclePrice>,
+}
+
+/// Decoded event of a lending protocol, amounts are in tokens
+/// and rates are yearly, eg. 0.05 for 5 %
+#[derive(Debug, Clone, Queryable, Insertable, Serialize, Deserialize, PartialEq)]
+#[diesel(table_name = lending_events)]
+pub struct LendingEvent {
+    pub id: String,
+    pub protocol: String,
+    pub market: String,
+    pub action: String,
+    pub user_address: Option<String>,
+    pub amount: Option<f64>,
+    pub lending_rate: Option<f64>,
+    pub borrowing_rate: Option<f64>,
+    pub block_number: i64,
+    pub event_index: i64,
+    pub timestamp: i64,
+    pub transaction_hash: String,
+}
+
+/// State of a lending market at the end of a day
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct LendingMarketPoint {
+    pub timestamp: i64,
+    pub total_supply: f64,
+    pub total_borrow: f64,
+    pub lending_rate: Option<f64>,
+    pub borrowing_rate: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LendingMarketStats {
+    pub protocol: String,
+    pub market: String,
+    pub total_supply: f64,
+    pub total_borrow: f64,
+    pub utilization: Option<f64>,
+    pub lending_rate: Option<f64>,
+    pub borrowing_rate: Option<f64>,
+    pub last_block: i64,
+    pub history: Vec<LendingMarketPoint>,
+}
+
+/// ERC-721 metadata of the hedge token
+#[derive(Serialize, Debug)]
 pub struct PailToken {
     pub name: String,
     pub description: String,
     pub token_id: u64,
     pub image: String,
+    pub attributes: Vec<TokenAttribute>,
+}
+
+/// OpenSea metadata attribute, `display_type` is "number" or "date" for numeric traits
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TokenAttribute {
+    pub trait_type: String,
+    pub value: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -520,6 +997,118 @@ pub enum PailEvents {
     Settle(PailHedgeFinalized),
 }
 
+/// Pail hedge joined with the event that closed or settled it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Hedge {
+    pub token_id: u64,
+    pub user: String,
+    /// "open", "closed" or "settled"
+    pub status: String,
+    pub base_token: String,
+    pub quote_token: String,
+    pub base_symbol: Option<String>,
+    pub quote_symbol: Option<String>,
+    /// hedged amount in base token
+    pub amount: f64,
+    /// price of base token in quote token the hedge was opened at
+    pub at_price: f64,
+    pub maturity: i64,
+    pub opened_block: i64,
+    pub opened_timestamp: i64,
+    pub opened_transaction_hash: String,
+    pub finalized_block: Option<i64>,
+    pub finalized_timestamp: Option<i64>,
+    pub finalized_transaction_hash: Option<String>,
+    /// seconds until the hedge was closed or settled, until now for open hedges
+    pub duration: i64,
+    /// current price of base token in quote token, open hedges only
+    pub current_price: Option<f64>,
+    /// amount at the current USD price of base token, open hedges only
+    pub value_usd: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReferredWallet {
+    pub address: String,
+    /// first referral event of the wallet
+    pub referred_at: i64,
+    /// first trade since the referral
+    pub first_trade: Option<i64>,
+    pub trades: usize,
+    /// capital transfered in USD by trades since the referral
+    pub volume_usd: f64,
+    pub premia_usd: f64,
+    /// premia made within the reward window
+    pub rewarded_premia_usd: f64,
+    /// flagged by abuse detection, not counted for rewards and points
+    pub flagged: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReferrerSummary {
+    /// by rewards, starting at 1
+    pub position: usize,
+    pub address: String,
+    pub referral_code: String,
+    /// volume, premia and rewards are of the wallets that are not flagged
+    pub referred_wallets: usize,
+    pub flagged_wallets: usize,
+    /// referred wallets with at least one trade
+    pub trading_wallets: usize,
+    pub volume_usd: f64,
+    pub premia_usd: f64,
+    /// part of the rewarded premia owed, by the tier the volume reached
+    pub reward_share: f64,
+    pub rewards_usd: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReferrerDetail {
+    #[serde(flatten)]
+    pub summary: ReferrerSummary,
+    /// earliest referred first
+    pub wallets: Vec<ReferredWallet>,
+}
+
+/// Referral excluded from rewards and points
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReferralFlag {
+    pub referrer: String,
+    pub referred_wallet: String,
+    pub referral_code: String,
+    pub referred_at: i64,
+    /// "self_referral", "cycle" or "lockstep"
+    pub reasons: Vec<String>,
+    /// wallets referring each other in a ring, in referral order
+    pub cycle: Option<Vec<String>>,
+    /// wallets of the referrer trading the same options at the same time
+    pub cluster: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ReferralAbuseReport {
+    /// referred wallets
+    pub referrals: usize,
+    pub flagged: usize,
+    pub self_referrals: usize,
+    pub cycles: usize,
+    pub lockstep_clusters: usize,
+    pub flags: Vec<ReferralFlag>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PailStats {
+    pub hedges: usize,
+    pub open: usize,
+    pub closed: usize,
+    pub settled: usize,
+    pub users: usize,
+    /// open hedges without a price are not included
+    pub open_value_usd: f64,
+    /// average seconds closed and settled hedges were held
+    pub average_duration: Option<f64>,
+}
+
 impl PailEvents {
     pub fn get_user(&self) -> String {
         match self {
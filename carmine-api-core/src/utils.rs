@@ -41,6 +41,30 @@ pub fn normalize_address(address: &str) -> String {
     format!("0x{}", res)
 }
 
+/// Lowercase without leading zeroes, for joining addresses stored
+/// padded and unpadded, does not panic on malformed input
+pub fn canonical_address(address: &str) -> String {
+    let lowercase = address.to_lowercase();
+    let hex = lowercase
+        .strip_prefix("0x")
+        .unwrap_or(&lowercase)
+        .trim_start_matches('0');
+    match hex.is_empty() {
+        true => "0x0".to_string(),
+        false => format!("0x{}", hex),
+    }
+}
+
+/// "0x" followed by at most 64 hex digits
+pub fn is_hex_address(address: &str) -> bool {
+    match address.strip_prefix("0x") {
+        Some(hex) => {
+            !hex.is_empty() && hex.len() <= 64 && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
 pub fn felt_to_float(felt: FieldElement, decimals: usize) -> f64 {
     let input_str = felt.to_string(); // decimal number as string
 
@@ -0,0 +1,26 @@
+use carmine_api_core::network::Network;
+use carmine_api_db::{count_incomplete_rows, delete_incomplete_rows};
+use dotenvy::dotenv;
+use std::env;
+
+/// Lists rows without the values the complete_schema migration makes NOT NULL,
+/// `incomplete_rows delete` deletes them so that the migration can run
+fn main() {
+    dotenv().ok();
+
+    let network = &Network::Mainnet;
+
+    if env::args().nth(1).as_deref() == Some("delete") {
+        let deleted = delete_incomplete_rows(network).expect("Failed deleting incomplete rows");
+        for (table, count) in deleted {
+            println!("Deleted {} rows from {}", count, table);
+        }
+        return;
+    }
+
+    let counts = count_incomplete_rows(network).expect("Failed counting incomplete rows");
+
+    for (table, count) in counts {
+        println!("{} {}", table, count);
+    }
+}
@@ -5,7 +5,8 @@ use dotenvy::dotenv;
 fn main() {
     dotenv().ok();
 
-    let state = carmine_api_db::get_pool_state(&MAINNET_ETH_USDC_CALL.address, &Network::Mainnet);
+    let state = carmine_api_db::get_pool_state(&MAINNET_ETH_USDC_CALL.address, &Network::Mainnet)
+        .expect("Failed getting pool state");
 
     let max_element = state.iter().max_by_key(|v| v.block_number);
 
@@ -0,0 +1,173 @@
+use carmine_api_core::network::{ Network, Protocol };
+use carmine_api_core::types::{
+    BlockState,
+    CheckpointStatus,
+    IngestionCheckpoint,
+    IngestionStream,
+    StarkScanEventSettled,
+    UnparsedEvent,
+};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use std::time::SystemTime;
+
+use crate::unparsed::insert_unparsed_events;
+use crate::{ establish_connection, DbError, BATCH_SIZE };
+
+pub fn get_checkpoint(
+    stream: &IngestionStream,
+    network: &Network
+) -> Result<Option<IngestionCheckpoint>, DbError> {
+    use carmine_api_core::schema::ingestion_checkpoints::dsl as cp;
+
+    let connection = &mut establish_connection(network)?;
+
+    cp::ingestion_checkpoints
+        .filter(cp::stream.eq(stream.id()))
+        .first::<IngestionCheckpoint>(connection)
+        .optional()
+        .map_err(DbError::Query)
+}
+
+/// Records a failed run, keeps the last block if the stream already has a checkpoint
+pub fn mark_checkpoint_failed(
+    stream: &IngestionStream,
+    last_block: i64,
+    network: &Network
+) -> Result<(), DbError> {
+    use carmine_api_core::schema::ingestion_checkpoints::dsl as cp;
+
+    let connection = &mut establish_connection(network)?;
+
+    diesel
+        ::insert_into(cp::ingestion_checkpoints)
+        .values(
+            &(IngestionCheckpoint {
+                stream: stream.id(),
+                last_block,
+                status: CheckpointStatus::Failed.as_str().to_string(),
+                updated_at: SystemTime::now(),
+            })
+        )
+        .on_conflict(cp::stream)
+        .do_update()
+        .set((
+            cp::status.eq(CheckpointStatus::Failed.as_str()),
+            cp::updated_at.eq(SystemTime::now()),
+        ))
+        .execute(connection)?;
+
+    Ok(())
+}
+
+/// Moves the checkpoint forward, never back - plugging holes
+/// stores blocks older than the checkpoint
+pub(crate) fn advance_checkpoint(
+    connection: &mut PgConnection,
+    stream: &IngestionStream,
+    last_block: i64
+) -> Result<(), diesel::result::Error> {
+    use carmine_api_core::schema::ingestion_checkpoints::dsl as cp;
+
+    diesel
+        ::insert_into(cp::ingestion_checkpoints)
+        .values(
+            &(IngestionCheckpoint {
+                stream: stream.id(),
+                last_block,
+                status: CheckpointStatus::Ok.as_str().to_string(),
+                updated_at: SystemTime::now(),
+            })
+        )
+        .on_conflict(cp::stream)
+        .do_update()
+        .set((
+            cp::last_block.eq(
+                sql::<BigInt>("GREATEST(excluded.last_block, ingestion_checkpoints.last_block)")
+            ),
+            cp::status.eq(CheckpointStatus::Ok.as_str()),
+            cp::updated_at.eq(SystemTime::now()),
+        ))
+        .execute(connection)?;
+
+    Ok(())
+}
+
+/// Stores events of the protocol, the ones that failed to decode
+/// and its checkpoint in a single transaction
+pub fn store_protocol_events(
+    events: &Vec<StarkScanEventSettled>,
+    unparsed: &Vec<UnparsedEvent>,
+    protocol: &Protocol,
+    last_block: i64,
+    network: &Network
+) -> Result<usize, DbError> {
+    use carmine_api_core::schema::starkscan_events::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        let mut inserted = 0;
+
+        for chunk in events.chunks(BATCH_SIZE) {
+            inserted += diesel
+                ::insert_into(starkscan_events)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        insert_unparsed_events(conn, unparsed)?;
+
+        advance_checkpoint(conn, &IngestionStream::ProtocolEvents(*protocol), last_block)?;
+
+        Ok(inserted)
+    })
+}
+
+/// Stores state of a block and moves the AMM state, volatility
+/// and oracle prices checkpoints in a single transaction
+pub fn store_block_state(state: &BlockState, network: &Network) -> Result<(), DbError> {
+    use carmine_api_core::schema::{ blocks, options_volatility, oracle_prices, pool_state };
+
+    let connection = &mut establish_connection(network)?;
+    let block_number = state.block.block_number;
+
+    connection.transaction(|conn| {
+        diesel
+            ::insert_into(blocks::table)
+            .values(&state.block)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        for chunk in state.volatilities.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(options_volatility::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        for chunk in state.pool_states.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(pool_state::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        diesel
+            ::insert_into(oracle_prices::table)
+            .values(&state.oracle_prices)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        advance_checkpoint(conn, &IngestionStream::AmmState, block_number)?;
+        advance_checkpoint(conn, &IngestionStream::Volatility, block_number)?;
+        advance_checkpoint(conn, &IngestionStream::OraclePrices, block_number)?;
+
+        Ok(())
+    })
+}
+
@@ -0,0 +1,52 @@
+use std::fmt;
+
+use diesel::r2d2::PoolError;
+
+#[derive(Debug)]
+pub enum DbError {
+    /// missing or invalid DB configuration
+    Config(String),
+    /// could not get a connection from the pool
+    Connection(String),
+    /// query failed
+    Query(diesel::result::Error),
+    /// stored data could not be interpreted
+    InvalidData(String),
+    /// applying migrations failed
+    Migration(String),
+    /// tables in the DB do not match schema.rs
+    SchemaMismatch(Vec<String>),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Config(msg) => write!(f, "DB config error: {}", msg),
+            DbError::Connection(msg) => write!(f, "DB connection error: {}", msg),
+            DbError::Query(e) => write!(f, "DB query error: {}", e),
+            DbError::InvalidData(msg) => write!(f, "Invalid data in DB: {}", msg),
+            DbError::Migration(msg) => write!(f, "DB migration error: {}", msg),
+            DbError::SchemaMismatch(issues) => {
+                write!(
+                    f,
+                    "DB schema does not match schema.rs: {}",
+                    issues.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<diesel::result::Error> for DbError {
+    fn from(e: diesel::result::Error) -> Self {
+        DbError::Query(e)
+    }
+}
+
+impl From<PoolError> for DbError {
+    fn from(e: PoolError) -> Self {
+        DbError::Connection(e.to_string())
+    }
+}
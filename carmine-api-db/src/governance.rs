@@ -0,0 +1,114 @@
+use carmine_api_core::network::Network;
+use carmine_api_core::types::{ IngestionStream, Proposal, VotingPower };
+use diesel::prelude::*;
+
+use crate::checkpoints::advance_checkpoint;
+use crate::{ establish_connection, DbError };
+
+pub fn get_proposals(network: &Network) -> Result<Vec<Proposal>, DbError> {
+    use carmine_api_core::schema::proposals::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    proposals.order(prop_id.asc()).load::<Proposal>(connection).map_err(DbError::Query)
+}
+
+/// Stores new proposals and executions given as (prop_id, block_number, timestamp)
+/// together with the proposals checkpoint, stored proposals keep their metadata
+pub fn store_proposal_events(
+    created: &Vec<Proposal>,
+    executed: &Vec<(i64, i64, i64)>,
+    last_block: i64,
+    network: &Network
+) -> Result<usize, DbError> {
+    use carmine_api_core::schema::proposals::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        let inserted = diesel
+            ::insert_into(proposals)
+            .values(created)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        for (id, block, ts) in executed {
+            diesel
+                ::update(proposals)
+                .filter(prop_id.eq(*id))
+                .filter(executed_block_number.is_null())
+                .set((executed_block_number.eq(*block), executed_timestamp.eq(*ts)))
+                .execute(conn)?;
+        }
+
+        advance_checkpoint(conn, &IngestionStream::Proposals, last_block)?;
+
+        Ok(inserted)
+    })
+}
+
+/// Sets title and description of an indexed proposal, returns number of updated rows
+pub fn set_proposal_metadata(
+    id: i64,
+    new_title: &str,
+    new_description: Option<&str>,
+    network: &Network
+) -> Result<usize, DbError> {
+    use carmine_api_core::schema::proposals::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    diesel
+        ::update(proposals)
+        .filter(prop_id.eq(id))
+        .set((title.eq(new_title), description.eq(new_description)))
+        .execute(connection)
+        .map_err(DbError::Query)
+}
+
+pub fn get_voting_power(network: &Network) -> Result<Vec<VotingPower>, DbError> {
+    use carmine_api_core::schema::voting_power::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    voting_power
+        .order((prop_id.asc(), user_address.asc()))
+        .load::<VotingPower>(connection)
+        .map_err(DbError::Query)
+}
+
+/// Stores voting power fetched at the snapshot blocks, balances
+/// at a past block do not change so stored rows are kept
+pub fn store_voting_power(
+    powers: &Vec<VotingPower>,
+    network: &Network
+) -> Result<usize, DbError> {
+    use carmine_api_core::schema::voting_power::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    diesel
+        ::insert_into(voting_power)
+        .values(powers)
+        .on_conflict_do_nothing()
+        .execute(connection)
+        .map_err(DbError::Query)
+}
+
+/// Sets the governance token supply at the snapshot block of the proposal
+pub fn set_proposal_total_supply(
+    id: i64,
+    supply: f64,
+    network: &Network
+) -> Result<usize, DbError> {
+    use carmine_api_core::schema::proposals::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    diesel
+        ::update(proposals)
+        .filter(prop_id.eq(id))
+        .set(total_supply.eq(supply))
+        .execute(connection)
+        .map_err(DbError::Query)
+}
@@ -0,0 +1,92 @@
+use carmine_api_core::network::{ Network, Protocol };
+use carmine_api_core::types::{ IngestionStream, LendingEvent, UnparsedEvent };
+use diesel::prelude::*;
+use std::time::SystemTime;
+
+use crate::checkpoints::advance_checkpoint;
+use crate::unparsed::insert_unparsed_events;
+use crate::{ establish_connection, DbError, BATCH_SIZE };
+
+fn insert_lending_events(
+    connection: &mut PgConnection,
+    lending: &Vec<LendingEvent>
+) -> Result<usize, diesel::result::Error> {
+    use carmine_api_core::schema::lending_events;
+
+    let mut inserted = 0;
+
+    for chunk in lending.chunks(BATCH_SIZE) {
+        inserted += diesel
+            ::insert_into(lending_events::table)
+            .values(chunk)
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+    }
+
+    Ok(inserted)
+}
+
+/// Stores decoded events of the lending contract, the ones that failed
+/// to decode and its checkpoint in a single transaction,
+/// returns number of inserted lending events
+pub fn store_lending_events(
+    lending: &Vec<LendingEvent>,
+    unparsed: &Vec<UnparsedEvent>,
+    protocol: &Protocol,
+    last_block: i64,
+    network: &Network
+) -> Result<usize, DbError> {
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        let inserted = insert_lending_events(conn, lending)?;
+
+        insert_unparsed_events(conn, unparsed)?;
+
+        advance_checkpoint(conn, &IngestionStream::LendingEvents(*protocol), last_block)?;
+
+        Ok(inserted)
+    })
+}
+
+/// Stores lending events decoded from unparsed events and marks
+/// their unparsed rows as reprocessed in a single transaction
+pub fn store_reprocessed_lending_events(
+    lending: &Vec<LendingEvent>,
+    reprocessed_ids: &Vec<String>,
+    network: &Network
+) -> Result<(), DbError> {
+    use carmine_api_core::schema::raw_events_unparsed;
+
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        insert_lending_events(conn, lending)?;
+
+        diesel
+            ::update(raw_events_unparsed::table)
+            .filter(raw_events_unparsed::id.eq_any(reprocessed_ids))
+            .set(raw_events_unparsed::reprocessed_at.eq(SystemTime::now()))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// Events of the market in the order they were emitted
+pub fn get_lending_events(
+    lending_protocol: &str,
+    lending_market: &str,
+    network: &Network
+) -> Result<Vec<LendingEvent>, DbError> {
+    use carmine_api_core::schema::lending_events::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    lending_events
+        .filter(protocol.eq(lending_protocol))
+        .filter(market.eq(lending_market))
+        .order((block_number.asc(), event_index.asc()))
+        .load::<LendingEvent>(connection)
+        .map_err(DbError::Query)
+}
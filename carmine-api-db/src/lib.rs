@@ -29,6 +29,7 @@ use carmine_api_core::types::{
     OptionVolatility,
     OptionWithVolatility,
     OraclePrice,
+    PRIMARY_ORACLE,
     Pool,
     PoolState,
     PoolStatePriceUpdate,
@@ -47,60 +48,159 @@ use carmine_api_core::types::{
 
 use carmine_api_referral::referral_code::generate_referral_code;
 use diesel::dsl::max;
-use diesel::sql_types::{ Array, Text };
-use diesel::{ insert_into, prelude::*, update };
+use diesel::r2d2::{ ConnectionManager, Pool as ConnectionPool, PooledConnection };
+use diesel::pg::Pg;
+use diesel::sql_types::{ Array, BigInt, Double, Text };
+use diesel::{ insert_into, prelude::*, sql_query, update };
 use std::collections::HashMap;
 use std::env;
-use std::time::{ SystemTime, UNIX_EPOCH };
-
-pub mod lp_value;
+use std::sync::OnceLock;
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+mod checkpoints;
+mod error;
+mod governance;
+mod lending;
+mod memory;
+mod migrations;
+mod store;
+mod unparsed;
+
+pub use checkpoints::{
+    get_checkpoint,
+    mark_checkpoint_failed,
+    store_block_state,
+    store_protocol_events,
+};
+pub use error::DbError;
+pub use governance::{
+    get_proposals,
+    get_voting_power,
+    set_proposal_metadata,
+    set_proposal_total_supply,
+    store_proposal_events,
+    store_voting_power,
+};
+pub use lending::{
+    get_lending_events,
+    store_lending_events,
+    store_reprocessed_lending_events,
+};
+pub use memory::{ Fixtures, MemoryStore };
+pub use migrations::{
+    check_schema,
+    count_incomplete_rows,
+    delete_incomplete_rows,
+    prepare_database,
+    run_migrations,
+};
+pub use store::{ PgStore, Store };
+pub use unparsed::{
+    create_batch_of_unparsed_events,
+    get_unparsed_events,
+    get_unparsed_selector_counts,
+    store_fetched_events,
+    store_reprocessed_events,
+};
 
 const BATCH_SIZE: usize = 500;
 
-fn get_db_url(network: &Network) -> String {
-    let environment = env::var("ENVIRONMENT").expect("Could not read \"ENVIRONMENT\"");
+// 4 bind parameters per row, Postgres allows 65535 per statement
+const PRICE_UPDATE_CHUNK_SIZE: usize = 10_000;
+
+// endstate of old AMM
+const OLD_AMM_LAST_BLOCK: i64 = 495000;
+
+const VOTE_EVENT_NAMES: [&str; 3] = [
+    "Voted",
+    "governance::contract::Governance::Voted",
+    "ProposalsEvent",
+];
+
+const BRAAVOS_REFERRAL_CODE: &str = "braavos-referral-bonus";
+
+type DbPool = ConnectionPool<ConnectionManager<PgConnection>>;
+type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
+
+const POOL_MAX_SIZE: u32 = 10;
+const POOL_CONNECTION_TIMEOUT_SECS: u64 = 10;
+
+// one pool per network, created on first use
+static MAINNET_POOL: OnceLock<DbPool> = OnceLock::new();
+static TESTNET_POOL: OnceLock<DbPool> = OnceLock::new();
+
+fn env_var(name: &str) -> Result<String, DbError> {
+    env::var(name).map_err(|_| DbError::Config(format!("Could not read \"{}\"", name)))
+}
+
+fn get_db_url(network: &Network) -> Result<String, DbError> {
+    let environment = env_var("ENVIRONMENT")?;
     // your local DB
     if environment.as_str() == "local" {
-        return match network {
+        return Ok(match network {
             Network::Testnet => "postgres://localhost/carmine-testnet".to_string(),
             Network::Mainnet => "postgres://localhost/carmine-mainnet".to_string(),
-        };
+        });
     }
-    let username = env::var("DB_USER").expect("Could not read \"DB_USER\"");
-    let password = env::var("DB_PASSWORD").expect("Could not read \"DB_PASSWORD\"");
-    let ip = env::var("DB_IP").expect("Could not read \"DB_IP\"");
+    let username = env_var("DB_USER")?;
+    let password = env_var("DB_PASSWORD")?;
+    let ip = env_var("DB_IP")?;
 
     let base = format!("postgres://{}:{}@{}", username, password, ip);
-    match network {
-        Network::Testnet => format!("{}/carmine-testnet", base).to_string(),
-        Network::Mainnet => format!("{}/carmine-mainnet", base).to_string(),
+    Ok(match network {
+        Network::Testnet => format!("{}/carmine-testnet", base),
+        Network::Mainnet => format!("{}/carmine-mainnet", base),
+    })
+}
+
+fn get_pool(network: &Network) -> Result<&'static DbPool, DbError> {
+    let cell = match network {
+        Network::Mainnet => &MAINNET_POOL,
+        Network::Testnet => &TESTNET_POOL,
+    };
+
+    if let Some(pool) = cell.get() {
+        return Ok(pool);
     }
+
+    let manager = ConnectionManager::<PgConnection>::new(get_db_url(network)?);
+    let pool = ConnectionPool::builder()
+        .max_size(POOL_MAX_SIZE)
+        .connection_timeout(Duration::from_secs(POOL_CONNECTION_TIMEOUT_SECS))
+        .build(manager)?;
+
+    // if another thread was faster its pool is kept and this one dropped
+    Ok(cell.get_or_init(|| pool))
 }
 
-fn establish_connection(network: &Network) -> PgConnection {
-    let database_url = get_db_url(network);
-    PgConnection::establish(&database_url).unwrap_or_else(|e|
-        panic!("Error connecting to {}: {:?}", database_url, e)
-    )
+fn establish_connection(network: &Network) -> Result<DbConnection, DbError> {
+    Ok(get_pool(network)?.get()?)
+}
+
+#[derive(QueryableByName)]
+struct BlockNumberRow {
+    #[diesel(sql_type = BigInt)]
+    block_number: i64,
 }
 
-pub fn create_event(new_event: Event, network: &Network) {
+pub fn create_event(new_event: Event, network: &Network) -> Result<(), DbError> {
     use crate::schema::events::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     diesel
         ::insert_into(events)
         .values(&new_event)
         .on_conflict_do_nothing()
-        .execute(&mut connection)
-        .expect("Error saving event");
+        .execute(&mut connection)?;
+
+    Ok(())
 }
 
-pub fn create_batch_of_events(new_events: &Vec<Event>, network: &Network) {
+pub fn create_batch_of_events(new_events: &Vec<Event>, network: &Network) -> Result<(), DbError> {
     use crate::schema::events::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let chunks = new_events.chunks(BATCH_SIZE);
 
@@ -109,15 +209,19 @@ pub fn create_batch_of_events(new_events: &Vec<Event>, network: &Network) {
             ::insert_into(events)
             .values(chunk)
             .on_conflict_do_nothing()
-            .execute(&mut connection)
-            .expect("Error saving batch of events");
+            .execute(&mut connection)?;
     }
+
+    Ok(())
 }
 
-pub fn create_batch_of_starkscan_events(events: &Vec<StarkScanEventSettled>, network: &Network) {
+pub fn create_batch_of_starkscan_events(
+    events: &Vec<StarkScanEventSettled>,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let chunks = events.chunks(BATCH_SIZE);
 
@@ -128,31 +232,36 @@ pub fn create_batch_of_starkscan_events(events: &Vec<StarkScanEventSettled>, net
             ::insert_into(starkscan_events)
             .values(chunk)
             .on_conflict_do_nothing()
-            .execute(&mut connection)
-            .expect("Error saving batch of events");
+            .execute(&mut connection)?;
         inserted += res as u32;
     }
 
     println!("Inserted {} Starkscan events", inserted);
+
+    Ok(())
 }
 
-pub fn create_option(option: IOption, network: &Network) {
+pub fn create_option(option: IOption, network: &Network) -> Result<(), DbError> {
     use crate::schema::options::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     diesel
         ::insert_into(options)
         .values(&option)
         .on_conflict_do_nothing()
-        .execute(&mut connection)
-        .expect("Error saving option");
+        .execute(&mut connection)?;
+
+    Ok(())
 }
 
-pub fn create_batch_of_options(new_options: &Vec<IOption>, network: &Network) {
+pub fn create_batch_of_options(
+    new_options: &Vec<IOption>,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::options::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let chunks = new_options.chunks(BATCH_SIZE);
 
@@ -161,112 +270,143 @@ pub fn create_batch_of_options(new_options: &Vec<IOption>, network: &Network) {
             ::insert_into(options)
             .values(chunk)
             .on_conflict_do_nothing()
-            .execute(&mut connection)
-            .expect("Error saving batch of options");
+            .execute(&mut connection)?;
     }
+
+    Ok(())
 }
 
-pub fn create_block(data: &DbBlock, network: &Network) {
+pub fn create_block(data: &DbBlock, network: &Network) -> Result<(), DbError> {
     use crate::schema::blocks::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     diesel
         ::insert_into(blocks)
         .values(data)
         .on_conflict_do_nothing()
-        .execute(&mut connection)
-        .expect("Error saving block");
+        .execute(&mut connection)?;
+
+    Ok(())
 }
 
-pub fn create_pools(data: Vec<Pool>, network: &Network) {
+pub fn create_pools(data: Vec<Pool>, network: &Network) -> Result<(), DbError> {
     use crate::schema::pools::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     diesel
         ::insert_into(pools)
         .values(&data)
         .on_conflict_do_nothing()
-        .execute(&mut connection)
-        .expect("Error saving pools");
+        .execute(&mut connection)?;
+
+    Ok(())
 }
 
-pub fn create_oracle_price(data: &OraclePrice, network: &Network) {
+pub fn create_oracle_price(data: &OraclePrice, network: &Network) -> Result<(), DbError> {
     use crate::schema::oracle_prices::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     diesel
         ::insert_into(oracle_prices)
         .values(data)
         .on_conflict_do_nothing()
-        .execute(&mut connection)
-        .expect("Error saving oracle price");
+        .execute(&mut connection)?;
+
+    Ok(())
 }
 
-pub fn get_last_block_for_protocol_event(network: &Network, protocol: &Protocol) -> Option<i64> {
+pub fn get_last_block_for_protocol_event(
+    network: &Network,
+    protocol: &Protocol
+) -> Result<Option<i64>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     starkscan_events
         .filter(from_address.eq(protocol_address(network, protocol)))
         .select(max(block_number))
         .first(connection)
-        .expect("Error getting last block_number for protocol events")
+        .map_err(DbError::Query)
 }
 
 pub fn get_last_timestamp_for_protocol_event(
     network: &Network,
     protocol: &Protocol
-) -> Option<i64> {
+) -> Result<Option<i64>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     starkscan_events
         .filter(from_address.eq(protocol_address(network, protocol)))
         .select(max(timestamp))
         .first(connection)
-        .expect("Error loading last timestamp for protocol event")
+        .map_err(DbError::Query)
 }
 
 // TODO: move events (Carmine specific) to starkscan_events (general)
-pub fn get_last_timestamp_carmine_event(network: &Network) -> Option<i64> {
+pub fn get_last_timestamp_carmine_event(network: &Network) -> Result<Option<i64>, DbError> {
     use crate::schema::events::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     events
         .filter(from_address.eq(protocol_address(network, &Protocol::CarmineOptions)))
         .select(max(timestamp))
         .first(connection)
-        .expect("Error loading last timestamp for protocol event")
+        .map_err(DbError::Query)
 }
 
-pub fn get_oracle_prices(network: &Network) -> Vec<OraclePrice> {
+pub fn get_oracle_prices(network: &Network) -> Result<Vec<OraclePrice>, DbError> {
     use crate::schema::oracle_prices::dsl::*;
 
-    let connection = &mut establish_connection(network);
-    oracle_prices.load::<OraclePrice>(connection).expect("Error loading oracle prices")
+    let connection = &mut establish_connection(network)?;
+    oracle_prices
+        .filter(oracle_name.eq(PRIMARY_ORACLE))
+        .load::<OraclePrice>(connection)
+        .map_err(DbError::Query)
 }
 
-pub fn get_oracle_prices_from_block(network: &Network, initial_block: i64) -> Vec<OraclePrice> {
+pub fn get_oracle_prices_from_block(
+    network: &Network,
+    initial_block: i64
+) -> Result<Vec<OraclePrice>, DbError> {
     use crate::schema::oracle_prices::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     oracle_prices
+        .filter(oracle_name.eq(PRIMARY_ORACLE))
         .filter(block_number.ge(initial_block))
         .load::<OraclePrice>(connection)
-        .expect("Error loading oracle prices")
+        .map_err(DbError::Query)
 }
 
-pub fn get_oracle_prices_since_new_amm() -> Vec<OraclePrice> {
+pub fn get_oracle_prices_since_new_amm() -> Result<Vec<OraclePrice>, DbError> {
     get_oracle_prices_from_block(&Network::Mainnet, NEW_AMM_GENESIS_BLOCK_NUMBER)
 }
 
-pub fn get_pools(network: &Network) -> Vec<Pool> {
+pub fn get_latest_oracle_prices(
+    network: &Network,
+    pair: &str,
+    limit: i64
+) -> Result<Vec<OraclePrice>, DbError> {
+    use crate::schema::oracle_prices::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+    oracle_prices
+        .filter(oracle_name.eq(PRIMARY_ORACLE))
+        .filter(token_pair.eq(pair))
+        .order(block_number.desc())
+        .limit(limit)
+        .load::<OraclePrice>(connection)
+        .map_err(DbError::Query)
+}
+
+pub fn get_pools(network: &Network) -> Result<Vec<Pool>, DbError> {
     use crate::schema::pools::dsl::*;
 
     // TODO: currently old AMM is still in the DB, but we no longer care
@@ -275,67 +415,76 @@ pub fn get_pools(network: &Network) -> Vec<Pool> {
         "0x18a6abca394bd5f822cfa5f88783c01b13e593d1603e7b41b00d31d2ea4827a"
     ];
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     pools
         .filter(lp_address.ne_all(legacy_lp_addresses))
         .load::<Pool>(connection)
-        .expect("Error loading pools")
+        .map_err(DbError::Query)
 }
 
-pub fn get_protocol_events(network: &Network, protocol: &Protocol) -> Vec<StarkScanEventSettled> {
+pub fn get_protocol_events(
+    network: &Network,
+    protocol: &Protocol
+) -> Result<Vec<StarkScanEventSettled>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
     let address = protocol_address(network, protocol);
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     starkscan_events
         .filter(from_address.eq(address))
         .load::<StarkScanEventSettled>(connection)
-        .expect("Error loading starkscan events")
+        .map_err(DbError::Query)
 }
 
-pub fn get_events_by_address(network: &Network, address: &str) -> Vec<StarkScanEventSettled> {
+pub fn get_events_by_address(
+    network: &Network,
+    address: &str
+) -> Result<Vec<StarkScanEventSettled>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     starkscan_events
         .filter(from_address.eq(address))
         .load::<StarkScanEventSettled>(connection)
-        .expect("Error loading starkscan events")
+        .map_err(DbError::Query)
 }
 
 pub fn get_protocol_events_from_block(
     network: &Network,
     protocol: &Protocol,
     from_block_number: i64
-) -> Vec<StarkScanEventSettled> {
+) -> Result<Vec<StarkScanEventSettled>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
     let address = protocol_address(network, protocol);
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     starkscan_events
         .filter(block_number.gt(from_block_number))
         .filter(from_address.eq(address))
         .load::<StarkScanEventSettled>(connection)
-        .expect("Error loading starkscan events")
+        .map_err(DbError::Query)
 }
 
-pub fn get_events(network: &Network) -> Vec<Event> {
+pub fn get_events(network: &Network) -> Result<Vec<Event>, DbError> {
     use crate::schema::events::dsl::*;
 
-    let connection = &mut establish_connection(network);
-    events.load::<Event>(connection).expect("Error loading events")
+    let connection = &mut establish_connection(network)?;
+    events.load::<Event>(connection).map_err(DbError::Query)
 }
 
-pub fn get_events_by_caller_address(address: &str, network: &Network) -> Vec<Event> {
+pub fn get_events_by_caller_address(
+    address: &str,
+    network: &Network
+) -> Result<Vec<Event>, DbError> {
     use crate::schema::events::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     events
         .filter(caller.eq(address))
         .load::<Event>(connection)
-        .expect("Error loading events by caller address")
+        .map_err(DbError::Query)
 }
 
 pub fn get_option_with_address(
@@ -344,77 +493,73 @@ pub fn get_option_with_address(
     in_maturity: i64,
     in_strike_price: &String,
     in_lp_address: &String
-) -> Option<IOption> {
+) -> Result<Option<IOption>, DbError> {
     use crate::schema::options::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     let res: Option<IOption> = options
         .filter(lp_address.eq(in_lp_address))
         .filter(maturity.eq(in_maturity))
         .filter(strike_price.eq(in_strike_price))
         .filter(option_side.eq(in_option_side))
         .first::<IOption>(connection)
-        .optional()
-        .expect("Error loading options");
+        .optional()?;
 
-    res
+    Ok(res)
 }
 
-pub fn get_options(network: &Network) -> Vec<IOption> {
+pub fn get_options(network: &Network) -> Result<Vec<IOption>, DbError> {
     use crate::schema::options::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     options
         .filter(maturity.gt(NEW_AMM_GENESIS_TIMESTAMP)) // only get new AMM options
         .load::<IOption>(connection)
-        .expect("Error loading options")
+        .map_err(DbError::Query)
 }
 
-pub fn get_non_expired_options(network: &Network, ts: i64) -> Vec<IOption> {
+pub fn get_non_expired_options(network: &Network, ts: i64) -> Result<Vec<IOption>, DbError> {
     use crate::schema::options::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     options
         .filter(maturity.gt(ts)) // only get new AMM options
         .load::<IOption>(connection)
-        .expect("Error loading options")
+        .map_err(DbError::Query)
 }
 
-pub fn get_legacy_options(network: &Network) -> Vec<IOption> {
+pub fn get_legacy_options(network: &Network) -> Result<Vec<IOption>, DbError> {
     use crate::schema::options::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     options
         .filter(maturity.lt(NEW_AMM_GENESIS_TIMESTAMP)) // only get new AMM options
         .load::<IOption>(connection)
-        .expect("Error loading options")
+        .map_err(DbError::Query)
 }
 
-pub fn get_block_by_number(num: i64, network: &Network) -> Option<DbBlock> {
+pub fn get_block_by_number(num: i64, network: &Network) -> Result<Option<DbBlock>, DbError> {
     use crate::schema::blocks::dsl::*;
 
-    let connection = &mut establish_connection(network);
-    match blocks.find(num).first(connection) {
-        Ok(v) => Some(v),
-        _ => None,
-    }
+    let connection = &mut establish_connection(network)?;
+    blocks.find(num).first(connection).optional().map_err(DbError::Query)
 }
 
-pub fn get_blocks_greater_than(min: i64, network: &Network) -> Vec<DbBlock> {
+pub fn get_blocks_greater_than(min: i64, network: &Network) -> Result<Vec<DbBlock>, DbError> {
     use crate::schema::blocks::dsl::*;
 
-    let connection = &mut establish_connection(network);
-    blocks.filter(block_number.gt(min)).load::<DbBlock>(connection).expect("Failed getting blocks")
+    let connection = &mut establish_connection(network)?;
+    blocks.filter(block_number.gt(min)).load::<DbBlock>(connection).map_err(DbError::Query)
 }
 
-pub fn get_blocks_since_new_amm() -> Vec<DbBlock> {
+pub fn get_blocks_since_new_amm() -> Result<Vec<DbBlock>, DbError> {
     get_blocks_greater_than(NEW_AMM_GENESIS_BLOCK_NUMBER, &Network::Mainnet)
 }
 
-pub fn get_last_block_in_db(network: &Network) -> DbBlock {
+pub fn get_last_block_in_db(network: &Network) -> Result<DbBlock, DbError> {
     use crate::schema::blocks::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     // TODO: this is the right way to do it, but Diesel has some weird problem with it
     // let res = blocks.select(max(block_number)).first(connection);
@@ -424,19 +569,20 @@ pub fn get_last_block_in_db(network: &Network) -> DbBlock {
     // }
 
     // get all and find max, because Diesel does not like the SQL solution ¯\_(ツ)_/¯
-    if let Ok(all_blocks) = blocks.load::<DbBlock>(connection) {
-        return all_blocks
-            .into_iter()
-            .max_by_key(|b| b.block_number)
-            .expect("did not find last block in DB");
-    }
-    unreachable!("did not find last block in DB");
+    blocks
+        .load::<DbBlock>(connection)?
+        .into_iter()
+        .max_by_key(|b| b.block_number)
+        .ok_or_else(|| DbError::InvalidData("did not find last block in DB".to_string()))
 }
 
-pub fn create_batch_of_volatilities(volatilities: &Vec<OptionVolatility>, network: &Network) {
+pub fn create_batch_of_volatilities(
+    volatilities: &Vec<OptionVolatility>,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::options_volatility::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let chunks = volatilities.chunks(BATCH_SIZE);
 
@@ -445,15 +591,19 @@ pub fn create_batch_of_volatilities(volatilities: &Vec<OptionVolatility>, networ
             ::insert_into(options_volatility)
             .values(chunk)
             .on_conflict_do_nothing()
-            .execute(&mut connection)
-            .expect("Error saving batch of volatilities");
+            .execute(&mut connection)?;
     }
+
+    Ok(())
 }
 
-pub fn update_batch_of_volatilities(new_volatilities: &Vec<OptionVolatility>, network: &Network) {
+pub fn update_batch_of_volatilities(
+    new_volatilities: &Vec<OptionVolatility>,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::options_volatility::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let mut updated_sum = 0;
 
@@ -467,19 +617,23 @@ pub fn update_batch_of_volatilities(new_volatilities: &Vec<OptionVolatility>, ne
                 )
             )
             .set(new_volatility)
-            .execute(&mut connection)
-            .expect("Error updating volatilities");
+            .execute(&mut connection)?;
 
         updated_sum += res;
     }
 
     println!("Updated volatilities: {}", updated_sum);
+
+    Ok(())
 }
 
-pub fn create_batch_of_pool_states(states: &Vec<PoolState>, network: &Network) {
+pub fn create_batch_of_pool_states(
+    states: &Vec<PoolState>,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::pool_state::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
     let chunks = states.chunks(BATCH_SIZE);
 
@@ -488,24 +642,26 @@ pub fn create_batch_of_pool_states(states: &Vec<PoolState>, network: &Network) {
             ::insert_into(pool_state)
             .values(chunk)
             .on_conflict_do_nothing()
-            .execute(&mut connection)
-            .expect("Error saving batch of pool states");
+            .execute(&mut connection)?;
     }
+
+    Ok(())
 }
 
-pub fn get_pool_state(pool_address: &str, network: &Network) -> Vec<PoolStateWithTimestamp> {
+pub fn get_pool_state(
+    pool_address: &str,
+    network: &Network
+) -> Result<Vec<PoolStateWithTimestamp>, DbError> {
     use crate::schema::blocks::dsl::blocks;
     use crate::schema::pool_state::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     let mut data: Vec<PoolStateWithTimestamp> = pool_state
         .inner_join(blocks)
         .filter(lp_address.eq(pool_address))
-        // endstate of old AMM
-        .filter(block_number.gt(495000))
+        .filter(block_number.gt(OLD_AMM_LAST_BLOCK))
         .select((PoolState::as_select(), DbBlock::as_select()))
-        .load::<(PoolState, DbBlock)>(connection)
-        .expect("Error loading pool state")
+        .load::<(PoolState, DbBlock)>(connection)?
         .into_iter()
         .map(|(pool, block): (PoolState, DbBlock)| PoolStateWithTimestamp {
             unlocked_cap: pool.unlocked_cap,
@@ -523,28 +679,31 @@ pub fn get_pool_state(pool_address: &str, network: &Network) -> Vec<PoolStateWit
 
     data.sort_by(|a, b| b.block_number.cmp(&a.block_number));
 
-    data
+    Ok(data)
 }
 
 pub fn get_pool_states_with_prices(
     pool_address: &str,
     network: &Network
-) -> Vec<(PoolState, Vec<OraclePrice>)> {
-    use crate::schema::oracle_prices::dsl::{ block_number as oracle_block_number, oracle_prices };
+) -> Result<Vec<(PoolState, Vec<OraclePrice>)>, DbError> {
+    use crate::schema::oracle_prices::dsl::{
+        block_number as oracle_block_number,
+        oracle_name,
+        oracle_prices,
+    };
     use crate::schema::pool_state::dsl::{
         block_number as pool_state_block_number,
         lp_address,
         pool_state,
     };
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     let pool_states_with_blocks: Vec<PoolState> = pool_state
         .filter(lp_address.eq(pool_address))
-        .filter(pool_state_block_number.gt(495000))
+        .filter(pool_state_block_number.gt(OLD_AMM_LAST_BLOCK))
         .filter(lp_token_value_usd.is_null()) // only states that do not have price yet
-        .load::<PoolState>(connection)
-        .expect("Error loading pool state");
+        .load::<PoolState>(connection)?;
 
     let block_numbers: Vec<i64> = pool_states_with_blocks
         .iter()
@@ -552,9 +711,9 @@ pub fn get_pool_states_with_prices(
         .collect();
 
     let prices: Vec<OraclePrice> = oracle_prices
+        .filter(oracle_name.eq(PRIMARY_ORACLE))
         .filter(oracle_block_number.eq_any(&block_numbers))
-        .load::<OraclePrice>(connection)
-        .expect("Error loading oracle prices");
+        .load::<OraclePrice>(connection)?;
 
     let mut prices_map: std::collections::HashMap<
         i64,
@@ -574,71 +733,104 @@ pub fn get_pool_states_with_prices(
 
     results.sort_by(|a, b| b.0.block_number.cmp(&a.0.block_number));
 
-    results
+    Ok(results)
 }
 
 pub fn update_pool_state_asset_prices(
     pool_state_price_updates: Vec<PoolStatePriceUpdate>
-) -> Result<(), diesel::result::Error> {
-    use self::schema::pool_state::dsl::*;
-
-    let connection = &mut establish_connection(&Network::Mainnet);
-
-    for price_update in pool_state_price_updates {
-        diesel
-            ::update(
-                pool_state.filter(
-                    lp_address
-                        .eq(price_update.lp_address)
-                        .and(block_number.eq(price_update.block_number))
+) -> Result<(), DbError> {
+    let connection = &mut establish_connection(&Network::Mainnet)?;
+
+    connection.transaction(|conn| {
+        for chunk in pool_state_price_updates.chunks(PRICE_UPDATE_CHUNK_SIZE) {
+            let rows: Vec<String> = (0..chunk.len())
+                .map(|i| {
+                    let n = i * 4;
+                    format!(
+                        "(${}::float8, ${}::float8, ${}::int8, ${}::text)",
+                        n + 1,
+                        n + 2,
+                        n + 3,
+                        n + 4
+                    )
+                })
+                .collect();
+
+            let mut query = sql_query(
+                format!(
+                    "UPDATE pool_state \
+                    SET lp_token_value_usd = v.lp_token_value_usd, \
+                    underlying_asset_price = v.underlying_asset_price \
+                    FROM (VALUES {}) \
+                    AS v (lp_token_value_usd, underlying_asset_price, block_number, lp_address) \
+                    WHERE pool_state.lp_address = v.lp_address \
+                    AND pool_state.block_number = v.block_number",
+                    rows.join(", ")
                 )
-            )
-            .set((
-                lp_token_value_usd.eq(price_update.lp_token_value_usd),
-                underlying_asset_price.eq(price_update.underlying_asset_price),
-            ))
-            .execute(connection)?;
-    }
+            ).into_boxed::<Pg>();
+
+            for price_update in chunk {
+                query = query
+                    .bind::<Double, _>(price_update.lp_token_value_usd)
+                    .bind::<Double, _>(price_update.underlying_asset_price)
+                    .bind::<BigInt, _>(price_update.block_number)
+                    .bind::<Text, _>(&price_update.lp_address);
+            }
 
-    Ok(())
+            query.execute(conn)?;
+        }
+        Ok(())
+    })
 }
 
 pub fn get_pool_state_block_numbers_in_range(
     start_block: i64,
     end_block: i64,
     network: &Network
-) -> Vec<i64> {
+) -> Result<Vec<i64>, DbError> {
     use crate::schema::pool_state::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
     pool_state
         .select(block_number)
         .filter(block_number.gt(start_block - 1).and(block_number.lt(end_block + 1)))
         .order(block_number.asc())
         .load::<i64>(connection)
-        .expect("Error loading pool_state")
+        .map_err(DbError::Query)
 }
 
-pub fn get_pool_state_block_holes(start: i64, end: i64, network: &Network) -> Vec<i64> {
-    let blocks = get_pool_state_block_numbers_in_range(start, end, network);
-
-    let range_numbers: Vec<i64> = (start..=end).collect();
-
-    let holes: Vec<i64> = range_numbers
-        .iter()
-        .filter(|&x| !blocks.contains(x))
-        .cloned()
-        .collect();
+/// Blocks between start and end (inclusive) without any pool state
+pub fn get_pool_state_block_holes(
+    start: i64,
+    end: i64,
+    network: &Network
+) -> Result<Vec<i64>, DbError> {
+    let connection = &mut establish_connection(network)?;
+
+    let holes: Vec<BlockNumberRow> = sql_query(
+        "SELECT s.block_number FROM generate_series($1::int8, $2::int8) AS s (block_number) \
+        WHERE NOT EXISTS \
+        (SELECT 1 FROM pool_state p WHERE p.block_number = s.block_number) \
+        ORDER BY s.block_number"
+    )
+        .bind::<BigInt, _>(start)
+        .bind::<BigInt, _>(end)
+        .load(connection)?;
 
-    holes
+    Ok(
+        holes
+            .into_iter()
+            .map(|row| row.block_number)
+            .collect()
+    )
 }
 
-pub fn get_options_volatility(network: &Network) -> Vec<OptionWithVolatility> {
+pub fn get_options_volatility(network: &Network) -> Result<Vec<OptionWithVolatility>, DbError> {
     use crate::schema::blocks::dsl::*;
     use crate::schema::options::dsl::*;
     use crate::schema::options_volatility::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
     let start = SystemTime::now();
     let timestamp_now = start.duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
@@ -648,8 +840,7 @@ pub fn get_options_volatility(network: &Network) -> Vec<OptionWithVolatility> {
     let live_options: Vec<IOption> = options
         .filter(maturity.gt(cutoff))
         .select(IOption::as_select())
-        .load(connection)
-        .expect("Failed getting all options");
+        .load(connection)?;
 
     let mut options_with_volatilities: Vec<OptionWithVolatility> = vec![];
 
@@ -659,8 +850,7 @@ pub fn get_options_volatility(network: &Network) -> Vec<OptionWithVolatility> {
             .inner_join(blocks)
             .order(crate::schema::blocks::dsl::block_number.desc())
             .select((OptionVolatility::as_select(), DbBlock::as_select()))
-            .load::<(OptionVolatility, DbBlock)>(connection)
-            .expect("Error loading option volatility")
+            .load::<(OptionVolatility, DbBlock)>(connection)?
             .iter()
             .map(|(vol, block)| Volatility {
                 block_number: block.block_number,
@@ -684,7 +874,43 @@ pub fn get_options_volatility(network: &Network) -> Vec<OptionWithVolatility> {
         });
     }
 
-    options_with_volatilities
+    Ok(options_with_volatilities)
+}
+
+/// (block_number, timestamp, maturity, volatility) of all options in the pool
+/// in blocks after `after_block`, sorted by block
+pub fn get_pool_volatilities(
+    network: &Network,
+    pool_address: &str,
+    after_block: i64
+) -> Result<Vec<(i64, i64, i64, Option<String>)>, DbError> {
+    use crate::schema::blocks::dsl as blocks_dsl;
+    use crate::schema::options::dsl as options_dsl;
+    use crate::schema::options_volatility::dsl as volatility_dsl;
+
+    let connection = &mut establish_connection(network)?;
+
+    options_dsl::options
+        .inner_join(
+            volatility_dsl::options_volatility.on(
+                options_dsl::option_address.eq(volatility_dsl::option_address)
+            )
+        )
+        .inner_join(
+            blocks_dsl::blocks.on(volatility_dsl::block_number.eq(blocks_dsl::block_number))
+        )
+        .filter(options_dsl::lp_address.eq(pool_address))
+        .filter(volatility_dsl::block_number.gt(after_block))
+        .filter(volatility_dsl::volatility.is_not_null())
+        .order(blocks_dsl::block_number.asc())
+        .select((
+            blocks_dsl::block_number,
+            blocks_dsl::timestamp,
+            options_dsl::maturity,
+            volatility_dsl::volatility,
+        ))
+        .load::<(i64, i64, i64, Option<String>)>(connection)
+        .map_err(DbError::Query)
 }
 
 pub fn update_option_volatility(
@@ -693,57 +919,60 @@ pub fn update_option_volatility(
     vol: Option<String>,
     pos: Option<String>,
     address: String
-) {
+) -> Result<(), DbError> {
     use crate::schema::options_volatility::dsl::*;
 
-    let mut connection = establish_connection(network);
+    let mut connection = establish_connection(network)?;
 
-    let res = diesel
+    diesel
         ::update(options_volatility)
         .filter(block_number.eq(block))
         .filter(option_address.eq(address))
         .set((volatility.eq(vol), option_position.eq(pos)))
-        .execute(&mut connection);
+        .execute(&mut connection)?;
 
-    if let Err(_e) = res {
-        println!("FAILED! {}", block);
-    }
+    Ok(())
 }
 
-pub fn update_token_value(block: i64, pool: String, new_value: String, network: &Network) {
+pub fn update_token_value(
+    block: i64,
+    pool: String,
+    new_value: String,
+    network: &Network
+) -> Result<(), DbError> {
     use crate::schema::pool_state::dsl::*;
 
-    let connection = &mut establish_connection(network);
+    let connection = &mut establish_connection(network)?;
 
-    let res = diesel
+    let size = diesel
         ::update(pool_state)
         .filter(lp_address.eq(pool))
         .filter(block_number.eq(block))
         .set(lp_token_value.eq(new_value))
-        .execute(connection);
+        .execute(connection)?;
 
-    match res {
-        Ok(size) => println!("SUCCESS: updated block {} - returned value: {}", block, size),
-        Err(e) => println!("FAIL: block {} failed: {:?}", block, e),
-    }
+    println!("SUCCESS: updated block {} - returned value: {}", block, size);
+
+    Ok(())
 }
 
-pub fn create_referral_pair(referral_pair: ReferralCode) {
+pub fn create_referral_pair(referral_pair: ReferralCode) -> Result<(), DbError> {
     use crate::schema::referral_codes::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
-    let _ = diesel
+    diesel
         ::insert_into(referral_codes)
         .values(&referral_pair)
-        .execute(connection)
-        .expect("Error loading pool_state");
+        .execute(connection)?;
+
+    Ok(())
 }
 
-fn is_referral_code_available(code: &str) -> QueryResult<bool> {
+fn is_referral_code_available(code: &str) -> Result<bool, DbError> {
     use crate::schema::referral_codes::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let count = referral_codes
         .filter(referral_code.eq(code))
@@ -753,52 +982,51 @@ fn is_referral_code_available(code: &str) -> QueryResult<bool> {
     Ok(count == 0)
 }
 
-pub fn get_referral_code(referrer: String) -> String {
+pub fn get_referral_code(referrer: String) -> Result<String, DbError> {
     use crate::schema::referral_codes::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
-    let res: QueryResult<String> = referral_codes
+    let res: Option<String> = referral_codes
         .filter(wallet_address.eq(&referrer))
         .select(referral_code)
-        .first(connection);
+        .first(connection)
+        .optional()?;
 
-    if let Ok(code) = res {
+    if let Some(code) = res {
         // referral code already in DB
-        return code;
+        return Ok(code);
     }
 
     let new_referral_code = loop {
         let temp = generate_referral_code();
 
-        if let Ok(is_available) = is_referral_code_available(&temp) {
-            if is_available {
-                break temp;
-            }
+        if is_referral_code_available(&temp)? {
+            break temp;
         }
     };
 
     create_referral_pair(ReferralCode {
         wallet_address: referrer,
         referral_code: new_referral_code.clone(),
-    });
+    })?;
 
-    new_referral_code
+    Ok(new_referral_code)
 }
 
-pub fn create_referral_event(event: NewReferralEvent) -> Result<usize, diesel::result::Error> {
+pub fn create_referral_event(event: NewReferralEvent) -> Result<usize, DbError> {
     use crate::schema::referral_events::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
-    diesel::insert_into(referral_events).values(&event).execute(connection)
+    diesel::insert_into(referral_events).values(&event).execute(connection).map_err(DbError::Query)
 }
 
-pub fn get_referral_events() -> Vec<ReferralEventDigest> {
+pub fn get_referral_events() -> Result<Vec<ReferralEventDigest>, DbError> {
     use crate::schema::referral_codes;
     use crate::schema::referral_events;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     referral_events::table
         .inner_join(
@@ -831,85 +1059,82 @@ pub fn get_referral_events() -> Vec<ReferralEventDigest> {
                 )
                 .collect()
         })
-        .expect("Error loading referral events")
+        .map_err(DbError::Query)
 }
 
-pub fn create_insurance_event(event: InsuranceEvent) -> Result<usize, diesel::result::Error> {
+pub fn create_insurance_event(event: InsuranceEvent) -> Result<usize, DbError> {
     use crate::schema::insurance_events::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
-    diesel::insert_into(insurance_events).values(&event).execute(connection)
+    diesel::insert_into(insurance_events).values(&event).execute(connection).map_err(DbError::Query)
 }
 
-pub fn get_insurance_events() -> Vec<InsuranceEventQueryable> {
+pub fn get_insurance_events() -> Result<Vec<InsuranceEventQueryable>, DbError> {
     use crate::schema::insurance_events::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     insurance_events
         .load::<InsuranceEventQueryable>(connection)
-        .expect("Failed getting insurance events")
+        .map_err(DbError::Query)
 }
 
-pub fn get_user_points(address: &str) -> Option<UserPointsDb> {
+pub fn get_user_points(address: &str) -> Result<Option<UserPointsDb>, DbError> {
     use crate::schema::user_points::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
-    let res: QueryResult<UserPointsDb> = user_points
+    let connection = &mut establish_connection(&Network::Mainnet)?;
+    user_points
         .filter(user_address.eq(address))
         .order(timestamp.desc())
-        .first(connection);
-
-    match res {
-        Ok(points) => Some(points),
-        Err(_) => None,
-    }
+        .first(connection)
+        .optional()
+        .map_err(DbError::Query)
 }
 
-pub fn get_user_points_lastest_timestamp() -> Option<SystemTime> {
+pub fn get_user_points_lastest_timestamp() -> Result<Option<SystemTime>, DbError> {
     use crate::schema::user_points::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     user_points
         .select(max(timestamp))
         .first(connection)
-        .expect("Failed getting user points timestamp")
+        .map_err(DbError::Query)
 }
 
-pub fn get_all_user_points(ts: SystemTime) -> Vec<UserPoints> {
+pub fn get_all_user_points(ts: SystemTime) -> Result<Vec<UserPoints>, DbError> {
     use crate::schema::user_points::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let records = user_points
         .filter(timestamp.eq(ts))
-        .load::<UserPointsDb>(connection)
-        .expect("Failed getting user points");
+        .load::<UserPointsDb>(connection)?;
 
-    records
-        .into_iter()
-        .map(|v| UserPoints {
-            address: v.user_address,
-            trading_points: v.trading_points,
-            liquidity_points: v.liquidity_points,
-            referral_points: v.referral_points,
-            vote_points: v.vote_points,
-        })
-        .collect()
+    Ok(
+        records
+            .into_iter()
+            .map(|v| UserPoints {
+                address: v.user_address,
+                trading_points: v.trading_points,
+                liquidity_points: v.liquidity_points,
+                referral_points: v.referral_points,
+                vote_points: v.vote_points,
+            })
+            .collect()
+    )
 }
 
-pub fn get_braavos_eligible_user_addresses() -> Vec<String> {
+pub fn get_braavos_eligible_user_addresses() -> Result<Vec<String>, DbError> {
     use crate::schema::user_points::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     // Subquery to find the maximum timestamp
     let max_timestamp_option = user_points
         .select(max(timestamp))
-        .first::<Option<SystemTime>>(connection)
-        .expect("Failed getting braavos eligible timestamp");
+        .first::<Option<SystemTime>>(connection)?;
 
     if let Some(max_timestamp) = max_timestamp_option {
         user_points
@@ -917,61 +1142,57 @@ pub fn get_braavos_eligible_user_addresses() -> Vec<String> {
             .select(user_address)
             .distinct()
             .load::<String>(connection)
-            .expect("Failed getting braavos eligible user addresses")
+            .map_err(DbError::Query)
     } else {
-        vec![]
+        Ok(vec![])
     }
 }
 
-pub fn get_votes() -> Vec<Vote> {
+pub fn get_votes() -> Result<Vec<Vote>, DbError> {
     use crate::schema::starkscan_events::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let events: Vec<StarkScanEventSettled> = starkscan_events
-        .filter(
-            key_name.eq_any(
-                vec!["Voted", "governance::contract::Governance::Voted", "ProposalsEvent"]
-            )
-        )
-        .load::<StarkScanEventSettled>(connection)
-        .expect("Error getting votes");
+        .filter(key_name.eq_any(VOTE_EVENT_NAMES.to_vec()))
+        .load::<StarkScanEventSettled>(connection)?;
 
-    events
-        .into_iter()
-        .map(|event| {
-            let (prop_id_str, user_address, opinion_str) = (
-                event.data[0].as_str(),
-                event.data[1].to_string(),
-                event.data[2].as_str(),
-            );
-
-            // historically there are multiple options for "nay"
-            // but only "0x1" for "yay"
-            let opinion = match opinion_str {
-                "0x1" => 1,
-                _ => 0,
-            };
-
-            Vote {
-                timestamp: event.timestamp,
-                user_address,
-                prop_id: usize
-                    ::from_str_radix(&prop_id_str[2..], 16)
-                    .expect("Failed parsing prop_id"),
-                opinion,
-            }
-        })
-        .collect()
+    events.iter().map(vote_from_event).collect()
+}
+
+fn vote_from_event(event: &StarkScanEventSettled) -> Result<Vote, DbError> {
+    let (prop_id_str, user_address, opinion_str) = (
+        event.data[0].as_str(),
+        event.data[1].to_string(),
+        event.data[2].as_str(),
+    );
+
+    // historically there are multiple options for "nay"
+    // but only "0x1" for "yay"
+    let opinion = match opinion_str {
+        "0x1" => 1,
+        _ => 0,
+    };
+
+    let prop_id = usize
+        ::from_str_radix(prop_id_str.trim_start_matches("0x"), 16)
+        .map_err(|_| DbError::InvalidData(format!("Invalid prop_id {}", prop_id_str)))?;
+
+    Ok(Vote {
+        timestamp: event.timestamp,
+        user_address,
+        prop_id,
+        opinion,
+    })
 }
 
-pub fn get_tvl_info() -> QueryResult<Vec<PoolTvlInfo>> {
+pub fn get_tvl_info() -> Result<Vec<PoolTvlInfo>, DbError> {
     use crate::schema::blocks::dsl as blocks_dsl;
     use crate::schema::options::dsl as options_dsl;
     use crate::schema::options_volatility::dsl as volatility_dsl;
     use crate::schema::pool_state::dsl as pool_state_dsl;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let (max_block_num, max_block_timestamp) = blocks_dsl::blocks
         .order(blocks_dsl::block_number.desc())
@@ -1013,8 +1234,14 @@ pub fn get_tvl_info() -> QueryResult<Vec<PoolTvlInfo>> {
     Ok(results)
 }
 
-pub fn get_pool_tvl_map() -> HashMap<String, u128> {
-    let tvl = get_tvl_info().expect("Query failed");
+fn parse_hex_u128(hex: &str) -> Result<u128, DbError> {
+    u128::from_str_radix(hex.trim_start_matches("0x"), 16).map_err(|_|
+        DbError::InvalidData(format!("Invalid hex value {}", hex))
+    )
+}
+
+pub fn get_pool_tvl_map() -> Result<HashMap<String, u128>, DbError> {
+    let tvl = get_tvl_info()?;
 
     let dec_18: u128 = 1_000_000_000_000_000_000;
     let dec_8: u128 = 100_000_000;
@@ -1041,54 +1268,75 @@ pub fn get_pool_tvl_map() -> HashMap<String, u128> {
 
         let (pool_divisor, option_divisor) = address_to_divisor
             .get(&tvl_info.lp_address.as_str())
-            .expect("Failed getting divisor");
-
-        tvl +=
-            (u128
-                ::from_str_radix(&tvl_info.locked_capital[2..], 16)
-                .expect("Failed to parse locked_capital") *
-                ad_hoc_precission) /
-            pool_divisor;
-        tvl +=
-            (u128
-                ::from_str_radix(&tvl_info.unlocked_capital[2..], 16)
-                .expect("Failed to parse unlocked_capital") *
-                ad_hoc_precission) /
-            pool_divisor;
+            .ok_or_else(|| DbError::InvalidData(format!("Unknown pool {}", lp_address)))?;
+
+        tvl += (parse_hex_u128(&tvl_info.locked_capital)? * ad_hoc_precission) / pool_divisor;
+        tvl += (parse_hex_u128(&tvl_info.unlocked_capital)? * ad_hoc_precission) / pool_divisor;
 
         for hex in tvl_info.option_positions {
-            tvl +=
-                (u128::from_str_radix(&hex[2..], 16).expect("Failed to parse option position") *
-                    ad_hoc_precission) /
-                option_divisor;
+            tvl += (parse_hex_u128(&hex)? * ad_hoc_precission) / option_divisor;
         }
 
         pool_tvl_map.insert(lp_address, tvl);
     }
 
-    pool_tvl_map
+    Ok(pool_tvl_map)
 }
 
-pub fn get_price_block_numbers(pair: &TokenPair, min_block: i64, max_block: i64) -> Vec<i64> {
+pub fn get_price_block_numbers(
+    pair: &TokenPair,
+    min_block: i64,
+    max_block: i64
+) -> Result<Vec<i64>, DbError> {
     use crate::schema::oracle_prices::dsl::*;
 
     let token_id = pair.id();
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     oracle_prices
         .select(block_number)
+        .filter(oracle_name.eq(PRIMARY_ORACLE))
         .filter(token_pair.eq(token_id))
         .filter(block_number.ge(min_block))
         .filter(block_number.le(max_block))
         .load::<i64>(connection)
-        .expect("Error loading block numbers")
+        .map_err(DbError::Query)
 }
 
-pub fn upsert_braavos_pro_score_80(address: &str, ts: i64) -> QueryResult<usize> {
+/// Blocks between min and max (inclusive) without a price of the pair
+pub fn get_missing_price_block_numbers(
+    pair: &TokenPair,
+    min_block: i64,
+    max_block: i64
+) -> Result<Vec<i64>, DbError> {
+    let connection = &mut establish_connection(&Network::Mainnet)?;
+
+    let missing: Vec<BlockNumberRow> = sql_query(
+        "SELECT s.block_number FROM generate_series($1::int8, $2::int8) AS s (block_number) \
+        WHERE NOT EXISTS \
+        (SELECT 1 FROM oracle_prices o \
+        WHERE o.token_pair = $3 AND o.oracle_name = $4 AND o.block_number = s.block_number) \
+        ORDER BY s.block_number"
+    )
+        .bind::<BigInt, _>(min_block)
+        .bind::<BigInt, _>(max_block)
+        .bind::<Text, _>(pair.id())
+        .bind::<Text, _>(PRIMARY_ORACLE)
+        .load(connection)?;
+
+    Ok(
+        missing
+            .into_iter()
+            .map(|row| row.block_number)
+            .collect()
+    )
+}
+
+pub fn upsert_braavos_pro_score_80(address: &str, ts: i64) -> Result<usize, DbError> {
     use crate::schema::braavos_bonus::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     // Check if the user exists
     let existing_user: Option<BraavosBonus> = braavos_bonus
@@ -1100,7 +1348,10 @@ pub fn upsert_braavos_pro_score_80(address: &str, ts: i64) -> QueryResult<usize>
         Some(mut user) => {
             if user.pro_score_80.is_none() {
                 user.pro_score_80 = Some(ts);
-                update(braavos_bonus.find(address)).set(&user).execute(connection)
+                update(braavos_bonus.find(address))
+                    .set(&user)
+                    .execute(connection)
+                    .map_err(DbError::Query)
             } else {
                 Ok(0)
             }
@@ -1114,14 +1365,15 @@ pub fn upsert_braavos_pro_score_80(address: &str, ts: i64) -> QueryResult<usize>
                         braavos_referral: None,
                     })
                 )
-                .execute(connection),
+                .execute(connection)
+                .map_err(DbError::Query),
     }
 }
 
-pub fn upsert_braavos_referral(address: &str, ts: i64) -> QueryResult<usize> {
+pub fn upsert_braavos_referral(address: &str, ts: i64) -> Result<usize, DbError> {
     use crate::schema::braavos_bonus::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     // Check if the user exists
     let existing_user: Option<BraavosBonus> = braavos_bonus
@@ -1133,7 +1385,10 @@ pub fn upsert_braavos_referral(address: &str, ts: i64) -> QueryResult<usize> {
         Some(mut user) => {
             if user.braavos_referral.is_none() {
                 user.braavos_referral = Some(ts);
-                update(braavos_bonus.find(address)).set(&user).execute(connection)
+                update(braavos_bonus.find(address))
+                    .set(&user)
+                    .execute(connection)
+                    .map_err(DbError::Query)
             } else {
                 Ok(0)
             }
@@ -1147,32 +1402,35 @@ pub fn upsert_braavos_referral(address: &str, ts: i64) -> QueryResult<usize> {
                         braavos_referral: Some(ts),
                     })
                 )
-                .execute(connection),
+                .execute(connection)
+                .map_err(DbError::Query),
     }
 }
 
-pub fn get_braavos_users_proscore_80() -> Vec<String> {
+pub fn get_braavos_users_proscore_80() -> Result<Vec<String>, DbError> {
     use crate::schema::braavos_bonus::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     braavos_bonus
         .filter(pro_score_80.is_not_null())
         .select(user_address)
         .load::<String>(connection)
-        .expect("Error loading pro score users")
+        .map_err(DbError::Query)
 }
 
-pub fn get_braavos_users_proscore_80_with_timestamp() -> HashMap<String, BraavosBonusValues> {
+pub fn get_braavos_users_proscore_80_with_timestamp() -> Result<
+    HashMap<String, BraavosBonusValues>,
+    DbError
+> {
     use crate::schema::braavos_bonus::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let results = braavos_bonus
         .filter(pro_score_80.is_not_null().or(braavos_referral.is_not_null()))
         .select((user_address, pro_score_80, braavos_referral))
-        .load::<BraavosBonus>(connection)
-        .expect("Error loading pro score users");
+        .load::<BraavosBonus>(connection)?;
 
     let mut map = HashMap::new();
     for bonus in results {
@@ -1182,20 +1440,24 @@ pub fn get_braavos_users_proscore_80_with_timestamp() -> HashMap<String, Braavos
         });
     }
 
-    map
+    Ok(map)
 }
 
-pub fn get_first_braavos_referrals() -> Result<Vec<(i64, String)>, diesel::result::Error> {
+pub fn get_first_braavos_referrals() -> Result<Vec<(i64, String)>, DbError> {
     use crate::schema::referral_events::dsl::*;
 
-    let connection = &mut establish_connection(&Network::Mainnet);
+    let connection = &mut establish_connection(&Network::Mainnet)?;
 
     let braavos_referral_events: Vec<ReferralEvent> = referral_events
-        .filter(referral_code.eq("braavos-referral-bonus"))
-        .load::<ReferralEvent>(connection)
-        .expect("Failed getting braavos referral events");
+        .filter(referral_code.eq(BRAAVOS_REFERRAL_CODE))
+        .load::<ReferralEvent>(connection)?;
+
+    Ok(first_referrals(braavos_referral_events))
+}
 
-    let tuples: Vec<(i64, String)> = braavos_referral_events
+/// (timestamp, address) of the first referral event of every referred wallet
+fn first_referrals(events: Vec<ReferralEvent>) -> Vec<(i64, String)> {
+    let tuples: Vec<(i64, String)> = events
         .into_iter()
         .map(|e| {
             let ts = e.timestamp.duration_since(UNIX_EPOCH).expect("Time went backwards");
@@ -1215,10 +1477,7 @@ pub fn get_first_braavos_referrals() -> Result<Vec<(i64, String)>, diesel::resul
             .or_insert(ts);
     }
 
-    let without_duplicates: Vec<(i64, String)> = map
-        .into_iter()
+    map.into_iter()
         .map(|(s, ts)| (ts, s))
-        .collect();
-
-    Ok(without_duplicates)
+        .collect()
 }
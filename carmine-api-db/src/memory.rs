@@ -0,0 +1,787 @@
+use carmine_api_core::network::{
+    protocol_address,
+    Network,
+    Protocol,
+    NEW_AMM_GENESIS_TIMESTAMP,
+};
+use carmine_api_core::types::{
+    BlockState,
+    BraavosBonus,
+    BraavosBonusValues,
+    CheckpointStatus,
+    DbBlock,
+    IOption,
+    IngestionCheckpoint,
+    IngestionStream,
+    InsuranceEvent,
+    InsuranceEventQueryable,
+    LendingEvent,
+    NewReferralEvent,
+    OptionVolatility,
+    OraclePrice,
+    PRIMARY_ORACLE,
+    PoolState,
+    PoolStateWithTimestamp,
+    Proposal,
+    ReferralCode,
+    ReferralEvent,
+    ReferralEventDigest,
+    StarkScanEventSettled,
+    UserPoints,
+    UserPointsDb,
+    Vote,
+    VotingPower,
+};
+use carmine_api_referral::referral_code::generate_referral_code;
+use serde::Deserialize;
+use std::collections::{ HashMap, HashSet };
+use std::fs;
+use std::path::Path;
+use std::sync::{ RwLock, RwLockReadGuard, RwLockWriteGuard };
+use std::time::SystemTime;
+
+use crate::{
+    first_referrals,
+    vote_from_event,
+    DbError,
+    Store,
+    BRAAVOS_REFERRAL_CODE,
+    OLD_AMM_LAST_BLOCK,
+    VOTE_EVENT_NAMES,
+};
+
+/// Rows of the tables, missing tables are empty
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Fixtures {
+    pub starkscan_events: Vec<StarkScanEventSettled>,
+    pub options: Vec<IOption>,
+    pub blocks: Vec<DbBlock>,
+    pub pool_state: Vec<PoolState>,
+    pub options_volatility: Vec<OptionVolatility>,
+    pub oracle_prices: Vec<OraclePrice>,
+    pub referral_codes: Vec<ReferralCode>,
+    pub referral_events: Vec<ReferralEvent>,
+    pub insurance_events: Vec<InsuranceEventQueryable>,
+    pub user_points: Vec<UserPointsDb>,
+    pub braavos_bonus: Vec<BraavosBonus>,
+    pub ingestion_checkpoints: Vec<IngestionCheckpoint>,
+    pub lending_events: Vec<LendingEvent>,
+    pub proposals: Vec<Proposal>,
+    pub voting_power: Vec<VotingPower>,
+}
+
+/// In memory store for tests and local development, seeded from JSON fixtures
+pub struct MemoryStore {
+    network: Network,
+    data: RwLock<Fixtures>,
+}
+
+impl MemoryStore {
+    pub fn new(network: Network, fixtures: Fixtures) -> Self {
+        MemoryStore {
+            network,
+            data: RwLock::new(fixtures),
+        }
+    }
+
+    pub fn from_json(network: Network, json: &str) -> Result<Self, DbError> {
+        let fixtures: Fixtures = serde_json
+            ::from_str(json)
+            .map_err(|e| DbError::InvalidData(format!("Invalid fixtures: {}", e)))?;
+        Ok(MemoryStore::new(network, fixtures))
+    }
+
+    pub fn from_file(network: Network, path: impl AsRef<Path>) -> Result<Self, DbError> {
+        let json = fs
+            ::read_to_string(path.as_ref())
+            .map_err(|e| DbError::Config(format!("Could not read fixtures: {}", e)))?;
+        MemoryStore::from_json(network, &json)
+    }
+
+    fn read(&self) -> Result<RwLockReadGuard<Fixtures>, DbError> {
+        self.data.read().map_err(|_| DbError::Connection("Memory store poisoned".to_string()))
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<Fixtures>, DbError> {
+        self.data.write().map_err(|_| DbError::Connection("Memory store poisoned".to_string()))
+    }
+
+    fn events_where<F>(&self, predicate: F) -> Result<Vec<StarkScanEventSettled>, DbError>
+        where F: Fn(&StarkScanEventSettled) -> bool
+    {
+        Ok(
+            self
+                .read()?
+                .starkscan_events.iter()
+                .filter(|e| predicate(e))
+                .cloned()
+                .collect()
+        )
+    }
+
+    fn options_where<F>(&self, predicate: F) -> Result<Vec<IOption>, DbError>
+        where F: Fn(&IOption) -> bool
+    {
+        Ok(
+            self
+                .read()?
+                .options.iter()
+                .filter(|o| predicate(o))
+                .cloned()
+                .collect()
+        )
+    }
+}
+
+impl Store for MemoryStore {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn get_protocol_events(
+        &self,
+        protocol: &Protocol
+    ) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        let address = protocol_address(&self.network, protocol);
+        self.events_where(|e| e.from_address == address)
+    }
+
+    fn get_protocol_events_from_block(
+        &self,
+        protocol: &Protocol,
+        from_block_number: i64
+    ) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        let address = protocol_address(&self.network, protocol);
+        self.events_where(|e| e.from_address == address && e.block_number > from_block_number)
+    }
+
+    fn get_events_by_address(&self, address: &str) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        self.events_where(|e| e.from_address == address)
+    }
+
+    fn get_options(&self) -> Result<Vec<IOption>, DbError> {
+        self.options_where(|o| o.maturity > NEW_AMM_GENESIS_TIMESTAMP)
+    }
+
+    fn get_legacy_options(&self) -> Result<Vec<IOption>, DbError> {
+        self.options_where(|o| o.maturity < NEW_AMM_GENESIS_TIMESTAMP)
+    }
+
+    fn create_block(&self, block: &DbBlock) -> Result<(), DbError> {
+        let mut data = self.write()?;
+        if !data.blocks.iter().any(|b| b.block_number == block.block_number) {
+            data.blocks.push(block.clone());
+        }
+        Ok(())
+    }
+
+    fn get_block_by_number(&self, num: i64) -> Result<Option<DbBlock>, DbError> {
+        Ok(
+            self
+                .read()?
+                .blocks.iter()
+                .find(|b| b.block_number == num)
+                .cloned()
+        )
+    }
+
+    fn get_blocks_greater_than(&self, min: i64) -> Result<Vec<DbBlock>, DbError> {
+        Ok(
+            self
+                .read()?
+                .blocks.iter()
+                .filter(|b| b.block_number > min)
+                .cloned()
+                .collect()
+        )
+    }
+
+    fn get_last_block_in_db(&self) -> Result<DbBlock, DbError> {
+        self
+            .read()?
+            .blocks.iter()
+            .max_by_key(|b| b.block_number)
+            .cloned()
+            .ok_or_else(|| DbError::InvalidData("did not find last block in DB".to_string()))
+    }
+
+    fn create_batch_of_pool_states(&self, states: &Vec<PoolState>) -> Result<(), DbError> {
+        let mut data = self.write()?;
+        for state in states {
+            let exists = data.pool_state
+                .iter()
+                .any(|s| s.lp_address == state.lp_address && s.block_number == state.block_number);
+            if !exists {
+                data.pool_state.push(state.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn create_batch_of_volatilities(
+        &self,
+        volatilities: &Vec<OptionVolatility>
+    ) -> Result<(), DbError> {
+        let mut data = self.write()?;
+        for volatility in volatilities {
+            let exists = data.options_volatility
+                .iter()
+                .any(|v| {
+                    v.option_address == volatility.option_address &&
+                        v.block_number == volatility.block_number
+                });
+            if !exists {
+                data.options_volatility.push(volatility.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn get_pool_state(&self, pool_address: &str) -> Result<Vec<PoolStateWithTimestamp>, DbError> {
+        let data = self.read()?;
+
+        let timestamps: HashMap<i64, i64> = data.blocks
+            .iter()
+            .map(|b| (b.block_number, b.timestamp))
+            .collect();
+
+        let mut states: Vec<PoolStateWithTimestamp> = data.pool_state
+            .iter()
+            .filter(|s| s.lp_address == pool_address && s.block_number > OLD_AMM_LAST_BLOCK)
+            .filter_map(|s| {
+                let timestamp = *timestamps.get(&s.block_number)?;
+                Some(PoolStateWithTimestamp {
+                    unlocked_cap: s.unlocked_cap.clone(),
+                    locked_cap: s.locked_cap.clone(),
+                    lp_balance: s.lp_balance.clone(),
+                    pool_position: s.pool_position.clone(),
+                    lp_token_value: s.lp_token_value.clone(),
+                    lp_token_value_usd: s.lp_token_value_usd,
+                    underlying_asset_price: s.underlying_asset_price,
+                    block_number: s.block_number,
+                    lp_address: s.lp_address.clone(),
+                    timestamp,
+                })
+            })
+            .collect();
+
+        states.sort_by(|a, b| b.block_number.cmp(&a.block_number));
+
+        Ok(states)
+    }
+
+    fn get_pool_state_block_holes(&self, start: i64, end: i64) -> Result<Vec<i64>, DbError> {
+        let data = self.read()?;
+
+        let blocks: HashSet<i64> = data.pool_state
+            .iter()
+            .map(|s| s.block_number)
+            .filter(|n| *n >= start && *n <= end)
+            .collect();
+
+        Ok(
+            (start..=end).filter(|n| !blocks.contains(n)).collect()
+        )
+    }
+
+    fn get_pool_volatilities(
+        &self,
+        pool_address: &str,
+        after_block: i64
+    ) -> Result<Vec<(i64, i64, i64, Option<String>)>, DbError> {
+        let data = self.read()?;
+
+        let maturities: HashMap<&str, i64> = data.options
+            .iter()
+            .filter(|o| o.lp_address == pool_address)
+            .map(|o| (o.option_address.as_str(), o.maturity))
+            .collect();
+        let timestamps: HashMap<i64, i64> = data.blocks
+            .iter()
+            .map(|b| (b.block_number, b.timestamp))
+            .collect();
+
+        let mut rows: Vec<(i64, i64, i64, Option<String>)> = data.options_volatility
+            .iter()
+            .filter(|v| v.volatility.is_some() && v.block_number > after_block)
+            .filter_map(|v| {
+                let maturity = *maturities.get(v.option_address.as_str())?;
+                let timestamp = *timestamps.get(&v.block_number)?;
+                Some((v.block_number, timestamp, maturity, v.volatility.clone()))
+            })
+            .collect();
+
+        rows.sort_by_key(|(block_number, _, _, _)| *block_number);
+
+        Ok(rows)
+    }
+
+    fn get_checkpoint(
+        &self,
+        stream: &IngestionStream
+    ) -> Result<Option<IngestionCheckpoint>, DbError> {
+        Ok(
+            self
+                .read()?
+                .ingestion_checkpoints.iter()
+                .find(|c| c.stream == stream.id())
+                .cloned()
+        )
+    }
+
+    fn mark_checkpoint_failed(
+        &self,
+        stream: &IngestionStream,
+        last_block: i64
+    ) -> Result<(), DbError> {
+        let mut data = self.write()?;
+        match data.ingestion_checkpoints.iter_mut().find(|c| c.stream == stream.id()) {
+            Some(checkpoint) => {
+                checkpoint.status = CheckpointStatus::Failed.as_str().to_string();
+                checkpoint.updated_at = SystemTime::now();
+            }
+            None =>
+                data.ingestion_checkpoints.push(IngestionCheckpoint {
+                    stream: stream.id(),
+                    last_block,
+                    status: CheckpointStatus::Failed.as_str().to_string(),
+                    updated_at: SystemTime::now(),
+                }),
+        }
+        Ok(())
+    }
+
+    fn store_block_state(&self, state: &BlockState) -> Result<(), DbError> {
+        self.create_block(&state.block)?;
+        self.create_batch_of_volatilities(&state.volatilities)?;
+        self.create_batch_of_pool_states(&state.pool_states)?;
+        for price in state.oracle_prices.iter() {
+            self.create_oracle_price(price)?;
+        }
+
+        let mut data = self.write()?;
+        let block_number = state.block.block_number;
+        let streams = [
+            IngestionStream::AmmState,
+            IngestionStream::Volatility,
+            IngestionStream::OraclePrices,
+        ];
+        for stream in streams {
+            match data.ingestion_checkpoints.iter_mut().find(|c| c.stream == stream.id()) {
+                Some(checkpoint) => {
+                    checkpoint.last_block = checkpoint.last_block.max(block_number);
+                    checkpoint.status = CheckpointStatus::Ok.as_str().to_string();
+                    checkpoint.updated_at = SystemTime::now();
+                }
+                None =>
+                    data.ingestion_checkpoints.push(IngestionCheckpoint {
+                        stream: stream.id(),
+                        last_block: block_number,
+                        status: CheckpointStatus::Ok.as_str().to_string(),
+                        updated_at: SystemTime::now(),
+                    }),
+            }
+        }
+        Ok(())
+    }
+
+    fn create_oracle_price(&self, price: &OraclePrice) -> Result<(), DbError> {
+        let mut data = self.write()?;
+        if !data.oracle_prices.iter().any(|p| p.id == price.id) {
+            data.oracle_prices.push(price.clone());
+        }
+        Ok(())
+    }
+
+    fn get_oracle_prices_from_block(
+        &self,
+        initial_block: i64
+    ) -> Result<Vec<OraclePrice>, DbError> {
+        Ok(
+            self
+                .read()?
+                .oracle_prices.iter()
+                .filter(|p| p.oracle_name == PRIMARY_ORACLE && p.block_number >= initial_block)
+                .cloned()
+                .collect()
+        )
+    }
+
+    fn get_latest_oracle_prices(
+        &self,
+        pair: &str,
+        limit: i64
+    ) -> Result<Vec<OraclePrice>, DbError> {
+        let mut prices: Vec<OraclePrice> = self
+            .read()?
+            .oracle_prices.iter()
+            .filter(|p| p.oracle_name == PRIMARY_ORACLE && p.token_pair == pair)
+            .cloned()
+            .collect();
+
+        prices.sort_by(|a, b| b.block_number.cmp(&a.block_number));
+        prices.truncate(usize::try_from(limit).unwrap_or(0));
+
+        Ok(prices)
+    }
+
+    fn get_referral_code(&self, referrer: String) -> Result<String, DbError> {
+        let mut data = self.write()?;
+
+        if let Some(code) = data.referral_codes.iter().find(|c| c.wallet_address == referrer) {
+            // referral code already stored
+            return Ok(code.referral_code.clone());
+        }
+
+        let new_referral_code = loop {
+            let temp = generate_referral_code();
+
+            if !data.referral_codes.iter().any(|c| c.referral_code == temp) {
+                break temp;
+            }
+        };
+
+        data.referral_codes.push(ReferralCode {
+            wallet_address: referrer,
+            referral_code: new_referral_code.clone(),
+        });
+
+        Ok(new_referral_code)
+    }
+
+    fn create_referral_event(&self, event: NewReferralEvent) -> Result<usize, DbError> {
+        let mut data = self.write()?;
+
+        if !data.referral_codes.iter().any(|c| c.referral_code == event.referral_code) {
+            // query error like the foreign key violation in the DB
+            return Err(DbError::Query(diesel::result::Error::NotFound));
+        }
+
+        let id = data.referral_events
+            .iter()
+            .map(|e| e.id)
+            .max()
+            .unwrap_or(0) + 1;
+
+        data.referral_events.push(ReferralEvent {
+            id,
+            referred_wallet_address: event.referred_wallet_address.to_string(),
+            referral_code: event.referral_code.to_string(),
+            source: event.source.to_string(),
+            timestamp: SystemTime::now(),
+        });
+
+        Ok(1)
+    }
+
+    fn get_referral_events(&self) -> Result<Vec<ReferralEventDigest>, DbError> {
+        let data = self.read()?;
+
+        let referees: HashMap<&str, &str> = data.referral_codes
+            .iter()
+            .map(|c| (c.referral_code.as_str(), c.wallet_address.as_str()))
+            .collect();
+
+        Ok(
+            data.referral_events
+                .iter()
+                .filter_map(|e| {
+                    let referee = referees.get(e.referral_code.as_str())?;
+                    Some(ReferralEventDigest {
+                        referred_wallet_address: e.referred_wallet_address.clone(),
+                        referee_wallet_address: referee.to_string(),
+                        referral_code: e.referral_code.clone(),
+                        timestamp: e.timestamp,
+                    })
+                })
+                .collect()
+        )
+    }
+
+    fn create_insurance_event(&self, event: InsuranceEvent) -> Result<usize, DbError> {
+        let mut data = self.write()?;
+
+        let id = data.insurance_events
+            .iter()
+            .map(|e| e.id)
+            .max()
+            .unwrap_or(0) + 1;
+
+        data.insurance_events.push(InsuranceEventQueryable {
+            id,
+            user_address: event.user_address.to_string(),
+            calldata: event.calldata
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            timestamp: SystemTime::now(),
+        });
+
+        Ok(1)
+    }
+
+    fn get_insurance_events(&self) -> Result<Vec<InsuranceEventQueryable>, DbError> {
+        Ok(self.read()?.insurance_events.clone())
+    }
+
+    fn get_user_points_lastest_timestamp(&self) -> Result<Option<SystemTime>, DbError> {
+        Ok(
+            self
+                .read()?
+                .user_points.iter()
+                .map(|p| p.timestamp)
+                .max()
+        )
+    }
+
+    fn get_all_user_points(&self, ts: SystemTime) -> Result<Vec<UserPoints>, DbError> {
+        Ok(
+            self
+                .read()?
+                .user_points.iter()
+                .filter(|p| p.timestamp == ts)
+                .map(|v| UserPoints {
+                    address: v.user_address.clone(),
+                    trading_points: v.trading_points,
+                    liquidity_points: v.liquidity_points,
+                    referral_points: v.referral_points,
+                    vote_points: v.vote_points,
+                })
+                .collect()
+        )
+    }
+
+    fn get_braavos_eligible_user_addresses(&self) -> Result<Vec<String>, DbError> {
+        let max_timestamp = match self.get_user_points_lastest_timestamp()? {
+            Some(ts) => ts,
+            None => {
+                return Ok(vec![]);
+            }
+        };
+
+        let addresses: HashSet<String> = self
+            .read()?
+            .user_points.iter()
+            .filter(|p| p.timestamp == max_timestamp)
+            .map(|p| p.user_address.clone())
+            .collect();
+
+        Ok(addresses.into_iter().collect())
+    }
+
+    fn get_braavos_users_proscore_80(&self) -> Result<Vec<String>, DbError> {
+        Ok(
+            self
+                .read()?
+                .braavos_bonus.iter()
+                .filter(|b| b.pro_score_80.is_some())
+                .map(|b| b.user_address.clone())
+                .collect()
+        )
+    }
+
+    fn get_braavos_users_proscore_80_with_timestamp(
+        &self
+    ) -> Result<HashMap<String, BraavosBonusValues>, DbError> {
+        Ok(
+            self
+                .read()?
+                .braavos_bonus.iter()
+                .filter(|b| b.pro_score_80.is_some() || b.braavos_referral.is_some())
+                .map(|b| (
+                    b.user_address.clone(),
+                    BraavosBonusValues {
+                        pro_score_80: b.pro_score_80,
+                        braavos_referral: b.braavos_referral,
+                    },
+                ))
+                .collect()
+        )
+    }
+
+    fn get_first_braavos_referrals(&self) -> Result<Vec<(i64, String)>, DbError> {
+        let events: Vec<ReferralEvent> = self
+            .read()?
+            .referral_events.iter()
+            .filter(|e| e.referral_code == BRAAVOS_REFERRAL_CODE)
+            .cloned()
+            .collect();
+
+        Ok(first_referrals(events))
+    }
+
+    fn upsert_braavos_pro_score_80(&self, address: &str, ts: i64) -> Result<usize, DbError> {
+        let mut data = self.write()?;
+
+        match data.braavos_bonus.iter_mut().find(|b| b.user_address == address) {
+            Some(user) if user.pro_score_80.is_none() => {
+                user.pro_score_80 = Some(ts);
+                Ok(1)
+            }
+            Some(_) => Ok(0),
+            None => {
+                data.braavos_bonus.push(BraavosBonus {
+                    user_address: address.to_string(),
+                    pro_score_80: Some(ts),
+                    braavos_referral: None,
+                });
+                Ok(1)
+            }
+        }
+    }
+
+    fn upsert_braavos_referral(&self, address: &str, ts: i64) -> Result<usize, DbError> {
+        let mut data = self.write()?;
+
+        match data.braavos_bonus.iter_mut().find(|b| b.user_address == address) {
+            Some(user) if user.braavos_referral.is_none() => {
+                user.braavos_referral = Some(ts);
+                Ok(1)
+            }
+            Some(_) => Ok(0),
+            None => {
+                data.braavos_bonus.push(BraavosBonus {
+                    user_address: address.to_string(),
+                    pro_score_80: None,
+                    braavos_referral: Some(ts),
+                });
+                Ok(1)
+            }
+        }
+    }
+
+    fn get_votes(&self) -> Result<Vec<Vote>, DbError> {
+        self
+            .read()?
+            .starkscan_events.iter()
+            .filter(|e| VOTE_EVENT_NAMES.contains(&e.key_name.as_str()))
+            .map(vote_from_event)
+            .collect()
+    }
+
+    fn get_proposals(&self) -> Result<Vec<Proposal>, DbError> {
+        let mut proposals = self.read()?.proposals.clone();
+        proposals.sort_by_key(|p| p.prop_id);
+        Ok(proposals)
+    }
+
+    fn get_voting_power(&self) -> Result<Vec<VotingPower>, DbError> {
+        let mut powers = self.read()?.voting_power.clone();
+        powers.sort_by(|a, b| (a.prop_id, &a.user_address).cmp(&(b.prop_id, &b.user_address)));
+        Ok(powers)
+    }
+
+    fn get_lending_events(
+        &self,
+        protocol: &str,
+        market: &str
+    ) -> Result<Vec<LendingEvent>, DbError> {
+        let mut events: Vec<LendingEvent> = self
+            .read()?
+            .lending_events.iter()
+            .filter(|e| e.protocol == protocol && e.market == market)
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| (e.block_number, e.event_index));
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryStore;
+    use crate::Store;
+    use carmine_api_core::network::{ Network, Protocol };
+    use carmine_api_core::types::{ BlockState, DbBlock, IngestionStream, NewReferralEvent };
+
+    fn store() -> MemoryStore {
+        MemoryStore::from_json(Network::Mainnet, include_str!("../fixtures/mainnet.json")).unwrap()
+    }
+
+    #[test]
+    fn pool_state_joins_blocks() {
+        let store = store();
+        let state = store
+            .get_pool_state("0x70cad6be2c3fc48c745e4a4b70ef578d9c79b46ffac4cd93ec7b61f951c7c5c")
+            .unwrap();
+        // newest first, the state without stored block is skipped
+        assert_eq!(
+            state
+                .iter()
+                .map(|s| (s.block_number, s.timestamp))
+                .collect::<Vec<_>>(),
+            vec![(600002, 1700000200), (600000, 1700000000)]
+        );
+        assert_eq!(store.get_pool_state_block_holes(600000, 600003).unwrap(), vec![600001]);
+    }
+
+    #[test]
+    fn events_and_votes() {
+        let store = store();
+        assert_eq!(store.get_protocol_events(&Protocol::CarmineOptions).unwrap().len(), 1);
+        let newer = store.get_protocol_events_from_block(&Protocol::CarmineOptions, 600000);
+        assert!(newer.unwrap().is_empty());
+
+        let votes = store.get_votes().unwrap();
+        assert_eq!(votes.len(), 1);
+        assert_eq!((votes[0].prop_id, votes[0].opinion), (26, 1));
+    }
+
+    #[test]
+    fn referrals() {
+        let store = store();
+        let code = store.get_referral_code("0x123".to_string()).unwrap();
+        assert_eq!(store.get_referral_code("0x123".to_string()).unwrap(), code);
+
+        let event = NewReferralEvent {
+            referred_wallet_address: "0x456",
+            referral_code: &code,
+            source: "test",
+        };
+        assert_eq!(store.create_referral_event(event).unwrap(), 1);
+
+        let unknown = NewReferralEvent {
+            referred_wallet_address: "0x456",
+            referral_code: "unknown",
+            source: "test",
+        };
+        assert!(store.create_referral_event(unknown).is_err());
+
+        let referrals = store.get_referral_events().unwrap();
+        assert_eq!(referrals.len(), 1);
+        assert_eq!(referrals[0].referee_wallet_address, "0x123");
+    }
+
+    #[test]
+    fn braavos_bonus_is_set_once() {
+        let store = store();
+        assert_eq!(store.upsert_braavos_pro_score_80("0x1", 10).unwrap(), 1);
+        assert_eq!(store.upsert_braavos_pro_score_80("0x1", 20).unwrap(), 0);
+        assert_eq!(store.upsert_braavos_referral("0x1", 30).unwrap(), 1);
+
+        let bonus = store.get_braavos_users_proscore_80_with_timestamp().unwrap();
+        assert_eq!(bonus["0x1"].pro_score_80, Some(10));
+        assert_eq!(bonus["0x1"].braavos_referral, Some(30));
+    }
+
+    #[test]
+    fn checkpoints_only_move_forward() {
+        let store = store();
+        let state = |block_number: i64| BlockState {
+            block: DbBlock { block_number, timestamp: 1700000000 + block_number },
+            volatilities: vec![],
+            pool_states: vec![],
+            oracle_prices: vec![],
+        };
+
+        store.store_block_state(&state(600010)).unwrap();
+        store.store_block_state(&state(600005)).unwrap();
+        store.mark_checkpoint_failed(&IngestionStream::AmmState, 0).unwrap();
+
+        let checkpoint = store.get_checkpoint(&IngestionStream::AmmState).unwrap().unwrap();
+        assert_eq!((checkpoint.last_block, checkpoint.status.as_str()), (600010, "failed"));
+        assert!(store.get_block_by_number(600005).unwrap().is_some());
+        assert!(store.get_checkpoint(&IngestionStream::Volatility).unwrap().is_some());
+    }
+}
@@ -0,0 +1,542 @@
+use carmine_api_core::network::Network;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{ BigInt, Text };
+use diesel_migrations::{ embed_migrations, EmbeddedMigrations, MigrationHarness };
+use std::collections::{ HashMap, HashSet };
+use std::env;
+
+use crate::{ establish_connection, DbError };
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../carmine-api-core/migrations");
+
+/// (column, Postgres type, nullable) of every table in schema.rs
+const SCHEMA: &[(&str, &[(&str, &str, bool)])] = &[
+    (
+        "events",
+        &[
+            ("block_hash", "text", false),
+            ("block_number", "int8", false),
+            ("transaction_hash", "text", false),
+            ("event_index", "int8", false),
+            ("from_address", "text", false),
+            ("timestamp", "int8", false),
+            ("action", "text", false),
+            ("caller", "text", false),
+            ("token_address", "text", false),
+            ("capital_transfered", "text", false),
+            ("tokens_minted", "text", false),
+        ],
+    ),
+    (
+        "options",
+        &[
+            ("option_side", "int2", false),
+            ("maturity", "int8", false),
+            ("strike_price", "text", false),
+            ("quote_token_address", "text", false),
+            ("base_token_address", "text", false),
+            ("option_type", "int2", false),
+            ("option_address", "text", false),
+            ("lp_address", "text", false),
+        ],
+    ),
+    (
+        "blocks",
+        &[
+            ("block_number", "int8", false),
+            ("timestamp", "int8", false),
+        ],
+    ),
+    ("pools", &[("lp_address", "text", false)]),
+    (
+        "pool_state",
+        &[
+            ("unlocked_cap", "text", false),
+            ("locked_cap", "text", false),
+            ("lp_balance", "text", false),
+            ("pool_position", "text", true),
+            ("lp_token_value", "text", true),
+            ("lp_token_value_usd", "float8", true),
+            ("underlying_asset_price", "float8", true),
+            ("block_number", "int8", false),
+            ("lp_address", "text", false),
+        ],
+    ),
+    (
+        "options_volatility",
+        &[
+            ("option_address", "text", false),
+            ("block_number", "int8", false),
+            ("volatility", "text", true),
+            ("option_position", "text", true),
+        ],
+    ),
+    (
+        "oracle_prices",
+        &[
+            ("id", "text", false),
+            ("token_pair", "text", false),
+            ("price", "int8", false),
+            ("decimals", "int2", false),
+            ("last_updated_timestamp", "int8", false),
+            ("num_sources_aggregated", "int2", false),
+            ("oracle_name", "text", false),
+            ("block_number", "int8", false),
+        ],
+    ),
+    (
+        "starkscan_events",
+        &[
+            ("id", "text", false),
+            ("block_hash", "text", false),
+            ("block_number", "int8", false),
+            ("transaction_hash", "text", false),
+            ("event_index", "int8", false),
+            ("from_address", "text", false),
+            ("keys", "_text", false),
+            ("data", "_text", false),
+            ("timestamp", "int8", false),
+            ("key_name", "text", false),
+        ],
+    ),
+    (
+        "referral_codes",
+        &[
+            ("wallet_address", "text", false),
+            ("referral_code", "text", false),
+        ],
+    ),
+    (
+        "referral_events",
+        &[
+            ("id", "int4", false),
+            ("referred_wallet_address", "text", false),
+            ("referral_code", "text", false),
+            ("source", "text", false),
+            ("timestamp", "timestamp", false),
+        ],
+    ),
+    (
+        "insurance_events",
+        &[
+            ("id", "int4", false),
+            ("user_address", "text", false),
+            ("calldata", "_text", false),
+            ("timestamp", "timestamp", false),
+        ],
+    ),
+    (
+        "user_points",
+        &[
+            ("id", "int4", false),
+            ("user_address", "text", false),
+            ("timestamp", "timestamp", false),
+            ("trading_points", "int8", false),
+            ("liquidity_points", "int8", false),
+            ("referral_points", "int8", false),
+            ("vote_points", "int8", false),
+        ],
+    ),
+    (
+        "braavos_bonus",
+        &[
+            ("user_address", "text", false),
+            ("pro_score_80", "int8", true),
+            ("braavos_referral", "int8", true),
+        ],
+    ),
+    (
+        "ingestion_checkpoints",
+        &[
+            ("stream", "text", false),
+            ("last_block", "int8", false),
+            ("status", "text", false),
+            ("updated_at", "timestamp", false),
+        ],
+    ),
+    (
+        "raw_events_unparsed",
+        &[
+            ("id", "text", false),
+            ("block_hash", "text", false),
+            ("block_number", "int8", false),
+            ("transaction_hash", "text", false),
+            ("event_index", "int8", false),
+            ("from_address", "text", false),
+            ("keys", "_text", false),
+            ("data", "_text", false),
+            ("timestamp", "int8", false),
+            ("key_name", "text", true),
+            ("selector", "text", true),
+            ("reason", "text", false),
+            ("reprocessed_at", "timestamp", true),
+        ],
+    ),
+    (
+        "lending_events",
+        &[
+            ("id", "text", false),
+            ("protocol", "text", false),
+            ("market", "text", false),
+            ("action", "text", false),
+            ("user_address", "text", true),
+            ("amount", "float8", true),
+            ("lending_rate", "float8", true),
+            ("borrowing_rate", "float8", true),
+            ("block_number", "int8", false),
+            ("event_index", "int8", false),
+            ("timestamp", "int8", false),
+            ("transaction_hash", "text", false),
+        ],
+    ),
+    (
+        "proposals",
+        &[
+            ("prop_id", "int8", false),
+            ("payload", "text", false),
+            ("to_upgrade", "int8", false),
+            ("title", "text", true),
+            ("description", "text", true),
+            ("block_number", "int8", false),
+            ("timestamp", "int8", false),
+            ("transaction_hash", "text", false),
+            ("executed_block_number", "int8", true),
+            ("executed_timestamp", "int8", true),
+            ("total_supply", "float8", true),
+        ],
+    ),
+    (
+        "voting_power",
+        &[
+            ("prop_id", "int8", false),
+            ("user_address", "text", false),
+            ("voting_power", "float8", false),
+            ("block_number", "int8", false),
+        ],
+    ),
+];
+
+/// (table, rows) the complete_schema migration cannot make NOT NULL, options without pool
+/// take their volatilities with them, events without timestamp are kept when `blocks` has it
+const INCOMPLETE_ROWS: &[(&str, &str)] = &[
+    (
+        "options_volatility",
+        "options_volatility WHERE option_address IN \
+        (SELECT option_address FROM options WHERE lp_address IS NULL)",
+    ),
+    ("options", "options WHERE lp_address IS NULL"),
+    (
+        "starkscan_events",
+        "starkscan_events WHERE from_address IS NULL OR keys IS NULL OR data IS NULL \
+        OR (timestamp IS NULL AND NOT EXISTS \
+        (SELECT 1 FROM blocks WHERE blocks.block_number = starkscan_events.block_number))",
+    ),
+];
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct DbColumn {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    #[diesel(sql_type = Text)]
+    column_name: String,
+    #[diesel(sql_type = Text)]
+    udt_name: String,
+    #[diesel(sql_type = Text)]
+    is_nullable: String,
+}
+
+/// Applies pending migrations when RUN_MIGRATIONS=true
+/// and fails if the DB does not match schema.rs
+pub fn prepare_database(network: &Network) -> Result<(), DbError> {
+    if env::var("RUN_MIGRATIONS").map_or(false, |v| v == "true") {
+        let applied = run_migrations(network)?;
+        println!("🗄️  Applied {} migrations {:?}", applied.len(), applied);
+    }
+    check_schema(network)
+}
+
+/// Applies pending migrations, returns versions of the applied ones
+pub fn run_migrations(network: &Network) -> Result<Vec<String>, DbError> {
+    let mut connection = establish_connection(network)?;
+    apply_migrations(&mut connection)
+}
+
+fn apply_migrations(connection: &mut PgConnection) -> Result<Vec<String>, DbError> {
+    let applied = connection
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| DbError::Migration(e.to_string()))?;
+
+    Ok(
+        applied
+            .into_iter()
+            .map(|version| version.to_string())
+            .collect()
+    )
+}
+
+/// Fails when tables of schema.rs are missing in the DB
+/// or their columns differ in name, type or nullability
+pub fn check_schema(network: &Network) -> Result<(), DbError> {
+    let mut connection = establish_connection(network)?;
+    let issues = db_schema_issues(&mut connection)?;
+
+    match issues.is_empty() {
+        true => Ok(()),
+        false => Err(DbError::SchemaMismatch(issues)),
+    }
+}
+
+fn db_schema_issues(connection: &mut PgConnection) -> Result<Vec<String>, DbError> {
+    let columns: Vec<DbColumn> = sql_query(
+        "SELECT table_name::text AS table_name, column_name::text AS column_name, \
+        udt_name::text AS udt_name, is_nullable::text AS is_nullable \
+        FROM information_schema.columns WHERE table_schema = 'public'"
+    ).load(connection)?;
+
+    let mut db_tables: HashMap<&str, HashMap<&str, (&str, bool)>> = HashMap::new();
+    for column in columns.iter() {
+        db_tables
+            .entry(column.table_name.as_str())
+            .or_default()
+            .insert(column.column_name.as_str(), (
+                column.udt_name.as_str(),
+                column.is_nullable == "YES",
+            ));
+    }
+
+    Ok(schema_issues(&db_tables))
+}
+
+/// Number of rows by table that block the complete_schema migration
+pub fn count_incomplete_rows(network: &Network) -> Result<Vec<(String, i64)>, DbError> {
+    let connection = &mut establish_connection(network)?;
+
+    INCOMPLETE_ROWS.iter()
+        .map(|(table, rows)| -> Result<(String, i64), DbError> {
+            let row_count: RowCount = sql_query(
+                format!("SELECT COUNT(*) AS count FROM {}", rows)
+            ).get_result(connection)?;
+            Ok((table.to_string(), row_count.count))
+        })
+        .collect()
+}
+
+/// Deletes rows that block the complete_schema migration in a single transaction,
+/// returns number of deleted rows by table
+pub fn delete_incomplete_rows(network: &Network) -> Result<Vec<(String, usize)>, DbError> {
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        INCOMPLETE_ROWS.iter()
+            .map(|(table, rows)| -> Result<(String, usize), DbError> {
+                let deleted = sql_query(format!("DELETE FROM {}", rows)).execute(conn)?;
+                Ok((table.to_string(), deleted))
+            })
+            .collect()
+    })
+}
+
+fn schema_issues(db_tables: &HashMap<&str, HashMap<&str, (&str, bool)>>) -> Vec<String> {
+    let mut issues = vec![];
+
+    for (table, expected_columns) in SCHEMA {
+        let db_columns = match db_tables.get(table) {
+            Some(columns) => columns,
+            None => {
+                issues.push(format!("missing table {}", table));
+                continue;
+            }
+        };
+
+        for (column, expected_type, expected_nullable) in expected_columns.iter() {
+            match db_columns.get(column) {
+                None => issues.push(format!("missing column {}.{}", table, column)),
+                Some((db_type, _)) if db_type != expected_type => {
+                    issues.push(
+                        format!("{}.{} is {}, expected {}", table, column, db_type, expected_type)
+                    );
+                }
+                Some((_, db_nullable)) if db_nullable != expected_nullable => {
+                    let nullable = |n: bool| if n { "nullable" } else { "not null" };
+                    issues.push(
+                        format!(
+                            "{}.{} is {}, expected {}",
+                            table,
+                            column,
+                            nullable(*db_nullable),
+                            nullable(*expected_nullable)
+                        )
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+
+        let expected_names: HashSet<&str> = expected_columns
+            .iter()
+            .map(|(name, _, _)| *name)
+            .collect();
+        for db_column in db_columns.keys() {
+            if !expected_names.contains(db_column) {
+                issues.push(format!("column {}.{} is not in schema.rs", table, db_column));
+            }
+        }
+    }
+
+    issues.sort();
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ apply_migrations, db_schema_issues, schema_issues, MIGRATIONS, SCHEMA };
+    use diesel::pg::Pg;
+    use diesel::prelude::*;
+    use diesel::sql_query;
+    use diesel::sql_types::Text;
+    use diesel_migrations::MigrationSource;
+    use std::collections::HashMap;
+    use std::env;
+    use std::process;
+
+    /// Postgres type of a Diesel type in schema.rs
+    fn pg_type(diesel_type: &str) -> (String, bool) {
+        let (inner, nullable) = match diesel_type.strip_prefix("Nullable<") {
+            Some(rest) => (rest.trim_end_matches('>'), true),
+            None => (diesel_type, false),
+        };
+        let pg = match inner {
+            "Text" => "text",
+            "Int2" | "SmallInt" => "int2",
+            "Int4" => "int4",
+            "Int8" => "int8",
+            "Double" => "float8",
+            "Timestamp" => "timestamp",
+            "Array<Text>" => "_text",
+            other => panic!("unknown type {}", other),
+        };
+        (pg.to_string(), nullable)
+    }
+
+    #[test]
+    fn expected_schema_follows_schema_rs() {
+        let schema_rs = include_str!("../../carmine-api-core/src/schema.rs");
+
+        let mut tables: Vec<(String, Vec<(String, String, bool)>)> = vec![];
+        let mut in_table = false;
+        for line in schema_rs.lines().map(str::trim) {
+            if let Some((name, _)) = line.split_once(" (").filter(|_| line.ends_with('{')) {
+                tables.push((name.to_string(), vec![]));
+                in_table = true;
+            } else if line == "}" {
+                in_table = false;
+            } else if let Some((column, diesel_type)) = line.split_once(" -> ") {
+                if in_table {
+                    let (pg, nullable) = pg_type(diesel_type.trim_end_matches(','));
+                    tables.last_mut().unwrap().1.push((column.to_string(), pg, nullable));
+                }
+            }
+        }
+
+        let expected: Vec<(String, Vec<(String, String, bool)>)> = SCHEMA.iter()
+            .map(|(table, columns)| (
+                table.to_string(),
+                columns
+                    .iter()
+                    .map(|(c, t, n)| (c.to_string(), t.to_string(), *n))
+                    .collect(),
+            ))
+            .collect();
+
+        assert_eq!(tables, expected);
+    }
+
+    #[test]
+    fn reports_differences() {
+        let mut db_tables = HashMap::new();
+        for (table, columns) in SCHEMA.iter().filter(|(table, _)| *table != "pools") {
+            let columns: HashMap<&str, (&str, bool)> = columns
+                .iter()
+                .map(|(c, t, n)| (*c, (*t, *n)))
+                .collect();
+            db_tables.insert(*table, columns);
+        }
+        assert_eq!(schema_issues(&db_tables), vec!["missing table pools".to_string()]);
+
+        let blocks = db_tables.get_mut("blocks").unwrap();
+        blocks.insert("timestamp", ("int8", true));
+        blocks.insert("hash", ("text", true));
+        assert_eq!(
+            schema_issues(&db_tables),
+            vec![
+                "blocks.timestamp is nullable, expected not null".to_string(),
+                "column blocks.hash is not in schema.rs".to_string(),
+                "missing table pools".to_string()
+            ]
+        );
+    }
+
+    #[derive(QueryableByName)]
+    struct SchemaName {
+        #[diesel(sql_type = Text)]
+        name: String,
+    }
+
+    /// Needs MIGRATIONS_TEST_DATABASE_URL of a Postgres server where databases can be created
+    #[test]
+    #[ignore]
+    fn migrations_run_on_empty_database() {
+        let server_url = env::var("MIGRATIONS_TEST_DATABASE_URL").expect(
+            "MIGRATIONS_TEST_DATABASE_URL not set"
+        );
+        let (server, _) = server_url.rsplit_once('/').expect("Database URL without path");
+        let database = format!("carmine_migrations_test_{}", process::id());
+
+        let server_connection = &mut PgConnection::establish(&server_url).unwrap();
+        sql_query(format!("DROP DATABASE IF EXISTS {}", database))
+            .execute(server_connection)
+            .unwrap();
+        sql_query(format!("CREATE DATABASE {}", database))
+            .execute(server_connection)
+            .unwrap();
+
+        let connection = &mut PgConnection::establish(&format!("{}/{}", server, database)).unwrap();
+        let applied = apply_migrations(connection);
+        let issues = db_schema_issues(connection);
+        // read before dropping the database, checked after
+        let referenced_schemas: QueryResult<Vec<SchemaName>> = sql_query(
+            "SELECT n.nspname::text AS name FROM pg_constraint c \
+            JOIN pg_class t ON t.oid = c.confrelid \
+            JOIN pg_namespace n ON n.oid = t.relnamespace \
+            WHERE c.conrelid = 'public.referral_events'::regclass AND c.contype = 'f'"
+        ).load(connection);
+        let placeholders: QueryResult<Vec<SchemaName>> = sql_query(
+            "SELECT schema_name::text AS name FROM information_schema.schemata \
+            WHERE schema_name = 'referral_placeholder'"
+        ).load(connection);
+
+        sql_query(format!("DROP DATABASE {} WITH (FORCE)", database))
+            .execute(server_connection)
+            .unwrap();
+
+        let applied = applied.unwrap();
+        assert_eq!(applied.len(), MigrationSource::<Pg>::migrations(&MIGRATIONS).unwrap().len());
+        assert_eq!(issues.unwrap(), Vec::<String>::new());
+        assert_eq!(
+            referenced_schemas
+                .unwrap()
+                .into_iter()
+                .map(|schema| schema.name)
+                .collect::<Vec<String>>(),
+            vec!["public".to_string()]
+        );
+        assert!(placeholders.unwrap().is_empty());
+    }
+}
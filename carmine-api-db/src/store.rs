@@ -0,0 +1,330 @@
+use carmine_api_core::network::{ Network, Protocol };
+use carmine_api_core::types::{
+    BlockState,
+    BraavosBonusValues,
+    DbBlock,
+    IOption,
+    IngestionCheckpoint,
+    IngestionStream,
+    InsuranceEvent,
+    InsuranceEventQueryable,
+    LendingEvent,
+    NewReferralEvent,
+    OptionVolatility,
+    OraclePrice,
+    PoolState,
+    PoolStateWithTimestamp,
+    Proposal,
+    ReferralEventDigest,
+    StarkScanEventSettled,
+    UserPoints,
+    Vote,
+    VotingPower,
+};
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::DbError;
+
+/// Storage used by the cache, the handlers and the fetchers.
+/// One store holds the data of a single network.
+pub trait Store: Send + Sync {
+    fn network(&self) -> Network;
+
+    // events
+    fn get_protocol_events(
+        &self,
+        protocol: &Protocol
+    ) -> Result<Vec<StarkScanEventSettled>, DbError>;
+    fn get_protocol_events_from_block(
+        &self,
+        protocol: &Protocol,
+        from_block_number: i64
+    ) -> Result<Vec<StarkScanEventSettled>, DbError>;
+    fn get_events_by_address(&self, address: &str) -> Result<Vec<StarkScanEventSettled>, DbError>;
+
+    // options
+    fn get_options(&self) -> Result<Vec<IOption>, DbError>;
+    fn get_legacy_options(&self) -> Result<Vec<IOption>, DbError>;
+
+    // blocks and pool state
+    fn create_block(&self, block: &DbBlock) -> Result<(), DbError>;
+    fn get_block_by_number(&self, num: i64) -> Result<Option<DbBlock>, DbError>;
+    fn get_blocks_greater_than(&self, min: i64) -> Result<Vec<DbBlock>, DbError>;
+    fn get_last_block_in_db(&self) -> Result<DbBlock, DbError>;
+    fn create_batch_of_pool_states(&self, states: &Vec<PoolState>) -> Result<(), DbError>;
+    fn create_batch_of_volatilities(
+        &self,
+        volatilities: &Vec<OptionVolatility>
+    ) -> Result<(), DbError>;
+    fn get_pool_state(&self, pool_address: &str) -> Result<Vec<PoolStateWithTimestamp>, DbError>;
+    fn get_pool_state_block_holes(&self, start: i64, end: i64) -> Result<Vec<i64>, DbError>;
+    /// (block_number, timestamp, maturity, volatility) of all options in the pool
+    /// in blocks after `after_block`, sorted by block
+    fn get_pool_volatilities(
+        &self,
+        pool_address: &str,
+        after_block: i64
+    ) -> Result<Vec<(i64, i64, i64, Option<String>)>, DbError>;
+
+    // ingestion checkpoints
+    fn get_checkpoint(
+        &self,
+        stream: &IngestionStream
+    ) -> Result<Option<IngestionCheckpoint>, DbError>;
+    fn mark_checkpoint_failed(
+        &self,
+        stream: &IngestionStream,
+        last_block: i64
+    ) -> Result<(), DbError>;
+    /// Stores the block state together with its checkpoints, all or nothing
+    fn store_block_state(&self, state: &BlockState) -> Result<(), DbError>;
+
+    // prices
+    fn create_oracle_price(&self, price: &OraclePrice) -> Result<(), DbError>;
+    fn get_oracle_prices_from_block(
+        &self,
+        initial_block: i64
+    ) -> Result<Vec<OraclePrice>, DbError>;
+    fn get_latest_oracle_prices(
+        &self,
+        pair: &str,
+        limit: i64
+    ) -> Result<Vec<OraclePrice>, DbError>;
+
+    // referrals
+    fn get_referral_code(&self, referrer: String) -> Result<String, DbError>;
+    fn create_referral_event(&self, event: NewReferralEvent) -> Result<usize, DbError>;
+    fn get_referral_events(&self) -> Result<Vec<ReferralEventDigest>, DbError>;
+
+    // insurance
+    fn create_insurance_event(&self, event: InsuranceEvent) -> Result<usize, DbError>;
+    fn get_insurance_events(&self) -> Result<Vec<InsuranceEventQueryable>, DbError>;
+
+    // user points
+    fn get_user_points_lastest_timestamp(&self) -> Result<Option<SystemTime>, DbError>;
+    fn get_all_user_points(&self, ts: SystemTime) -> Result<Vec<UserPoints>, DbError>;
+
+    // braavos bonus
+    fn get_braavos_eligible_user_addresses(&self) -> Result<Vec<String>, DbError>;
+    fn get_braavos_users_proscore_80(&self) -> Result<Vec<String>, DbError>;
+    fn get_braavos_users_proscore_80_with_timestamp(
+        &self
+    ) -> Result<HashMap<String, BraavosBonusValues>, DbError>;
+    fn get_first_braavos_referrals(&self) -> Result<Vec<(i64, String)>, DbError>;
+    fn upsert_braavos_pro_score_80(&self, address: &str, ts: i64) -> Result<usize, DbError>;
+    fn upsert_braavos_referral(&self, address: &str, ts: i64) -> Result<usize, DbError>;
+
+    // governance
+    fn get_votes(&self) -> Result<Vec<Vote>, DbError>;
+    fn get_proposals(&self) -> Result<Vec<Proposal>, DbError>;
+    /// Voting power of voters at the snapshot blocks of the proposals
+    fn get_voting_power(&self) -> Result<Vec<VotingPower>, DbError>;
+
+    // lending
+    /// Decoded events of the lending market sorted by block and event index
+    fn get_lending_events(
+        &self,
+        protocol: &str,
+        market: &str
+    ) -> Result<Vec<LendingEvent>, DbError>;
+}
+
+/// Diesel / Postgres store, connections come from the pool of the network
+pub struct PgStore {
+    network: Network,
+}
+
+impl PgStore {
+    pub fn new(network: Network) -> Self {
+        PgStore { network }
+    }
+}
+
+impl Store for PgStore {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn get_protocol_events(
+        &self,
+        protocol: &Protocol
+    ) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        crate::get_protocol_events(&self.network, protocol)
+    }
+
+    fn get_protocol_events_from_block(
+        &self,
+        protocol: &Protocol,
+        from_block_number: i64
+    ) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        crate::get_protocol_events_from_block(&self.network, protocol, from_block_number)
+    }
+
+    fn get_events_by_address(&self, address: &str) -> Result<Vec<StarkScanEventSettled>, DbError> {
+        crate::get_events_by_address(&self.network, address)
+    }
+
+    fn get_options(&self) -> Result<Vec<IOption>, DbError> {
+        crate::get_options(&self.network)
+    }
+
+    fn get_legacy_options(&self) -> Result<Vec<IOption>, DbError> {
+        crate::get_legacy_options(&self.network)
+    }
+
+    fn create_block(&self, block: &DbBlock) -> Result<(), DbError> {
+        crate::create_block(block, &self.network)
+    }
+
+    fn get_block_by_number(&self, num: i64) -> Result<Option<DbBlock>, DbError> {
+        crate::get_block_by_number(num, &self.network)
+    }
+
+    fn get_blocks_greater_than(&self, min: i64) -> Result<Vec<DbBlock>, DbError> {
+        crate::get_blocks_greater_than(min, &self.network)
+    }
+
+    fn get_last_block_in_db(&self) -> Result<DbBlock, DbError> {
+        crate::get_last_block_in_db(&self.network)
+    }
+
+    fn create_batch_of_pool_states(&self, states: &Vec<PoolState>) -> Result<(), DbError> {
+        crate::create_batch_of_pool_states(states, &self.network)
+    }
+
+    fn create_batch_of_volatilities(
+        &self,
+        volatilities: &Vec<OptionVolatility>
+    ) -> Result<(), DbError> {
+        crate::create_batch_of_volatilities(volatilities, &self.network)
+    }
+
+    fn get_pool_state(&self, pool_address: &str) -> Result<Vec<PoolStateWithTimestamp>, DbError> {
+        crate::get_pool_state(pool_address, &self.network)
+    }
+
+    fn get_pool_state_block_holes(&self, start: i64, end: i64) -> Result<Vec<i64>, DbError> {
+        crate::get_pool_state_block_holes(start, end, &self.network)
+    }
+
+    fn get_pool_volatilities(
+        &self,
+        pool_address: &str,
+        after_block: i64
+    ) -> Result<Vec<(i64, i64, i64, Option<String>)>, DbError> {
+        crate::get_pool_volatilities(&self.network, pool_address, after_block)
+    }
+
+    fn get_checkpoint(
+        &self,
+        stream: &IngestionStream
+    ) -> Result<Option<IngestionCheckpoint>, DbError> {
+        crate::get_checkpoint(stream, &self.network)
+    }
+
+    fn mark_checkpoint_failed(
+        &self,
+        stream: &IngestionStream,
+        last_block: i64
+    ) -> Result<(), DbError> {
+        crate::mark_checkpoint_failed(stream, last_block, &self.network)
+    }
+
+    fn store_block_state(&self, state: &BlockState) -> Result<(), DbError> {
+        crate::store_block_state(state, &self.network)
+    }
+
+    fn create_oracle_price(&self, price: &OraclePrice) -> Result<(), DbError> {
+        crate::create_oracle_price(price, &self.network)
+    }
+
+    fn get_oracle_prices_from_block(
+        &self,
+        initial_block: i64
+    ) -> Result<Vec<OraclePrice>, DbError> {
+        crate::get_oracle_prices_from_block(&self.network, initial_block)
+    }
+
+    fn get_latest_oracle_prices(
+        &self,
+        pair: &str,
+        limit: i64
+    ) -> Result<Vec<OraclePrice>, DbError> {
+        crate::get_latest_oracle_prices(&self.network, pair, limit)
+    }
+
+    fn get_referral_code(&self, referrer: String) -> Result<String, DbError> {
+        crate::get_referral_code(referrer)
+    }
+
+    fn create_referral_event(&self, event: NewReferralEvent) -> Result<usize, DbError> {
+        crate::create_referral_event(event)
+    }
+
+    fn get_referral_events(&self) -> Result<Vec<ReferralEventDigest>, DbError> {
+        crate::get_referral_events()
+    }
+
+    fn create_insurance_event(&self, event: InsuranceEvent) -> Result<usize, DbError> {
+        crate::create_insurance_event(event)
+    }
+
+    fn get_insurance_events(&self) -> Result<Vec<InsuranceEventQueryable>, DbError> {
+        crate::get_insurance_events()
+    }
+
+    fn get_user_points_lastest_timestamp(&self) -> Result<Option<SystemTime>, DbError> {
+        crate::get_user_points_lastest_timestamp()
+    }
+
+    fn get_all_user_points(&self, ts: SystemTime) -> Result<Vec<UserPoints>, DbError> {
+        crate::get_all_user_points(ts)
+    }
+
+    fn get_braavos_eligible_user_addresses(&self) -> Result<Vec<String>, DbError> {
+        crate::get_braavos_eligible_user_addresses()
+    }
+
+    fn get_braavos_users_proscore_80(&self) -> Result<Vec<String>, DbError> {
+        crate::get_braavos_users_proscore_80()
+    }
+
+    fn get_braavos_users_proscore_80_with_timestamp(
+        &self
+    ) -> Result<HashMap<String, BraavosBonusValues>, DbError> {
+        crate::get_braavos_users_proscore_80_with_timestamp()
+    }
+
+    fn get_first_braavos_referrals(&self) -> Result<Vec<(i64, String)>, DbError> {
+        crate::get_first_braavos_referrals()
+    }
+
+    fn upsert_braavos_pro_score_80(&self, address: &str, ts: i64) -> Result<usize, DbError> {
+        crate::upsert_braavos_pro_score_80(address, ts)
+    }
+
+    fn upsert_braavos_referral(&self, address: &str, ts: i64) -> Result<usize, DbError> {
+        crate::upsert_braavos_referral(address, ts)
+    }
+
+    fn get_votes(&self) -> Result<Vec<Vote>, DbError> {
+        crate::get_votes()
+    }
+
+    fn get_proposals(&self) -> Result<Vec<Proposal>, DbError> {
+        crate::get_proposals(&self.network)
+    }
+
+    fn get_voting_power(&self) -> Result<Vec<VotingPower>, DbError> {
+        crate::get_voting_power(&self.network)
+    }
+
+    fn get_lending_events(
+        &self,
+        protocol: &str,
+        market: &str
+    ) -> Result<Vec<LendingEvent>, DbError> {
+        crate::get_lending_events(protocol, market, &self.network)
+    }
+}
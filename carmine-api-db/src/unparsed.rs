@@ -0,0 +1,149 @@
+use carmine_api_core::network::Network;
+use carmine_api_core::types::{ Event, StarkScanEventSettled, UnparsedEvent, UnparsedSelectorCount };
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use std::time::SystemTime;
+
+use crate::{ establish_connection, DbError, BATCH_SIZE };
+
+pub(crate) fn insert_unparsed_events(
+    connection: &mut PgConnection,
+    unparsed: &Vec<UnparsedEvent>
+) -> Result<usize, diesel::result::Error> {
+    use carmine_api_core::schema::raw_events_unparsed::dsl::*;
+
+    let mut inserted = 0;
+
+    for chunk in unparsed.chunks(BATCH_SIZE) {
+        inserted += diesel
+            ::insert_into(raw_events_unparsed)
+            .values(chunk)
+            .on_conflict_do_nothing()
+            .execute(connection)?;
+    }
+
+    Ok(inserted)
+}
+
+pub fn create_batch_of_unparsed_events(
+    unparsed: &Vec<UnparsedEvent>,
+    network: &Network
+) -> Result<usize, DbError> {
+    let connection = &mut establish_connection(network)?;
+
+    Ok(insert_unparsed_events(connection, unparsed)?)
+}
+
+/// Stores fetched Starkscan events, events decoded from them and
+/// the ones that failed to decode in a single transaction
+pub fn store_fetched_events(
+    settled: &Vec<StarkScanEventSettled>,
+    parsed: &Vec<Event>,
+    unparsed: &Vec<UnparsedEvent>,
+    network: &Network
+) -> Result<(), DbError> {
+    use carmine_api_core::schema::{ events, starkscan_events };
+
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        for chunk in settled.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(starkscan_events::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        for chunk in parsed.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(events::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        insert_unparsed_events(conn, unparsed)?;
+
+        Ok(())
+    })
+}
+
+/// Unparsed events that were not reprocessed yet
+pub fn get_unparsed_events(network: &Network) -> Result<Vec<UnparsedEvent>, DbError> {
+    use carmine_api_core::schema::raw_events_unparsed::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    raw_events_unparsed
+        .filter(reprocessed_at.is_null())
+        .order((block_number.asc(), event_index.asc()))
+        .load::<UnparsedEvent>(connection)
+        .map_err(DbError::Query)
+}
+
+/// Counts of not reprocessed events by contract, selector and reason, biggest first
+pub fn get_unparsed_selector_counts(
+    network: &Network
+) -> Result<Vec<UnparsedSelectorCount>, DbError> {
+    use carmine_api_core::schema::raw_events_unparsed::dsl::*;
+
+    let connection = &mut establish_connection(network)?;
+
+    let mut counts: Vec<UnparsedSelectorCount> = raw_events_unparsed
+        .filter(reprocessed_at.is_null())
+        .group_by((from_address, selector, reason))
+        .select((from_address, selector, reason, count_star()))
+        .load::<(String, Option<String>, String, i64)>(connection)?
+        .into_iter()
+        .map(|(address, event_selector, event_reason, count)| UnparsedSelectorCount {
+            from_address: address,
+            selector: event_selector,
+            reason: event_reason,
+            count,
+        })
+        .collect();
+
+    counts.sort_by(|a, b| b.count.cmp(&a.count));
+
+    Ok(counts)
+}
+
+/// Stores newly decoded events and marks their unparsed rows
+/// as reprocessed in a single transaction
+pub fn store_reprocessed_events(
+    settled: &Vec<StarkScanEventSettled>,
+    parsed: &Vec<Event>,
+    reprocessed_ids: &Vec<String>,
+    network: &Network
+) -> Result<(), DbError> {
+    use carmine_api_core::schema::{ events, raw_events_unparsed, starkscan_events };
+
+    let connection = &mut establish_connection(network)?;
+
+    connection.transaction(|conn| {
+        for chunk in settled.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(starkscan_events::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        for chunk in parsed.chunks(BATCH_SIZE) {
+            diesel
+                ::insert_into(events::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        diesel
+            ::update(raw_events_unparsed::table)
+            .filter(raw_events_unparsed::id.eq_any(reprocessed_ids))
+            .set(raw_events_unparsed::reprocessed_at.eq(SystemTime::now()))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
@@ -1,8 +1,10 @@
+use carmine_api_core::network::Network;
+use carmine_api_db::PgStore;
 use carmine_api_fetcher::braavos::update_braavos_proscore;
 use dotenvy::dotenv;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    update_braavos_proscore().await;
+    update_braavos_proscore(&PgStore::new(Network::Mainnet)).await;
 }
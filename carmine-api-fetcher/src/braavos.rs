@@ -6,10 +6,7 @@ use std::{
 use reqwest::{Client, Error};
 use serde::{Deserialize, Serialize};
 
-use carmine_api_db::{
-    get_braavos_eligible_user_addresses, get_braavos_users_proscore_80,
-    get_first_braavos_referrals, upsert_braavos_pro_score_80, upsert_braavos_referral,
-};
+use carmine_api_db::Store;
 
 #[derive(Serialize)]
 struct RequestBody {
@@ -54,14 +51,18 @@ async fn get_braavos_proscore(
     Ok(transformed_response)
 }
 
-pub async fn set_braavos_proscore(addresses: Vec<String>, ts: i64) -> Result<usize, Error> {
+pub async fn set_braavos_proscore(
+    store: &dyn Store,
+    addresses: Vec<String>,
+    ts: i64,
+) -> Result<usize, Error> {
     let scores = get_braavos_proscore(addresses).await?;
 
     let mut updated = 0;
 
     for (address, score) in scores {
         if score >= 80 {
-            match upsert_braavos_pro_score_80(address.as_str(), ts) {
+            match store.upsert_braavos_pro_score_80(address.as_str(), ts) {
                 Ok(_) => updated += 1,
                 Err(_) => (),
             }
@@ -71,19 +72,33 @@ pub async fn set_braavos_proscore(addresses: Vec<String>, ts: i64) -> Result<usi
     Ok(updated)
 }
 
-pub fn update_braavos_referrals() {
-    let referrals = get_first_braavos_referrals().expect("Failed getting braavos referrals");
+pub fn update_braavos_referrals(store: &dyn Store) {
+    let referrals = match store.get_first_braavos_referrals() {
+        Ok(referrals) => referrals,
+        Err(e) => {
+            println!("Failed getting Braavos referrals: {}", e);
+            return;
+        }
+    };
 
     for (ts, s) in referrals {
-        let _ = upsert_braavos_referral(s.as_str(), ts);
+        let _ = store.upsert_braavos_referral(s.as_str(), ts);
     }
 
     println!("Updated Braavos referrals");
 }
 
-pub async fn update_braavos_proscore() {
-    let mut eligible = get_braavos_eligible_user_addresses();
-    let proscore_users = get_braavos_users_proscore_80();
+pub async fn update_braavos_proscore(store: &dyn Store) {
+    let (mut eligible, proscore_users) = match (
+        store.get_braavos_eligible_user_addresses(),
+        store.get_braavos_users_proscore_80(),
+    ) {
+        (Ok(eligible), Ok(proscore_users)) => (eligible, proscore_users),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Failed getting Braavos users: {}", e);
+            return;
+        }
+    };
 
     println!("All eligible: {}", eligible.len());
     println!("Pros: {}", proscore_users.len());
@@ -103,7 +118,7 @@ pub async fn update_braavos_proscore() {
 
     for chunk in eligible.chunks(100) {
         let chunk_vec = chunk.to_vec();
-        let res = set_braavos_proscore(chunk_vec, ts).await;
+        let res = set_braavos_proscore(store, chunk_vec, ts).await;
 
         if let Ok(updated) = res {
             count += updated;
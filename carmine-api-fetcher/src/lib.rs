@@ -1 +1,2 @@
 pub mod braavos;
+pub mod oracle_monitor;
@@ -1,12 +1,19 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use actix_web::{get, App, HttpResponse, HttpServer, Responder};
-use carmine_api_db::lp_value::update_lp_prices;
-use carmine_api_fetcher::braavos::{update_braavos_proscore, update_braavos_referrals};
+use carmine_api_db::{prepare_database, PgStore};
+use carmine_api_fetcher::{
+    braavos::{update_braavos_proscore, update_braavos_referrals},
+    oracle_monitor::OracleMonitor,
+};
+use carmine_api_prices::lp_value::update_lp_prices;
 use carmine_api_rpc_gateway::{blast_api_latest_block_number, carmine_latest_block_number};
 use tokio::time::{sleep, Duration};
 
-use carmine_api_core::telegram_bot;
+use carmine_api_core::{
+    network::Network,
+    telegram_bot::{self, TelegramBot},
+};
 use carmine_api_starknet::{update_database_amm_state_for_latest_block, update_database_events};
 
 const GET_NEW_BLOCKS: bool = true;
@@ -14,6 +21,7 @@ const GET_NEW_EVENTS: bool = true;
 const UPDATE_POOL_PRICES: bool = true;
 const BRAAVOS_PROSCORE: bool = true;
 const BRAAVOS_REFERRAL: bool = true;
+const ORACLE_MONITOR: bool = true;
 
 const BLOCK_DISCREPENCY_THRESHOLD: i64 = 5;
 
@@ -63,6 +71,8 @@ async fn liveness() -> impl Responder {
 async fn main() -> std::io::Result<()> {
     println!("👷 Starting fetcher");
 
+    prepare_database(&Network::Mainnet).expect("Failed preparing DB");
+
     if GET_NEW_EVENTS {
         println!("🛠️  Spawning event fetching thread...");
         actix_web::rt::spawn(async move {
@@ -138,8 +148,10 @@ async fn main() -> std::io::Result<()> {
         println!("🛠️  Spawning Braavos proscore updating thread...");
         actix_web::rt::spawn(async move {
             loop {
-                if let Err(err) =
-                    actix_web::rt::spawn(async { update_braavos_proscore().await }).await
+                if let Err(err) = actix_web::rt::spawn(async {
+                    update_braavos_proscore(&PgStore::new(Network::Mainnet)).await
+                })
+                .await
                 {
                     // failed, probably network overload, wait to send message
                     sleep(Duration::from_secs(120)).await;
@@ -161,7 +173,7 @@ async fn main() -> std::io::Result<()> {
         actix_web::rt::spawn(async move {
             loop {
                 if let Err(err) = actix_web::rt::spawn(async move {
-                    update_braavos_referrals();
+                    update_braavos_referrals(&PgStore::new(Network::Mainnet));
                     Ok::<(), ()>(())
                 })
                 .await
@@ -181,6 +193,39 @@ async fn main() -> std::io::Result<()> {
         });
     }
 
+    if ORACLE_MONITOR {
+        println!("🛠️  Spawning oracle monitoring thread...");
+        actix_web::rt::spawn(async move {
+            let mut monitor =
+                OracleMonitor::new(Arc::new(TelegramBot::new()), PgStore::new(Network::Mainnet));
+            loop {
+                // monitor is moved into the task to keep reported issues between runs
+                match actix_web::rt::spawn(async move {
+                    monitor.check().await;
+                    monitor
+                })
+                .await
+                {
+                    Ok(m) => {
+                        monitor = m;
+                        println!("Oracle health checked");
+                    }
+                    Err(err) => {
+                        sleep(Duration::from_secs(60)).await;
+                        println!("Oracle monitor panicked\n{:?}", err);
+                        telegram_bot::send_message("Carmine API oracle monitor just panicked")
+                            .await;
+                        monitor = OracleMonitor::new(
+                            Arc::new(TelegramBot::new()),
+                            PgStore::new(Network::Mainnet),
+                        );
+                    }
+                }
+                sleep(Duration::from_secs(120)).await;
+            }
+        });
+    }
+
     println!("🚀 Fetcher started successfully");
 
     HttpServer::new(|| App::new().service(liveness))
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use carmine_api_core::types::{Messenger, OracleHealth, OracleIssue, OraclePrice};
+use carmine_api_db::Store;
+use carmine_api_prices::{
+    monitor::check_new_prices,
+    sources::{CoinGeckoSource, PriceSource},
+};
+
+/// Alerts when oracle health of a pair changes, the same kinds of issues
+/// are not reported again until they change, whatever the values.
+/// Every price stored since the previous check is checked.
+pub struct OracleMonitor<M: Messenger, S: Store> {
+    reported: HashMap<String, Vec<OracleIssue>>,
+    last_checked: HashMap<String, OraclePrice>,
+    messenger: M,
+    store: S,
+}
+
+impl<M: Messenger, S: Store> OracleMonitor<M, S> {
+    pub fn new(messenger: M, store: S) -> Self {
+        Self {
+            reported: HashMap::new(),
+            last_checked: HashMap::new(),
+            messenger,
+            store,
+        }
+    }
+
+    pub fn report(&mut self, health: &[OracleHealth]) {
+        for pair_health in health {
+            let previous = self
+                .reported
+                .get(&pair_health.token_pair)
+                .cloned()
+                .unwrap_or_default();
+
+            if previous == pair_health.issue_kinds {
+                continue;
+            }
+
+            let message = match pair_health.healthy {
+                true => format!(
+                    "Oracle {} {} recovered at block {}",
+                    pair_health.oracle_name, pair_health.token_pair, pair_health.block_number
+                ),
+                false => format!(
+                    "Oracle {} {} at block {}: {}",
+                    pair_health.oracle_name,
+                    pair_health.token_pair,
+                    pair_health.block_number,
+                    pair_health.issues.join(", ")
+                ),
+            };
+            self.messenger.send_message(&message);
+
+            self.reported.insert(
+                pair_health.token_pair.to_string(),
+                pair_health.issue_kinds.clone(),
+            );
+        }
+    }
+
+    pub async fn check(&mut self) {
+        let reference = match CoinGeckoSource.fetch_prices().await {
+            Ok(prices) => prices,
+            Err(e) => {
+                println!("Oracle monitor failed getting reference prices: {}", e);
+                HashMap::new()
+            }
+        };
+        let health = check_new_prices(&self.store, &reference, &mut self.last_checked);
+        self.report(&health);
+    }
+}
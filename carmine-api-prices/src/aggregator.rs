@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use carmine_api_core::types::{AggregatedPrice, TokenPrices};
+
+use crate::sources::{now, PriceSource, SourcePrices, StaticSource};
+
+/// Quotes older than this are ignored
+pub const DEFAULT_MAX_AGE_SECS: i64 = 60 * 60;
+/// Quotes further than this ratio from the median are ignored
+pub const DEFAULT_MAX_DEVIATION: f64 = 0.05;
+
+pub struct PriceAggregator {
+    sources: Vec<Box<dyn PriceSource>>,
+    fallback: Option<StaticSource>,
+    max_age_secs: i64,
+    max_deviation: f64,
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AggregatedPrices {
+    pub prices: HashMap<String, AggregatedPrice>,
+}
+
+impl AggregatedPrices {
+    pub fn price(&self, token: &str) -> Option<f64> {
+        self.prices.get(token).map(|p| p.price)
+    }
+
+    /// Price of `base` denominated in `quote`
+    pub fn cross_price(&self, base: &str, quote: &str) -> Option<f64> {
+        let quote_price = self.price(quote).filter(|p| *p != 0.0)?;
+        Some(self.price(base)? / quote_price)
+    }
+
+    /// Tokens without aggregated price keep the `previous` value,
+    /// tokens never priced stay `None`
+    pub fn token_prices(&self, previous: &TokenPrices) -> TokenPrices {
+        TokenPrices {
+            eth: self.price("eth").or(previous.eth),
+            usdc: self.price("usdc").or(previous.usdc),
+            strk: self.price("strk").or(previous.strk),
+            btc: self.price("btc").or(previous.btc),
+            ekubo: self.price("ekubo").or(previous.ekubo),
+        }
+    }
+}
+
+impl PriceAggregator {
+    pub fn new(sources: Vec<Box<dyn PriceSource>>) -> Self {
+        PriceAggregator {
+            sources,
+            fallback: None,
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+            max_deviation: DEFAULT_MAX_DEVIATION,
+        }
+    }
+
+    /// Static prices used only for tokens no live source could price
+    pub fn fallback(mut self, fallback: StaticSource) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    pub fn max_age_secs(mut self, secs: i64) -> Self {
+        self.max_age_secs = secs;
+        self
+    }
+
+    pub fn max_deviation(mut self, ratio: f64) -> Self {
+        self.max_deviation = ratio;
+        self
+    }
+
+    /// Median of fresh quotes, quotes deviating from it are dropped
+    /// and the median is taken again from the rest.
+    fn aggregate_token(&self, quotes: &[(&str, f64, i64)], now: i64) -> Option<AggregatedPrice> {
+        let fresh: Vec<&(&str, f64, i64)> = quotes
+            .iter()
+            .filter(|(_, price, _)| price.is_finite() && *price > 0.0)
+            .filter(|(_, _, ts)| now - ts <= self.max_age_secs)
+            .collect();
+
+        let mut fresh_prices: Vec<f64> = fresh.iter().map(|(_, price, _)| *price).collect();
+        let first_median = median(&mut fresh_prices)?;
+
+        let (accepted, rejected): (Vec<_>, Vec<_>) =
+            fresh.into_iter().partition(|(_, price, _)| {
+                ((price - first_median) / first_median).abs() <= self.max_deviation
+            });
+
+        let mut accepted_prices: Vec<f64> = accepted.iter().map(|(_, price, _)| *price).collect();
+        let price = median(&mut accepted_prices)?;
+
+        Some(AggregatedPrice {
+            price,
+            sources: accepted
+                .iter()
+                .map(|(name, _, _)| name.to_string())
+                .collect(),
+            rejected_sources: rejected
+                .iter()
+                .map(|(name, _, _)| name.to_string())
+                .collect(),
+            stale_sources: quotes
+                .iter()
+                .filter(|(_, _, ts)| now - ts > self.max_age_secs)
+                .map(|(name, _, _)| name.to_string())
+                .collect(),
+            timestamp: accepted.iter().map(|(_, _, ts)| *ts).max().unwrap_or(now),
+        })
+    }
+
+    /// Aggregates quotes reported by sources, `now` is the reference
+    /// time for the staleness check
+    pub fn aggregate(&self, quotes: &[(&str, SourcePrices)], now: i64) -> AggregatedPrices {
+        let mut by_token: HashMap<&str, Vec<(&str, f64, i64)>> = HashMap::new();
+
+        for (source, prices) in quotes {
+            for (token, quote) in prices {
+                by_token
+                    .entry(token.as_str())
+                    .or_insert_with(Vec::new)
+                    .push((*source, quote.price, quote.timestamp));
+            }
+        }
+
+        let mut prices: HashMap<String, AggregatedPrice> = by_token
+            .into_iter()
+            .filter_map(|(token, token_quotes)| {
+                let price = self.aggregate_token(&token_quotes, now)?;
+                Some((token.to_string(), price))
+            })
+            .collect();
+
+        if let Some(fallback) = &self.fallback {
+            for (token, quote) in fallback.quotes(now) {
+                prices.entry(token).or_insert(AggregatedPrice {
+                    price: quote.price,
+                    sources: vec![fallback.name().to_string()],
+                    rejected_sources: vec![],
+                    stale_sources: vec![],
+                    timestamp: quote.timestamp,
+                });
+            }
+        }
+
+        AggregatedPrices { prices }
+    }
+
+    /// Fetches all sources, failing sources are skipped
+    pub async fn fetch(&self) -> AggregatedPrices {
+        let mut quotes: Vec<(&str, SourcePrices)> = vec![];
+
+        for source in self.sources.iter() {
+            match source.fetch_prices().await {
+                Ok(prices) => quotes.push((source.name(), prices)),
+                Err(e) => println!("Price source {} failed: {}", source.name(), e),
+            }
+        }
+
+        self.aggregate(&quotes, now())
+    }
+}
+
+impl Default for PriceAggregator {
+    /// CoinGecko and Pragma with pegged USDC fallback
+    fn default() -> Self {
+        PriceAggregator::new(vec![
+            Box::new(crate::sources::CoinGeckoSource),
+            Box::new(crate::sources::PragmaSource::new()),
+        ])
+        .fallback(StaticSource::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use carmine_api_core::types::TokenPrices;
+
+    use super::PriceAggregator;
+    use crate::sources::{SourcePrices, SourceQuote, StaticSource};
+
+    fn quotes(prices: &[(&str, f64, i64)]) -> SourcePrices {
+        prices
+            .iter()
+            .map(|(token, price, timestamp)| {
+                (
+                    token.to_string(),
+                    SourceQuote {
+                        price: *price,
+                        timestamp: *timestamp,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn median_with_deviation_and_staleness() {
+        let aggregator = PriceAggregator::new(vec![]);
+        let res = aggregator.aggregate(
+            &[
+                ("a", quotes(&[("eth", 3000.0, 1000)])),
+                ("b", quotes(&[("eth", 3010.0, 1000)])),
+                ("c", quotes(&[("eth", 4000.0, 1000)])),
+                ("d", quotes(&[("eth", 3005.0, 0)])),
+            ],
+            3700,
+        );
+        let eth = res.prices.get("eth").unwrap();
+        assert_eq!(eth.price, 3005.0);
+        assert_eq!(eth.sources, vec!["a", "b"]);
+        assert_eq!(eth.rejected_sources, vec!["c"]);
+        assert_eq!(eth.stale_sources, vec!["d"]);
+    }
+
+    #[test]
+    fn fallback_only_for_missing_tokens() {
+        let aggregator = PriceAggregator::new(vec![]).fallback(StaticSource::new(HashMap::from([
+            ("usdc".to_string(), 1.0),
+            ("eth".to_string(), 1.0),
+        ])));
+        let res = aggregator.aggregate(&[("a", quotes(&[("eth", 3000.0, 100)]))], 100);
+        assert_eq!(res.price("eth"), Some(3000.0));
+        assert_eq!(res.price("usdc"), Some(1.0));
+        assert_eq!(res.prices.get("usdc").unwrap().sources, vec!["static"]);
+        assert_eq!(res.cross_price("eth", "usdc"), Some(3000.0));
+    }
+
+    #[test]
+    fn unpriced_tokens_are_unavailable() {
+        let aggregator = PriceAggregator::new(vec![]);
+        let first = aggregator
+            .aggregate(&[("a", quotes(&[("eth", 3000.0, 100)]))], 100)
+            .token_prices(&TokenPrices::default());
+        assert_eq!(first.eth, Some(3000.0));
+        assert_eq!(first.strk, None);
+
+        // every source failed, previous prices are kept
+        let second = aggregator.aggregate(&[], 200).token_prices(&first);
+        assert_eq!(second.eth, Some(3000.0));
+        assert_eq!(second.btc, None);
+    }
+}
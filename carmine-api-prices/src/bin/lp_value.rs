@@ -1,4 +1,4 @@
-use carmine_api_db::lp_value::update_lp_prices;
+use carmine_api_prices::lp_value::update_lp_prices;
 use dotenvy::dotenv;
 
 fn main() {
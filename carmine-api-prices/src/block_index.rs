@@ -0,0 +1,89 @@
+use carmine_api_core::types::DbBlock;
+
+/// Blocks sorted by block number, Starknet timestamps never decrease
+/// with block number so the same vec answers lookups in both directions.
+#[derive(Default)]
+pub struct BlockIndex {
+    blocks: Vec<DbBlock>,
+}
+
+impl BlockIndex {
+    pub fn new(mut blocks: Vec<DbBlock>) -> Self {
+        blocks.sort_by_key(|block| block.block_number);
+        blocks.dedup_by_key(|block| block.block_number);
+        BlockIndex { blocks }
+    }
+
+    /// Appends blocks newer than the last indexed one, older blocks are ignored
+    pub fn extend(&mut self, blocks: Vec<DbBlock>) {
+        let last = self.last_block_number();
+        let mut new_blocks: Vec<DbBlock> = blocks
+            .into_iter()
+            .filter(|block| last.map_or(true, |last| block.block_number > last))
+            .collect();
+        new_blocks.sort_by_key(|block| block.block_number);
+        new_blocks.dedup_by_key(|block| block.block_number);
+        self.blocks.extend(new_blocks);
+    }
+
+    pub fn last_block_number(&self) -> Option<i64> {
+        self.blocks.last().map(|block| block.block_number)
+    }
+
+    /// Last block with timestamp at or before `ts`
+    pub fn block_at_timestamp(&self, ts: i64) -> Option<i64> {
+        let idx = self.blocks.partition_point(|block| block.timestamp <= ts);
+        match idx {
+            0 => None,
+            i => Some(self.blocks[i - 1].block_number),
+        }
+    }
+
+    pub fn timestamp_of(&self, block_number: i64) -> Option<i64> {
+        self.blocks
+            .binary_search_by_key(&block_number, |block| block.block_number)
+            .ok()
+            .map(|idx| self.blocks[idx].timestamp)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockIndex;
+    use carmine_api_core::types::DbBlock;
+
+    fn block(block_number: i64, timestamp: i64) -> DbBlock {
+        DbBlock {
+            block_number,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        let index = BlockIndex::new(vec![block(3, 130), block(1, 100), block(2, 100)]);
+        assert_eq!(index.block_at_timestamp(99), None);
+        assert_eq!(index.block_at_timestamp(100), Some(2));
+        assert_eq!(index.block_at_timestamp(129), Some(2));
+        assert_eq!(index.block_at_timestamp(500), Some(3));
+        assert_eq!(index.timestamp_of(3), Some(130));
+        assert_eq!(index.timestamp_of(4), None);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut index = BlockIndex::new(vec![block(1, 100), block(2, 110)]);
+        index.extend(vec![block(4, 130), block(2, 110), block(3, 120)]);
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.last_block_number(), Some(4));
+        assert_eq!(index.block_at_timestamp(125), Some(3));
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use carmine_api_core::{
+    network::Network,
+    pool::{get_all_pools, Type},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceError {
+    UnknownPool(String),
+    UnknownToken(String),
+    NoPath { base: String, quote: String },
+    MissingPrice { pair: String, block_number: i64 },
+    BlockNotFound(i64),
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceError::UnknownPool(pool_id) => write!(f, "unknown pool {}", pool_id),
+            PriceError::UnknownToken(token) => write!(f, "unknown token {}", token),
+            PriceError::NoPath { base, quote } => {
+                write!(f, "no price path from {} to {}", base, quote)
+            }
+            PriceError::MissingPrice { pair, block_number } => {
+                write!(f, "missing {} price at block {}", pair, block_number)
+            }
+            PriceError::BlockNotFound(ts) => write!(f, "no block before timestamp {}", ts),
+        }
+    }
+}
+
+/// Stored oracle pair, price is amount of `quote` for one `base`
+pub struct PairEdge {
+    pub pair_id: &'static str,
+    pub base: &'static str,
+    pub quote: &'static str,
+}
+
+// Pragma pairs are quoted in USD, USDC is treated as USD
+pub const ORACLE_PAIRS: [PairEdge; 4] = [
+    PairEdge {
+        pair_id: "eth-usdc",
+        base: "eth",
+        quote: "usdc",
+    },
+    PairEdge {
+        pair_id: "btc-usdc",
+        base: "btc",
+        quote: "usdc",
+    },
+    PairEdge {
+        pair_id: "strk-usdc",
+        base: "strk",
+        quote: "usdc",
+    },
+    PairEdge {
+        pair_id: "ekubo-usdc",
+        base: "ekubo",
+        quote: "usdc",
+    },
+];
+
+/// Single step of a conversion, `inverted` legs divide by the pair price
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leg {
+    pub pair_id: String,
+    pub inverted: bool,
+}
+
+pub struct PriceGraph {
+    edges: HashMap<String, Vec<(String, Leg)>>,
+}
+
+impl PriceGraph {
+    pub fn new(pairs: &[PairEdge]) -> Self {
+        let mut edges: HashMap<String, Vec<(String, Leg)>> = HashMap::new();
+
+        for pair in pairs {
+            edges
+                .entry(pair.base.to_string())
+                .or_insert_with(Vec::new)
+                .push((
+                    pair.quote.to_string(),
+                    Leg {
+                        pair_id: pair.pair_id.to_string(),
+                        inverted: false,
+                    },
+                ));
+            edges
+                .entry(pair.quote.to_string())
+                .or_insert_with(Vec::new)
+                .push((
+                    pair.base.to_string(),
+                    Leg {
+                        pair_id: pair.pair_id.to_string(),
+                        inverted: true,
+                    },
+                ));
+        }
+
+        PriceGraph { edges }
+    }
+
+    /// Shortest chain of stored pairs converting `base` into `quote`
+    pub fn path(&self, base: &str, quote: &str) -> Result<Vec<Leg>, PriceError> {
+        for token in [base, quote] {
+            if !self.edges.contains_key(token) {
+                return Err(PriceError::UnknownToken(token.to_string()));
+            }
+        }
+
+        if base == quote {
+            return Ok(vec![]);
+        }
+
+        let mut visited: HashSet<&str> = HashSet::from([base]);
+        let mut previous: HashMap<&str, (&str, &Leg)> = HashMap::new();
+        let mut queue: VecDeque<&str> = VecDeque::from([base]);
+
+        while let Some(token) = queue.pop_front() {
+            if token == quote {
+                break;
+            }
+            for (next, leg) in self.edges.get(token).into_iter().flatten() {
+                if visited.insert(next.as_str()) {
+                    previous.insert(next.as_str(), (token, leg));
+                    queue.push_back(next.as_str());
+                }
+            }
+        }
+
+        let mut legs = vec![];
+        let mut current = quote;
+
+        while current != base {
+            let (prev, leg) = previous.get(current).ok_or_else(|| PriceError::NoPath {
+                base: base.to_string(),
+                quote: quote.to_string(),
+            })?;
+            legs.push((*leg).clone());
+            current = prev;
+        }
+
+        legs.reverse();
+        Ok(legs)
+    }
+}
+
+impl Default for PriceGraph {
+    fn default() -> Self {
+        PriceGraph::new(&ORACLE_PAIRS)
+    }
+}
+
+/// Symbol of the asset the pool is denominated in,
+/// base token for Call pools and quote token for Put pools
+pub fn pool_underlying_token(pool_id: &str) -> Result<String, PriceError> {
+    get_all_pools(&Network::Mainnet)
+        .into_iter()
+        .find(|pool| pool.id == pool_id)
+        .map(|pool| match pool.type_ {
+            Type::Call => pool.base.symbol.to_lowercase(),
+            Type::Put => pool.quote.symbol.to_lowercase(),
+        })
+        .ok_or_else(|| PriceError::UnknownPool(pool_id.to_string()))
+}
+
+/// Converts price of `legs` into single rate, `pair_price` returns
+/// stored price of the pair
+pub fn resolve_legs<F>(legs: &[Leg], mut pair_price: F) -> Result<f64, PriceError>
+where
+    F: FnMut(&str) -> Result<f64, PriceError>,
+{
+    let mut rate = 1.0;
+
+    for leg in legs {
+        let price = pair_price(&leg.pair_id)?;
+        if leg.inverted {
+            rate /= price;
+        } else {
+            rate *= price;
+        }
+    }
+
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_legs, PriceError, PriceGraph};
+
+    #[test]
+    fn cross_rate_path() {
+        let graph = PriceGraph::default();
+        let legs = graph.path("eth", "strk").unwrap();
+        assert_eq!(legs.len(), 2);
+        assert_eq!(legs[0].pair_id, "eth-usdc");
+        assert!(!legs[0].inverted);
+        assert_eq!(legs[1].pair_id, "strk-usdc");
+        assert!(legs[1].inverted);
+
+        let rate = resolve_legs(&legs, |pair| match pair {
+            "eth-usdc" => Ok(3000.0),
+            "strk-usdc" => Ok(0.5),
+            _ => unreachable!(),
+        });
+        assert_eq!(rate, Ok(6000.0));
+    }
+
+    #[test]
+    fn usd_quoted() {
+        let graph = PriceGraph::default();
+        assert_eq!(graph.path("usdc", "usdc").unwrap().len(), 0);
+        assert_eq!(graph.path("ekubo", "usdc").unwrap().len(), 1);
+        assert_eq!(
+            graph.path("doge", "usdc"),
+            Err(PriceError::UnknownToken("doge".to_string()))
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use carmine_api_core::types::{PriceCandle, PricePoint};
+
+/// Candle intervals in seconds precomputed on every cache refresh
+pub const CANDLE_INTERVALS: [i64; 6] = [60, 300, 900, 3600, 4 * 3600, 86400];
+
+/// Last price stored at or before the given timestamp.
+/// `series` must be sorted by timestamp ascending.
+pub fn price_at_timestamp(series: &[PricePoint], ts: i64) -> Option<&PricePoint> {
+    let idx = series.partition_point(|p| p.timestamp <= ts);
+    match idx {
+        0 => None,
+        i => series.get(i - 1),
+    }
+}
+
+/// Last price stored at or before the given block.
+/// `series` must be sorted by block number ascending.
+pub fn price_at_block(series: &[PricePoint], block_number: i64) -> Option<&PricePoint> {
+    let idx = series.partition_point(|p| p.block_number <= block_number);
+    match idx {
+        0 => None,
+        i => series.get(i - 1),
+    }
+}
+
+/// Builds OHLC candles of `interval` seconds for points with `from <= timestamp < to`.
+/// Intervals without any price are skipped.
+pub fn candles(series: &[PricePoint], interval: i64, from: i64, to: i64) -> Vec<PriceCandle> {
+    let start = series.partition_point(|p| p.timestamp < from);
+    let end = series.partition_point(|p| p.timestamp < to);
+
+    let mut res: Vec<PriceCandle> = vec![];
+
+    for point in &series[start..end.max(start)] {
+        let candle_start = point.timestamp - point.timestamp.rem_euclid(interval);
+
+        match res.last_mut() {
+            Some(candle) if candle.timestamp == candle_start => {
+                candle.high = candle.high.max(point.price);
+                candle.low = candle.low.min(point.price);
+                candle.close = point.price;
+                candle.last_block_number = point.block_number;
+                candle.samples += 1;
+            }
+            _ => res.push(PriceCandle {
+                timestamp: candle_start,
+                open: point.price,
+                high: point.price,
+                low: point.price,
+                close: point.price,
+                first_block_number: point.block_number,
+                last_block_number: point.block_number,
+                samples: 1,
+            }),
+        }
+    }
+
+    res
+}
+
+/// Candles of the whole series for each of CANDLE_INTERVALS
+pub fn candle_series(series: &[PricePoint]) -> HashMap<i64, Vec<PriceCandle>> {
+    CANDLE_INTERVALS
+        .iter()
+        .map(|interval| (*interval, candles(series, *interval, i64::MIN, i64::MAX)))
+        .collect()
+}
+
+/// Candles of `interval` seconds overlapping `from..to`.
+/// `candles` must be sorted by timestamp ascending.
+pub fn candles_in_range(
+    candles: &[PriceCandle],
+    interval: i64,
+    from: i64,
+    to: i64,
+) -> &[PriceCandle] {
+    let start = candles.partition_point(|c| c.timestamp.saturating_add(interval) <= from);
+    let end = candles.partition_point(|c| c.timestamp < to);
+    &candles[start..end.max(start)]
+}
+
+/// Time weighted average price over `from..to`.
+/// Each price is held until the next one, the price in effect at `from`
+/// is the last one stored before it.
+pub fn twap(series: &[PricePoint], from: i64, to: i64) -> Option<f64> {
+    if from >= to {
+        return None;
+    }
+
+    let start = series.partition_point(|p| p.timestamp <= from);
+    let end = series.partition_point(|p| p.timestamp < to);
+
+    // price in effect at the start of the window
+    let (mut current_price, mut current_ts) = match start {
+        0 => {
+            let first = series.first().filter(|p| p.timestamp < to)?;
+            (first.price, first.timestamp)
+        }
+        i => (series[i - 1].price, from),
+    };
+
+    let mut weighted_sum = 0.0;
+
+    for point in &series[start..end.max(start)] {
+        if point.timestamp <= current_ts {
+            current_price = point.price;
+            continue;
+        }
+        weighted_sum += current_price * (point.timestamp - current_ts) as f64;
+        current_price = point.price;
+        current_ts = point.timestamp;
+    }
+
+    weighted_sum += current_price * (to - current_ts) as f64;
+
+    let duration = to - series.first().map_or(from, |p| p.timestamp.max(from));
+
+    match duration {
+        0 => Some(current_price),
+        d => Some(weighted_sum / d as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        candle_series, candles, candles_in_range, price_at_block, price_at_timestamp, twap,
+    };
+    use carmine_api_core::types::PricePoint;
+
+    fn series() -> Vec<PricePoint> {
+        vec![
+            PricePoint {
+                block_number: 1,
+                timestamp: 100,
+                price: 10.0,
+            },
+            PricePoint {
+                block_number: 2,
+                timestamp: 130,
+                price: 12.0,
+            },
+            PricePoint {
+                block_number: 3,
+                timestamp: 170,
+                price: 8.0,
+            },
+            PricePoint {
+                block_number: 4,
+                timestamp: 210,
+                price: 9.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn point_in_time() {
+        let s = series();
+        assert_eq!(price_at_timestamp(&s, 99), None);
+        assert_eq!(price_at_timestamp(&s, 150).unwrap().price, 12.0);
+        assert_eq!(price_at_block(&s, 3).unwrap().price, 8.0);
+        assert_eq!(price_at_block(&s, 100).unwrap().price, 9.0);
+    }
+
+    #[test]
+    fn ohlc_candles() {
+        let res = candles(&series(), 100, 0, 300);
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].timestamp, 100);
+        assert_eq!(res[0].open, 10.0);
+        assert_eq!(res[0].high, 12.0);
+        assert_eq!(res[0].low, 8.0);
+        assert_eq!(res[0].close, 8.0);
+        assert_eq!(res[0].samples, 3);
+        assert_eq!(res[1].timestamp, 200);
+        assert_eq!(res[1].close, 9.0);
+    }
+
+    #[test]
+    fn precomputed_candles_in_range() {
+        let all = candle_series(&series());
+        let hourly = &all[&3600];
+        assert_eq!(hourly.len(), 1);
+        assert_eq!(hourly[0].samples, 4);
+
+        let minutes = &all[&60];
+        assert_eq!(minutes.len(), 3);
+        // candle starting at 120 overlaps from
+        let range = candles_in_range(minutes, 60, 150, 180);
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].timestamp, 120);
+        assert_eq!(range[0].samples, 2);
+        assert!(candles_in_range(minutes, 60, 300, 400).is_empty());
+    }
+
+    #[test]
+    fn time_weighted_average() {
+        // 10 for 30s, 12 for 40s, 8 for 30s
+        assert_eq!(twap(&series(), 100, 200), Some(10.2));
+        // window starting between points uses price in effect
+        assert_eq!(twap(&series(), 140, 160), Some(12.0));
+        assert_eq!(twap(&series(), 0, 50), None);
+    }
+}
@@ -1,11 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use carmine_api_core::types::{DbBlock, OraclePriceConcise};
-use carmine_api_db::get_blocks_since_new_amm;
+use block_index::BlockIndex;
+use carmine_api_core::{
+    network::NEW_AMM_GENESIS_BLOCK_NUMBER,
+    types::{OraclePriceConcise, PricePoint},
+};
+use carmine_api_db::{DbError, Store};
+use graph::{pool_underlying_token, resolve_legs, PriceError, PriceGraph};
+
+pub mod aggregator;
+pub mod block_index;
+pub mod graph;
+pub mod history;
+pub mod lp_value;
+pub mod monitor;
+pub mod sources;
+pub mod volatility;
+
+/// Oracle prices are quoted in USDC which is treated as USD
+pub const USD: &str = "usdc";
 
 pub struct HistoricalPrices {
-    prices: HashMap<String, HashMap<i64, f32>>,
-    blocks: Vec<DbBlock>,
+    prices: HashMap<String, BTreeMap<i64, f64>>,
+    series: HashMap<String, Vec<PricePoint>>,
+    blocks: BlockIndex,
+    graph: PriceGraph,
 }
 
 pub enum BlockId {
@@ -13,82 +32,236 @@ pub enum BlockId {
     BlockNumber(i64),
 }
 
+fn to_numeric_price(price: &OraclePriceConcise) -> f64 {
+    price.price as f64 / 10f64.powi(price.decimals as i32)
+}
+
 impl HistoricalPrices {
-    pub fn new(oracle_prices: &HashMap<String, Vec<OraclePriceConcise>>) -> Self {
-        let mut nested_map: HashMap<String, HashMap<i64, f32>> = HashMap::new();
+    pub fn new(
+        store: &dyn Store,
+        oracle_prices: &HashMap<String, Vec<OraclePriceConcise>>,
+    ) -> Result<Self, DbError> {
+        let blocks = BlockIndex::new(store.get_blocks_greater_than(NEW_AMM_GENESIS_BLOCK_NUMBER)?);
+        Ok(HistoricalPrices::with_blocks(oracle_prices, blocks))
+    }
 
+    fn with_blocks(
+        oracle_prices: &HashMap<String, Vec<OraclePriceConcise>>,
+        blocks: BlockIndex,
+    ) -> Self {
+        let mut historical_prices = HistoricalPrices {
+            prices: HashMap::new(),
+            series: HashMap::new(),
+            blocks,
+            graph: PriceGraph::default(),
+        };
+        historical_prices.insert_prices(oracle_prices);
+        historical_prices.extend_series();
+        historical_prices
+    }
+
+    /// Adds prices stored since the last update, only blocks newer
+    /// than the last indexed one are loaded. Nothing changes on error.
+    pub fn update(
+        &mut self,
+        store: &dyn Store,
+        new_prices: &HashMap<String, Vec<OraclePriceConcise>>,
+    ) -> Result<(), DbError> {
+        let last = self
+            .blocks
+            .last_block_number()
+            .unwrap_or(NEW_AMM_GENESIS_BLOCK_NUMBER);
+        let new_blocks = store.get_blocks_greater_than(last)?;
+        self.blocks.extend(new_blocks);
+        self.insert_prices(new_prices);
+        self.extend_series();
+        Ok(())
+    }
+
+    fn insert_prices(&mut self, oracle_prices: &HashMap<String, Vec<OraclePriceConcise>>) {
         for (token_pair, prices) in oracle_prices {
-            let mut inner_map: HashMap<i64, f32> = HashMap::new();
+            let pair_map = self.prices.entry(token_pair.to_string()).or_default();
             for price in prices {
-                let numeric_price = price.price as f32 / 10f32.powi(price.decimals as i32);
-                inner_map.insert(price.block_number, numeric_price);
+                pair_map.insert(price.block_number, to_numeric_price(price));
             }
-            nested_map.insert(token_pair.to_string(), inner_map);
         }
+    }
 
-        let mut blocks = get_blocks_since_new_amm();
-        blocks.sort_by_key(|block| block.timestamp);
+    /// Appends prices newer than the last series point. Stops at the first
+    /// price whose block is not indexed yet so it is picked up by the next update.
+    fn extend_series(&mut self) {
+        let last_indexed = self.blocks.last_block_number().unwrap_or(i64::MIN);
 
-        HistoricalPrices {
-            prices: nested_map,
-            blocks,
+        for (token_pair, pair_map) in &self.prices {
+            let points = self.series.entry(token_pair.to_string()).or_default();
+            let last_point = points.last().map_or(i64::MIN, |p| p.block_number);
+
+            for (&block_number, &price) in pair_map.range(last_point.saturating_add(1)..) {
+                match self.blocks.timestamp_of(block_number) {
+                    Some(timestamp) => points.push(PricePoint {
+                        block_number,
+                        timestamp,
+                        price,
+                    }),
+                    None if block_number > last_indexed => break,
+                    // prices without stored block cannot be placed in time
+                    None => continue,
+                }
+            }
         }
     }
 
-    fn get_block_number_from_timestamp(&self, ts: i64) -> i64 {
-        // Try to find the exact match first
-        if let Some(block) = self.blocks.iter().find(|&block| block.timestamp == ts) {
-            return block.block_number;
+    /// Highest block number with a stored price of any pair
+    pub fn last_price_block_number(&self) -> Option<i64> {
+        self.prices
+            .values()
+            .filter_map(|pair_map| pair_map.keys().next_back().copied())
+            .max()
+    }
+
+    /// Price points of the token pair sorted by block number
+    pub fn get_series(&self, token_pair: &str) -> Option<&Vec<PricePoint>> {
+        self.series.get(token_pair)
+    }
+
+    pub fn all_series(&self) -> &HashMap<String, Vec<PricePoint>> {
+        &self.series
+    }
+
+    /// Point in time lookup of token pair price
+    pub fn get_pair_price(&self, token_pair: &str, block_id: BlockId) -> Option<PricePoint> {
+        let series = self.series.get(token_pair)?;
+        match block_id {
+            BlockId::Timestamp(ts) => history::price_at_timestamp(series, ts).copied(),
+            BlockId::BlockNumber(n) => history::price_at_block(series, n).copied(),
         }
+    }
 
-        // If no exact match, find the closest timestamp less than the target_timestamp
-        let nearest_less_than = self
-            .blocks
-            .iter()
-            .filter(|&block| block.timestamp < ts)
-            .max_by_key(|&block| block.timestamp)
-            .map(|block| block.block_number);
-
-        nearest_less_than.expect("Failed to find block number for timestamp")
-    }
-
-    pub fn get_price(&self, pool_id: &str, block_id: BlockId) -> f32 {
-        let usdc_price = 1.0;
-        let pair: &str = match pool_id {
-            "eth-usdc-call" => "eth-usdc",
-            "eth-usdc-put" => return usdc_price,
-            "btc-usdc-call" => "btc-usdc",
-            "btc-usdc-put" => return usdc_price,
-            "eth-strk-call" => "eth-usdc",
-            "eth-strk-put" => "strk-usdc",
-            "strk-usdc-call" => "strk-usdc",
-            "strk-usdc-put" => return usdc_price,
-            "ekubo-usdc-call" => "ekubo-usdc",
-            "ekubo-usdc-put" => return usdc_price,
-            _ => unreachable!("invalid pool id"),
-        };
-        let pair_map = match self.prices.get(pair) {
-            Some(map) => map,
-            None => panic!("Invalid token_pair"),
-        };
+    fn get_block_number_from_timestamp(&self, ts: i64) -> Result<i64, PriceError> {
+        self.blocks
+            .block_at_timestamp(ts)
+            .ok_or(PriceError::BlockNotFound(ts))
+    }
+
+    /// Last stored price of the pair at or before `block_number`
+    fn get_stored_pair_price(&self, pair: &str, block_number: i64) -> Result<f64, PriceError> {
+        self.prices
+            .get(pair)
+            .and_then(|pair_map| pair_map.range(..=block_number).next_back())
+            .map(|(_, &price)| price)
+            .ok_or_else(|| PriceError::MissingPrice {
+                pair: pair.to_string(),
+                block_number,
+            })
+    }
+
+    /// Price of `base` denominated in `quote`, derived from the stored
+    /// USD pairs when there is no direct pair
+    pub fn get_cross_price(
+        &self,
+        base: &str,
+        quote: &str,
+        block_id: BlockId,
+    ) -> Result<f64, PriceError> {
+        let legs = self.graph.path(base, quote)?;
 
         let block_number: i64 = match block_id {
-            BlockId::Timestamp(n) => self.get_block_number_from_timestamp(n),
+            BlockId::Timestamp(n) => self.get_block_number_from_timestamp(n)?,
             BlockId::BlockNumber(n) => n,
         };
 
-        if let Some(price) = pair_map.get(&block_number) {
-            return price.clone();
+        resolve_legs(&legs, |pair| self.get_stored_pair_price(pair, block_number))
+    }
+
+    /// Price series of `base` in `quote`, direct pairs are returned as stored,
+    /// cross rates are derived at every price update of the first leg
+    pub fn get_cross_series(&self, base: &str, quote: &str) -> Result<Vec<PricePoint>, PriceError> {
+        let legs = self.graph.path(base, quote)?;
+
+        let first_leg = match legs.first() {
+            Some(leg) => leg,
+            None => return Ok(vec![]),
+        };
+        let first_series =
+            self.series
+                .get(&first_leg.pair_id)
+                .ok_or_else(|| PriceError::NoPath {
+                    base: base.to_string(),
+                    quote: quote.to_string(),
+                })?;
+
+        if legs.len() == 1 && !first_leg.inverted {
+            return Ok(first_series.clone());
         }
 
-        match pair_map
+        Ok(first_series
             .iter()
-            .filter(|(&block, _)| block <= block_number)
-            .max_by_key(|(&block, _)| block)
-            .map(|(_, &price)| price)
-        {
-            Some(nearest_price) => nearest_price,
-            None => panic!("Could not find price"),
+            .filter_map(|point| {
+                let price = resolve_legs(&legs, |pair| {
+                    self.get_stored_pair_price(pair, point.block_number)
+                })
+                .ok()?;
+                Some(PricePoint {
+                    block_number: point.block_number,
+                    timestamp: point.timestamp,
+                    price,
+                })
+            })
+            .collect())
+    }
+
+    /// USD price of the asset the pool is denominated in
+    pub fn get_price(&self, pool_id: &str, block_id: BlockId) -> Result<f32, PriceError> {
+        let token = pool_underlying_token(pool_id)?;
+        self.get_cross_price(&token, USD, block_id)
+            .map(|price| price as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use carmine_api_core::types::{DbBlock, OraclePriceConcise};
+
+    use crate::{block_index::BlockIndex, BlockId, HistoricalPrices};
+
+    fn price(block_number: i64, price: i64) -> OraclePriceConcise {
+        OraclePriceConcise {
+            price,
+            decimals: 2,
+            last_updated_timestamp: 0,
+            block_number,
         }
     }
+
+    #[test]
+    fn series_waits_for_indexed_blocks() {
+        let blocks = BlockIndex::new(vec![DbBlock {
+            block_number: 1,
+            timestamp: 100,
+        }]);
+        let prices = HashMap::from([(
+            "eth-usdc".to_string(),
+            vec![price(1, 300000), price(2, 310000)],
+        )]);
+        let mut historical = HistoricalPrices::with_blocks(&prices, blocks);
+
+        assert_eq!(historical.get_series("eth-usdc").unwrap().len(), 1);
+        assert_eq!(
+            historical.get_cross_price("eth", "usdc", BlockId::BlockNumber(5)),
+            Ok(3100.0)
+        );
+
+        historical.blocks.extend(vec![DbBlock {
+            block_number: 2,
+            timestamp: 110,
+        }]);
+        historical.extend_series();
+
+        let series = historical.get_series("eth-usdc").unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[1].timestamp, 110);
+        assert_eq!(historical.last_price_block_number(), Some(2));
+    }
 }
@@ -0,0 +1,78 @@
+use carmine_api_core::{
+    network::Network, pool::get_all_pools, types::PoolStatePriceUpdate, utils::string_to_float,
+};
+use carmine_api_db::{get_pool_states_with_prices, update_pool_state_asset_prices};
+
+use crate::{
+    aggregator::PriceAggregator,
+    graph::pool_underlying_token,
+    sources::{PragmaSource, StaticSource},
+    USD,
+};
+
+pub fn update_lp_prices() {
+    let net = &Network::Mainnet;
+    // historical prices are only available from the stored Pragma prices
+    let aggregator = PriceAggregator::new(vec![]).fallback(StaticSource::default());
+
+    for pool in get_all_pools(net) {
+        let token = match pool_underlying_token(pool.id) {
+            Ok(token) => token,
+            Err(e) => {
+                println!("{} Skipped: {}", pool.address, e);
+                continue;
+            }
+        };
+
+        let pool_states = match get_pool_states_with_prices(pool.address, net) {
+            Ok(states) => states,
+            Err(e) => {
+                println!("{} Failed getting pool states: {}", pool.address, e);
+                continue;
+            }
+        };
+
+        let mut updates = vec![];
+
+        println!("Iterating over {} items", pool_states.len());
+
+        for rich_pool_state in pool_states {
+            let (pool_state, prices) = rich_pool_state;
+
+            // block timestamp is not loaded with the pool state,
+            // staleness is checked against the newest oracle update in the block
+            let reference_ts = prices
+                .iter()
+                .map(|p| p.last_updated_timestamp)
+                .max()
+                .unwrap_or_default();
+            let quotes = PragmaSource::quotes_from_oracle_prices(&prices);
+            let aggregated = aggregator.aggregate(&[("pragma", quotes)], reference_ts);
+
+            let price = match aggregated.cross_price(&token, USD) {
+                Some(price) => price,
+                None => continue,
+            };
+
+            if let Some(lp_token_value) = pool_state.lp_token_value {
+                let lp_float = string_to_float(lp_token_value.as_str(), 18);
+
+                let info = PoolStatePriceUpdate {
+                    lp_token_value_usd: lp_float * price,
+                    underlying_asset_price: price,
+                    block_number: pool_state.block_number,
+                    lp_address: pool_state.lp_address,
+                };
+
+                updates.push(info);
+            }
+        }
+
+        println!("Got {} pool updates", updates.len());
+
+        match update_pool_state_asset_prices(updates) {
+            Ok(_) => println!("{} Succeeded", pool.address),
+            Err(e) => println!("{} Failed: {}", pool.address, e),
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use carmine_api_core::types::{OracleHealth, OracleIssue, OraclePrice, TokenPair};
+use carmine_api_db::{DbError, Store};
+
+use crate::sources::{now, SourcePrices};
+
+const PAIRS: [TokenPair; 4] = [
+    TokenPair::EthUsdc,
+    TokenPair::BtcUsdc,
+    TokenPair::StrkUsdc,
+    TokenPair::EkuboUsdc,
+];
+
+pub struct OracleThresholds {
+    /// max relative change against the previous stored price
+    pub max_change: f64,
+    /// max relative deviation from the reference (CoinGecko) price
+    pub max_reference_deviation: f64,
+    /// max age of the oracle update at the time of the block
+    pub max_lag_secs: i64,
+    /// max drop in number of aggregated sources against the previous price
+    pub max_sources_drop: i16,
+}
+
+pub fn thresholds(token_pair: &str) -> OracleThresholds {
+    match token_pair {
+        "eth-usdc" | "btc-usdc" => OracleThresholds {
+            max_change: 0.05,
+            max_reference_deviation: 0.02,
+            max_lag_secs: 30 * 60,
+            max_sources_drop: 2,
+        },
+        "strk-usdc" => OracleThresholds {
+            max_change: 0.1,
+            max_reference_deviation: 0.04,
+            max_lag_secs: 30 * 60,
+            max_sources_drop: 2,
+        },
+        // thinner markets
+        _ => OracleThresholds {
+            max_change: 0.15,
+            max_reference_deviation: 0.08,
+            max_lag_secs: 2 * 60 * 60,
+            max_sources_drop: 1,
+        },
+    }
+}
+
+fn to_price(price: &OraclePrice) -> f64 {
+    price.price as f64 / 10f64.powi(price.decimals as i32)
+}
+
+fn relative(a: f64, b: f64) -> f64 {
+    ((a - b) / b).abs()
+}
+
+/// Compares the latest oracle price with the previous one and the reference price
+pub fn check_oracle(
+    latest: &OraclePrice,
+    previous: Option<&OraclePrice>,
+    reference_price: Option<f64>,
+    block_timestamp: i64,
+    thresholds: &OracleThresholds,
+) -> OracleHealth {
+    let price = to_price(latest);
+    let previous_price = previous.map(to_price);
+    let price_change = previous_price
+        .filter(|p| *p != 0.0)
+        .map(|p| relative(price, p));
+    let reference_deviation = reference_price
+        .filter(|p| *p != 0.0)
+        .map(|p| relative(price, p));
+    let lag_secs = block_timestamp - latest.last_updated_timestamp;
+    let previous_num_sources_aggregated = previous.map(|p| p.num_sources_aggregated);
+
+    let mut issues: Vec<(OracleIssue, String)> = vec![];
+
+    if let Some(change) = price_change.filter(|c| *c > thresholds.max_change) {
+        issues.push((
+            OracleIssue::PriceChange,
+            format!("price moved {:.2}% since previous block", change * 100.0),
+        ));
+    }
+    if let Some(deviation) = reference_deviation.filter(|d| *d > thresholds.max_reference_deviation)
+    {
+        issues.push((
+            OracleIssue::ReferenceDeviation,
+            format!("price deviates {:.2}% from reference", deviation * 100.0),
+        ));
+    }
+    if lag_secs > thresholds.max_lag_secs {
+        issues.push((
+            OracleIssue::StaleUpdate,
+            format!("last update {}s before block", lag_secs),
+        ));
+    }
+    if let Some(prev_sources) = previous_num_sources_aggregated {
+        if prev_sources - latest.num_sources_aggregated > thresholds.max_sources_drop {
+            issues.push((
+                OracleIssue::SourcesDropped,
+                format!(
+                    "sources dropped from {} to {}",
+                    prev_sources, latest.num_sources_aggregated
+                ),
+            ));
+        }
+    }
+
+    OracleHealth {
+        token_pair: latest.token_pair.to_string(),
+        oracle_name: latest.oracle_name.to_string(),
+        healthy: issues.is_empty(),
+        issue_kinds: issues.iter().map(|(kind, _)| *kind).collect(),
+        issues: issues.into_iter().map(|(_, message)| message).collect(),
+        block_number: latest.block_number,
+        price,
+        previous_price,
+        price_change,
+        reference_price,
+        reference_deviation,
+        lag_secs,
+        num_sources_aggregated: latest.num_sources_aggregated,
+        previous_num_sources_aggregated,
+    }
+}
+
+/// Timestamp of the block, unknown block is checked against the current time
+fn block_timestamp(store: &dyn Store, block_number: i64) -> i64 {
+    match store.get_block_by_number(block_number) {
+        Ok(Some(block)) => block.timestamp,
+        _ => now(),
+    }
+}
+
+/// Health of the latest stored price of every pair,
+/// `reference` is keyed by token symbol
+pub fn get_oracle_health(store: &dyn Store, reference: &SourcePrices) -> Vec<OracleHealth> {
+    PAIRS
+        .iter()
+        .filter_map(|pair| {
+            let pair_id = pair.id();
+            let latest_prices = match store.get_latest_oracle_prices(&pair_id, 2) {
+                Ok(prices) => prices,
+                Err(e) => {
+                    println!("Failed getting oracle prices for {}: {}", pair_id, e);
+                    return None;
+                }
+            };
+            let latest = latest_prices.first()?;
+            let token = pair_id.split('-').next()?;
+
+            Some(check_oracle(
+                latest,
+                latest_prices.get(1),
+                reference.get(token).map(|quote| quote.price),
+                block_timestamp(store, latest.block_number),
+                &thresholds(&pair_id),
+            ))
+        })
+        .collect()
+}
+
+/// Prices of the pair stored after the last checked one sorted by block number,
+/// with the price before them. Without checked price only the latest is returned.
+fn unchecked_prices(
+    store: &dyn Store,
+    pair_id: &str,
+    last_checked: Option<&OraclePrice>,
+) -> Result<(Option<OraclePrice>, Vec<OraclePrice>), DbError> {
+    match last_checked {
+        Some(last) => {
+            let mut prices: Vec<OraclePrice> = store
+                .get_oracle_prices_from_block(last.block_number + 1)?
+                .into_iter()
+                .filter(|price| price.token_pair == pair_id)
+                .collect();
+            prices.sort_by_key(|price| price.block_number);
+            Ok((Some(last.clone()), prices))
+        }
+        None => {
+            let mut latest = store.get_latest_oracle_prices(pair_id, 2)?;
+            // oldest first
+            latest.reverse();
+            let newest = latest.pop();
+            Ok((latest.pop(), newest.into_iter().collect()))
+        }
+    }
+}
+
+/// Health of every price stored since the last run by pair in block order,
+/// `last_checked` keeps the last checked price of every pair between runs.
+/// The reference price is only compared with the newest price of a pair.
+pub fn check_new_prices(
+    store: &dyn Store,
+    reference: &SourcePrices,
+    last_checked: &mut HashMap<String, OraclePrice>,
+) -> Vec<OracleHealth> {
+    let mut health = vec![];
+
+    for pair in PAIRS.iter() {
+        let pair_id = pair.id();
+        let (mut previous, prices) =
+            match unchecked_prices(store, &pair_id, last_checked.get(&pair_id)) {
+                Ok(prices) => prices,
+                Err(e) => {
+                    println!("Failed getting oracle prices for {}: {}", pair_id, e);
+                    continue;
+                }
+            };
+        let token = pair_id.split('-').next().unwrap_or_default();
+        let newest_block = prices.last().map(|price| price.block_number);
+
+        for price in prices {
+            let reference_price = reference
+                .get(token)
+                .filter(|_| Some(price.block_number) == newest_block)
+                .map(|quote| quote.price);
+            health.push(check_oracle(
+                &price,
+                previous.as_ref(),
+                reference_price,
+                block_timestamp(store, price.block_number),
+                &thresholds(&pair_id),
+            ));
+            previous = Some(price);
+        }
+
+        if let Some(price) = previous {
+            last_checked.insert(pair_id, price);
+        }
+    }
+
+    health
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{check_new_prices, check_oracle, thresholds};
+    use crate::sources::now;
+    use carmine_api_core::{
+        network::Network,
+        types::{OracleHealth, OracleIssue, OraclePrice},
+    };
+    use carmine_api_db::{Fixtures, MemoryStore, Store};
+
+    fn oracle_price(price: i64, num_sources_aggregated: i16) -> OraclePrice {
+        OraclePrice {
+            id: "id".to_string(),
+            token_pair: "eth-usdc".to_string(),
+            price,
+            decimals: 2,
+            last_updated_timestamp: 1000,
+            num_sources_aggregated,
+            oracle_name: "pragma".to_string(),
+            block_number: 10,
+        }
+    }
+
+    #[test]
+    fn healthy() {
+        let latest = oracle_price(300000, 7);
+        let previous = oracle_price(301000, 7);
+        let health = check_oracle(
+            &latest,
+            Some(&previous),
+            Some(3010.0),
+            1100,
+            &thresholds("eth-usdc"),
+        );
+        assert!(health.healthy);
+        assert_eq!(health.price, 3000.0);
+        assert_eq!(health.lag_secs, 100);
+    }
+
+    #[test]
+    fn all_checks_fail() {
+        let latest = oracle_price(300000, 2);
+        let previous = oracle_price(200000, 7);
+        let health = check_oracle(
+            &latest,
+            Some(&previous),
+            Some(2000.0),
+            100000,
+            &thresholds("eth-usdc"),
+        );
+        assert!(!health.healthy);
+        assert_eq!(health.issues.len(), 4);
+        assert_eq!(
+            health.issue_kinds,
+            vec![
+                OracleIssue::PriceChange,
+                OracleIssue::ReferenceDeviation,
+                OracleIssue::StaleUpdate,
+                OracleIssue::SourcesDropped
+            ]
+        );
+    }
+
+    #[test]
+    fn issue_kinds_do_not_change_with_values() {
+        let latest = oracle_price(300000, 7);
+        let stale = |block_timestamp| {
+            check_oracle(
+                &latest,
+                None,
+                None,
+                block_timestamp,
+                &thresholds("eth-usdc"),
+            )
+        };
+        let (first, second) = (stale(10000), stale(10120));
+        assert_ne!(first.issues, second.issues);
+        assert_eq!(first.issue_kinds, vec![OracleIssue::StaleUpdate]);
+        assert_eq!(first.issue_kinds, second.issue_kinds);
+    }
+
+    fn checked_blocks(health: &[OracleHealth]) -> Vec<(i64, bool)> {
+        health.iter().map(|h| (h.block_number, h.healthy)).collect()
+    }
+
+    #[test]
+    fn checks_every_new_price() {
+        let store = MemoryStore::new(Network::Mainnet, Fixtures::default());
+        // blocks are not stored, prices are checked against the current time
+        let store_price = |block_number: i64, price: i64| {
+            let price = OraclePrice {
+                id: format!("pragma-eth-usdc-{}", block_number),
+                last_updated_timestamp: now(),
+                block_number,
+                ..oracle_price(price, 7)
+            };
+            store.create_oracle_price(&price).unwrap();
+        };
+        let mut last_checked = HashMap::new();
+
+        store_price(1, 300000);
+        store_price(2, 300100);
+        let health = check_new_prices(&store, &HashMap::new(), &mut last_checked);
+        // the first run starts from the latest price
+        assert_eq!(checked_blocks(&health), vec![(2, true)]);
+        assert_eq!(health[0].previous_price, Some(3000.0));
+
+        // a spike between two runs is not hidden by the price after it
+        store_price(3, 400000);
+        store_price(4, 300000);
+        let health = check_new_prices(&store, &HashMap::new(), &mut last_checked);
+        assert_eq!(checked_blocks(&health), vec![(3, false), (4, false)]);
+
+        assert!(check_new_prices(&store, &HashMap::new(), &mut last_checked).is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use carmine_api_core::{
+    types::{OracleName, OraclePrice, TokenPair},
+    utils::get_coingecko_prices,
+};
+use carmine_api_rpc_gateway::{carmine_get_block_header, BlockTag};
+use carmine_api_starknet::oracle::Oracle;
+
+/// USD price of a single token as reported by a source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceQuote {
+    pub price: f64,
+    /// unix timestamp of the last price update
+    pub timestamp: i64,
+}
+
+/// Token symbol (lowercase) -> USD quote
+pub type SourcePrices = HashMap<String, SourceQuote>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceError {
+    RequestFailed(String),
+    InvalidData(String),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceError::RequestFailed(msg) => write!(f, "request failed: {}", msg),
+            SourceError::InvalidData(msg) => write!(f, "invalid data: {}", msg),
+        }
+    }
+}
+
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn fetch_prices(&self) -> Result<SourcePrices, SourceError>;
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64
+}
+
+pub struct CoinGeckoSource;
+
+#[async_trait]
+impl PriceSource for CoinGeckoSource {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn fetch_prices(&self) -> Result<SourcePrices, SourceError> {
+        let res = get_coingecko_prices()
+            .await
+            .map_err(|e| SourceError::RequestFailed(e.to_string()))?;
+        // simple price endpoint does not return update time
+        let timestamp = now();
+
+        Ok(HashMap::from([
+            ("eth".to_string(), res.ethereum.usd),
+            ("usdc".to_string(), res.usd_coin.usd),
+            ("strk".to_string(), res.starknet.usd),
+            ("btc".to_string(), res.bitcoin.usd),
+            ("ekubo".to_string(), res.ekubo.usd),
+        ])
+        .into_iter()
+        .map(|(token, price)| (token, SourceQuote { price, timestamp }))
+        .collect())
+    }
+}
+
+/// Pragma spot median read from the oracle contract at the latest block
+pub struct PragmaSource {
+    oracle: Oracle,
+}
+
+impl PragmaSource {
+    pub fn new() -> Self {
+        PragmaSource {
+            oracle: Oracle::new(OracleName::Pragma),
+        }
+    }
+
+    /// Quotes from oracle prices already stored in the DB
+    pub fn quotes_from_oracle_prices(prices: &[OraclePrice]) -> SourcePrices {
+        prices
+            .iter()
+            .filter_map(|p| {
+                // all Pragma pairs are quoted in USD
+                let token = p.token_pair.split('-').next()?;
+                let quote = SourceQuote {
+                    price: p.price as f64 / 10f64.powi(p.decimals as i32),
+                    timestamp: p.last_updated_timestamp,
+                };
+                Some((token.to_string(), quote))
+            })
+            .collect()
+    }
+}
+
+impl Default for PragmaSource {
+    fn default() -> Self {
+        PragmaSource::new()
+    }
+}
+
+#[async_trait]
+impl PriceSource for PragmaSource {
+    fn name(&self) -> &'static str {
+        "pragma"
+    }
+
+    async fn fetch_prices(&self) -> Result<SourcePrices, SourceError> {
+        let block = carmine_get_block_header(BlockTag::Latest)
+            .await
+            .map_err(|e| SourceError::RequestFailed(format!("{:?}", e)))?;
+
+        let pairs = [
+            TokenPair::EthUsdc,
+            TokenPair::BtcUsdc,
+            TokenPair::StrkUsdc,
+            TokenPair::EkuboUsdc,
+        ];
+        let mut prices = vec![];
+
+        for pair in pairs.iter() {
+            match self.oracle.get_spot_median(pair, &block).await {
+                Ok(price) => prices.push(price),
+                Err(e) => println!("Pragma source failed for {}: {}", pair, e),
+            }
+        }
+
+        if prices.is_empty() {
+            return Err(SourceError::InvalidData(
+                "no pair returned a price".to_string(),
+            ));
+        }
+
+        Ok(PragmaSource::quotes_from_oracle_prices(&prices))
+    }
+}
+
+/// Manually configured prices, always fresh
+pub struct StaticSource {
+    prices: HashMap<String, f64>,
+}
+
+impl StaticSource {
+    pub fn new(prices: HashMap<String, f64>) -> Self {
+        StaticSource { prices }
+    }
+
+    pub fn quotes(&self, timestamp: i64) -> SourcePrices {
+        self.prices
+            .iter()
+            .map(|(token, price)| {
+                (
+                    token.to_string(),
+                    SourceQuote {
+                        price: *price,
+                        timestamp,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+impl Default for StaticSource {
+    /// USDC pegged to USD
+    fn default() -> Self {
+        StaticSource::new(HashMap::from([("usdc".to_string(), 1.0)]))
+    }
+}
+
+#[async_trait]
+impl PriceSource for StaticSource {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    async fn fetch_prices(&self) -> Result<SourcePrices, SourceError> {
+        Ok(self.quotes(now()))
+    }
+}
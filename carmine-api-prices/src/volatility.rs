@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use carmine_api_core::{
+    constants::MATH_64,
+    types::{PricePoint, VolatilityPremiumPoint},
+};
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+
+/// Annualized realized volatility from hourly log returns.
+/// Prefix sums make every window lookup constant time.
+pub struct RealizedVolatility {
+    start: i64,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+}
+
+impl RealizedVolatility {
+    /// `series` must be sorted by timestamp ascending
+    pub fn new(series: &[PricePoint]) -> Self {
+        let first = match series.first() {
+            Some(p) => p,
+            None => {
+                return RealizedVolatility {
+                    start: 0,
+                    sum: vec![],
+                    sum_sq: vec![],
+                }
+            }
+        };
+        let last_ts = series.last().map_or(first.timestamp, |p| p.timestamp);
+
+        // first full hour with a price in effect
+        let start = first.timestamp + (HOUR - first.timestamp.rem_euclid(HOUR)) % HOUR;
+
+        let mut sum = vec![0.0];
+        let mut sum_sq = vec![0.0];
+        let mut idx = 0;
+        let mut previous_price: Option<f64> = None;
+        let mut hour = start;
+
+        while hour <= last_ts {
+            // last price at or before the hour
+            while idx + 1 < series.len() && series[idx + 1].timestamp <= hour {
+                idx += 1;
+            }
+            let price = series[idx].price;
+
+            if let Some(prev) = previous_price {
+                let r = match prev > 0.0 && price > 0.0 {
+                    true => (price / prev).ln(),
+                    false => 0.0,
+                };
+                sum.push(sum[sum.len() - 1] + r);
+                sum_sq.push(sum_sq[sum_sq.len() - 1] + r * r);
+            }
+
+            previous_price = Some(price);
+            hour += HOUR;
+        }
+
+        RealizedVolatility { start, sum, sum_sq }
+    }
+
+    /// Volatility in percent over `window` seconds ending at `ts`,
+    /// `None` when the window is not fully covered by prices
+    pub fn at(&self, ts: i64, window: i64) -> Option<f64> {
+        if ts < self.start || self.sum.len() < 2 {
+            return None;
+        }
+        // number of returns up to `ts`, return k ends at start + k * HOUR
+        let end = (((ts - self.start) / HOUR) as usize).min(self.sum.len() - 1);
+        let n = (window / HOUR) as usize;
+        if n < 2 || end < n {
+            return None;
+        }
+        let begin = end - n;
+
+        let s = self.sum[end] - self.sum[begin];
+        let s2 = self.sum_sq[end] - self.sum_sq[begin];
+        let nf = n as f64;
+        let variance = ((s2 - s * s / nf) / (nf - 1.0)).max(0.0);
+
+        Some((variance * HOURS_PER_YEAR).sqrt() * 100.0)
+    }
+}
+
+/// Volatility stored as Cubit Fixed hex, in percent
+fn parse_volatility(hex: &str) -> Option<f64> {
+    let raw = u128::from_str_radix(hex.trim_start_matches("0x"), 16).ok()?;
+    Some(raw as f64 / MATH_64)
+}
+
+/// Hourly AMM volatility of the pool options, one series per maturity
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AmmVolatility {
+    /// last block of the loaded rows, the next load starts after it
+    pub last_block: Option<i64>,
+    /// (block_number, timestamp, volatility) by maturity, the volatility
+    /// is the average of the options of the maturity in the block
+    pub series: BTreeMap<i64, Vec<(i64, i64, f64)>>,
+}
+
+impl AmmVolatility {
+    /// Adds rows of blocks after `last_block`, a point in the same hour
+    /// as the last stored point of the maturity replaces it.
+    /// `rows` are (block_number, timestamp, maturity, volatility) sorted by block number.
+    pub fn extend(&mut self, rows: &[(i64, i64, i64, Option<String>)]) {
+        let mut per_block: BTreeMap<i64, Vec<(i64, i64, f64, usize)>> = BTreeMap::new();
+
+        for (block_number, timestamp, maturity, volatility) in rows {
+            self.last_block = Some(
+                self.last_block
+                    .map_or(*block_number, |b| b.max(*block_number)),
+            );
+            let vol = match volatility.as_deref().and_then(parse_volatility) {
+                Some(v) => v,
+                None => continue,
+            };
+            let blocks = per_block.entry(*maturity).or_default();
+            match blocks.last_mut() {
+                Some(last) if last.0 == *block_number => {
+                    last.2 += vol;
+                    last.3 += 1;
+                }
+                _ => blocks.push((*block_number, *timestamp, vol, 1)),
+            }
+        }
+
+        for (maturity, blocks) in per_block {
+            let hourly = self.series.entry(maturity).or_default();
+            for (block_number, timestamp, total, count) in blocks {
+                let point = (block_number, timestamp, total / count as f64);
+                match hourly.last_mut() {
+                    Some(last) if last.1 / HOUR == timestamp / HOUR => *last = point,
+                    _ => hourly.push(point),
+                }
+            }
+        }
+    }
+}
+
+/// AMM volatility minus realized volatility of the underlying
+pub fn volatility_premium(
+    amm: &[(i64, i64, f64)],
+    realized: &RealizedVolatility,
+) -> Vec<VolatilityPremiumPoint> {
+    amm.iter()
+        .map(|(block_number, timestamp, amm_volatility)| {
+            let realized_1d = realized.at(*timestamp, DAY);
+            let realized_7d = realized.at(*timestamp, 7 * DAY);
+            let realized_30d = realized.at(*timestamp, 30 * DAY);
+
+            VolatilityPremiumPoint {
+                block_number: *block_number,
+                timestamp: *timestamp,
+                amm_volatility: *amm_volatility,
+                realized_volatility_1d: realized_1d,
+                realized_volatility_7d: realized_7d,
+                realized_volatility_30d: realized_30d,
+                premium_1d: realized_1d.map(|r| amm_volatility - r),
+                premium_7d: realized_7d.map(|r| amm_volatility - r),
+                premium_30d: realized_30d.map(|r| amm_volatility - r),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{volatility_premium, AmmVolatility, RealizedVolatility, DAY, HOUR};
+    use carmine_api_core::types::PricePoint;
+
+    #[test]
+    fn constant_price_has_no_volatility() {
+        let series: Vec<PricePoint> = (0..(3 * 24))
+            .map(|i| PricePoint {
+                block_number: i,
+                timestamp: i * HOUR,
+                price: 100.0,
+            })
+            .collect();
+        let realized = RealizedVolatility::new(&series);
+        assert_eq!(realized.at(2 * DAY, DAY), Some(0.0));
+        // window longer than available history
+        assert_eq!(realized.at(2 * DAY, 7 * DAY), None);
+    }
+
+    #[test]
+    fn alternating_returns() {
+        // +-1% every hour
+        let series: Vec<PricePoint> = (0..(2 * 24 + 1))
+            .map(|i| PricePoint {
+                block_number: i,
+                timestamp: i * HOUR,
+                price: if i % 2 == 0 { 100.0 } else { 101.0 },
+            })
+            .collect();
+        let realized = RealizedVolatility::new(&series);
+        let vol = realized.at(2 * DAY, DAY).unwrap();
+        let r = (101.0f64 / 100.0).ln();
+        let expected = (r * r * 24.0 / 23.0 * 24.0 * 365.0).sqrt() * 100.0;
+        assert!((vol - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn amm_points_hourly_per_maturity() {
+        // 80 and 100 in Cubit Fixed
+        let eighty = format!("{:#x}", 80u128 << 64);
+        let hundred = format!("{:#x}", 100u128 << 64);
+        let mut amm = AmmVolatility::default();
+        amm.extend(&[
+            (1, 10, 7, Some(eighty.clone())),
+            (1, 10, 7, Some(hundred.clone())),
+            (1, 10, 14, Some(eighty.clone())),
+            (2, 20, 7, Some(hundred.clone())),
+            (3, HOUR + 5, 7, Some(eighty.clone())),
+            (3, HOUR + 5, 7, None),
+        ]);
+        assert_eq!(amm.last_block, Some(3));
+        assert_eq!(amm.series[&7], vec![(2, 20, 100.0), (3, HOUR + 5, 80.0)]);
+        assert_eq!(amm.series[&14], vec![(1, 10, 80.0)]);
+
+        // later load replaces the last point of the hour
+        amm.extend(&[(4, HOUR + 50, 7, Some(hundred))]);
+        assert_eq!(amm.last_block, Some(4));
+        assert_eq!(amm.series[&7], vec![(2, 20, 100.0), (4, HOUR + 50, 100.0)]);
+
+        let premium = volatility_premium(&amm.series[&7], &RealizedVolatility::new(&[]));
+        assert_eq!(premium.len(), 2);
+        assert_eq!(premium[0].premium_1d, None);
+    }
+}
@@ -3,11 +3,12 @@ use std::env;
 
 use carmine_api_core::{
     network::{amm_address, Network},
+    selectors::selector,
     types::DbBlock,
 };
 use lazy_static::lazy_static;
 use reqwest::RequestBuilder;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 lazy_static! {
     static ref BLAST_API_URL: String =
@@ -70,116 +71,74 @@ pub struct RpcCallBody {
     params: Params,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
 pub enum Entrypoint {
-    #[serde(rename = "0x2b20b26ede4304b68503c401a342731579b75844e5696ee13e6286cd51a9621")]
     GetOptionWithPositionOfUser,
-    #[serde(rename = "0x28465ebd72d95a0985251c1cbd769fd70bd499003d1ed138cc4263dcd4154a8")]
     GetAllNonExpiredOptionsWithPremia,
-    #[serde(rename = "0x3dbcec84ecc7488ae5f857e7a396bd0db953174c6824154aa472341d1fc6f63")]
     GetUserPoolInfos,
-    #[serde(rename = "0x2f38757c6884edf9bd154a4cc0f03e9532c951f013089950d0a03242ca0c266")]
     GetTotalPremia,
-    #[serde(rename = "0x1600ab5a061ebfec75cb9a452efd442a99a0afeaa7c910b4083114f30bff2f1")]
     GetOptionInfoFromAddress,
-    #[serde(rename = "0x14e79ebec158e1f661acf7d89ad12cd6cc4a47a712c3fbd62bc96bf65ca52f0")]
     GetOptionTokenAddress,
-    #[serde(rename = "0x0230b3b6ebadc35ebd0b91e93d39824daff6574cbe99bb7882037547cbb75197")]
     GetAllOptions,
-    #[serde(rename = "0x3a59b17481476f4a9926cf55852dcc59e941e04e7c7afc16d1c887637e6b349")]
     GetAllLPTokenAddresses,
-    #[serde(rename = "0xf58610cee3c804f0e87861ce266e465952f846d7f11a298b4a37f548065494")]
     GetPoolLockedCapital,
-    #[serde(rename = "0x27e73afcf5eeea68f07ecec320a8a6ef66a0fec2a6555c98d7906efd26bafb9")]
     GetUnlockedCapital,
-    #[serde(rename = "0x2b70e1b30215b8a9fdff94bce47077d43936e89d1180300a63f6b176b7d699e")]
     GetLpoolBalance,
-    #[serde(rename = "0x399adda47235e1d39043a5931bead6042f3990866c6bd3091f582014f8a4f90")]
     GetValueOfPoolPosition,
-    #[serde(rename = "0x68bb6b599048b94cdd7832f2ebbbda4b596b150896fc09bd70f88e2c488595")]
     GetUnderlyingForLptoken,
-    #[serde(rename = "0xe8cc8c9fca554ee3ae877935823ca461ba94b34a427e3272fd465e0790e1af")]
     GetPoolVolatilityAuto, // legacy AMM only
-    #[serde(rename = "0x2902df4b2064da30c68f1bfad76271da9c6b10a3cfc41396ae75eef960bfcb")]
     GetOptionPosition,
-    #[serde(rename = "0x3bceef179de4e1b5164a50dcfc0f32930c38e7d39d10cac000e9650c0cefd30")]
     GetOptionVolatility,
-    #[serde(untagged)]
+    /// selector as hex
     Literal(String),
 }
 
-impl fmt::Display for Entrypoint {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Entrypoint {
+    /// Name of the function in the contract
+    pub fn name(&self) -> &str {
+        match self {
+            Entrypoint::GetOptionWithPositionOfUser => "get_option_with_position_of_user",
+            Entrypoint::GetAllNonExpiredOptionsWithPremia => {
+                "get_all_non_expired_options_with_premia"
+            }
+            Entrypoint::GetUserPoolInfos => "get_user_pool_infos",
+            Entrypoint::GetTotalPremia => "get_total_premia",
+            Entrypoint::GetOptionInfoFromAddress => "get_option_info_from_addresses",
+            Entrypoint::GetOptionTokenAddress => "get_option_token_address",
+            Entrypoint::GetAllOptions => "get_all_options",
+            Entrypoint::GetAllLPTokenAddresses => "get_all_lptoken_addresses",
+            Entrypoint::GetPoolLockedCapital => "get_pool_locked_capital",
+            Entrypoint::GetUnlockedCapital => "get_unlocked_capital",
+            Entrypoint::GetLpoolBalance => "get_lpool_balance",
+            Entrypoint::GetValueOfPoolPosition => "get_value_of_pool_position",
+            Entrypoint::GetUnderlyingForLptoken => "get_underlying_for_lptokens",
+            Entrypoint::GetPoolVolatilityAuto => "get_pool_volatility_auto",
+            Entrypoint::GetOptionPosition => "get_option_position",
+            Entrypoint::GetOptionVolatility => "get_option_volatility",
+            Entrypoint::Literal(s) => s.as_str(),
+        }
+    }
+
+    pub fn selector(&self) -> String {
         match self {
-            Entrypoint::GetOptionWithPositionOfUser => write!(
-                f,
-                "0x2b20b26ede4304b68503c401a342731579b75844e5696ee13e6286cd51a9621"
-            ),
-            Entrypoint::GetAllNonExpiredOptionsWithPremia => write!(
-                f,
-                "0x28465ebd72d95a0985251c1cbd769fd70bd499003d1ed138cc4263dcd4154a8"
-            ),
-            Entrypoint::GetUserPoolInfos => write!(
-                f,
-                "0x3dbcec84ecc7488ae5f857e7a396bd0db953174c6824154aa472341d1fc6f63"
-            ),
-            Entrypoint::GetTotalPremia => write!(
-                f,
-                "0x2f38757c6884edf9bd154a4cc0f03e9532c951f013089950d0a03242ca0c266"
-            ),
-            Entrypoint::GetOptionInfoFromAddress => write!(
-                f,
-                "0x1600ab5a061ebfec75cb9a452efd442a99a0afeaa7c910b4083114f30bff2f1"
-            ),
-            Entrypoint::GetOptionTokenAddress => write!(
-                f,
-                "0x14e79ebec158e1f661acf7d89ad12cd6cc4a47a712c3fbd62bc96bf65ca52f0"
-            ),
-            Entrypoint::GetAllOptions => write!(
-                f,
-                "0x0230b3b6ebadc35ebd0b91e93d39824daff6574cbe99bb7882037547cbb75197"
-            ),
-            Entrypoint::GetAllLPTokenAddresses => write!(
-                f,
-                "0x3a59b17481476f4a9926cf55852dcc59e941e04e7c7afc16d1c887637e6b349"
-            ),
-            Entrypoint::GetPoolLockedCapital => write!(
-                f,
-                "0xf58610cee3c804f0e87861ce266e465952f846d7f11a298b4a37f548065494"
-            ),
-            Entrypoint::GetUnlockedCapital => write!(
-                f,
-                "0x27e73afcf5eeea68f07ecec320a8a6ef66a0fec2a6555c98d7906efd26bafb9"
-            ),
-            Entrypoint::GetLpoolBalance => write!(
-                f,
-                "0x2b70e1b30215b8a9fdff94bce47077d43936e89d1180300a63f6b176b7d699e"
-            ),
-            Entrypoint::GetValueOfPoolPosition => write!(
-                f,
-                "0x399adda47235e1d39043a5931bead6042f3990866c6bd3091f582014f8a4f90"
-            ),
-            Entrypoint::GetUnderlyingForLptoken => write!(
-                f,
-                "0x68bb6b599048b94cdd7832f2ebbbda4b596b150896fc09bd70f88e2c488595"
-            ),
-            Entrypoint::GetPoolVolatilityAuto => write!(
-                f,
-                "0xe8cc8c9fca554ee3ae877935823ca461ba94b34a427e3272fd465e0790e1af"
-            ),
-            Entrypoint::GetOptionPosition => write!(
-                f,
-                "0x2902df4b2064da30c68f1bfad76271da9c6b10a3cfc41396ae75eef960bfcb"
-            ),
-            Entrypoint::GetOptionVolatility => write!(
-                f,
-                "0x3bceef179de4e1b5164a50dcfc0f32930c38e7d39d10cac000e9650c0cefd30"
-            ),
-            Entrypoint::Literal(s) => write!(f, "{}", s.clone()),
+            Entrypoint::Literal(s) => s.clone(),
+            entrypoint => selector(entrypoint.name()),
         }
     }
 }
 
+impl Serialize for Entrypoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.selector())
+    }
+}
+
+impl fmt::Display for Entrypoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.selector())
+    }
+}
+
 pub enum Contract {
     AMM,
     AMMTestnet,
@@ -210,6 +169,8 @@ struct RpcErrorResponse {
 #[derive(Debug, Deserialize)]
 pub enum RpcError {
     ContractNotFound,
+    /// returned by older nodes, newer ones fail with ContractError
+    EntrypointNotFound,
     ContractError(String),
     BlockNotFound,
     Other(String),
@@ -407,6 +368,7 @@ pub async fn rpc_call(
     if let Some(e) = rpc_response.error {
         return match e.code {
             20 => Err(RpcError::ContractNotFound),
+            21 => Err(RpcError::EntrypointNotFound),
             24 => Err(RpcError::BlockNotFound),
             40 => Err(RpcError::ContractError(if let Some(data) = e.data {
                 data.revert_error.unwrap_or("".to_string())
@@ -1,30 +1,88 @@
 use std::time::{Duration, Instant};
 
-use carmine_api_core::{
-    network::Network,
-    types::{DbBlock, OracleName, TokenPair},
-};
-use carmine_api_db::{
-    create_batch_of_pool_states, create_batch_of_volatilities, create_block, create_oracle_price,
-    get_last_block_in_db, get_pool_state_block_holes,
-};
+use carmine_api_core::types::{BlockState, DbBlock, IngestionStream, OraclePrice};
+use carmine_api_db::{DbError, Store};
 use carmine_api_rpc_gateway::BlockTag;
+use futures::future::join_all;
 use tokio::{join, time::sleep};
 
-use crate::{carmine::Carmine, oracle::Oracle};
+use crate::carmine::Carmine;
+use crate::oracle::{OracleConfig, OracleSource};
 
-pub struct AmmStateObserver {
-    network: Network,
+pub struct AmmStateObserver<S: Store> {
+    store: S,
     carmine: Carmine,
-    pragma: Oracle,
+    oracle_sources: Vec<Box<dyn OracleSource>>,
 }
 
-impl AmmStateObserver {
-    pub fn new() -> Self {
+impl<S: Store> AmmStateObserver<S> {
+    pub fn new(store: S) -> Self {
+        let oracle_sources = OracleConfig::load()
+            .and_then(|config| config.sources())
+            .expect("Failed loading oracle config");
+
         AmmStateObserver {
-            carmine: Carmine::new(Network::Mainnet),
-            network: Network::Mainnet,
-            pragma: Oracle::new(OracleName::Pragma),
+            carmine: Carmine::new(store.network()),
+            store,
+            oracle_sources,
+        }
+    }
+
+    /// Last block stored by the observer, blocks table is used
+    /// until the AMM state checkpoint exists
+    fn last_processed_block(&self) -> Result<i64, DbError> {
+        match self.store.get_checkpoint(&IngestionStream::AmmState)? {
+            Some(checkpoint) => Ok(checkpoint.last_block),
+            None => Ok(self.store.get_last_block_in_db()?.block_number),
+        }
+    }
+
+    fn mark_failed(&self, block_number: i64) {
+        if let Err(e) = self
+            .store
+            .mark_checkpoint_failed(&IngestionStream::AmmState, block_number - 1)
+        {
+            println!("Failed marking AMM state checkpoint as failed: {}", e);
+        }
+    }
+
+    /// Prices of every configured pair from every oracle source,
+    /// fails if any price of a required source is missing
+    async fn get_oracle_prices(&self, block: &DbBlock) -> Result<Vec<OraclePrice>, ()> {
+        let requests: Vec<(&dyn OracleSource, String)> = self
+            .oracle_sources
+            .iter()
+            .flat_map(|source| {
+                source
+                    .pairs()
+                    .into_iter()
+                    .map(move |pair| (source.as_ref(), pair))
+            })
+            .collect();
+
+        let results = join_all(
+            requests
+                .iter()
+                .map(|(source, pair)| source.get_price(pair, block)),
+        )
+        .await;
+
+        let mut prices = vec![];
+        let mut missing_required = false;
+
+        for ((source, pair), result) in requests.iter().zip(results) {
+            match result {
+                Ok(price) => prices.push(price),
+                Err(e) => {
+                    println!("Error in {} {} price: {}", source.name(), pair, e);
+                    missing_required |= source.required();
+                }
+            }
+        }
+
+        match missing_required {
+            true => Err(()),
+            false => Ok(prices),
         }
     }
 
@@ -46,20 +104,10 @@ impl AmmStateObserver {
             timestamp: i64::try_from(strk_block.timestamp).unwrap(),
         };
 
-        let (
-            options_volatility_result,
-            amm_state_result,
-            pragma_eth_usdc_result,
-            pragma_btc_usdc_result,
-            pragma_strk_usdc_result,
-            pragma_ekubo_usdc_result,
-        ) = join!(
+        let (options_volatility_result, amm_state_result, oracle_prices_result) = join!(
             self.carmine.get_all_options_volatility(&block),
             self.carmine.get_amm_state(&block),
-            self.pragma.get_spot_median(&TokenPair::EthUsdc, &block),
-            self.pragma.get_spot_median(&TokenPair::BtcUsdc, &block),
-            self.pragma.get_spot_median(&TokenPair::StrkUsdc, &block),
-            self.pragma.get_spot_median(&TokenPair::EkuboUsdc, &block),
+            self.get_oracle_prices(&block),
         );
 
         if let Err(err) = &options_volatility_result {
@@ -68,43 +116,24 @@ impl AmmStateObserver {
         if let Err(err) = &amm_state_result {
             println!("Error in amm_state_result {:?}", err);
         }
-        if let Err(err) = &pragma_eth_usdc_result {
-            println!("Error in pragma_eth_usdc_result {:?}", err);
-        }
-        if let Err(err) = &pragma_btc_usdc_result {
-            println!("Error in pragma_btc_usdc_result {:?}", err);
-        }
-        if let Err(err) = &pragma_strk_usdc_result {
-            println!("Error in pragma_strk_usdc_result {:?}", err);
-        }
-        if let Err(err) = &pragma_ekubo_usdc_result {
-            println!("Error in pragma_ekubo_usdc_result {:?}", err);
-        }
 
         match (
             options_volatility_result,
             amm_state_result,
-            pragma_eth_usdc_result,
-            pragma_btc_usdc_result,
-            pragma_strk_usdc_result,
-            pragma_ekubo_usdc_result,
+            oracle_prices_result,
         ) {
-            (
-                Ok(options_volatility),
-                Ok(amm_state),
-                Ok(pragma_eth_usdc),
-                Ok(pragma_btc_usdc),
-                Ok(pragma_strk_usdc),
-                Ok(pragma_ekubo_usdc),
-            ) => {
+            (Ok(options_volatility), Ok(amm_state), Ok(oracle_prices)) => {
                 // got everything - store it to the database
-                create_block(&block, &self.network);
-                create_batch_of_volatilities(&options_volatility, &self.network);
-                create_batch_of_pool_states(&amm_state, &self.network);
-                create_oracle_price(&pragma_eth_usdc, &self.network);
-                create_oracle_price(&pragma_btc_usdc, &self.network);
-                create_oracle_price(&pragma_strk_usdc, &self.network);
-                create_oracle_price(&pragma_ekubo_usdc, &self.network);
+                let state = BlockState {
+                    block,
+                    volatilities: options_volatility,
+                    pool_states: amm_state,
+                    oracle_prices,
+                };
+                if let Err(e) = self.store.store_block_state(&state) {
+                    println!("Failed storing block {} state: {}", block_number, e);
+                    return Err(());
+                }
                 println!(
                     "Fetched and stored block {} state in {:.2?}",
                     block_number,
@@ -117,7 +146,16 @@ impl AmmStateObserver {
     }
 
     pub async fn update_state(&self, n: i64) {
-        let last_block_db = get_last_block_in_db(&self.network);
+        let last_block_db = match self.last_processed_block() {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                println!(
+                    "Failed getting last block from DB, skipping this update cycle.\n{}",
+                    e
+                );
+                return;
+            }
+        };
         let last_block_starknet_result = self.carmine.get_latest_block().await;
 
         let last_block_starknet: DbBlock = match last_block_starknet_result {
@@ -131,7 +169,7 @@ impl AmmStateObserver {
             }
         };
 
-        let start = last_block_db.block_number + 1;
+        let start = last_block_db + 1;
         let finish = i64::try_from(last_block_starknet.block_number).unwrap();
 
         let rounded_start = start - start % n;
@@ -145,7 +183,16 @@ impl AmmStateObserver {
 
     pub async fn update_state_latest_block(&self) {
         let now = Instant::now();
-        let last_block_db = get_last_block_in_db(&self.network);
+        let last_block_db = match self.last_processed_block() {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                println!(
+                    "Failed getting last block from DB, skipping this update cycle.\n{}",
+                    e
+                );
+                return;
+            }
+        };
         let last_block_starknet_result = self.carmine.get_latest_block().await;
 
         let last_block_starknet: DbBlock = match last_block_starknet_result {
@@ -159,7 +206,7 @@ impl AmmStateObserver {
             }
         };
 
-        if last_block_db.block_number >= last_block_starknet.block_number {
+        if last_block_db >= last_block_starknet.block_number {
             println!(
                 "Last block DB: {:#?}, last block starknet: {:#?} - nothing to do",
                 last_block_db, last_block_starknet,
@@ -184,6 +231,7 @@ impl AmmStateObserver {
                     last_block_starknet.block_number,
                     now.elapsed()
                 );
+                self.mark_failed(last_block_starknet.block_number);
             }
         }
     }
@@ -204,7 +252,16 @@ impl AmmStateObserver {
         let start = 751467; // up to here holes are plugged
         let finish = i64::try_from(last_block_starknet.block_number).unwrap();
 
-        let holes = get_pool_state_block_holes(start, finish, &Network::Mainnet);
+        let holes = match self.store.get_pool_state_block_holes(start, finish) {
+            Ok(holes) => holes,
+            Err(e) => {
+                println!(
+                    "Failed getting state holes, skipping this update cycle.\n{}",
+                    e
+                );
+                return;
+            }
+        };
         for block_number in holes {
             let now = Instant::now();
             match self.update_single_block(block_number).await {
@@ -242,6 +299,7 @@ impl AmmStateObserver {
                         n,
                         now.elapsed()
                     );
+                    self.mark_failed(n);
 
                     // error is most likely rate limit
                     // wait 3s to be able to fetch again
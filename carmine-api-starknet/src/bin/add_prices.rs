@@ -2,7 +2,7 @@ use carmine_api_core::{
     network::Network,
     types::{DbBlock, OracleName, TokenPair},
 };
-use carmine_api_db::{create_oracle_price, get_block_by_number};
+use carmine_api_db::{create_oracle_price, get_block_by_number, PgStore};
 use carmine_api_starknet::{amm_state::AmmStateObserver, oracle::Oracle};
 use dotenvy::dotenv;
 use futures::future::try_join_all;
@@ -12,7 +12,7 @@ use tokio::time::sleep;
 async fn add_price_for_block(pragma: &Oracle, block: &DbBlock) -> Result<(), ()> {
     let pragma_eth_usdc_result = pragma.get_spot_median(&TokenPair::EthUsdc, block).await;
     if let Ok(pragma_eth_usdc) = pragma_eth_usdc_result {
-        create_oracle_price(&pragma_eth_usdc, &Network::Mainnet);
+        create_oracle_price(&pragma_eth_usdc, &Network::Mainnet).map_err(|_| ())?;
         println!("updated prices for block {}", block.block_number);
         return Ok(());
     } else {
@@ -26,7 +26,7 @@ async fn main() {
     env::set_var("ENVIRONMENT", "docker");
     env::set_var("DB_IP", "34.76.28.66");
 
-    let state_updater = AmmStateObserver::new();
+    let state_updater = AmmStateObserver::new(PgStore::new(Network::Mainnet));
     let pragma = Oracle::new(OracleName::Pragma);
 
     let mut current_block_number = 39319;
@@ -39,8 +39,8 @@ async fn main() {
         for n in (current_block_number - increment + 1)..=current_block_number {
             let block_res = get_block_by_number(n, &Network::Mainnet);
             match block_res {
-                Some(block) => blocks.push(block),
-                None => missing_block_numbers.push(n),
+                Ok(Some(block)) => blocks.push(block),
+                _ => missing_block_numbers.push(n),
             }
         }
 
@@ -1,6 +1,6 @@
 use carmine_api_core::network::{Network, Protocol};
 
-use carmine_api_db::create_batch_of_starkscan_events;
+use carmine_api_db::store_fetched_events;
 use carmine_api_starknet::starkscan::get_block_range_events;
 use dotenvy::dotenv;
 
@@ -17,19 +17,22 @@ async fn main() {
     let max = 894449;
 
     let mut events = vec![];
+    let mut unparsed = vec![];
 
     for protocol in protocols {
         current = start;
 
         while current < max {
-            let mut new_events =
+            let mut fetched =
                 get_block_range_events(protocol, network, current - 1, current + increment + 1)
-                    .await;
+                    .await
+                    .expect("Failed fetching events");
             println!("{} fetched {} - {}", protocol, current, current + increment);
             current = current + increment;
-            events.append(&mut new_events);
+            events.append(&mut fetched.settled);
+            unparsed.append(&mut fetched.unparsed);
         }
     }
 
-    create_batch_of_starkscan_events(&events, network);
+    store_fetched_events(&events, &vec![], &unparsed, network).expect("Failed storing events");
 }
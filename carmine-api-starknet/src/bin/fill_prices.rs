@@ -2,10 +2,9 @@ use carmine_api_core::{
     network::Network,
     types::{DbBlock, OracleName, TokenPair},
 };
-use carmine_api_db::{create_oracle_price, get_price_block_numbers};
+use carmine_api_db::{create_oracle_price, get_missing_price_block_numbers};
 use carmine_api_starknet::oracle::Oracle;
 use dotenvy::dotenv;
-use std::collections::HashSet;
 use tokio::task;
 
 async fn add_price_for_block(
@@ -20,30 +19,14 @@ async fn add_price_for_block(
         Err(e) => return Err(e),
     };
 
-    create_oracle_price(&pragma_price, &Network::Mainnet);
+    create_oracle_price(&pragma_price, &Network::Mainnet).map_err(|e| e.to_string())?;
     println!("updated prices for block {}", block.block_number);
     Ok(())
 }
 
-pub fn get_missing_block_numbers(
-    token_pair: &TokenPair,
-    min_block: i64,
-    max_block: i64,
-) -> Vec<i64> {
-    let existing_block_numbers = get_price_block_numbers(token_pair, min_block, max_block);
-    let existing_block_numbers_set: HashSet<_> = existing_block_numbers.into_iter().collect();
-    let all_block_numbers: Vec<i64> = (min_block..=max_block).collect();
-    let mut missing_block_numbers: Vec<i64> = all_block_numbers
-        .into_iter()
-        .filter(|block_number| !existing_block_numbers_set.contains(block_number))
-        .collect();
-
-    missing_block_numbers.sort();
-    missing_block_numbers
-}
-
 async fn fill_prices(token_pair: &TokenPair, start_block: i64, end_block: i64) {
-    let blocks = get_missing_block_numbers(token_pair, start_block, end_block);
+    let blocks = get_missing_price_block_numbers(token_pair, start_block, end_block)
+        .expect("Failed getting missing price block numbers");
     println!(
         "Updating {} blocks between {} - {}",
         blocks.len(),
@@ -14,7 +14,8 @@ async fn main() {
     };
 
     let non_expired_options =
-        get_non_expired_options(&Network::Mainnet, block.timestamp - TWO_DAYS_SECS);
+        get_non_expired_options(&Network::Mainnet, block.timestamp - TWO_DAYS_SECS)
+            .expect("Failed getting options");
 
     println!("ALL {}", &non_expired_options.len(),);
 
@@ -12,8 +12,8 @@ fn validate_db(n: &Network) {
     println!(
         "Network {}\nevents: {}\noptions: {}",
         n,
-        get_events(n).len(),
-        get_options(n).len(),
+        get_events(n).expect("Failed getting events").len(),
+        get_options(n).expect("Failed getting options").len(),
     );
 }
 
@@ -23,7 +23,8 @@ async fn update_token_pair(token_pair: TokenPair) {
 
     println!("Starting from {}", first_block_number);
 
-    let blocks = get_blocks_greater_than(first_block_number, &network);
+    let blocks =
+        get_blocks_greater_than(first_block_number, &network).expect("Failed getting blocks");
 
     for block in blocks {
         // if block.block_number % 100 != 0 {
@@ -31,7 +32,14 @@ async fn update_token_pair(token_pair: TokenPair) {
         // }
         println!("Updating {}", &block.block_number);
         match pragma.get_spot_median(&token_pair, &block).await {
-            Ok(data) => create_oracle_price(&data, &network),
+            Ok(data) => {
+                if let Err(e) = create_oracle_price(&data, &network) {
+                    println!(
+                        "{} {} Failed storing price: {}",
+                        &block.block_number, &token_pair, e
+                    );
+                }
+            }
             Err(e) => println!(
                 "{} {} Failed, error: {:#?}",
                 &block.block_number, &token_pair, e
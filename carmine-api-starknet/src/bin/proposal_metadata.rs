@@ -0,0 +1,26 @@
+use carmine_api_core::network::Network;
+use carmine_api_db::set_proposal_metadata;
+use dotenvy::dotenv;
+use std::env;
+
+/// Sets title and description of a proposal:
+/// `proposal_metadata <prop_id> <title> [description]`
+fn main() {
+    dotenv().ok();
+
+    let args: Vec<String> = env::args().collect();
+    let (prop_id, title) = match (args.get(1).map(|id| id.parse::<i64>()), args.get(2)) {
+        (Some(Ok(prop_id)), Some(title)) => (prop_id, title),
+        _ => {
+            println!("Usage: proposal_metadata <prop_id> <title> [description]");
+            return;
+        }
+    };
+    let description = args.get(3).map(String::as_str);
+
+    match set_proposal_metadata(prop_id, title, description, &Network::Mainnet) {
+        Ok(0) => println!("Proposal {} is not indexed yet", prop_id),
+        Ok(_) => println!("Updated proposal {}", prop_id),
+        Err(e) => println!("Failed updating proposal {}: {}", prop_id, e),
+    }
+}
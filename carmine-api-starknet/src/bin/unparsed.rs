@@ -0,0 +1,39 @@
+use carmine_api_core::network::Network;
+use carmine_api_db::get_unparsed_selector_counts;
+use carmine_api_starknet::lending::reprocess_unparsed_lending_events;
+use carmine_api_starknet::starkscan::reprocess_unparsed_events;
+use dotenvy::dotenv;
+use std::env;
+
+/// Lists events that failed to decode by contract and selector,
+/// `unparsed reprocess` decodes them again after a decoder was added
+fn main() {
+    dotenv().ok();
+
+    let network = &Network::Mainnet;
+
+    if env::args().nth(1).as_deref() == Some("reprocess") {
+        let reprocessed = reprocess_unparsed_events(network).expect("Failed reprocessing events");
+        let lending =
+            reprocess_unparsed_lending_events(network).expect("Failed reprocessing lending events");
+        println!("Reprocessed {} events", reprocessed + lending);
+        return;
+    }
+
+    let counts = get_unparsed_selector_counts(network).expect("Failed getting unparsed events");
+
+    if counts.is_empty() {
+        println!("No unparsed events");
+        return;
+    }
+
+    for count in counts {
+        println!(
+            "{} {} {} {}",
+            count.from_address,
+            count.selector.unwrap_or_else(|| "-".to_string()),
+            count.reason,
+            count.count
+        );
+    }
+}
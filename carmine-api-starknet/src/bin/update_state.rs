@@ -1,3 +1,5 @@
+use carmine_api_core::network::Network;
+use carmine_api_db::PgStore;
 use carmine_api_starknet::amm_state::AmmStateObserver;
 use dotenvy::dotenv;
 
@@ -5,7 +7,7 @@ use dotenvy::dotenv;
 async fn main() {
     dotenv().ok();
 
-    let so = AmmStateObserver::new();
+    let so = AmmStateObserver::new(PgStore::new(Network::Mainnet));
 
     let mut n = 879200;
     let max = 879253;
@@ -0,0 +1,486 @@
+use std::fmt;
+
+pub use starknet::core::types::FieldElement;
+
+/// Decoding of call results or arguments failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// result ended before the value was complete
+    MissingFelts(String),
+    /// felt does not fit into the Rust type
+    OutOfRange(String, String),
+    /// enum variant index unknown to the Rust type
+    UnknownVariant(String, u64),
+    /// result has more felts than the decoded value
+    TrailingFelts(usize),
+    /// string is not a hex or decimal felt
+    InvalidFelt(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::MissingFelts(cairo_type) => {
+                write!(f, "Not enough felts to decode {}", cairo_type)
+            }
+            DecodeError::OutOfRange(value, cairo_type) => {
+                write!(f, "{} does not fit into {}", value, cairo_type)
+            }
+            DecodeError::UnknownVariant(cairo_type, index) => {
+                write!(f, "Unknown variant {} of {}", index, cairo_type)
+            }
+            DecodeError::TrailingFelts(count) => {
+                write!(f, "{} felts left after decoding the result", count)
+            }
+            DecodeError::InvalidFelt(value) => write!(f, "Invalid felt \"{}\"", value),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Reads felts of a call result in order
+pub struct FeltReader<'a> {
+    felts: &'a [FieldElement],
+    position: usize,
+}
+
+impl<'a> FeltReader<'a> {
+    pub fn new(felts: &'a [FieldElement]) -> Self {
+        FeltReader { felts, position: 0 }
+    }
+
+    pub fn next(&mut self, cairo_type: &str) -> Result<FieldElement, DecodeError> {
+        let felt = self
+            .felts
+            .get(self.position)
+            .ok_or_else(|| DecodeError::MissingFelts(cairo_type.to_string()))?;
+        self.position += 1;
+        Ok(*felt)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.felts.len() - self.position
+    }
+}
+
+/// Rust counterpart of a Cairo type, serialized the way Cairo serializes calldata
+pub trait CairoSerde: Sized {
+    /// Type as written in Cairo 1 ABIs, eg. "core::integer::u128"
+    fn cairo_type() -> String;
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>);
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError>;
+}
+
+/// Object safe part of CairoSerde, lets calls take arguments of different types
+pub trait CairoArg {
+    fn arg_type(&self) -> String;
+
+    fn encode_arg(&self, calldata: &mut Vec<FieldElement>);
+}
+
+impl<T: CairoSerde> CairoArg for T {
+    fn arg_type(&self) -> String {
+        T::cairo_type()
+    }
+
+    fn encode_arg(&self, calldata: &mut Vec<FieldElement>) {
+        self.encode(calldata)
+    }
+}
+
+/// Decodes the whole result, leftover felts mean the type does not match the ABI
+pub fn decode_all<T: CairoSerde>(felts: &[FieldElement]) -> Result<T, DecodeError> {
+    let mut reader = FeltReader::new(felts);
+    let value = T::decode(&mut reader)?;
+    match reader.remaining() {
+        0 => Ok(value),
+        count => Err(DecodeError::TrailingFelts(count)),
+    }
+}
+
+/// Parses felt strings returned by the RPC node, hex or decimal
+pub fn parse_felts(data: &[String]) -> Result<Vec<FieldElement>, DecodeError> {
+    data.iter().map(|value| parse_felt(value)).collect()
+}
+
+/// Fields of an event in the order they are declared,
+/// keys after the selector come before the data
+pub fn event_felts(keys: &[String], data: &[String]) -> Result<Vec<FieldElement>, DecodeError> {
+    let keys = keys.get(1..).unwrap_or_default();
+    parse_felts(&[keys, data].concat())
+}
+
+pub fn parse_felt(value: &str) -> Result<FieldElement, DecodeError> {
+    let parsed = if value.starts_with("0x") {
+        FieldElement::from_hex_be(value)
+    } else {
+        FieldElement::from_dec_str(value)
+    };
+    parsed.map_err(|_| DecodeError::InvalidFelt(value.to_string()))
+}
+
+pub fn felt_to_hex(felt: &FieldElement) -> String {
+    format!("{:#x}", felt)
+}
+
+fn felt_from_u128(value: u128) -> FieldElement {
+    let mut bytes = [0u8; 32];
+    bytes[16..].copy_from_slice(&value.to_be_bytes());
+    FieldElement::from_bytes_be(&bytes).expect("u128 always fits into a felt")
+}
+
+fn felt_to_u128(felt: &FieldElement, cairo_type: &str) -> Result<u128, DecodeError> {
+    let bytes = felt.to_bytes_be();
+    if bytes[..16].iter().any(|byte| *byte != 0) {
+        return Err(DecodeError::OutOfRange(
+            felt_to_hex(felt),
+            cairo_type.to_string(),
+        ));
+    }
+    let mut low = [0u8; 16];
+    low.copy_from_slice(&bytes[16..]);
+    Ok(u128::from_be_bytes(low))
+}
+
+impl CairoSerde for FieldElement {
+    fn cairo_type() -> String {
+        "core::felt252".to_string()
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        calldata.push(*self);
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        reader.next("core::felt252")
+    }
+}
+
+macro_rules! cairo_uint {
+    ($rust_type:ty, $cairo_type:expr) => {
+        impl CairoSerde for $rust_type {
+            fn cairo_type() -> String {
+                $cairo_type.to_string()
+            }
+
+            fn encode(&self, calldata: &mut Vec<FieldElement>) {
+                calldata.push(felt_from_u128(*self as u128));
+            }
+
+            fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+                let felt = reader.next($cairo_type)?;
+                let value = felt_to_u128(&felt, $cairo_type)?;
+                <$rust_type>::try_from(value).map_err(|_| {
+                    DecodeError::OutOfRange(felt_to_hex(&felt), $cairo_type.to_string())
+                })
+            }
+        }
+    };
+}
+
+cairo_uint!(u8, "core::integer::u8");
+cairo_uint!(u16, "core::integer::u16");
+cairo_uint!(u32, "core::integer::u32");
+cairo_uint!(u64, "core::integer::u64");
+cairo_uint!(u128, "core::integer::u128");
+
+impl CairoSerde for bool {
+    fn cairo_type() -> String {
+        "core::bool".to_string()
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        calldata.push(felt_from_u128(*self as u128));
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            index => Err(DecodeError::UnknownVariant(
+                "core::bool".to_string(),
+                index as u64,
+            )),
+        }
+    }
+}
+
+impl CairoSerde for () {
+    fn cairo_type() -> String {
+        "()".to_string()
+    }
+
+    fn encode(&self, _calldata: &mut Vec<FieldElement>) {}
+
+    fn decode(_reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        Ok(())
+    }
+}
+
+/// Serialized as length followed by the elements
+impl<T: CairoSerde> CairoSerde for Vec<T> {
+    fn cairo_type() -> String {
+        format!("core::array::Array::<{}>", T::cairo_type())
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        calldata.push(felt_from_u128(self.len() as u128));
+        for element in self {
+            element.encode(calldata);
+        }
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        let length = u32::decode(reader)? as usize;
+        // every element takes at least one felt
+        if length > reader.remaining() {
+            return Err(DecodeError::MissingFelts(Self::cairo_type()));
+        }
+        (0..length).map(|_| T::decode(reader)).collect()
+    }
+}
+
+/// Cairo Option is an enum, Some is variant 0 and None variant 1
+impl<T: CairoSerde> CairoSerde for Option<T> {
+    fn cairo_type() -> String {
+        format!("core::option::Option::<{}>", T::cairo_type())
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        match self {
+            Some(value) => {
+                calldata.push(FieldElement::ZERO);
+                value.encode(calldata);
+            }
+            None => calldata.push(FieldElement::ONE),
+        }
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        match u64::decode(reader)? {
+            0 => Ok(Some(T::decode(reader)?)),
+            1 => Ok(None),
+            index => Err(DecodeError::UnknownVariant(Self::cairo_type(), index)),
+        }
+    }
+}
+
+impl<A: CairoSerde, B: CairoSerde> CairoSerde for (A, B) {
+    fn cairo_type() -> String {
+        format!("({}, {})", A::cairo_type(), B::cairo_type())
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        self.0.encode(calldata);
+        self.1.encode(calldata);
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+/// Implements CairoSerde for a struct, fields are serialized in the given order
+#[macro_export]
+macro_rules! cairo_struct {
+    ($name:ident, $cairo_type:expr, { $($field:ident),* $(,)? }) => {
+        impl $crate::cairo_serde::CairoSerde for $name {
+            fn cairo_type() -> String {
+                $cairo_type.to_string()
+            }
+
+            fn encode(&self, calldata: &mut Vec<$crate::cairo_serde::FieldElement>) {
+                $($crate::cairo_serde::CairoSerde::encode(&self.$field, calldata);)*
+            }
+
+            fn decode(
+                reader: &mut $crate::cairo_serde::FeltReader,
+            ) -> Result<Self, $crate::cairo_serde::DecodeError> {
+                Ok($name {
+                    $($field: $crate::cairo_serde::CairoSerde::decode(reader)?,)*
+                })
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContractAddress(pub FieldElement);
+
+impl ContractAddress {
+    pub fn from_hex(address: &str) -> Result<Self, DecodeError> {
+        parse_felt(address).map(ContractAddress)
+    }
+}
+
+impl fmt::Display for ContractAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl CairoSerde for ContractAddress {
+    fn cairo_type() -> String {
+        "core::starknet::contract_address::ContractAddress".to_string()
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        calldata.push(self.0);
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        reader.next(&Self::cairo_type()).map(ContractAddress)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct U256 {
+    pub low: u128,
+    pub high: u128,
+}
+
+impl U256 {
+    pub fn from_u128(value: u128) -> Self {
+        U256 {
+            low: value,
+            high: 0,
+        }
+    }
+
+    /// Hex without leading zeroes, same as felts returned by the RPC node
+    pub fn to_hex(&self) -> String {
+        match self.high {
+            0 => format!("{:#x}", self.low),
+            high => format!("{:#x}{:032x}", high, self.low),
+        }
+    }
+
+    /// Raw integer value, precision is lost above 2**53
+    pub fn to_f64(&self) -> f64 {
+        self.high as f64 * 2f64.powi(128) + self.low as f64
+    }
+}
+
+cairo_struct!(U256, "core::integer::u256", { low, high });
+
+/// Cubit 64.64 fixed point number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fixed {
+    pub mag: u128,
+    pub sign: bool,
+}
+
+impl Fixed {
+    const ONE: f64 = 18446744073709551616.0; // 2**64
+
+    pub fn from_mag(mag: u128) -> Self {
+        Fixed { mag, sign: false }
+    }
+
+    /// Parses the magnitude stored in the DB as hex or decimal string
+    pub fn from_mag_str(mag: &str) -> Result<Self, DecodeError> {
+        let felt = parse_felt(mag)?;
+        Ok(Fixed::from_mag(felt_to_u128(&felt, "core::integer::u128")?))
+    }
+
+    pub fn mag_hex(&self) -> String {
+        format!("{:#x}", self.mag)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let value = self.mag as f64 / Fixed::ONE;
+        match self.sign {
+            true => -value,
+            false => value,
+        }
+    }
+}
+
+cairo_struct!(Fixed, "cubit::f128::types::fixed::Fixed", { mag, sign });
+
+#[cfg(test)]
+mod tests {
+    use starknet::core::types::FieldElement;
+
+    use super::{decode_all, parse_felts, CairoSerde, ContractAddress, DecodeError, Fixed, U256};
+
+    fn felts(values: &[&str]) -> Vec<FieldElement> {
+        let strings: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        parse_felts(&strings).unwrap()
+    }
+
+    #[test]
+    fn encodes_calldata() {
+        let mut calldata = vec![];
+        ContractAddress::from_hex("0x123")
+            .unwrap()
+            .encode(&mut calldata);
+        1u8.encode(&mut calldata);
+        Fixed::from_mag(0x1a).encode(&mut calldata);
+        U256::from_u128(5).encode(&mut calldata);
+        vec![7u64, 8].encode(&mut calldata);
+
+        assert_eq!(
+            calldata,
+            felts(&["0x123", "0x1", "0x1a", "0x0", "0x5", "0x0", "0x2", "0x7", "0x8"])
+        );
+    }
+
+    #[test]
+    fn decodes_results() {
+        let decoded: Vec<(U256, Option<u64>)> = decode_all(&felts(&[
+            "0x2", "0x1", "0x0", "0x0", "0x5", "0x2", "0x1", "0x1",
+        ]))
+        .unwrap();
+        assert_eq!(
+            decoded,
+            vec![
+                (U256::from_u128(1), Some(5)),
+                (U256 { low: 2, high: 1 }, None)
+            ]
+        );
+        assert_eq!(
+            U256 { low: 2, high: 1 }.to_hex(),
+            "0x100000000000000000000000000000002"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_results() {
+        assert_eq!(
+            decode_all::<U256>(&felts(&["0x1"])),
+            Err(DecodeError::MissingFelts("core::integer::u128".to_string()))
+        );
+        assert_eq!(
+            decode_all::<u8>(&felts(&["0x100"])),
+            Err(DecodeError::OutOfRange(
+                "0x100".to_string(),
+                "core::integer::u8".to_string()
+            ))
+        );
+        assert_eq!(
+            decode_all::<Fixed>(&felts(&["0x1", "0x0", "0x0"])),
+            Err(DecodeError::TrailingFelts(1))
+        );
+        assert_eq!(
+            decode_all::<bool>(&felts(&["0x2"])),
+            Err(DecodeError::UnknownVariant("core::bool".to_string(), 2))
+        );
+    }
+
+    #[test]
+    fn fixed_to_f64() {
+        let half = Fixed {
+            mag: 1 << 63,
+            sign: true,
+        };
+        assert_eq!(half.to_f64(), -0.5);
+        assert_eq!(
+            Fixed::from_mag_str("0x8000000000000000"),
+            Ok(Fixed::from_mag(1 << 63))
+        );
+    }
+}
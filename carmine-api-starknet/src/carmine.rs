@@ -13,34 +13,72 @@ use carmine_api_core::types::{DbBlock, IOption, OptionVolatility, PoolState};
 use carmine_api_db::{
     create_batch_of_options, get_non_expired_options, get_option_with_address, get_pools,
 };
-use carmine_api_rpc_gateway::{
-    call, carmine_get_block_header, is_contract_deployed, BlockTag, Entrypoint, RpcError,
-};
+use carmine_api_rpc_gateway::{carmine_get_block_header, is_contract_deployed, BlockTag, RpcError};
 use futures::future::join_all;
 use futures::FutureExt;
-use starknet::core::types::FieldElement;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
-use tokio::try_join;
+use tokio::{join, try_join};
+
+use crate::cairo_serde::{felt_to_hex, CairoSerde, ContractAddress, Fixed, U256};
+use crate::cairo_struct;
+use crate::contract::{address_arg, ContractAbi, ContractClient, ContractError};
 
 const TWO_DAYS_SECS: i64 = 172800;
 
-#[allow(dead_code)]
-fn to_hex(v: FieldElement) -> String {
-    format!("{:#x}", v)
+const TEN_POW_18: u128 = 1_000_000_000_000_000_000;
+
+/// carmine_protocol::types::option_::Option_
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmmOption {
+    pub option_side: u8,
+    pub maturity: u64,
+    pub strike_price: Fixed,
+    pub quote_token_address: ContractAddress,
+    pub base_token_address: ContractAddress,
+    pub option_type: u8,
 }
 
-const TEN_POW_18: &'static str = "0xde0b6b3a7640000";
+cairo_struct!(AmmOption, "carmine_protocol::types::option_::Option_", {
+    option_side,
+    maturity,
+    strike_price,
+    quote_token_address,
+    base_token_address,
+    option_type,
+});
+
+impl AmmOption {
+    pub fn into_ioption(self, option_address: &str, lp_address: &str) -> IOption {
+        IOption {
+            option_side: self.option_side as i16,
+            option_type: self.option_type as i16,
+            strike_price: self.strike_price.mag_hex(),
+            maturity: self.maturity as i64,
+            quote_token_address: self.quote_token_address.to_string(),
+            base_token_address: self.base_token_address.to_string(),
+            option_address: option_address.to_lowercase(),
+            lp_address: lp_address.to_string(),
+        }
+    }
+}
 
-#[allow(dead_code)]
-struct FunctionDescriptor<'a> {
-    name: &'a str,
-    selector: FieldElement,
+/// carmine_protocol::types::option_::OptionWithPremia
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionWithPremia {
+    pub option: AmmOption,
+    pub premia: Fixed,
 }
 
+cairo_struct!(OptionWithPremia, "carmine_protocol::types::option_::OptionWithPremia", {
+    option,
+    premia,
+});
+
 pub struct Carmine {
     pools: Vec<Pool>,
     network: Network,
+    amm: ContractClient,
 }
 
 pub async fn filter_deployed_options(opts: Vec<IOption>, block_number: i64) -> Vec<IOption> {
@@ -78,165 +116,62 @@ impl Carmine {
         Carmine {
             network,
             pools: get_all_pools(&network),
+            amm: ContractClient::new(amm_address(&network), ContractAbi::Amm, network),
         }
     }
 
-    pub async fn amm_call(
+    pub async fn get_all_non_expired_options_with_premia(
         &self,
-        entry_point_selector: String,
-        calldata: Vec<String>,
-        block: BlockTag,
-    ) -> Result<Vec<String>, RpcError> {
-        let contract_address = amm_address(&self.network).to_string();
-        call(
-            contract_address,
-            entry_point_selector,
-            calldata,
-            block,
-            &self.network,
-        )
-        .await
-    }
-
-    pub async fn get_all_non_expired_options_with_premia(&self) -> Result<Vec<String>, RpcError> {
-        let mut futures = vec![];
-
-        match self.network {
-            Network::Mainnet => {
-                // ETH from AMM
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_ETH_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_ETH_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_ETH_STRK_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_ETH_STRK_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_STRK_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_STRK_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_EKUBO_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_EKUBO_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-
-                // BTC from AuxContract
-                futures.push(call(
-                    MAINNET_AUXILIARY_CONTRACT.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_BTC_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    MAINNET_AUXILIARY_CONTRACT.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![MAINNET_BTC_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-            }
-            Network::Testnet => {
+    ) -> Result<Vec<String>, ContractError> {
+        let sources: Vec<(&str, &Pool)> = match self.network {
+            Network::Mainnet => vec![
                 // ETH from AMM
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_ETH_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_ETH_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_ETH_STRK_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_ETH_STRK_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_STRK_USDC_CALL.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
-                futures.push(call(
-                    TESTNET_CONTRACT_ADDRESS.to_string(), // aux contract to bypass BTC option problem
-                    format!("{}", Entrypoint::GetAllNonExpiredOptionsWithPremia),
-                    vec![TESTNET_STRK_USDC_PUT.address.to_string()],
-                    BlockTag::Latest,
-                    &self.network,
-                ));
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_ETH_USDC_CALL),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_ETH_USDC_PUT),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_ETH_STRK_CALL),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_ETH_STRK_PUT),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_STRK_USDC_CALL),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_STRK_USDC_PUT),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_EKUBO_USDC_CALL),
+                (MAINNET_CONTRACT_ADDRESS, &MAINNET_EKUBO_USDC_PUT),
+                // BTC from AuxContract to bypass BTC option problem
+                (MAINNET_AUXILIARY_CONTRACT, &MAINNET_BTC_USDC_CALL),
+                (MAINNET_AUXILIARY_CONTRACT, &MAINNET_BTC_USDC_PUT),
+            ],
+            Network::Testnet => vec![
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_ETH_USDC_CALL),
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_ETH_USDC_PUT),
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_ETH_STRK_CALL),
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_ETH_STRK_PUT),
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_STRK_USDC_CALL),
+                (TESTNET_CONTRACT_ADDRESS, &TESTNET_STRK_USDC_PUT),
                 // TODO: add BTC pools
-            }
-        }
+            ],
+        };
+
+        let futures = sources.into_iter().map(|(contract, pool)| async move {
+            let client = ContractClient::new(contract, ContractAbi::Amm, self.network);
+            let lp_address = address_arg(pool.address)?;
+            client
+                .call::<Vec<OptionWithPremia>>(
+                    "get_all_non_expired_options_with_premia",
+                    &[&lp_address],
+                    BlockTag::Latest,
+                )
+                .await
+        });
 
         let call_results = join_all(futures).await;
 
         let mut option_data = vec![];
 
         for result in call_results {
-            match result {
-                Err(e) => return Err(e),
-                Ok(mut v) => {
-                    v.remove(0); // first element specifies length - remove it
-                    option_data.extend(v);
-                }
+            // frontend expects options with premia serialized without the array length
+            let mut felts = vec![];
+            for option in result? {
+                option.encode(&mut felts);
             }
+            option_data.extend(felts.iter().map(felt_to_hex));
         }
 
         Ok(option_data)
@@ -246,6 +181,11 @@ impl Carmine {
         &self,
         option_address: &str,
     ) -> Result<IOption, &str> {
+        let option_token_address = match address_arg(option_address) {
+            Ok(address) => address,
+            Err(_) => return Err("Invalid option address"),
+        };
+
         let pool_addresses: Vec<String> = self
             .pools
             .iter()
@@ -255,37 +195,22 @@ impl Carmine {
         let mut futures = vec![];
 
         for address in &pool_addresses {
-            futures.push(self.amm_call(
-                format!("{}", Entrypoint::GetOptionInfoFromAddress),
-                vec![address.to_string(), option_address.to_string()],
-                BlockTag::Latest,
-            ))
+            futures.push(async move {
+                self.amm
+                    .call::<AmmOption>(
+                        "get_option_info_from_addresses",
+                        &[&address_arg(address)?, &option_token_address],
+                        BlockTag::Latest,
+                    )
+                    .await
+            })
         }
 
         let contract_results = join_all(futures).await;
 
-        for (i, result) in contract_results.iter().enumerate() {
-            if let Ok(data) = result {
-                assert_eq!(data.len(), 6, "Got wrong size Option result");
-
-                let option_side = data[0].parse::<i16>().expect("Failed to parse side");
-                let option_type = data[5].parse::<i16>().expect("Failed to parse type");
-                let maturity = data[1].parse::<i64>().expect("Failed to parse maturity");
-                let strike_price = data[2].to_owned();
-                let quote_token_address = data[3].to_owned();
-                let base_token_address = data[4].to_owned();
-                let lp_address = pool_addresses[i].to_owned();
-
-                return Ok(IOption {
-                    option_side,
-                    option_type,
-                    strike_price,
-                    maturity,
-                    quote_token_address,
-                    base_token_address,
-                    option_address: String::from(option_address),
-                    lp_address,
-                });
+        for (i, result) in contract_results.into_iter().enumerate() {
+            if let Ok(option) = result {
+                return Ok(option.into_ioption(option_address, &pool_addresses[i]));
             }
         }
 
@@ -294,104 +219,76 @@ impl Carmine {
 
     pub async fn get_option_token_address(
         &self,
-        lptoken_address: &String,
-        option_side: String,
-        maturity: String,
-        strike_price: String,
-    ) -> Result<String, &str> {
-        let calldata = vec![
-            lptoken_address.to_owned(),
-            option_side,
-            maturity,
-            strike_price,
-            "0".to_string(), // zero for strike_price: cubit::f128::types::fixed::Fixed
-        ];
-
-        match self
-            .amm_call(
-                format!("{}", Entrypoint::GetOptionTokenAddress),
-                calldata,
+        lptoken_address: &str,
+        option_side: u8,
+        maturity: u64,
+        strike_price: Fixed,
+    ) -> Result<String, ContractError> {
+        let option_address = self
+            .amm
+            .call::<ContractAddress>(
+                "get_option_token_address",
+                &[
+                    &address_arg(lptoken_address)?,
+                    &option_side,
+                    &maturity,
+                    &strike_price,
+                ],
                 BlockTag::Latest,
             )
-            .await
-        {
-            Ok(data) => Ok(data[0].to_owned()),
-            Err(e) => {
-                println!("Failed \"get_option_token_address\" \n{:#?}", e);
-                Err("Failed \"get_option_token_address\"")
-            }
-        }
+            .await?;
+
+        Ok(option_address.to_string())
     }
 
     async fn get_options_with_addresses_from_single_pool(&self, pool_address: &String) {
         println!("LP_ADDRESS: {}", pool_address);
 
+        let lp_address = match address_arg(pool_address) {
+            Ok(address) => address,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+
         let contract_result = self
-            .amm_call(
-                format!("{}", Entrypoint::GetAllOptions),
-                vec![pool_address.to_owned()],
-                BlockTag::Latest,
-            )
+            .amm
+            .call::<Vec<AmmOption>>("get_all_options", &[&lp_address], BlockTag::Latest)
             .await;
 
-        let data: Vec<String> = match contract_result {
-            Err(provider_error) => {
-                println!("{:?}", provider_error);
+        let amm_options = match contract_result {
+            Err(e) => {
+                println!("{}", e);
                 return;
             }
-            Ok(v) => {
-                let mut res = v;
-                // first element is length of result array - remove it
-                res.remove(0);
-
-                res
-            }
+            Ok(v) => v,
         };
 
-        let option_length = 7;
-
-        // each option has 6 fields
-        let chunks = data.chunks(option_length);
-
         let mut options: Vec<IOption> = vec![];
 
         let mut cache_hit = 0;
         let mut fetched = 0;
 
-        for option_vec in chunks {
-            if option_vec.len() != option_length {
-                println!("Wrong option_vec size!");
-                continue;
-            }
-
-            let option_side =
-                i16::from_str_radix(&option_vec[0][2..], 16).expect("Failed to parse side");
-            let maturity =
-                i64::from_str_radix(&option_vec[1][2..], 16).expect("Failed to parse maturity");
-            let strike_price = option_vec[2].to_owned();
-            let lp_address = pool_address.to_owned();
+        for amm_option in amm_options {
+            let strike_price = amm_option.strike_price.mag_hex();
 
             let db_hit = get_option_with_address(
                 &self.network,
-                option_side,
-                maturity,
+                amm_option.option_side as i16,
+                amm_option.maturity as i64,
                 &strike_price,
-                &lp_address,
+                pool_address,
             );
 
-            if let Some(option_with_address) = db_hit {
+            // on DB error the option address is fetched again
+            if let Ok(Some(option_with_address)) = db_hit {
                 options.push(option_with_address);
                 cache_hit += 1;
                 continue;
             }
 
-            let (type_index, base_index, quote_index) = (6, 5, 4);
-
             // this part only runs if option not already in the DB
-            let option_type = i16::from_str_radix(&option_vec[type_index][2..], 16)
-                .expect("Failed to parse type");
-            let quote_token_address = option_vec[quote_index].to_owned();
-            let base_token_address = option_vec[base_index].to_owned();
 
             // avoid running into rate limit starknet error
             sleep(Duration::from_secs(2)).await;
@@ -399,9 +296,9 @@ impl Carmine {
             let option_address_result = self
                 .get_option_token_address(
                     pool_address,
-                    option_vec[0].to_owned(),
-                    option_vec[1].to_owned(),
-                    option_vec[2].to_owned(),
+                    amm_option.option_side,
+                    amm_option.maturity,
+                    amm_option.strike_price,
                 )
                 .await;
 
@@ -410,21 +307,11 @@ impl Carmine {
                     println!("Failed to get option address\n{}", e);
                     continue;
                 }
-                Ok(v) => v.to_lowercase(),
+                Ok(v) => v,
             };
 
-            let option = IOption {
-                option_side,
-                maturity,
-                strike_price,
-                quote_token_address,
-                base_token_address,
-                option_type,
-                option_address,
-                lp_address,
-            };
             fetched += 1;
-            options.push(option);
+            options.push(amm_option.into_ioption(&option_address, pool_address));
         }
 
         println!(
@@ -432,7 +319,9 @@ impl Carmine {
             cache_hit, fetched
         );
 
-        create_batch_of_options(&options, &self.network);
+        if let Err(e) = create_batch_of_options(&options, &self.network) {
+            println!("Failed storing options: {}", e);
+        }
     }
 
     pub async fn get_options_with_addresses(&self) {
@@ -448,53 +337,44 @@ impl Carmine {
 
     pub async fn get_all_lptoken_addresses(&self) -> Result<Vec<String>, ()> {
         let call_result = self
-            .amm_call(
-                format!("{}", Entrypoint::GetAllLPTokenAddresses),
-                vec![],
-                BlockTag::Latest,
-            )
+            .amm
+            .call::<Vec<ContractAddress>>("get_all_lptoken_addresses", &[], BlockTag::Latest)
             .await;
 
-        let mut data = match call_result {
-            Ok(v) => v,
-            _ => return Err(()),
-        };
-
-        if data.len() < 2 {
-            return Err(());
-        } else {
-            // remove length
-            data.remove(0);
+        match call_result {
+            Ok(addresses) if !addresses.is_empty() => Ok(addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect()),
+            _ => Err(()),
         }
-
-        Ok(data)
     }
 
-    pub async fn get_pool_single_value(
+    /// Calls pool getter returning u256 as hex
+    async fn get_pool_u256_value(
         &self,
         block_number: i64,
-        pool: String,
-        entry_point: Entrypoint,
-    ) -> Result<String, RpcError> {
-        match self
-            .amm_call(
-                format!("{}", entry_point),
-                vec![pool],
+        pool: &str,
+        function: &str,
+    ) -> Result<String, ContractError> {
+        let value = self
+            .amm
+            .call::<U256>(
+                function,
+                &[&address_arg(pool)?],
                 BlockTag::Number(block_number),
             )
-            .await
-        {
-            Ok(v) => Ok(v[0].to_owned()),
-            Err(e) => Err(e),
-        }
+            .await?;
+
+        Ok(value.to_hex())
     }
 
     pub async fn get_pool_locked_capital(
         &self,
         block_number: i64,
         pool: String,
-    ) -> Result<String, RpcError> {
-        self.get_pool_single_value(block_number, pool, Entrypoint::GetPoolLockedCapital)
+    ) -> Result<String, ContractError> {
+        self.get_pool_u256_value(block_number, &pool, "get_pool_locked_capital")
             .await
     }
 
@@ -502,8 +382,8 @@ impl Carmine {
         &self,
         block_number: i64,
         pool: String,
-    ) -> Result<String, RpcError> {
-        self.get_pool_single_value(block_number, pool, Entrypoint::GetUnlockedCapital)
+    ) -> Result<String, ContractError> {
+        self.get_pool_u256_value(block_number, &pool, "get_unlocked_capital")
             .await
     }
 
@@ -511,8 +391,8 @@ impl Carmine {
         &self,
         block_number: i64,
         pool: String,
-    ) -> Result<String, RpcError> {
-        self.get_pool_single_value(block_number, pool, Entrypoint::GetLpoolBalance)
+    ) -> Result<String, ContractError> {
+        self.get_pool_u256_value(block_number, &pool, "get_lpool_balance")
             .await
     }
 
@@ -520,13 +400,19 @@ impl Carmine {
         &self,
         block_number: i64,
         pool: String,
-    ) -> Result<Option<String>, RpcError> {
-        match self
-            .get_pool_single_value(block_number, pool, Entrypoint::GetValueOfPoolPosition)
-            .await
-        {
-            Ok(v) => Ok(Some(v)),
-            Err(e) if matches!(e, RpcError::ContractError(_)) => Ok(None),
+    ) -> Result<Option<String>, ContractError> {
+        let result = self
+            .amm
+            .call::<Fixed>(
+                "get_value_of_pool_position",
+                &[&address_arg(&pool)?],
+                BlockTag::Number(block_number),
+            )
+            .await;
+
+        match result {
+            Ok(v) => Ok(Some(v.mag_hex())),
+            Err(e) if e.is_revert() => Ok(None),
             Err(e) => Err(e),
         }
     }
@@ -535,17 +421,19 @@ impl Carmine {
         &self,
         block_number: i64,
         pool: String,
-    ) -> Result<Option<String>, RpcError> {
-        match self
-            .amm_call(
-                format!("{}", Entrypoint::GetUnderlyingForLptoken),
-                vec![pool, TEN_POW_18.to_owned(), "0".to_owned()],
+    ) -> Result<Option<String>, ContractError> {
+        let result = self
+            .amm
+            .call::<U256>(
+                "get_underlying_for_lptokens",
+                &[&address_arg(&pool)?, &U256::from_u128(TEN_POW_18)],
                 BlockTag::Number(block_number),
             )
-            .await
-        {
-            Ok(v) => Ok(Some(v[0].to_owned())),
-            Err(e) if matches!(e, RpcError::ContractError(_)) => Ok(None),
+            .await;
+
+        match result {
+            Ok(v) => Ok(Some(v.to_hex())),
+            Err(e) if e.is_revert() => Ok(None),
             Err(e) => Err(e),
         }
     }
@@ -563,7 +451,7 @@ impl Carmine {
             Option<String>,
             String,
         ),
-        RpcError,
+        ContractError,
     > {
         let now = Instant::now();
         let res = try_join!(
@@ -602,10 +490,14 @@ impl Carmine {
     }
 
     pub async fn get_amm_state(&self, block: &DbBlock) -> Result<Vec<PoolState>, ()> {
-        let pool_addresses: Vec<String> = get_pools(&self.network)
-            .iter()
-            .map(|p| p.lp_address.to_owned())
-            .collect();
+        let pools = match get_pools(&self.network) {
+            Ok(pools) => pools,
+            Err(e) => {
+                println!("Failed getting pools: {}", e);
+                return Err(());
+            }
+        };
+        let pool_addresses: Vec<String> = pools.iter().map(|p| p.lp_address.to_owned()).collect();
 
         let mut futures = vec![];
 
@@ -662,7 +554,13 @@ impl Carmine {
         let now = Instant::now();
 
         let non_expired_options =
-            get_non_expired_options(&self.network, block.timestamp - TWO_DAYS_SECS);
+            match get_non_expired_options(&self.network, block.timestamp - TWO_DAYS_SECS) {
+                Ok(options) => options,
+                Err(e) => {
+                    println!("Failed getting non expired options: {}", e);
+                    return Err(());
+                }
+            };
 
         println!("non_expired_options length {}", &non_expired_options.len());
 
@@ -713,41 +611,36 @@ impl Carmine {
         opt: IOption,
         block_number: i64,
     ) -> (Option<String>, Option<String>, String) {
-        let lp_address = opt.lp_address;
-        let maturity = format!("{:#x}", opt.maturity);
-        let strike = opt.strike_price;
-        let side = opt.option_side.to_string();
-
-        let volatility_future = Box::pin(self.amm_call(
-            format!("{}", Entrypoint::GetOptionVolatility),
-            vec![
-                lp_address.to_owned(),
-                maturity.to_owned(),
-                strike.to_owned(),
-                "0".to_string(), // zero for strike_price: cubit::f128::types::fixed::Fixed
-            ],
-            BlockTag::Number(block_number),
-        ));
-
-        let position_future = Box::pin(self.amm_call(
-            format!("{}", Entrypoint::GetOptionPosition),
-            vec![lp_address, side, maturity, strike, "0".to_string()], // zero for strike_price: cubit::f128::types::fixed::Fixed
-            BlockTag::Number(block_number),
-        ));
-
-        let mut results = join_all(vec![volatility_future, position_future]).await;
-
-        let volatility_result = results.remove(0);
-        let position_result = results.remove(0);
-
-        let volatility: Option<String> = match volatility_result {
-            Ok(v) => Some(v[0].to_owned()),
-            Err(_) => None,
-        };
-        let position: Option<String> = match position_result {
-            Ok(v) => Some(v[0].to_owned()),
-            Err(_) => None,
+        let (lp_address, strike_price) = match (
+            address_arg(&opt.lp_address),
+            Fixed::from_mag_str(&opt.strike_price),
+        ) {
+            (Ok(lp_address), Ok(strike_price)) => (lp_address, strike_price),
+            _ => {
+                println!("Invalid option {}", opt.option_address);
+                return (None, None, opt.option_address);
+            }
         };
+        let side = opt.option_side as u8;
+        let maturity = opt.maturity as u64;
+
+        let block = BlockTag::Number(block_number);
+
+        let (volatility_result, position_result) = join!(
+            self.amm.call::<Fixed>(
+                "get_option_volatility",
+                &[&lp_address, &maturity, &strike_price],
+                block,
+            ),
+            self.amm.call::<u128>(
+                "get_option_position",
+                &[&lp_address, &side, &maturity, &strike_price],
+                block,
+            )
+        );
+
+        let volatility = volatility_result.ok().map(|v| v.mag_hex());
+        let position = position_result.ok().map(|v| format!("{:#x}", v));
 
         (volatility, position, opt.option_address)
     }
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use carmine_api_core::network::Network;
+use carmine_api_core::selectors::{selector, SelectorRegistry};
+use carmine_api_rpc_gateway::{call, BlockTag, RpcError};
+use serde_json::Value;
+
+use crate::cairo_serde::{
+    decode_all, felt_to_hex, parse_felts, CairoArg, CairoSerde, ContractAddress, DecodeError,
+};
+
+/// ABIs of the contracts the API calls, trimmed to the functions and events it uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractAbi {
+    Amm,
+    Pail,
+    Governance,
+    Pragma,
+    PragmaSummaryStats,
+    Erc20,
+}
+
+impl ContractAbi {
+    fn json(&self) -> &'static str {
+        match self {
+            ContractAbi::Amm => include_str!("../abi/amm.json"),
+            ContractAbi::Pail => include_str!("../abi/pail.json"),
+            ContractAbi::Governance => include_str!("../abi/governance.json"),
+            ContractAbi::Pragma => include_str!("../abi/pragma.json"),
+            ContractAbi::PragmaSummaryStats => include_str!("../abi/pragma_summary_stats.json"),
+            ContractAbi::Erc20 => include_str!("../abi/erc20.json"),
+        }
+    }
+
+    /// Parsed ABI, the bundled JSON is checked by tests so parsing cannot fail
+    pub fn abi(&self) -> &'static Abi {
+        static AMM: OnceLock<Abi> = OnceLock::new();
+        static PAIL: OnceLock<Abi> = OnceLock::new();
+        static GOVERNANCE: OnceLock<Abi> = OnceLock::new();
+        static PRAGMA: OnceLock<Abi> = OnceLock::new();
+        static PRAGMA_SUMMARY_STATS: OnceLock<Abi> = OnceLock::new();
+        static ERC20: OnceLock<Abi> = OnceLock::new();
+
+        let cell = match self {
+            ContractAbi::Amm => &AMM,
+            ContractAbi::Pail => &PAIL,
+            ContractAbi::Governance => &GOVERNANCE,
+            ContractAbi::Pragma => &PRAGMA,
+            ContractAbi::PragmaSummaryStats => &PRAGMA_SUMMARY_STATS,
+            ContractAbi::Erc20 => &ERC20,
+        };
+
+        cell.get_or_init(|| Abi::parse(self.json()).expect("Failed parsing bundled ABI"))
+    }
+
+    /// Function and event names of the ABI by selector
+    pub fn selector_registry(&self) -> SelectorRegistry {
+        let mut registry = SelectorRegistry::new();
+        registry
+            .register_abi(self.json())
+            .expect("Failed parsing bundled ABI");
+        registry
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbiParam {
+    pub name: String,
+    pub cairo_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbiFunction {
+    pub name: String,
+    pub selector: String,
+    pub inputs: Vec<AbiParam>,
+    /// Cairo 1 functions return at most one value, None for no return value
+    pub output: Option<String>,
+}
+
+/// Functions of a Cairo 1 ABI
+#[derive(Debug, Clone, Default)]
+pub struct Abi {
+    functions: HashMap<String, AbiFunction>,
+}
+
+impl Abi {
+    pub fn parse(abi_json: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(abi_json).map_err(|e| format!("Invalid ABI JSON: {}", e))?;
+        let entries = value
+            .as_array()
+            .ok_or_else(|| "ABI must be an array".to_string())?;
+
+        let mut abi = Abi::default();
+        abi.collect_functions(entries)?;
+        Ok(abi)
+    }
+
+    fn collect_functions(&mut self, entries: &[Value]) -> Result<(), String> {
+        for entry in entries {
+            match entry.get("type").and_then(Value::as_str) {
+                Some("function") => {
+                    let function = parse_function(entry)?;
+                    self.functions.insert(function.name.clone(), function);
+                }
+                Some("interface") => {
+                    if let Some(items) = entry.get("items").and_then(Value::as_array) {
+                        self.collect_functions(items)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn function(&self, name: &str) -> Option<&AbiFunction> {
+        self.functions.get(name)
+    }
+}
+
+fn parse_function(entry: &Value) -> Result<AbiFunction, String> {
+    let name = entry
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "ABI function without name".to_string())?;
+
+    let params = |key: &str| -> Vec<&Value> {
+        entry
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|values| values.iter().collect())
+            .unwrap_or_default()
+    };
+
+    let inputs = params("inputs")
+        .into_iter()
+        .map(|input| {
+            match (
+                input.get("name").and_then(Value::as_str),
+                input.get("type").and_then(Value::as_str),
+            ) {
+                (Some(input_name), Some(cairo_type)) => Ok(AbiParam {
+                    name: input_name.to_string(),
+                    cairo_type: cairo_type.to_string(),
+                }),
+                _ => Err(format!("Invalid input of ABI function {}", name)),
+            }
+        })
+        .collect::<Result<Vec<AbiParam>, String>>()?;
+
+    let output = params("outputs")
+        .first()
+        .and_then(|output| output.get("type"))
+        .and_then(Value::as_str)
+        .map(|cairo_type| cairo_type.to_string());
+
+    Ok(AbiFunction {
+        name: name.to_string(),
+        selector: selector(name),
+        inputs,
+        output,
+    })
+}
+
+#[derive(Debug)]
+pub enum ContractError {
+    /// function is not in the ABI of the contract
+    UnknownFunction(String),
+    /// number of arguments does not match the ABI
+    ArgumentCount(String, usize, usize),
+    /// argument has a different type than the ABI input
+    ArgumentType(String, String, String, String),
+    /// requested result type does not match the ABI output
+    ResultType(String, String, String),
+    /// argument could not be converted to its Cairo type
+    InvalidArgument(String),
+    /// the call itself failed
+    Rpc(RpcError),
+    /// result could not be decoded into the requested type
+    Decode(String, DecodeError),
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContractError::UnknownFunction(name) => write!(f, "Function {} not in ABI", name),
+            ContractError::ArgumentCount(function, expected, got) => {
+                write!(f, "{} takes {} arguments, got {}", function, expected, got)
+            }
+            ContractError::ArgumentType(function, argument, expected, got) => write!(
+                f,
+                "{} argument {} is {}, got {}",
+                function, argument, expected, got
+            ),
+            ContractError::ResultType(function, expected, got) => {
+                write!(f, "{} returns {}, requested {}", function, expected, got)
+            }
+            ContractError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            ContractError::Rpc(e) => write!(f, "Call failed: {:?}", e),
+            ContractError::Decode(function, e) => {
+                write!(f, "Failed decoding result of {}: {}", function, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContractError {}
+
+impl ContractError {
+    /// Contract call reverted, eg. pool has no position yet
+    pub fn is_revert(&self) -> bool {
+        matches!(self, ContractError::Rpc(RpcError::ContractError(_)))
+    }
+}
+
+/// Parses contract address argument given as hex string
+pub fn address_arg(address: &str) -> Result<ContractAddress, ContractError> {
+    ContractAddress::from_hex(address).map_err(|e| ContractError::InvalidArgument(e.to_string()))
+}
+
+/// Calls functions of a deployed contract, arguments and results
+/// are checked against the ABI and (de)serialized from Rust types
+#[derive(Debug, Clone)]
+pub struct ContractClient {
+    address: String,
+    abi: &'static Abi,
+    network: Network,
+}
+
+impl ContractClient {
+    pub fn new(address: &str, abi: ContractAbi, network: Network) -> Self {
+        ContractClient {
+            address: address.to_string(),
+            abi: abi.abi(),
+            network,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Selector and calldata of the call, fails if arguments or
+    /// the result type do not match the function in the ABI
+    pub fn encode_call(
+        &self,
+        function: &str,
+        args: &[&dyn CairoArg],
+        result_type: &str,
+    ) -> Result<(String, Vec<String>), ContractError> {
+        let abi_function = self
+            .abi
+            .function(function)
+            .ok_or_else(|| ContractError::UnknownFunction(function.to_string()))?;
+
+        if abi_function.inputs.len() != args.len() {
+            return Err(ContractError::ArgumentCount(
+                function.to_string(),
+                abi_function.inputs.len(),
+                args.len(),
+            ));
+        }
+
+        let expected_result = abi_function.output.as_deref().unwrap_or("()");
+        if expected_result != result_type {
+            return Err(ContractError::ResultType(
+                function.to_string(),
+                expected_result.to_string(),
+                result_type.to_string(),
+            ));
+        }
+
+        let mut calldata = vec![];
+
+        for (input, arg) in abi_function.inputs.iter().zip(args) {
+            if input.cairo_type != arg.arg_type() {
+                return Err(ContractError::ArgumentType(
+                    function.to_string(),
+                    input.name.clone(),
+                    input.cairo_type.clone(),
+                    arg.arg_type(),
+                ));
+            }
+            arg.encode_arg(&mut calldata);
+        }
+
+        Ok((
+            abi_function.selector.clone(),
+            calldata.iter().map(felt_to_hex).collect(),
+        ))
+    }
+
+    pub async fn call<R: CairoSerde>(
+        &self,
+        function: &str,
+        args: &[&dyn CairoArg],
+        block: BlockTag,
+    ) -> Result<R, ContractError> {
+        let (entry_point_selector, calldata) =
+            self.encode_call(function, args, &R::cairo_type())?;
+
+        let data = call(
+            self.address.clone(),
+            entry_point_selector,
+            calldata,
+            block,
+            &self.network,
+        )
+        .await
+        .map_err(ContractError::Rpc)?;
+
+        parse_felts(&data)
+            .and_then(|felts| decode_all::<R>(&felts))
+            .map_err(|e| ContractError::Decode(function.to_string(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use carmine_api_core::network::Network;
+    use carmine_api_core::selectors::selector;
+
+    use super::{ContractAbi, ContractClient, ContractError};
+    use crate::cairo_serde::{CairoSerde, ContractAddress, Fixed, U256};
+
+    #[test]
+    fn bundled_abis_parse() {
+        for abi in [
+            ContractAbi::Amm,
+            ContractAbi::Pail,
+            ContractAbi::Governance,
+            ContractAbi::Pragma,
+            ContractAbi::PragmaSummaryStats,
+            ContractAbi::Erc20,
+        ] {
+            abi.abi();
+        }
+
+        let registry = ContractAbi::Pail.selector_registry();
+        assert_eq!(
+            registry.name("0x2e770a5d835a0fc0bf1f36b9b91399b8216f234b49647eea957ce5808318568"),
+            Some("HedgeOpened")
+        );
+    }
+
+    #[test]
+    fn encodes_call_from_abi() {
+        let client = ContractClient::new("0x1", ContractAbi::Amm, Network::Mainnet);
+        let pool = ContractAddress::from_hex("0x123").unwrap();
+
+        let (entry_point_selector, calldata) = client
+            .encode_call(
+                "get_option_token_address",
+                &[&pool, &1u8, &1704412799u64, &Fixed::from_mag(0x1a)],
+                &ContractAddress::cairo_type(),
+            )
+            .unwrap();
+
+        assert_eq!(entry_point_selector, selector("get_option_token_address"));
+        assert_eq!(calldata, vec!["0x123", "0x1", "0x6597467f", "0x1a", "0x0"]);
+    }
+
+    #[test]
+    fn rejects_calls_not_matching_abi() {
+        let client = ContractClient::new("0x1", ContractAbi::Amm, Network::Mainnet);
+        let pool = ContractAddress::from_hex("0x123").unwrap();
+
+        assert!(matches!(
+            client.encode_call("get_nothing", &[], "()"),
+            Err(ContractError::UnknownFunction(_))
+        ));
+        assert!(matches!(
+            client.encode_call("get_lpool_balance", &[], &U256::cairo_type()),
+            Err(ContractError::ArgumentCount(_, 1, 0))
+        ));
+        assert!(matches!(
+            client.encode_call("get_lpool_balance", &[&1u8], &U256::cairo_type()),
+            Err(ContractError::ArgumentType(..))
+        ));
+        assert!(matches!(
+            client.encode_call("get_lpool_balance", &[&pool], &u128::cairo_type()),
+            Err(ContractError::ResultType(..))
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use carmine_api_core::{
+    network::{protocol_address, Network, Protocol},
+    selectors::SelectorRegistry,
+    types::{IngestionStream, Proposal, StarkScanEventSettled, VotingPower},
+};
+use carmine_api_db::{
+    get_checkpoint, get_proposals, get_protocol_events_from_block, get_votes, get_voting_power,
+    set_proposal_total_supply, store_proposal_events, store_voting_power, DbError,
+};
+use carmine_api_rpc_gateway::BlockTag;
+use futures::future::join_all;
+
+use crate::cairo_serde::{
+    event_felts, felt_to_hex, CairoSerde, ContractAddress, DecodeError, FeltReader, U256,
+};
+use crate::contract::{address_arg, ContractAbi, ContractClient, ContractError};
+
+/// Carmine governance token has 18 decimals
+const GOVERNANCE_TOKEN_DECIMALS: i32 = 18;
+
+/// Balances requested from the node at once
+const BALANCE_BATCH_SIZE: usize = 20;
+
+/// Governance events decoded into proposals, named as their selectors are computed
+const PROPOSAL_EVENTS: [&str; 2] = ["Proposed", "Upgraded"];
+
+fn proposal_event_name(selector: &str) -> Option<&'static str> {
+    static REGISTRY: OnceLock<SelectorRegistry> = OnceLock::new();
+    let registry = REGISTRY.get_or_init(|| SelectorRegistry::from_names(&PROPOSAL_EVENTS));
+
+    let event_name = registry.name(selector)?;
+    PROPOSAL_EVENTS.into_iter().find(|name| *name == event_name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProposalEvent {
+    Created(Proposal),
+    /// (prop_id, block_number, timestamp)
+    Executed(i64, i64, i64),
+}
+
+/// prop_id is a felt252 counting from 1
+fn prop_id(fields: &mut FeltReader) -> Result<i64, DecodeError> {
+    let id = u64::decode(fields)?;
+    i64::try_from(id).map_err(|_| DecodeError::OutOfRange(id.to_string(), "prop_id".to_string()))
+}
+
+/// Decodes proposal creation and execution, None for other governance events.
+/// Events are matched by their selector, the first key
+///
+/// Proposed { prop_id: felt252, payload: felt252, to_upgrade: u64 }
+/// Upgraded { prop_id: felt252, upgrade_type: u64 }
+pub fn decode_proposal_event(
+    event: &StarkScanEventSettled,
+) -> Result<Option<ProposalEvent>, DecodeError> {
+    let name = match event.keys.first().and_then(|key| proposal_event_name(key)) {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let felts = event_felts(&event.keys, &event.data)?;
+    let mut fields = FeltReader::new(&felts);
+
+    let decoded = match name {
+        "Proposed" => {
+            let prop_id = prop_id(&mut fields)?;
+            let payload = fields.next("payload")?;
+            let to_upgrade = u64::decode(&mut fields)?;
+            Some(ProposalEvent::Created(Proposal {
+                prop_id,
+                payload: felt_to_hex(&payload),
+                to_upgrade: to_upgrade as i64,
+                title: None,
+                description: None,
+                block_number: event.block_number,
+                timestamp: event.timestamp,
+                transaction_hash: event.transaction_hash.to_string(),
+                executed_block_number: None,
+                executed_timestamp: None,
+                total_supply: None,
+            }))
+        }
+        "Upgraded" => Some(ProposalEvent::Executed(
+            prop_id(&mut fields)?,
+            event.block_number,
+            event.timestamp,
+        )),
+        _ => None,
+    };
+
+    Ok(decoded)
+}
+
+/// Decodes stored governance events since the checkpoint into proposals,
+/// returns number of new proposals
+pub fn update_proposals(network: &Network) -> Result<usize, DbError> {
+    // the checkpoint block is read again, late events of it may have been stored since
+    let from_block = get_checkpoint(&IngestionStream::Proposals, network)?
+        .map(|checkpoint| checkpoint.last_block)
+        .unwrap_or(0);
+
+    let mut events =
+        get_protocol_events_from_block(network, &Protocol::CarmineGovernance, from_block - 1)?;
+    events.sort_by_key(|event| (event.block_number, event.event_index));
+
+    let mut created = vec![];
+    let mut executed = vec![];
+    for event in events.iter() {
+        match decode_proposal_event(event) {
+            Ok(Some(ProposalEvent::Created(proposal))) => created.push(proposal),
+            Ok(Some(ProposalEvent::Executed(prop_id, block_number, timestamp))) => {
+                executed.push((prop_id, block_number, timestamp))
+            }
+            Ok(None) => {}
+            Err(e) => println!("Failed decoding governance event {}: {}", event.id, e),
+        }
+    }
+
+    let last_block = events
+        .iter()
+        .map(|event| event.block_number)
+        .max()
+        .unwrap_or(from_block)
+        .max(from_block);
+
+    store_proposal_events(&created, &executed, last_block, network)
+}
+
+fn token_amount(value: &U256) -> f64 {
+    value.to_f64() / 10f64.powi(GOVERNANCE_TOKEN_DECIMALS)
+}
+
+async fn balance_at(
+    token: &ContractClient,
+    user_address: &str,
+    block: i64,
+) -> Result<f64, ContractError> {
+    token
+        .call::<U256>(
+            "balance_of",
+            &[&address_arg(user_address)?],
+            BlockTag::Number(block),
+        )
+        .await
+        .map(|balance| token_amount(&balance))
+}
+
+/// Fetches voting power of the voters and token supply at the snapshot block
+/// of each proposal, the block it was submitted in. Values at a past block
+/// never change, so only those not stored yet are requested, failed calls
+/// are retried on the next run. Returns number of new voting power rows
+pub async fn update_voting_power(network: &Network) -> Result<usize, DbError> {
+    let proposals = get_proposals(network)?;
+    let votes = get_votes()?;
+    let stored: HashSet<(i64, String)> = get_voting_power(network)?
+        .into_iter()
+        .map(|power| (power.prop_id, power.user_address))
+        .collect();
+
+    let governance = ContractClient::new(
+        protocol_address(network, &Protocol::CarmineGovernance),
+        ContractAbi::Governance,
+        *network,
+    );
+    let token_address = match governance
+        .call::<ContractAddress>("get_governance_token_address", &[], BlockTag::Latest)
+        .await
+    {
+        Ok(address) => address.to_string(),
+        Err(e) => {
+            println!("Failed getting governance token address: {}", e);
+            return Ok(0);
+        }
+    };
+    let token = ContractClient::new(&token_address, ContractAbi::Erc20, *network);
+
+    let mut powers = vec![];
+
+    for proposal in proposals.iter() {
+        let block = proposal.block_number;
+
+        if proposal.total_supply.is_none() {
+            match token
+                .call::<U256>("total_supply", &[], BlockTag::Number(block))
+                .await
+            {
+                Ok(supply) => {
+                    set_proposal_total_supply(proposal.prop_id, token_amount(&supply), network)?;
+                }
+                Err(e) => println!(
+                    "Failed getting supply for proposal {}: {}",
+                    proposal.prop_id, e
+                ),
+            }
+        }
+
+        let mut missing: Vec<&str> = votes
+            .iter()
+            .filter(|vote| vote.prop_id as i64 == proposal.prop_id)
+            .map(|vote| vote.user_address.as_str())
+            .filter(|address| !stored.contains(&(proposal.prop_id, address.to_string())))
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        for batch in missing.chunks(BALANCE_BATCH_SIZE) {
+            let balances = join_all(
+                batch
+                    .iter()
+                    .map(|address| balance_at(&token, address, block)),
+            )
+            .await;
+
+            for (address, balance) in batch.iter().zip(balances) {
+                match balance {
+                    Ok(voting_power) => powers.push(VotingPower {
+                        prop_id: proposal.prop_id,
+                        user_address: address.to_string(),
+                        voting_power,
+                        block_number: block,
+                    }),
+                    Err(e) => println!(
+                        "Failed getting voting power of {} for proposal {}: {}",
+                        address, proposal.prop_id, e
+                    ),
+                }
+            }
+        }
+    }
+
+    store_voting_power(&powers, network)
+}
+
+#[cfg(test)]
+mod tests {
+    use carmine_api_core::selectors::selector;
+
+    use super::{decode_proposal_event, ProposalEvent};
+    use crate::test_utils::{settled_event, BLOCK_NUMBER, TIMESTAMP};
+
+    #[test]
+    fn decodes_proposal_events() {
+        let proposed_selector = selector("Proposed");
+        let proposed = settled_event(
+            "governance::contract::Governance::Proposed",
+            vec![&proposed_selector],
+            vec!["0x2a", "0x4d2", "0x1"],
+        );
+        match decode_proposal_event(&proposed).unwrap() {
+            Some(ProposalEvent::Created(proposal)) => {
+                assert_eq!(proposal.prop_id, 42);
+                assert_eq!(proposal.payload, "0x4d2");
+                assert_eq!(proposal.to_upgrade, 1);
+                assert_eq!(proposal.title, None);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let upgraded_selector = selector("Upgraded");
+        let upgraded = settled_event("Upgraded", vec![&upgraded_selector], vec!["0x2a", "0x1"]);
+        assert_eq!(
+            decode_proposal_event(&upgraded).unwrap(),
+            Some(ProposalEvent::Executed(42, BLOCK_NUMBER, TIMESTAMP))
+        );
+
+        // matched by the selector, not by the name
+        let other = settled_event("Upgraded", vec!["0xabc"], vec!["0x123"]);
+        assert_eq!(decode_proposal_event(&other).unwrap(), None);
+
+        let truncated = settled_event("Proposed", vec![&proposed_selector], vec!["0x2a"]);
+        assert!(decode_proposal_event(&truncated).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use carmine_api_core::{
+    lending::{
+        debt_token_market, lending_contract, market_decimals, zklend_market, ContractKind,
+        LendingAction, LendingProtocol, LENDING_PROTOCOLS,
+    },
+    network::{protocol_address, Network, Protocol},
+    types::{IngestionStream, LendingEvent, StarkScanEventSettled, UnparsedReason},
+    utils::normalize_address,
+};
+use carmine_api_db::{
+    get_checkpoint, get_protocol_events_from_block, get_unparsed_events, store_lending_events,
+    store_reprocessed_lending_events, DbError,
+};
+
+use crate::cairo_serde::{
+    event_felts, felt_to_hex, CairoSerde, ContractAddress, DecodeError, FeltReader, U256,
+};
+use crate::starkscan::unparsed_settled_event;
+
+// zkLend rates are scaled by 10^27, Nostra rates by 10^18
+const ZKLEND_RATE_SCALE: f64 = 1e27;
+const NOSTRA_RATE_SCALE: f64 = 1e18;
+
+struct Decoded {
+    market: &'static str,
+    action: LendingAction,
+    user: Option<ContractAddress>,
+    /// raw token units
+    amount: Option<f64>,
+    rates: Option<(f64, f64)>,
+}
+
+impl Decoded {
+    fn flow(
+        market: &'static str,
+        action: LendingAction,
+        user: ContractAddress,
+        amount: f64,
+    ) -> Self {
+        Decoded {
+            market,
+            action,
+            user: Some(user),
+            amount: Some(amount),
+            rates: None,
+        }
+    }
+
+    fn rates(market: &'static str, lending_rate: f64, borrowing_rate: f64) -> Self {
+        Decoded {
+            market,
+            action: LendingAction::RateUpdate,
+            user: None,
+            amount: None,
+            rates: Some((lending_rate, borrowing_rate)),
+        }
+    }
+}
+
+/// zkLend market events, all fields are felts
+fn decode_zklend(name: &str, fields: &mut FeltReader) -> Result<Option<Decoded>, DecodeError> {
+    let flow =
+        |action: LendingAction, user: ContractAddress, token: ContractAddress, amount: u128| {
+            zklend_market(&token.to_string())
+                .map(|market| Decoded::flow(market, action, user, amount as f64))
+        };
+
+    let decoded = match name {
+        "Deposit" | "Withdrawal" => {
+            let user = ContractAddress::decode(fields)?;
+            let token = ContractAddress::decode(fields)?;
+            let face_amount = u128::decode(fields)?;
+            let action = match name {
+                "Deposit" => LendingAction::Deposit,
+                _ => LendingAction::Withdraw,
+            };
+            flow(action, user, token, face_amount)
+        }
+        "Borrowing" => {
+            let user = ContractAddress::decode(fields)?;
+            let token = ContractAddress::decode(fields)?;
+            let _raw_amount = u128::decode(fields)?;
+            let face_amount = u128::decode(fields)?;
+            flow(LendingAction::Borrow, user, token, face_amount)
+        }
+        "Repayment" => {
+            let _repayer = ContractAddress::decode(fields)?;
+            let beneficiary = ContractAddress::decode(fields)?;
+            let token = ContractAddress::decode(fields)?;
+            let _raw_amount = u128::decode(fields)?;
+            let face_amount = u128::decode(fields)?;
+            flow(LendingAction::Repay, beneficiary, token, face_amount)
+        }
+        // seized collateral moves zTokens to the liquidator, supply does not change
+        "Liquidation" => {
+            let _liquidator = ContractAddress::decode(fields)?;
+            let user = ContractAddress::decode(fields)?;
+            let debt_token = ContractAddress::decode(fields)?;
+            let _debt_raw_amount = u128::decode(fields)?;
+            let debt_face_amount = u128::decode(fields)?;
+            flow(
+                LendingAction::Liquidation,
+                user,
+                debt_token,
+                debt_face_amount,
+            )
+        }
+        "InterestRatesSync" => {
+            let token = ContractAddress::decode(fields)?;
+            let lending_rate = u128::decode(fields)? as f64 / ZKLEND_RATE_SCALE;
+            let borrowing_rate = u128::decode(fields)? as f64 / ZKLEND_RATE_SCALE;
+            zklend_market(&token.to_string())
+                .map(|market| Decoded::rates(market, lending_rate, borrowing_rate))
+        }
+        _ => None,
+    };
+
+    Ok(decoded)
+}
+
+/// Nostra tokens emit Mint and Burn, the interest model emits the rates of every market.
+/// Liquidations burn the debt token like repays and are stored as repays
+fn decode_nostra(
+    lending_protocol: LendingProtocol,
+    kind: ContractKind,
+    name: &str,
+    fields: &mut FeltReader,
+) -> Result<Option<Decoded>, DecodeError> {
+    let decoded = match (kind, name) {
+        (ContractKind::SupplyToken(market) | ContractKind::DebtToken(market), "Mint" | "Burn") => {
+            let user = ContractAddress::decode(fields)?;
+            let amount = U256::decode(fields)?;
+            let action = match (kind, name) {
+                (ContractKind::SupplyToken(_), "Mint") => LendingAction::Deposit,
+                (ContractKind::SupplyToken(_), _) => LendingAction::Withdraw,
+                (_, "Mint") => LendingAction::Borrow,
+                _ => LendingAction::Repay,
+            };
+            Some(Decoded::flow(market, action, user, amount.to_f64()))
+        }
+        (ContractKind::InterestModel, "InterestStateUpdated") => {
+            let debt_token = ContractAddress::decode(fields)?;
+            let lending_rate = U256::decode(fields)?.to_f64() / NOSTRA_RATE_SCALE;
+            let borrowing_rate = U256::decode(fields)?.to_f64() / NOSTRA_RATE_SCALE;
+            debt_token_market(lending_protocol, &debt_token.to_string())
+                .map(|market| Decoded::rates(market, lending_rate, borrowing_rate))
+        }
+        _ => None,
+    };
+
+    Ok(decoded)
+}
+
+/// Hashstack rTokens and dTokens are ERC-4626 vaults, the diamond
+/// deposits into the dToken on borrow and withdraws on repay.
+/// Rates and liquidations are only emitted by the diamond, which is not
+/// decoded - Hashstack markets have no rates and liquidations are repays
+fn decode_hashstack(
+    kind: ContractKind,
+    name: &str,
+    fields: &mut FeltReader,
+) -> Result<Option<Decoded>, DecodeError> {
+    let (market, action) = match (kind, name) {
+        (ContractKind::SupplyToken(market), "Deposit") => (market, LendingAction::Deposit),
+        (ContractKind::SupplyToken(market), "Withdraw") => (market, LendingAction::Withdraw),
+        (ContractKind::DebtToken(market), "Deposit") => (market, LendingAction::Borrow),
+        (ContractKind::DebtToken(market), "Withdraw") => (market, LendingAction::Repay),
+        _ => return Ok(None),
+    };
+
+    let _sender = ContractAddress::decode(fields)?;
+    if name == "Withdraw" {
+        let _receiver = ContractAddress::decode(fields)?;
+    }
+    let owner = ContractAddress::decode(fields)?;
+    let assets = U256::decode(fields)?;
+
+    Ok(Some(Decoded::flow(market, action, owner, assets.to_f64())))
+}
+
+/// Decodes event of the lending contract, None for events that are not
+/// lending actions (eg. Transfer) or belong to an unknown market
+pub fn decode_lending_event(
+    event: &StarkScanEventSettled,
+    protocol: &Protocol,
+) -> Result<Option<LendingEvent>, DecodeError> {
+    let (lending_protocol, kind) = match lending_contract(protocol) {
+        Some(contract) => contract,
+        None => return Ok(None),
+    };
+
+    // Cairo 1 events are named by their path, eg. "zklend::market::Market::Deposit"
+    let name = event.key_name.rsplit("::").next().unwrap_or_default();
+
+    let felts = event_felts(&event.keys, &event.data)?;
+    let mut fields = FeltReader::new(&felts);
+
+    let decoded = match lending_protocol {
+        LendingProtocol::ZkLend => decode_zklend(name, &mut fields)?,
+        LendingProtocol::NostraAlpha | LendingProtocol::NostraMainnet => {
+            decode_nostra(lending_protocol, kind, name, &mut fields)?
+        }
+        LendingProtocol::Hashstack => decode_hashstack(kind, name, &mut fields)?,
+    };
+
+    let decoded = match decoded {
+        Some(decoded) => decoded,
+        None => return Ok(None),
+    };
+
+    let decimals = market_decimals(decoded.market).unwrap_or(18);
+
+    Ok(Some(LendingEvent {
+        id: event.id.to_string(),
+        protocol: lending_protocol.id().to_string(),
+        market: decoded.market.to_string(),
+        action: decoded.action.as_str().to_string(),
+        user_address: decoded.user.map(|user| felt_to_hex(&user.0)),
+        amount: decoded
+            .amount
+            .map(|amount| amount / 10f64.powi(decimals as i32)),
+        lending_rate: decoded.rates.map(|(lending_rate, _)| lending_rate),
+        borrowing_rate: decoded.rates.map(|(_, borrowing_rate)| borrowing_rate),
+        block_number: event.block_number,
+        event_index: event.event_index,
+        timestamp: event.timestamp,
+        transaction_hash: event.transaction_hash.to_string(),
+    }))
+}
+
+/// Decodes stored events of the lending contract since its checkpoint,
+/// returns number of inserted lending events. Events that fail to decode
+/// are stored in raw_events_unparsed with the checkpoint
+pub fn update_lending_events(network: &Network, protocol: &Protocol) -> Result<usize, DbError> {
+    let stream = IngestionStream::LendingEvents(*protocol);
+
+    // the checkpoint block is read again, late events of it may have been stored since
+    let from_block = get_checkpoint(&stream, network)?
+        .map(|checkpoint| checkpoint.last_block)
+        .unwrap_or(0);
+
+    let events = get_protocol_events_from_block(network, protocol, from_block - 1)?;
+
+    let mut lending_events = vec![];
+    let mut unparsed = vec![];
+    for event in events.iter() {
+        match decode_lending_event(event, protocol) {
+            Ok(Some(lending_event)) => lending_events.push(lending_event),
+            Ok(None) => {}
+            Err(e) => {
+                println!("Failed decoding {} event {}: {}", protocol, event.id, e);
+                unparsed.push(unparsed_settled_event(event, UnparsedReason::LendingDecode));
+            }
+        }
+    }
+
+    let last_block = events
+        .iter()
+        .map(|event| event.block_number)
+        .max()
+        .unwrap_or(from_block)
+        .max(from_block);
+
+    store_lending_events(&lending_events, &unparsed, protocol, last_block, network)
+}
+
+/// Lending contract the event was emitted by
+fn lending_protocol_of(network: &Network, from_address: &str) -> Option<Protocol> {
+    let from_address = normalize_address(from_address);
+    LENDING_PROTOCOLS
+        .into_iter()
+        .find(|protocol| normalize_address(protocol_address(network, protocol)) == from_address)
+}
+
+/// Decodes unparsed lending events again, the ones that decode now are
+/// stored and marked as reprocessed, returns number of reprocessed events
+pub fn reprocess_unparsed_lending_events(network: &Network) -> Result<usize, DbError> {
+    let mut lending_events = vec![];
+    let mut reprocessed_ids = vec![];
+
+    for unparsed in get_unparsed_events(network)? {
+        if unparsed.reason != UnparsedReason::LendingDecode.as_str() {
+            continue;
+        }
+        let protocol = match lending_protocol_of(network, &unparsed.from_address) {
+            Some(protocol) => protocol,
+            None => continue,
+        };
+        let event = StarkScanEventSettled {
+            id: unparsed.id.to_string(),
+            block_hash: unparsed.block_hash.to_string(),
+            block_number: unparsed.block_number,
+            transaction_hash: unparsed.transaction_hash.to_string(),
+            event_index: unparsed.event_index,
+            from_address: unparsed.from_address.to_string(),
+            keys: unparsed.keys.to_vec(),
+            data: unparsed.data.to_vec(),
+            timestamp: unparsed.timestamp,
+            key_name: unparsed.key_name.clone().unwrap_or_default(),
+        };
+        if let Ok(decoded) = decode_lending_event(&event, &protocol) {
+            lending_events.extend(decoded);
+            reprocessed_ids.push(unparsed.id);
+        }
+    }
+
+    if reprocessed_ids.is_empty() {
+        return Ok(0);
+    }
+
+    store_reprocessed_lending_events(&lending_events, &reprocessed_ids, network)?;
+
+    Ok(reprocessed_ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use carmine_api_core::network::{protocol_address, Network, Protocol};
+
+    use super::{decode_lending_event, lending_protocol_of};
+    use crate::test_utils::settled_event;
+
+    #[test]
+    fn decodes_zklend_market_events() {
+        let usdc = "0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8";
+
+        let borrowing = settled_event(
+            "zklend::market::Market::Borrowing",
+            vec!["0xabc"],
+            vec!["0x123", usdc, "0x1", "0x1e8480"],
+        );
+        let decoded = decode_lending_event(&borrowing, &Protocol::ZkLendMarket)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.protocol, "zklend");
+        assert_eq!(decoded.market, "usdc");
+        assert_eq!(decoded.action, "borrow");
+        assert_eq!(decoded.user_address.as_deref(), Some("0x123"));
+        assert_eq!(decoded.amount, Some(2.0));
+
+        // 5 % and 8 % scaled by 10^27
+        let rates = settled_event(
+            "InterestRatesSync",
+            vec!["0xabc"],
+            vec![usdc, "0x295be96e64066972000000", "0x422ca8b0a00a4250000000"],
+        );
+        let decoded = decode_lending_event(&rates, &Protocol::ZkLendMarket)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.action, "rate_update");
+        assert_eq!(decoded.user_address, None);
+        assert!((decoded.lending_rate.unwrap() - 0.05).abs() < 1e-12);
+        assert!((decoded.borrowing_rate.unwrap() - 0.08).abs() < 1e-12);
+
+        let unknown_token = settled_event("Deposit", vec!["0xabc"], vec!["0x123", "0x456", "0x1"]);
+        assert_eq!(
+            decode_lending_event(&unknown_token, &Protocol::ZkLendMarket).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn decodes_token_events() {
+        // 1.5 ETH burned from the debt token
+        let burn = settled_event(
+            "Burn",
+            vec!["0xabc"],
+            vec!["0x123", "0x14d1120d7b160000", "0x0"],
+        );
+        let decoded = decode_lending_event(&burn, &Protocol::NostraMainnetETHDebt)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.protocol, "nostra-mainnet");
+        assert_eq!(decoded.action, "repay");
+        assert_eq!(decoded.amount, Some(1.5));
+
+        // ERC-4626 Deposit with sender and owner as keys, 3 USDT
+        let deposit = settled_event(
+            "hashstack::rToken::Deposit",
+            vec!["0xabc", "0x1", "0x123"],
+            vec!["0x2dc6c0", "0x0", "0x2dc6c0", "0x0"],
+        );
+        let decoded = decode_lending_event(&deposit, &Protocol::HashstackUSDTRToken)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.action, "deposit");
+        assert_eq!(decoded.user_address.as_deref(), Some("0x123"));
+        assert_eq!(decoded.amount, Some(3.0));
+
+        let transfer = settled_event("Transfer", vec!["0xabc"], vec!["0x1", "0x2", "0x3", "0x0"]);
+        assert_eq!(
+            decode_lending_event(&transfer, &Protocol::HashstackUSDTRToken).unwrap(),
+            None
+        );
+        assert!(decode_lending_event(
+            &settled_event("Mint", vec!["0xabc"], vec!["0x123"]),
+            &Protocol::NostraAlphaUSDC
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn finds_contract_of_unparsed_event() {
+        let network = &Network::Mainnet;
+        let address = protocol_address(network, &Protocol::HashstackUSDTDToken);
+        assert_eq!(
+            lending_protocol_of(network, address),
+            Some(Protocol::HashstackUSDTDToken)
+        );
+        assert_eq!(lending_protocol_of(network, "0x123"), None);
+    }
+}
@@ -1,38 +1,425 @@
-use std::collections::VecDeque;
+use std::collections::BTreeMap;
+use std::{env, fs};
 
-use carmine_api_core::types::{DbBlock, OracleName, OraclePrice, TokenPair};
-use carmine_api_rpc_gateway::{mainnet_call, BlockTag};
+use async_trait::async_trait;
+use carmine_api_core::network::Network;
+use carmine_api_core::types::{DbBlock, OracleName, OraclePrice, TokenPair, PRIMARY_ORACLE};
+use carmine_api_rpc_gateway::BlockTag;
+use serde::Deserialize;
+use starknet::core::types::FieldElement;
+use starknet::core::utils::cairo_short_string_to_felt;
 
+use crate::cairo_serde::{CairoSerde, DecodeError, FeltReader};
+use crate::cairo_struct;
+use crate::contract::{ContractAbi, ContractClient};
+
+/// pragma_lib::types::DataType
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataType {
+    SpotEntry(FieldElement),
+    FutureEntry(FieldElement, u64),
+    GenericEntry(FieldElement),
+}
+
+impl CairoSerde for DataType {
+    fn cairo_type() -> String {
+        "pragma_lib::types::DataType".to_string()
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        match self {
+            DataType::SpotEntry(pair_id) => {
+                0u8.encode(calldata);
+                pair_id.encode(calldata);
+            }
+            DataType::FutureEntry(pair_id, expiration) => {
+                1u8.encode(calldata);
+                pair_id.encode(calldata);
+                expiration.encode(calldata);
+            }
+            DataType::GenericEntry(key) => {
+                2u8.encode(calldata);
+                key.encode(calldata);
+            }
+        }
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        match u8::decode(reader)? {
+            0 => Ok(DataType::SpotEntry(FieldElement::decode(reader)?)),
+            1 => Ok(DataType::FutureEntry(
+                FieldElement::decode(reader)?,
+                u64::decode(reader)?,
+            )),
+            2 => Ok(DataType::GenericEntry(FieldElement::decode(reader)?)),
+            index => Err(DecodeError::UnknownVariant(
+                Self::cairo_type(),
+                index as u64,
+            )),
+        }
+    }
+}
+
+/// pragma_lib::types::PragmaPricesResponse
+#[derive(Debug, Clone, PartialEq)]
+pub struct PragmaPricesResponse {
+    pub price: u128,
+    pub decimals: u32,
+    pub last_updated_timestamp: u64,
+    pub num_sources_aggregated: u32,
+    pub expiration_timestamp: Option<u64>,
+}
+
+cairo_struct!(PragmaPricesResponse, "pragma_lib::types::PragmaPricesResponse", {
+    price,
+    decimals,
+    last_updated_timestamp,
+    num_sources_aggregated,
+    expiration_timestamp,
+});
+
+/// pragma_lib::types::AggregationMode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregationMode {
+    Median,
+    Mean,
+}
+
+impl CairoSerde for AggregationMode {
+    fn cairo_type() -> String {
+        "pragma_lib::types::AggregationMode".to_string()
+    }
+
+    fn encode(&self, calldata: &mut Vec<FieldElement>) {
+        match self {
+            AggregationMode::Median => 0u8.encode(calldata),
+            AggregationMode::Mean => 1u8.encode(calldata),
+        }
+    }
+
+    fn decode(reader: &mut FeltReader) -> Result<Self, DecodeError> {
+        match u8::decode(reader)? {
+            0 => Ok(AggregationMode::Median),
+            1 => Ok(AggregationMode::Mean),
+            index => Err(DecodeError::UnknownVariant(
+                Self::cairo_type(),
+                index as u64,
+            )),
+        }
+    }
+}
+
+/// Oracle contract that reports prices of token pairs at a block
+#[async_trait]
+pub trait OracleSource: Send + Sync {
+    /// Stored in the oracle_name column
+    fn name(&self) -> &str;
+
+    /// Token pairs this source is configured for, eg. "eth-usdc"
+    fn pairs(&self) -> Vec<String>;
+
+    /// Whether a failed price fails the whole block
+    fn required(&self) -> bool;
+
+    async fn get_price(&self, token_pair: &str, block: &DbBlock) -> Result<OraclePrice, String>;
+}
+
+/// Token pair id -> oracle specific pair id
+pub type PairMapping = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OracleSourceConfig {
+    PragmaSpotMedian {
+        name: String,
+        address: String,
+        pairs: PairMapping,
+        #[serde(default = "default_required")]
+        required: bool,
+    },
+    PragmaTwap {
+        name: String,
+        address: String,
+        window_secs: u64,
+        pairs: PairMapping,
+        #[serde(default = "default_required")]
+        required: bool,
+    },
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct OracleConfig {
+    pub sources: Vec<OracleSourceConfig>,
+}
+
+impl OracleConfig {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid oracle config: {}", e))
+    }
+
+    /// Config from the file in ORACLE_CONFIG, bundled oracles.json if not set
+    pub fn load() -> Result<Self, String> {
+        match env::var("ORACLE_CONFIG") {
+            Ok(path) => {
+                let json = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed reading {}: {}", path, e))?;
+                OracleConfig::parse(&json)
+            }
+            Err(_) => OracleConfig::parse(include_str!("../oracles.json")),
+        }
+    }
+
+    pub fn sources(&self) -> Result<Vec<Box<dyn OracleSource>>, String> {
+        self.sources
+            .iter()
+            .map(|config| -> Result<Box<dyn OracleSource>, String> {
+                match config {
+                    OracleSourceConfig::PragmaSpotMedian {
+                        name,
+                        address,
+                        pairs,
+                        required,
+                    } => Ok(Box::new(PragmaSpotMedian::new(
+                        name, address, pairs, *required,
+                    )?)),
+                    OracleSourceConfig::PragmaTwap {
+                        name,
+                        address,
+                        window_secs,
+                        pairs,
+                        required,
+                    } => Ok(Box::new(PragmaTwap::new(
+                        name,
+                        address,
+                        *window_secs,
+                        pairs,
+                        *required,
+                    )?)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Pair ids are short strings like "ETH/USD"
+fn pair_ids(pairs: &PairMapping) -> Result<BTreeMap<String, FieldElement>, String> {
+    pairs
+        .iter()
+        .map(|(token_pair, pair_id)| {
+            cairo_short_string_to_felt(pair_id)
+                .map(|felt| (token_pair.to_string(), felt))
+                .map_err(|_| format!("Invalid pair id {} for {}", pair_id, token_pair))
+        })
+        .collect()
+}
+
+fn oracle_price(
+    name: &str,
+    token_pair: &str,
+    block_number: i64,
+    price: u128,
+    decimals: u32,
+    last_updated_timestamp: u64,
+    num_sources_aggregated: u32,
+) -> Result<OraclePrice, String> {
+    let out_of_range = |field: &str| format!("Oracle {} {} out of range", name, field);
+
+    Ok(OraclePrice {
+        id: format!("{}_{}_{}", block_number, token_pair, name),
+        token_pair: token_pair.to_string(),
+        price: i64::try_from(price).map_err(|_| out_of_range("price"))?,
+        decimals: i16::try_from(decimals).map_err(|_| out_of_range("decimals"))?,
+        last_updated_timestamp: i64::try_from(last_updated_timestamp)
+            .map_err(|_| out_of_range("timestamp"))?,
+        num_sources_aggregated: i16::try_from(num_sources_aggregated)
+            .map_err(|_| out_of_range("sources"))?,
+        block_number,
+        oracle_name: name.to_string(),
+    })
+}
+
+/// Median of spot prices aggregated by the Pragma oracle
+pub struct PragmaSpotMedian {
+    name: String,
+    client: ContractClient,
+    pair_ids: BTreeMap<String, FieldElement>,
+    required: bool,
+}
+
+impl PragmaSpotMedian {
+    pub fn new(
+        name: &str,
+        address: &str,
+        pairs: &PairMapping,
+        required: bool,
+    ) -> Result<Self, String> {
+        Ok(PragmaSpotMedian {
+            name: name.to_string(),
+            client: ContractClient::new(address, ContractAbi::Pragma, Network::Mainnet),
+            pair_ids: pair_ids(pairs)?,
+            required,
+        })
+    }
+}
+
+#[async_trait]
+impl OracleSource for PragmaSpotMedian {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn pairs(&self) -> Vec<String> {
+        self.pair_ids.keys().cloned().collect()
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    async fn get_price(&self, token_pair: &str, block: &DbBlock) -> Result<OraclePrice, String> {
+        let pair_id = self
+            .pair_ids
+            .get(token_pair)
+            .ok_or_else(|| format!("{} has no pair id for {}", self.name, token_pair))?;
+
+        let response = self
+            .client
+            .call::<PragmaPricesResponse>(
+                "get_data_median",
+                &[&DataType::SpotEntry(*pair_id)],
+                BlockTag::Number(block.block_number),
+            )
+            .await
+            .map_err(|e| format!("Unexpected oracle call result {}", e))?;
+
+        oracle_price(
+            &self.name,
+            token_pair,
+            block.block_number,
+            response.price,
+            response.decimals,
+            response.last_updated_timestamp,
+            response.num_sources_aggregated,
+        )
+    }
+}
+
+/// Time weighted average of the Pragma spot median over a window ending at the block
+pub struct PragmaTwap {
+    name: String,
+    client: ContractClient,
+    window_secs: u64,
+    pair_ids: BTreeMap<String, FieldElement>,
+    required: bool,
+}
+
+impl PragmaTwap {
+    pub fn new(
+        name: &str,
+        address: &str,
+        window_secs: u64,
+        pairs: &PairMapping,
+        required: bool,
+    ) -> Result<Self, String> {
+        Ok(PragmaTwap {
+            name: name.to_string(),
+            client: ContractClient::new(address, ContractAbi::PragmaSummaryStats, Network::Mainnet),
+            window_secs,
+            pair_ids: pair_ids(pairs)?,
+            required,
+        })
+    }
+}
+
+#[async_trait]
+impl OracleSource for PragmaTwap {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn pairs(&self) -> Vec<String> {
+        self.pair_ids.keys().cloned().collect()
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    async fn get_price(&self, token_pair: &str, block: &DbBlock) -> Result<OraclePrice, String> {
+        let pair_id = self
+            .pair_ids
+            .get(token_pair)
+            .ok_or_else(|| format!("{} has no pair id for {}", self.name, token_pair))?;
+        let block_timestamp = u64::try_from(block.timestamp)
+            .map_err(|_| format!("Invalid block timestamp {}", block.timestamp))?;
+        let start_time = block_timestamp.saturating_sub(self.window_secs);
+
+        let (price, decimals) = self
+            .client
+            .call::<(u128, u32)>(
+                "calculate_twap",
+                &[
+                    &DataType::SpotEntry(*pair_id),
+                    &AggregationMode::Median,
+                    &self.window_secs,
+                    &start_time,
+                ],
+                BlockTag::Number(block.block_number),
+            )
+            .await
+            .map_err(|e| format!("Unexpected oracle call result {}", e))?;
+
+        // TWAP has no update time or source count, the window ends at the block
+        oracle_price(
+            &self.name,
+            token_pair,
+            block.block_number,
+            price,
+            decimals,
+            block_timestamp,
+            0,
+        )
+    }
+}
+
+/// Single Pragma spot median source from the oracle config, for tools
+/// that work with one price per pair
 pub struct Oracle {
-    name: OracleName,
-    oracle_name: &'static str,
-    oracle_address: &'static str,
+    source: PragmaSpotMedian,
 }
 
 impl Oracle {
     pub fn new(oracle: OracleName) -> Self {
-        let (oracle_address, oracle_name) = match &oracle {
-            OracleName::Pragma => (
-                "0x02a85bd616f912537c50a49a4076db02c00b29b2cdc8a197ce92ed1837fa875b",
-                "pragma",
-            ),
+        let name = match oracle {
+            OracleName::Pragma => PRIMARY_ORACLE,
         };
-        let name = oracle;
 
-        Oracle {
-            name,
-            oracle_name,
-            oracle_address,
-        }
-    }
+        let config = OracleConfig::load().expect("Failed loading oracle config");
+        let source = config
+            .sources
+            .iter()
+            .find_map(|source| match source {
+                OracleSourceConfig::PragmaSpotMedian {
+                    name: source_name,
+                    address,
+                    pairs,
+                    required,
+                } if source_name == name => Some(PragmaSpotMedian::new(
+                    source_name,
+                    address,
+                    pairs,
+                    *required,
+                )),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("Oracle config has no {} spot median source", name))
+            .expect("Invalid oracle config");
 
-    fn oracle_specific_token_pair_id(&self, token_pair: &TokenPair) -> String {
-        match (&self.name, token_pair) {
-            (OracleName::Pragma, TokenPair::EthUsdc) => "0x4554482f555344".to_string(),
-            (OracleName::Pragma, TokenPair::BtcUsdc) => "0x4254432f555344".to_string(),
-            (OracleName::Pragma, TokenPair::StrkUsdc) => "0x5354524b2f555344".to_string(),
-            (OracleName::Pragma, TokenPair::EkuboUsdc) => "0x454b55424f2f555344".to_string(),
-        }
+        Oracle { source }
     }
 
     pub async fn get_spot_median(
@@ -40,57 +427,68 @@ impl Oracle {
         token_pair: &TokenPair,
         block: &DbBlock,
     ) -> Result<OraclePrice, String> {
-        let entrypoint =
-            "0x24b869ce68dd257b370701ca16e4aaf9c6483ff6805d04ba7661f3a0b6ce59".to_string(); // get_data_median
-        let block_number = block.block_number;
-        let block_tag = BlockTag::Number(block_number);
-        let calldata = vec![
-            "0".to_string(), // enum variation
-            self.oracle_specific_token_pair_id(&token_pair),
-        ];
-
-        let res = mainnet_call(
-            self.oracle_address.to_owned(),
-            entrypoint,
-            calldata,
-            block_tag,
+        self.source.get_price(&token_pair.id(), block).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pair_ids, DataType, OracleConfig, OracleSourceConfig, PragmaPricesResponse};
+    use crate::cairo_serde::{decode_all, felt_to_hex, parse_felts, CairoSerde};
+
+    #[test]
+    fn bundled_config_builds_sources() {
+        let config = OracleConfig::parse(include_str!("../oracles.json")).unwrap();
+        let sources = config.sources().unwrap();
+
+        let names: Vec<&str> = sources.iter().map(|source| source.name()).collect();
+        assert_eq!(names, vec!["pragma", "pragma_twap"]);
+        assert!(sources[0].required());
+        assert!(!sources[1].required());
+        assert!(sources[0].pairs().contains(&"ekubo-usdc".to_string()));
+    }
+
+    #[test]
+    fn encodes_pair_ids() {
+        let config = OracleConfig::parse(
+            r#"{"sources": [{"kind": "pragma_spot_median", "name": "pragma",
+                "address": "0x1", "pairs": {"ekubo-usdc": "EKUBO/USD"}}]}"#,
         )
-        .await;
-
-        let err_msg = format!("Unexpected oracle call result {:?}", res);
-        if let Ok(data) = res {
-            if data.len() < 4 {
-                return Err(format!(
-                    "Incorrect number of fields in Oracle response: {:?}",
-                    data
-                ));
+        .unwrap();
+        let pairs = match &config.sources[0] {
+            OracleSourceConfig::PragmaSpotMedian {
+                pairs, required, ..
+            } => {
+                assert!(required);
+                pair_ids(pairs).unwrap()
             }
+            _ => panic!("Expected spot median source"),
+        };
 
-            let stripped_data: VecDeque<&str> =
-                data.iter().map(|s| s.trim_start_matches("0x")).collect();
-            let mut deque = VecDeque::from(stripped_data);
-
-            let price = i64::from_str_radix(deque.pop_front().unwrap(), 16)
-                .expect("Failed parsing oracle price");
-            let decimals = i16::from_str_radix(deque.pop_front().unwrap(), 16)
-                .expect("Failed parsing oracle decimals");
-            let last_updated_timestamp = i64::from_str_radix(deque.pop_front().unwrap(), 16)
-                .expect("Failed parsing oracle timestamp");
-            let num_sources_aggregated = i16::from_str_radix(deque.pop_front().unwrap(), 16)
-                .expect("Failed parsing oracle sources");
-            let id = format!("{}_{}_{}", block_number, token_pair, self.oracle_name);
-
-            return Ok(OraclePrice {
-                token_pair: token_pair.id(),
-                id,
-                price,
-                decimals,
-                last_updated_timestamp,
-                num_sources_aggregated,
-                block_number,
-                oracle_name: self.oracle_name.to_string(),
-            });
-        }
-        return Err(err_msg);
+        let mut calldata = vec![];
+        DataType::SpotEntry(pairs["ekubo-usdc"]).encode(&mut calldata);
+
+        let calldata: Vec<String> = calldata.iter().map(felt_to_hex).collect();
+        assert_eq!(calldata, vec!["0x0", "0x454b55424f2f555344"]);
+    }
+
+    #[test]
+    fn decodes_prices_response() {
+        let data: Vec<String> = ["0x3c3f1e5c5a", "0x8", "0x6707a9d4", "0x5", "0x1"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+
+        let response: PragmaPricesResponse = decode_all(&parse_felts(&data).unwrap()).unwrap();
+        assert_eq!(
+            response,
+            PragmaPricesResponse {
+                price: 0x3c3f1e5c5a,
+                decimals: 8,
+                last_updated_timestamp: 0x6707a9d4,
+                num_sources_aggregated: 5,
+                expiration_timestamp: None,
+            }
+        );
     }
 }
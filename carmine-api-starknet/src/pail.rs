@@ -0,0 +1,164 @@
+use carmine_api_core::{
+    network::Network,
+    pail::{HedgeEvent, HedgeFinalized, HedgeOpened},
+    pool::token_by_address,
+    types::StarkScanEventSettled,
+};
+
+use crate::cairo_serde::{
+    event_felts, felt_to_hex, CairoSerde, ContractAddress, DecodeError, FeltReader, Fixed, U256,
+};
+
+/// Hedge tokens are ERC-721 ids, the API serves them as u64
+fn token_id(fields: &mut FeltReader) -> Result<u64, DecodeError> {
+    let id = U256::decode(fields)?;
+    match (id.high, u64::try_from(id.low)) {
+        (0, Ok(id)) => Ok(id),
+        _ => Err(DecodeError::OutOfRange(
+            id.to_hex(),
+            "hedge_token_id".to_string(),
+        )),
+    }
+}
+
+fn finalized(
+    event: &StarkScanEventSettled,
+    user: ContractAddress,
+    fields: &mut FeltReader,
+) -> Result<HedgeFinalized, DecodeError> {
+    Ok(HedgeFinalized {
+        token_id: token_id(fields)?,
+        user: felt_to_hex(&user.0),
+        block_number: event.block_number,
+        timestamp: event.timestamp,
+        transaction_hash: event.transaction_hash.to_string(),
+    })
+}
+
+/// Decodes event of the Pail contract, None for other events
+///
+/// HedgeOpened { #[key] user, hedge_token_id: u256, amount: u256, quote_token,
+///     base_token, maturity: u64, at_price: Fixed }
+/// HedgeClosed / HedgeSettled { #[key] user, hedge_token_id: u256 }
+pub fn decode_hedge_event(
+    event: &StarkScanEventSettled,
+) -> Result<Option<HedgeEvent>, DecodeError> {
+    let felts = event_felts(&event.keys, &event.data)?;
+    let mut fields = FeltReader::new(&felts);
+
+    let decoded = match event.key_name.as_str() {
+        "hedge_open" => {
+            let user = ContractAddress::decode(&mut fields)?;
+            let token_id = token_id(&mut fields)?;
+            let amount = U256::decode(&mut fields)?;
+            let quote_token = ContractAddress::decode(&mut fields)?;
+            let base_token = ContractAddress::decode(&mut fields)?;
+            let maturity = u64::decode(&mut fields)?;
+            let at_price = Fixed::decode(&mut fields)?;
+
+            // amount is in base token units
+            let decimals = token_by_address(&Network::Mainnet, &base_token.to_string())
+                .map(|token| token.decimals)
+                .unwrap_or(18);
+
+            Some(HedgeEvent::Opened(HedgeOpened {
+                token_id,
+                user: felt_to_hex(&user.0),
+                amount: amount.to_f64() / 10f64.powi(decimals as i32),
+                quote_token: felt_to_hex(&quote_token.0),
+                base_token: felt_to_hex(&base_token.0),
+                maturity: maturity as i64,
+                at_price: at_price.to_f64(),
+                block_number: event.block_number,
+                timestamp: event.timestamp,
+                transaction_hash: event.transaction_hash.to_string(),
+            }))
+        }
+        "hedge_close" => {
+            let user = ContractAddress::decode(&mut fields)?;
+            Some(HedgeEvent::Closed(finalized(event, user, &mut fields)?))
+        }
+        "hedge_settle" => {
+            let user = ContractAddress::decode(&mut fields)?;
+            Some(HedgeEvent::Settled(finalized(event, user, &mut fields)?))
+        }
+        _ => None,
+    };
+
+    Ok(decoded)
+}
+
+/// Decodes all Pail events, failures are logged and skipped
+pub fn decode_hedge_events(events: &[StarkScanEventSettled]) -> Vec<HedgeEvent> {
+    events
+        .iter()
+        .filter_map(|event| match decode_hedge_event(event) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                println!("Failed decoding Pail event {}: {}", event.id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use carmine_api_core::pail::HedgeEvent;
+
+    use super::decode_hedge_event;
+    use crate::test_utils::settled_event;
+
+    #[test]
+    fn decodes_hedge_events() {
+        let open = settled_event(
+            "hedge_open",
+            vec!["0xabc", "0x0123"],
+            vec![
+                "0x7",
+                "0x0",
+                // 1.5 ETH
+                "0x14d1120d7b160000",
+                "0x0",
+                "0x53c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8",
+                "0x49d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7",
+                "0x6789f8c0",
+                // 2500 * 2**64
+                "0x9c40000000000000000",
+                "0x0",
+            ],
+        );
+        match decode_hedge_event(&open).unwrap() {
+            Some(HedgeEvent::Opened(hedge)) => {
+                assert_eq!(hedge.token_id, 7);
+                assert_eq!(hedge.user, "0x123");
+                assert_eq!(hedge.amount, 1.5);
+                assert_eq!(hedge.maturity, 0x6789f8c0);
+                assert_eq!(hedge.at_price, 2500.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match decode_hedge_event(&settled_event(
+            "hedge_settle",
+            vec!["0xabc", "0x0123"],
+            vec!["0x7", "0x0"],
+        ))
+        .unwrap()
+        {
+            Some(HedgeEvent::Settled(hedge)) => assert_eq!(hedge.token_id, 7),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(decode_hedge_event(&settled_event(
+            "hedge_close",
+            vec!["0xabc", "0x0123"],
+            vec!["0x7", "0x1"]
+        ))
+        .is_err());
+        assert_eq!(
+            decode_hedge_event(&settled_event("Upgraded", vec!["0xabc", "0x0123"], vec![]))
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -1,14 +1,20 @@
-use std::{cmp::min, env, time::Duration};
+use std::{cmp::min, env, sync::OnceLock, time::Duration};
 
 use async_recursion::async_recursion;
 use carmine_api_core::{
     network::{protocol_address, starkscan_base_url, Network, Protocol, MAINNET_CONTRACT_ADDRESS},
     pool::get_all_pools,
+    selectors::SelectorRegistry,
     telegram_bot,
-    types::{Event, StarkScanEvent, StarkScanEventResult, StarkScanEventSettled},
+    types::{
+        Event, IngestionStream, StarkScanEvent, StarkScanEventResult, StarkScanEventSettled,
+        UnparsedEvent, UnparsedReason,
+    },
 };
 use carmine_api_db::{
-    create_batch_of_events, get_last_block_for_protocol_event, get_last_timestamp_carmine_event,
+    get_checkpoint, get_last_block_for_protocol_event, get_last_timestamp_carmine_event,
+    get_unparsed_events, mark_checkpoint_failed, store_fetched_events, store_protocol_events,
+    store_reprocessed_events, DbError,
 };
 use reqwest::{Client, Error, Response};
 use serde::de::DeserializeOwned;
@@ -76,6 +82,32 @@ impl<'a> StarkscanUrlBuilder<'a> {
 
 const STARKSCAN_REQUESTS_DELAY_IN_MS: u64 = 1500;
 
+// blocks behind the head that Starkscan may not have indexed yet
+const STARKSCAN_INDEXING_LAG_BLOCKS: i64 = 50;
+
+// events without key_name from Starkscan, (event name in the ABI, stored key_name)
+const KNOWN_EVENTS: [(&str, &str); 5] = [
+    // proposals component of the governance, only votes are emitted through it
+    ("ProposalsEvent", "governance::contract::Governance::Voted"),
+    ("Proposed", "governance::contract::Governance::Proposed"),
+    ("HedgeOpened", "hedge_open"),
+    ("HedgeClosed", "hedge_close"),
+    ("HedgeSettled", "hedge_settle"),
+];
+
+fn known_event_key_name(selector: &str) -> Option<String> {
+    static REGISTRY: OnceLock<SelectorRegistry> = OnceLock::new();
+    let registry = REGISTRY.get_or_init(|| {
+        SelectorRegistry::from_names(&KNOWN_EVENTS.map(|(event_name, _)| event_name))
+    });
+
+    let event_name = registry.name(selector)?;
+    KNOWN_EVENTS
+        .iter()
+        .find(|(name, _)| *name == event_name)
+        .map(|(_, key_name)| key_name.to_string())
+}
+
 // list of action names that will be stored
 const ALLOWED_ACTIONS: [&'static str; 5] = [
     "TradeOpen",
@@ -111,44 +143,26 @@ pub async fn events_call(url: &str) -> Result<StarkScanEventResult, Error> {
     api_call_json::<StarkScanEventResult>(url).await
 }
 
-pub fn parse_event(event: StarkScanEvent) -> Option<Event> {
-    // if "key_name" is null or not allowed action (eg "ExpireOptionTokenForPool")
-    // we can't handle the event so we don't store it
-    let action = match event.key_name {
-        Some(action) if ALLOWED_ACTIONS.iter().any(|&v| v == action) => action,
-        _ => {
-            return None;
-        }
-    };
-
-    // block_hash and block_number can sometimes be null, skip if that's the case
-    let block_hash = match event.block_hash {
-        Some(hash) => hash,
-        _ => {
-            return None;
-        }
-    };
-    // block_hash and block_number can sometimes be null, skip if that's the case
-    let block_number = match event.block_number {
-        Some(n) => n,
-        _ => {
-            return None;
-        }
+pub fn parse_settled_event(event: StarkScanEventSettled) -> Result<Event, UnparsedReason> {
+    // if "key_name" is not allowed action (eg "ExpireOptionTokenForPool")
+    // we can't handle the event, it is kept in raw_events_unparsed
+    if !ALLOWED_ACTIONS.iter().any(|&v| v == event.key_name) {
+        return Err(UnparsedReason::DisallowedAction);
     };
 
     // accessing data by index, make sure the length is correct
     if event.data.len() != 6 {
-        return None;
+        return Err(UnparsedReason::UnexpectedDataLength);
     }
 
-    Some(Event {
-        block_hash: block_hash,
-        block_number: block_number,
+    Ok(Event {
+        block_hash: event.block_hash,
+        block_number: event.block_number,
         transaction_hash: event.transaction_hash,
         event_index: event.event_index,
         from_address: event.from_address,
         timestamp: event.timestamp,
-        action: action,
+        action: event.key_name,
         caller: String::from(&event.data[0]),
         token_address: String::from(&event.data[1]),
         capital_transfered: String::from(&event.data[2]),
@@ -156,87 +170,93 @@ pub fn parse_event(event: StarkScanEvent) -> Option<Event> {
     })
 }
 
-// TODO: move Carmine events to Starkscan_events and then this will replace parse_event
-pub fn parse_settled_event(event: StarkScanEventSettled) -> Option<Event> {
-    // if "key_name" is null or not allowed action (eg "ExpireOptionTokenForPool")
-    // we can't handle the event so we don't store it
-    if !ALLOWED_ACTIONS.iter().any(|&v| v == event.key_name) {
-        return None;
-    };
-
-    // accessing data by index, make sure the length is correct
-    if event.data.len() != 6 {
-        return None;
-    }
-
-    Some(Event {
-        block_hash: event.block_hash,
-        block_number: event.block_number,
+fn unparsed_event(
+    event: StarkScanEvent,
+    block_hash: String,
+    block_number: i64,
+    reason: UnparsedReason,
+) -> UnparsedEvent {
+    UnparsedEvent {
+        id: format!("{}_{}", event.transaction_hash, event.event_index),
+        block_hash,
+        block_number,
         transaction_hash: event.transaction_hash,
         event_index: event.event_index,
         from_address: event.from_address,
+        selector: event.keys.first().cloned(),
+        keys: event.keys,
+        data: event.data,
         timestamp: event.timestamp,
-        action: event.key_name,
-        caller: String::from(&event.data[0]),
-        token_address: String::from(&event.data[1]),
-        capital_transfered: String::from(&event.data[2]),
-        tokens_minted: String::from(&event.data[4]),
-    })
+        key_name: event.key_name,
+        reason: reason.as_str().to_string(),
+        reprocessed_at: None,
+    }
 }
 
-fn get_settled_events(event: StarkScanEvent) -> Option<Vec<StarkScanEventSettled>> {
-    if event.block_hash.is_none() || event.block_number.is_none() {
-        return None;
+pub(crate) fn unparsed_settled_event(
+    event: &StarkScanEventSettled,
+    reason: UnparsedReason,
+) -> UnparsedEvent {
+    UnparsedEvent {
+        id: event.id.to_string(),
+        block_hash: event.block_hash.to_string(),
+        block_number: event.block_number,
+        transaction_hash: event.transaction_hash.to_string(),
+        event_index: event.event_index,
+        from_address: event.from_address.to_string(),
+        keys: event.keys.to_vec(),
+        data: event.data.to_vec(),
+        timestamp: event.timestamp,
+        key_name: Some(event.key_name.to_string()),
+        selector: event.keys.first().cloned(),
+        reason: reason.as_str().to_string(),
+        reprocessed_at: None,
     }
-    if event.key_name.is_some() {
+}
+
+/// Events that can't be decoded are returned as Err to be stored in raw_events_unparsed
+pub fn get_settled_events(
+    event: StarkScanEvent,
+) -> Result<Vec<StarkScanEventSettled>, UnparsedEvent> {
+    // pending events have no block yet, they are fetched again once accepted
+    let (block_hash, block_number) = match (&event.block_hash, event.block_number) {
+        (Some(hash), Some(number)) => (hash.to_string(), number),
+        _ => return Ok(vec![]),
+    };
+    if let Some(key_name) = &event.key_name {
         // regular event, return vec size 1
-        return Some(vec![StarkScanEventSettled {
+        return Ok(vec![StarkScanEventSettled {
             id: format!("{}_{}", event.transaction_hash, event.event_index),
-            block_hash: event.block_hash.unwrap(),
-            block_number: event.block_number.unwrap(),
+            block_hash,
+            block_number,
+            key_name: key_name.to_string(),
             transaction_hash: event.transaction_hash,
             event_index: event.event_index,
             from_address: event.from_address,
             keys: event.keys,
             data: event.data,
             timestamp: event.timestamp,
-            key_name: event.key_name.unwrap(),
         }]);
     }
 
-    let event_name_hash_option = &event.keys.get(0);
+    let event_name_option = event
+        .keys
+        .first()
+        .and_then(|selector| known_event_key_name(selector));
 
-    if let Some(event_name_hash) = event_name_hash_option {
-        let event_name_option = match event_name_hash.as_str() {
-            "0x1b5f21c50bf3288fb310446824298a349f0ed9e28fb480cc9a4d54d034652e1" => {
-                Some("governance::contract::Governance::Voted".to_string())
-            }
-            "0x2e770a5d835a0fc0bf1f36b9b91399b8216f234b49647eea957ce5808318568" => {
-                Some("hedge_open".to_string())
-            }
-            "0x13c3f174625b762b261f1b1433ad5bfff7d0cbaecaf4b503c89fce1f23e68a8" => {
-                Some("hedge_close".to_string())
-            }
-            "0x36af3feb5ea00cf41c0a5a0c1f795cd0ee1294433ccefb1916f0eafb4550b53" => {
-                Some("hedge_settle".to_string())
-            }
-            _ => None,
-        };
-
-        if let Some(key_name) = event_name_option {
-            return Some(vec![StarkScanEventSettled {
-                id: format!("{}_{}", event.transaction_hash, event.event_index),
-                block_hash: event.block_hash.unwrap(),
-                block_number: event.block_number.unwrap(),
-                transaction_hash: event.transaction_hash,
-                event_index: event.event_index,
-                from_address: event.from_address,
-                keys: event.keys,
-                data: event.data,
-                timestamp: event.timestamp,
-                key_name,
-            }]);
-        }
+    if let Some(key_name) = event_name_option {
+        return Ok(vec![StarkScanEventSettled {
+            id: format!("{}_{}", event.transaction_hash, event.event_index),
+            block_hash,
+            block_number,
+            transaction_hash: event.transaction_hash,
+            event_index: event.event_index,
+            from_address: event.from_address,
+            keys: event.keys,
+            data: event.data,
+            timestamp: event.timestamp,
+            key_name,
+        }]);
     }
 
     let pools = get_all_pools(&Network::Mainnet);
@@ -246,8 +266,18 @@ fn get_settled_events(event: StarkScanEvent) -> Option<Vec<StarkScanEventSettled
         .iter()
         .find(|&pool| pool.is_address(event.from_address.as_str()));
 
-    if matched_pool.is_none() || event.keys.len() != 4 || event.data.len() < 1 {
-        return None;
+    let reason = if matched_pool.is_none() {
+        Some(UnparsedReason::UnknownSelector)
+    } else if event.keys.len() != 4 {
+        Some(UnparsedReason::UnexpectedKeyCount)
+    } else if event.data.is_empty() {
+        Some(UnparsedReason::UnexpectedDataLength)
+    } else {
+        None
+    };
+
+    if let Some(reason) = reason {
+        return Err(unparsed_event(event, block_hash, block_number, reason));
     }
 
     let pool_address = matched_pool.unwrap().address;
@@ -261,12 +291,10 @@ fn get_settled_events(event: StarkScanEvent) -> Option<Vec<StarkScanEventSettled
 
     if from == &zero || to == &zero {
         // mint or burn
-        return None;
+        return Ok(vec![]);
     }
 
     let base_id = format!("{}_{}", event.transaction_hash, event.event_index);
-    let block_hash = event.block_hash.unwrap();
-    let block_number = event.block_number.unwrap();
     let transaction_hash = event.transaction_hash;
     let timestamp = event.timestamp;
 
@@ -312,20 +340,32 @@ fn get_settled_events(event: StarkScanEvent) -> Option<Vec<StarkScanEventSettled
         key_name: "synthetic::DepositLiquidity".to_string(),
     };
 
-    Some(vec![syntetic_withdraw, syntetic_deposit])
+    Ok(vec![syntetic_withdraw, syntetic_deposit])
+}
+
+/// Events decoded from Starkscan and the ones that failed to decode
+#[derive(Default)]
+pub struct FetchedEvents {
+    pub settled: Vec<StarkScanEventSettled>,
+    pub unparsed: Vec<UnparsedEvent>,
 }
 
 #[async_recursion]
-async fn _fetch_events(url: &str, data: &mut Vec<StarkScanEventSettled>, cutoff_timestamp: i64) {
+async fn _fetch_events(
+    url: &str,
+    data: &mut FetchedEvents,
+    cutoff_timestamp: i64,
+) -> Result<(), String> {
     let starkscan_response = match events_call(url).await {
         Ok(v) => v,
         Err(e) => {
             // request failed, we cannot store partly fetched events, because that
             // would create hole in the data -> throw away incomplete events
-            data.clear();
+            data.settled.clear();
+            data.unparsed.clear();
             println!("Error from StarkScan: {:?}, URL: {}", e, url);
             telegram_bot::send_message("Starkscan events fetching failed").await;
-            return;
+            return Err(format!("Starkscan request failed: {:?}", e));
         }
     };
     let next_url_option = &starkscan_response.next_url;
@@ -342,11 +382,12 @@ async fn _fetch_events(url: &str, data: &mut Vec<StarkScanEventSettled>, cutoff_
     if let Some(response_data) = starkscan_response.data {
         for event in response_data {
             if event.timestamp > cutoff_timestamp {
-                if let Some(settled) = get_settled_events(event) {
-                    data.extend(settled);
+                match get_settled_events(event) {
+                    Ok(settled) => data.settled.extend(settled),
+                    Err(unparsed) => data.unparsed.push(unparsed),
                 }
             } else {
-                return;
+                return Ok(());
             }
         }
     }
@@ -356,62 +397,148 @@ async fn _fetch_events(url: &str, data: &mut Vec<StarkScanEventSettled>, cutoff_
         sleep(Duration::from_millis(STARKSCAN_REQUESTS_DELAY_IN_MS)).await;
         return _fetch_events(next_url, data, cutoff_timestamp).await;
     }
+
+    Ok(())
 }
 
-pub async fn fetch_events(
+/// Fetches events newer than the cutoff, a failed fetch is an error rather than
+/// no new events; the caller stores the settled and unparsed events together
+pub async fn try_fetch_events(
     initial_url: String,
     cutoff_timestamp: i64,
-) -> Vec<StarkScanEventSettled> {
-    let mut data: Vec<StarkScanEventSettled> = vec![];
-    _fetch_events(&initial_url, &mut data, cutoff_timestamp).await;
-    println!("Fetching event, URL: {}, data: {}", initial_url, data.len());
-    data
+) -> Result<FetchedEvents, String> {
+    let mut data = FetchedEvents::default();
+    _fetch_events(&initial_url, &mut data, cutoff_timestamp).await?;
+    println!(
+        "Fetching event, URL: {}, data: {}, unparsed: {}",
+        initial_url,
+        data.settled.len(),
+        data.unparsed.len()
+    );
+    Ok(data)
 }
 
 pub async fn get_events_from_starkscan() {
     // no longer updating events for testnet
     let network = &Network::Mainnet;
     let last_timestamp = match get_last_timestamp_carmine_event(network) {
-        Some(t) => t,
-        None => return,
+        Ok(Some(t)) => t,
+        Ok(None) => return,
+        Err(e) => {
+            println!("Failed getting last Carmine event timestamp: {}", e);
+            return;
+        }
     };
 
     let url = StarkscanUrlBuilder::new(network)
         .protocol(&Protocol::CarmineOptions)
         .get_url();
 
-    let events = fetch_events(url, last_timestamp).await;
-    let parsed_events: Vec<Event> = events
-        .into_iter()
-        .filter_map(|e| parse_settled_event(e))
-        .collect();
+    let fetched = match try_fetch_events(url, last_timestamp).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            println!("Failed fetching events from Starkscan: {}", e);
+            return;
+        }
+    };
+    let mut parsed_events: Vec<Event> = vec![];
+    let mut unparsed = fetched.unparsed;
+    for event in fetched.settled {
+        match parse_settled_event(event.clone()) {
+            Ok(parsed) => parsed_events.push(parsed),
+            Err(reason) => unparsed.push(unparsed_settled_event(&event, reason)),
+        }
+    }
     // update DB
-    create_batch_of_events(&parsed_events, network);
-    println!("Stored {} events from Starkscan", &parsed_events.len());
+    match store_fetched_events(&vec![], &parsed_events, &unparsed, network) {
+        Ok(_) => println!(
+            "Stored {} events and {} unparsed events from Starkscan",
+            parsed_events.len(),
+            unparsed.len()
+        ),
+        Err(e) => println!("Failed storing events from Starkscan: {}", e),
+    }
 }
 
-pub async fn get_protocol_events(
+/// Block the checkpoint moves to after fetching from `from_block`. Starkscan indexes
+/// with a delay, the checkpoint stays behind the head so that late events are
+/// fetched again in the next run, even when a recent event was already returned
+fn next_checkpoint(from_block: i64, last_event_block: Option<i64>, head_block: i64) -> i64 {
+    let indexed_block = head_block - STARKSCAN_INDEXING_LAG_BLOCKS;
+    let last_block = match last_event_block {
+        Some(block) => block.min(indexed_block),
+        // nothing happened in the indexed blocks
+        None => indexed_block,
+    };
+    last_block.max(from_block)
+}
+
+/// Fetches events of the protocol since its checkpoint and stores them
+/// together with the new checkpoint, returns number of inserted events
+pub async fn update_protocol_events(
     network: &Network,
     protocol: &Protocol,
-) -> Vec<StarkScanEventSettled> {
-    let last_block_number: u32 = match get_last_block_for_protocol_event(network, protocol) {
-        Some(t) => t.try_into().expect("Failed parsing block_number -> u32"),
-        None => 0,
+    head_block: i64,
+) -> Result<usize, String> {
+    let stream = IngestionStream::ProtocolEvents(*protocol);
+
+    // protocols without checkpoint resume from their last event
+    let from_block = match get_checkpoint(&stream, network) {
+        Ok(Some(checkpoint)) => checkpoint.last_block,
+        Ok(None) => match get_last_block_for_protocol_event(network, protocol) {
+            Ok(last_block) => last_block.unwrap_or(0),
+            Err(e) => return Err(format!("Failed getting last block for {}: {}", protocol, e)),
+        },
+        // do not refetch everything from the first block
+        Err(e) => return Err(format!("Failed getting checkpoint for {}: {}", protocol, e)),
     };
-    println!("Protocol: {}, last block: {}", protocol, last_block_number);
+    println!("Protocol: {}, last block: {}", protocol, from_block);
+
     let url = StarkscanUrlBuilder::new(&network)
         .protocol(protocol)
-        .from_block(last_block_number)
+        .from_block(
+            from_block
+                .try_into()
+                .expect("Failed parsing block_number -> u32"),
+        )
         .get_url();
-    fetch_events(url, 0).await
+
+    let fetched = match try_fetch_events(url, 0).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            if let Err(db_err) = mark_checkpoint_failed(&stream, from_block, network) {
+                println!("Failed marking {} checkpoint: {}", protocol, db_err);
+            }
+            return Err(e);
+        }
+    };
+
+    let last_event_block = fetched
+        .settled
+        .iter()
+        .map(|e| e.block_number)
+        .chain(fetched.unparsed.iter().map(|e| e.block_number))
+        .max();
+    let last_block = next_checkpoint(from_block, last_event_block, head_block);
+
+    store_protocol_events(
+        &fetched.settled,
+        &fetched.unparsed,
+        protocol,
+        last_block,
+        network,
+    )
+    .map_err(|e| e.to_string())
 }
 
+/// Events of the protocol in the block range, the caller stores
+/// the settled and unparsed events together
 pub async fn get_block_range_events(
     protocol: &Protocol,
     network: &Network,
     from: u32,
     to: u32,
-) -> Vec<StarkScanEventSettled> {
+) -> Result<FetchedEvents, String> {
     // we want to fetch till there is no "next_url"
     let last_timestamp = 0;
     let url = StarkscanUrlBuilder::new(network)
@@ -420,5 +547,121 @@ pub async fn get_block_range_events(
         .to_block(to)
         .get_url();
 
-    fetch_events(url, last_timestamp).await
+    try_fetch_events(url, last_timestamp).await
+}
+
+/// Decodes unparsed events again, the ones that decode now are stored
+/// and marked as reprocessed, returns number of reprocessed events.
+/// Lending events are left to `reprocess_unparsed_lending_events`
+pub fn reprocess_unparsed_events(network: &Network) -> Result<usize, DbError> {
+    let mut settled: Vec<StarkScanEventSettled> = vec![];
+    let mut parsed: Vec<Event> = vec![];
+    let mut reprocessed_ids: Vec<String> = vec![];
+
+    for unparsed in get_unparsed_events(network)? {
+        if unparsed.reason == UnparsedReason::LendingDecode.as_str() {
+            continue;
+        }
+        match &unparsed.key_name {
+            // failed in the events table, the Starkscan event itself is stored
+            Some(key_name) => {
+                let event = StarkScanEventSettled {
+                    id: unparsed.id.to_string(),
+                    block_hash: unparsed.block_hash.to_string(),
+                    block_number: unparsed.block_number,
+                    transaction_hash: unparsed.transaction_hash.to_string(),
+                    event_index: unparsed.event_index,
+                    from_address: unparsed.from_address.to_string(),
+                    keys: unparsed.keys.to_vec(),
+                    data: unparsed.data.to_vec(),
+                    timestamp: unparsed.timestamp,
+                    key_name: key_name.to_string(),
+                };
+                if let Ok(event) = parse_settled_event(event) {
+                    parsed.push(event);
+                    reprocessed_ids.push(unparsed.id);
+                }
+            }
+            None => {
+                if let Ok(events) = get_settled_events(unparsed.to_starkscan_event()) {
+                    settled.extend(events);
+                    reprocessed_ids.push(unparsed.id);
+                }
+            }
+        }
+    }
+
+    if reprocessed_ids.is_empty() {
+        return Ok(0);
+    }
+
+    store_reprocessed_events(&settled, &parsed, &reprocessed_ids, network)?;
+
+    Ok(reprocessed_ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_settled_events, next_checkpoint, parse_settled_event, STARKSCAN_INDEXING_LAG_BLOCKS,
+    };
+    use crate::test_utils::starkscan_event;
+    use carmine_api_core::types::UnparsedReason;
+
+    #[test]
+    fn checkpoint_stays_behind_the_head() {
+        let head = 1000;
+        let indexed = head - STARKSCAN_INDEXING_LAG_BLOCKS;
+        // recent event does not move the checkpoint past the indexed blocks
+        assert_eq!(next_checkpoint(500, Some(head - 1), head), indexed);
+        assert_eq!(next_checkpoint(500, Some(600), head), 600);
+        assert_eq!(next_checkpoint(500, None, head), indexed);
+        // never moves back
+        assert_eq!(next_checkpoint(980, Some(990), head), 980);
+        assert_eq!(next_checkpoint(980, None, head), 980);
+    }
+
+    #[test]
+    fn unknown_selector_is_dead_lettered() {
+        let unparsed =
+            get_settled_events(starkscan_event("0x123", vec!["0xabc"], None)).unwrap_err();
+        assert_eq!(unparsed.selector.as_deref(), Some("0xabc"));
+        assert_eq!(unparsed.reason, UnparsedReason::UnknownSelector.as_str());
+        assert_eq!(unparsed.id, "0x1_0");
+    }
+
+    #[test]
+    fn known_selector_is_named() {
+        let voted = starkscan_event(
+            "0x123",
+            vec!["0x1b5f21c50bf3288fb310446824298a349f0ed9e28fb480cc9a4d54d034652e1"],
+            None,
+        );
+        assert_eq!(
+            get_settled_events(voted).unwrap()[0].key_name,
+            "governance::contract::Governance::Voted"
+        );
+    }
+
+    #[test]
+    fn disallowed_action_is_rejected() {
+        let settled = get_settled_events(starkscan_event(
+            "0x123",
+            vec!["0xabc"],
+            Some("ExpireOptionTokenForPool"),
+        ))
+        .unwrap();
+        assert_eq!(settled.len(), 1);
+        assert_eq!(
+            parse_settled_event(settled[0].clone()).unwrap_err(),
+            UnparsedReason::DisallowedAction
+        );
+    }
+
+    #[test]
+    fn pending_event_is_skipped() {
+        let mut pending = starkscan_event("0x123", vec!["0xabc"], None);
+        pending.block_hash = None;
+        assert!(get_settled_events(pending).unwrap().is_empty());
+    }
 }
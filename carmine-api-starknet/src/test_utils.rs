@@ -0,0 +1,39 @@
+use carmine_api_core::types::{StarkScanEvent, StarkScanEventSettled};
+
+pub const BLOCK_NUMBER: i64 = 600000;
+pub const TIMESTAMP: i64 = 1700000000;
+
+/// Event of transaction 0x1 emitted by 0x2 in block 600000
+pub fn settled_event(key_name: &str, keys: Vec<&str>, data: Vec<&str>) -> StarkScanEventSettled {
+    StarkScanEventSettled {
+        id: "0x1_0".to_string(),
+        block_hash: "0xb1".to_string(),
+        block_number: BLOCK_NUMBER,
+        transaction_hash: "0x1".to_string(),
+        event_index: 0,
+        from_address: "0x2".to_string(),
+        keys: keys.into_iter().map(String::from).collect(),
+        data: data.into_iter().map(String::from).collect(),
+        timestamp: TIMESTAMP,
+        key_name: key_name.to_string(),
+    }
+}
+
+/// Starkscan event of transaction 0x1 in block 600000 with data [0x1]
+pub fn starkscan_event(
+    from_address: &str,
+    keys: Vec<&str>,
+    key_name: Option<&str>,
+) -> StarkScanEvent {
+    StarkScanEvent {
+        block_hash: Some("0xb1".to_string()),
+        block_number: Some(BLOCK_NUMBER),
+        transaction_hash: "0x1".to_string(),
+        event_index: 0,
+        from_address: from_address.to_string(),
+        keys: keys.into_iter().map(String::from).collect(),
+        data: vec!["0x1".to_string()],
+        timestamp: TIMESTAMP,
+        key_name: key_name.map(String::from),
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::HttpRequest;
+use carmine_api_core::{
+    selectors::selector,
+    signature::{
+        check_expiry, parse_felt, parse_signature, payload_hash, verify_auth_signature,
+        AuthMessage, SignatureError,
+    },
+};
+use carmine_api_rpc_gateway::{mainnet_call, BlockTag, RpcError};
+use starknet_crypto::FieldElement;
+
+pub const SIGNATURE_HEADER: &str = "x-starknet-signature";
+pub const NONCE_HEADER: &str = "x-starknet-nonce";
+pub const EXPIRY_HEADER: &str = "x-starknet-expiry";
+
+/// Accounts can rotate their keys, refetch after an hour
+const PUBLIC_KEY_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Accounts without a public key are not looked up again for a minute,
+/// they may get deployed meanwhile
+const FAILED_LOOKUP_TTL: Duration = Duration::from_secs(60);
+
+/// Getters of the signer key - OpenZeppelin and Braavos, Argent, legacy Argent
+const PUBLIC_KEY_ENTRYPOINTS: [&str; 3] = ["get_public_key", "get_owner", "getSigner"];
+
+/// Set by SIGNATURE_AUTH, "optional" until clients sign all requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMode {
+    /// signatures are ignored
+    Off,
+    /// unsigned requests pass, signed ones must be valid
+    Optional,
+    Required,
+}
+
+impl AuthMode {
+    pub fn from_env() -> Self {
+        match env::var("SIGNATURE_AUTH").as_deref() {
+            Ok("off") => AuthMode::Off,
+            Ok("required") => AuthMode::Required,
+            _ => AuthMode::Optional,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingSignature,
+    Signature(SignatureError),
+    /// nonce was already used by the account
+    Replayed,
+    PublicKey(String),
+    /// public key could not be fetched because the node failed
+    Unavailable(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::MissingSignature => write!(
+                f,
+                "missing {}, {} or {} header",
+                SIGNATURE_HEADER, NONCE_HEADER, EXPIRY_HEADER
+            ),
+            AuthError::Signature(e) => write!(f, "{}", e),
+            AuthError::Replayed => write!(f, "nonce was already used"),
+            AuthError::PublicKey(msg) => write!(f, "failed getting public key: {}", msg),
+            AuthError::Unavailable(msg) => write!(f, "public key lookup unavailable: {}", msg),
+        }
+    }
+}
+
+impl From<SignatureError> for AuthError {
+    fn from(e: SignatureError) -> Self {
+        AuthError::Signature(e)
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Account -> value and when it was stored
+type Cache<T> = Mutex<HashMap<FieldElement, (T, Instant)>>;
+
+fn cached<T: Clone>(cache: &Cache<T>, account: &FieldElement, ttl: Duration) -> Option<T> {
+    cache
+        .lock()
+        .expect("public key cache poisoned")
+        .get(account)
+        .filter(|(_, stored)| stored.elapsed() < ttl)
+        .map(|(value, _)| value.clone())
+}
+
+/// Stores the value and drops expired ones, unknown accounts are not kept forever
+fn store<T>(cache: &Cache<T>, account: FieldElement, value: T, ttl: Duration) {
+    let mut cache = cache.lock().expect("public key cache poisoned");
+    cache.retain(|_, (_, stored)| stored.elapsed() < ttl);
+    cache.insert(account, (value, Instant::now()));
+}
+
+/// Verifies SNIP-12 signatures of POST requests, public keys and used nonces are kept in memory
+pub struct SignatureVerifier {
+    pub mode: AuthMode,
+    public_keys: Cache<FieldElement>,
+    /// accounts that are not deployed or have no known public key getter
+    failed_lookups: Cache<String>,
+    /// (account, nonce) -> expiry
+    nonces: Mutex<HashMap<(FieldElement, FieldElement), i64>>,
+}
+
+impl SignatureVerifier {
+    pub fn new(mode: AuthMode) -> Self {
+        SignatureVerifier {
+            mode,
+            public_keys: Mutex::new(HashMap::new()),
+            failed_lookups: Mutex::new(HashMap::new()),
+            nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn fetch_public_key(account: FieldElement) -> Result<FieldElement, AuthError> {
+        let address = format!("{:#x}", account);
+
+        for entrypoint in PUBLIC_KEY_ENTRYPOINTS {
+            match mainnet_call(
+                address.clone(),
+                selector(entrypoint),
+                vec![],
+                BlockTag::Latest,
+            )
+            .await
+            {
+                Ok(result) => match result.first() {
+                    Some(key) => return Ok(parse_felt(key)?),
+                    None => continue,
+                },
+                Err(RpcError::ContractNotFound) => {
+                    return Err(AuthError::PublicKey(format!(
+                        "account {} is not deployed",
+                        address
+                    )))
+                }
+                // entrypoint is not implemented by the account
+                Err(RpcError::EntrypointNotFound | RpcError::ContractError(_)) => continue,
+                // the node failed, the account may still be valid
+                Err(e) => return Err(AuthError::Unavailable(format!("{:?}", e))),
+            }
+        }
+
+        Err(AuthError::PublicKey(format!(
+            "account {} has no known public key getter",
+            address
+        )))
+    }
+
+    async fn public_key(&self, account: FieldElement) -> Result<FieldElement, AuthError> {
+        if let Some(key) = cached(&self.public_keys, &account, PUBLIC_KEY_TTL) {
+            return Ok(key);
+        }
+        if let Some(msg) = cached(&self.failed_lookups, &account, FAILED_LOOKUP_TTL) {
+            return Err(AuthError::PublicKey(msg));
+        }
+
+        match Self::fetch_public_key(account).await {
+            Ok(key) => {
+                store(&self.public_keys, account, key, PUBLIC_KEY_TTL);
+                Ok(key)
+            }
+            Err(AuthError::PublicKey(msg)) => {
+                store(
+                    &self.failed_lookups,
+                    account,
+                    msg.clone(),
+                    FAILED_LOOKUP_TTL,
+                );
+                Err(AuthError::PublicKey(msg))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remembers the nonce until it expires, fails if it is already used
+    fn use_nonce(&self, account: FieldElement, nonce: FieldElement, expiry: i64) -> bool {
+        let now = now();
+        let mut nonces = self.nonces.lock().expect("nonce store poisoned");
+        nonces.retain(|_, nonce_expiry| *nonce_expiry >= now);
+        nonces.insert((account, nonce), expiry).is_none()
+    }
+
+    /// Checks that `account` signed `body` for `action`
+    pub async fn authenticate(
+        &self,
+        req: &HttpRequest,
+        action: &str,
+        account: &str,
+        body: &[u8],
+    ) -> Result<(), AuthError> {
+        if self.mode == AuthMode::Off {
+            return Ok(());
+        }
+
+        let headers = (
+            header(req, SIGNATURE_HEADER),
+            header(req, NONCE_HEADER),
+            header(req, EXPIRY_HEADER),
+        );
+        let (signature, nonce, expiry) = match headers {
+            (Some(signature), Some(nonce), Some(expiry)) => (signature, nonce, expiry),
+            (None, None, None) if self.mode == AuthMode::Optional => return Ok(()),
+            _ => return Err(AuthError::MissingSignature),
+        };
+
+        let message = AuthMessage {
+            action: action.to_string(),
+            payload: payload_hash(body),
+            nonce: parse_felt(nonce)?,
+            expiry: expiry
+                .parse()
+                .map_err(|_| SignatureError::Malformed(format!("invalid expiry \"{}\"", expiry)))?,
+        };
+        let signature = parse_signature(signature)?;
+        let account = parse_felt(account)?;
+
+        // no RPC calls for expired requests
+        check_expiry(message.expiry, now())?;
+        let public_key = self.public_key(account).await?;
+        verify_auth_signature(public_key, account, &message, signature, now())?;
+
+        match self.use_nonce(account, message.nonce, message.expiry) {
+            true => Ok(()),
+            false => Err(AuthError::Replayed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn felt(hex: &str) -> FieldElement {
+        FieldElement::from_hex_be(hex).unwrap()
+    }
+
+    fn request(headers: &[(&'static str, &str)]) -> HttpRequest {
+        headers
+            .iter()
+            .fold(TestRequest::post(), |req, (name, value)| {
+                req.insert_header((*name, value.to_string()))
+            })
+            .to_http_request()
+    }
+
+    async fn authenticate(mode: AuthMode, req: &HttpRequest) -> Result<(), AuthError> {
+        SignatureVerifier::new(mode)
+            .authenticate(req, "referral_event", "0x123", b"{}")
+            .await
+    }
+
+    #[actix_web::test]
+    async fn mode_decides_unsigned_requests() {
+        let unsigned = request(&[]);
+        assert!(authenticate(AuthMode::Off, &unsigned).await.is_ok());
+        assert!(authenticate(AuthMode::Optional, &unsigned).await.is_ok());
+        assert!(matches!(
+            authenticate(AuthMode::Required, &unsigned).await,
+            Err(AuthError::MissingSignature)
+        ));
+
+        // some headers is not the same as unsigned
+        let partial = request(&[(SIGNATURE_HEADER, "0x1,0x2"), (NONCE_HEADER, "0x3")]);
+        assert!(authenticate(AuthMode::Off, &partial).await.is_ok());
+        assert!(matches!(
+            authenticate(AuthMode::Optional, &partial).await,
+            Err(AuthError::MissingSignature)
+        ));
+
+        // expired requests fail before the public key is fetched
+        let expired = request(&[
+            (SIGNATURE_HEADER, "0x1,0x2"),
+            (NONCE_HEADER, "0x3"),
+            (EXPIRY_HEADER, "1"),
+        ]);
+        assert!(matches!(
+            authenticate(AuthMode::Optional, &expired).await,
+            Err(AuthError::Signature(SignatureError::Expired))
+        ));
+    }
+
+    #[test]
+    fn nonces_are_used_once() {
+        let verifier = SignatureVerifier::new(AuthMode::Required);
+        let (account, nonce) = (felt("0x1"), felt("0x2"));
+        let expiry = now() + 60;
+
+        assert!(verifier.use_nonce(account, nonce, expiry));
+        assert!(!verifier.use_nonce(account, nonce, expiry));
+        assert!(verifier.use_nonce(account, felt("0x3"), expiry));
+        assert!(verifier.use_nonce(felt("0x4"), nonce, expiry));
+
+        // expired nonces are forgotten
+        assert!(verifier.use_nonce(account, felt("0x5"), now() - 1));
+        assert!(verifier.use_nonce(account, felt("0x5"), expiry));
+    }
+
+    #[test]
+    fn cached_values_expire() {
+        let cache: Cache<String> = Mutex::new(HashMap::new());
+        let account = felt("0x1");
+
+        store(&cache, account, "missing".to_string(), FAILED_LOOKUP_TTL);
+        assert_eq!(
+            cached(&cache, &account, FAILED_LOOKUP_TTL).as_deref(),
+            Some("missing")
+        );
+        assert_eq!(cached(&cache, &felt("0x2"), FAILED_LOOKUP_TTL), None);
+        assert_eq!(cached(&cache, &account, Duration::ZERO), None);
+
+        // expired values are dropped when another one is stored
+        store(&cache, felt("0x2"), "missing".to_string(), Duration::ZERO);
+        assert_eq!(cache.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn failed_lookups_are_not_repeated() {
+        let verifier = SignatureVerifier::new(AuthMode::Required);
+        let account = felt("0x1");
+        store(
+            &verifier.failed_lookups,
+            account,
+            "account 0x1 is not deployed".to_string(),
+            FAILED_LOOKUP_TTL,
+        );
+
+        assert!(matches!(
+            verifier.public_key(account).await,
+            Err(AuthError::PublicKey(_))
+        ));
+    }
+}
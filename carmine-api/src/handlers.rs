@@ -33,6 +33,7 @@ pub fn config(conf: &mut web::ServiceConfig) {
                         .service(v1::all_legacy_transactions)
                         .service(v1::votes)
                         .service(v1::pail_token)
+                        .service(v1::pail_token_image)
                         .service(v1::pail_events)
                         .service(v1::get_insurance_event_history)
                         .service(v1::get_insurance_users)
@@ -56,7 +57,26 @@ pub fn config(conf: &mut web::ServiceConfig) {
                         .service(v1::proxy_call),
                 )
                 // v2
-                .service(web::scope("/v2").service(v2::pool_apy)),
+                .service(
+                    web::scope("/v2")
+                        .service(v2::pool_apy)
+                        .service(v2::pool_volatility_premium)
+                        .service(v2::price_candles)
+                        .service(v2::price_twap)
+                        .service(v2::price_at)
+                        .service(v2::token_price_sources)
+                        .service(v2::oracle_health)
+                        .service(v2::oracle_pair_health)
+                        .service(v2::lending_market)
+                        .service(v2::proposals)
+                        .service(v2::proposal)
+                        .service(v2::hedges)
+                        .service(v2::hedge_stats)
+                        // before referrals/{address}
+                        .service(v2::referral_leaderboard)
+                        .service(v2::referral_abuse_report)
+                        .service(v2::referrals),
+                ),
         );
 
     conf.service(scope);
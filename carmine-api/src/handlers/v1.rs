@@ -1,4 +1,5 @@
 use crate::{
+    auth::{AuthError, SignatureVerifier},
     handlers::format_tx,
     types::{
         AllNonExpired, AllTradeHistoryResponse, DataResponse, GenericResponse,
@@ -10,17 +11,23 @@ use actix_web::{
     http::header::AcceptEncoding,
     post,
     web::{self},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder,
 };
 use carmine_api_core::{
+    insurance::decode_insurance_calldata,
     network::Network,
-    types::{AppState, InsuranceEvent, NewReferralEvent, PailToken, PoolStateWithTimestamp, Vote},
+    pail::{hedge_svg, hedge_token_metadata},
+    types::{
+        AppState, InsuranceEvent, NewReferralEvent, PoolStateWithTimestamp, StateResolution, Vote,
+    },
+    utils::is_hex_address,
 };
-use carmine_api_db::{create_insurance_event, create_referral_event, get_referral_code};
+use carmine_api_db::{DbError, Store};
 use lazy_static::lazy_static;
 use std::{
     collections::HashSet,
     env,
+    str::FromStr,
     sync::{Arc, Mutex},
 };
 
@@ -32,6 +39,11 @@ lazy_static! {
         env::var("CARMINE_JUNO_NODE_URL").expect("missing env var CARMINE_JUNO_NODE_URL");
     static ref CARMINE_JUNO_TESTNET_NODE_URL: String = env::var("CARMINE_JUNO_TESTNET_NODE_URL")
         .expect("missing env var CARMINE_JUNO_TESTNET_NODE_URL");
+    // where the API is reachable from outside, links in responses are built from it
+    static ref PUBLIC_API_URL: String = env::var("PUBLIC_API_URL")
+        .expect("missing env var PUBLIC_API_URL")
+        .trim_end_matches('/')
+        .to_string();
 }
 
 const TESTNET: &'static str = "testnet";
@@ -276,6 +288,10 @@ pub async fn pool_state(
 ) -> impl Responder {
     let pool_id = path.into_inner();
 
+    if let Some(resolution) = &opts.resolution {
+        return pool_state_history(&pool_id, resolution, opts.from, opts.to, &data);
+    }
+
     let min_block = match &opts.min_block_number {
         Some(block) => block,
         None => {
@@ -348,6 +364,108 @@ pub async fn pool_state(
         })
 }
 
+fn pool_state_history(
+    pool_id: &str,
+    resolution: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+    data: &web::Data<Arc<Mutex<AppState>>>,
+) -> HttpResponse {
+    let resolution = match StateResolution::from_str(resolution) {
+        Ok(v) => v,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "resolution must be one of block, hour, day, week".to_string(),
+            });
+        }
+    };
+
+    let from = from.unwrap_or(0);
+    let to = to.unwrap_or(i64::MAX);
+
+    if from >= to {
+        return HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "to must be greater than from".to_string(),
+        });
+    }
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    if let StateResolution::Block = resolution {
+        let pool_state = match app_state.mainnet.state.get(pool_id) {
+            Some(state) => state,
+            None => {
+                return HttpResponse::BadRequest().json(GenericResponse {
+                    status: "bad_request".to_string(),
+                    message: "Invalid pool".to_string(),
+                });
+            }
+        };
+
+        let filtered_state: Vec<&PoolStateWithTimestamp> = pool_state
+            .iter()
+            .filter(|state| state.timestamp >= from && state.timestamp < to)
+            .collect();
+
+        if filtered_state.len() as i64 > MAX_POOL_STATE_BLOCK_SIZE {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: format!(
+                    "can only retrieve {} blocks at a time, use coarser resolution",
+                    MAX_POOL_STATE_BLOCK_SIZE
+                ),
+            });
+        }
+
+        return HttpResponse::Ok()
+            .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+            .json(DataResponse {
+                status: "success".to_string(),
+                data: filtered_state,
+            });
+    }
+
+    let buckets = match app_state
+        .mainnet
+        .state_rollups
+        .get(pool_id)
+        .and_then(|rollups| rollups.get(&resolution))
+    {
+        Some(buckets) => buckets,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid pool".to_string(),
+            });
+        }
+    };
+
+    let bucket_secs = resolution
+        .seconds()
+        .expect("Block resolution is not bucketed");
+    // buckets are sorted by timestamp ascending, keep those partly after `from`
+    let start = buckets.partition_point(|b| b.timestamp + bucket_secs <= from);
+    let end = buckets.partition_point(|b| b.timestamp < to);
+
+    HttpResponse::Ok()
+        .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+        .json(DataResponse {
+            status: "success".to_string(),
+            data: &buckets[start..end.max(start)],
+        })
+}
+
 #[get("/mainnet/{pool}/state")]
 pub async fn pool_state_last(
     path: web::Path<String>,
@@ -541,7 +659,10 @@ async fn proxy_call(path: web::Path<String>, payload: Option<web::Bytes>) -> imp
 }
 
 #[get("/mainnet/get_referral")]
-pub async fn get_referral(opts: web::Query<QueryOptions>) -> impl Responder {
+pub async fn get_referral(
+    opts: web::Query<QueryOptions>,
+    store: web::Data<dyn Store>,
+) -> impl Responder {
     let address = match &opts.address {
         Some(address) => format_tx(address),
         _ => {
@@ -552,12 +673,19 @@ pub async fn get_referral(opts: web::Query<QueryOptions>) -> impl Responder {
         }
     };
 
-    let referral_code = get_referral_code(address);
-
-    HttpResponse::Ok().json(DataResponse::<String> {
-        status: "success".to_string(),
-        data: referral_code,
-    })
+    match store.get_referral_code(address) {
+        Ok(referral_code) => HttpResponse::Ok().json(DataResponse::<String> {
+            status: "success".to_string(),
+            data: referral_code,
+        }),
+        Err(e) => {
+            println!("Failed getting referral code: {}", e);
+            HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed getting referral code".to_string(),
+            })
+        }
+    }
 }
 
 #[get("/mainnet/user-points")]
@@ -614,8 +742,27 @@ pub async fn get_top_user_points(data: web::Data<Arc<Mutex<AppState>>>) -> impl
     })
 }
 
+fn auth_failed(e: AuthError) -> HttpResponse {
+    match e {
+        // the request may be valid, the client can retry
+        AuthError::Unavailable(_) => HttpResponse::ServiceUnavailable().json(GenericResponse {
+            status: "service_unavailable".to_string(),
+            message: format!("Could not verify signature: {}", e),
+        }),
+        _ => HttpResponse::Unauthorized().json(GenericResponse {
+            status: "unauthorized".to_string(),
+            message: format!("Unauthorized: {}", e),
+        }),
+    }
+}
+
 #[post("/mainnet/referral_event")]
-async fn referral_event(payload: Option<web::Bytes>) -> impl Responder {
+async fn referral_event(
+    req: HttpRequest,
+    payload: Option<web::Bytes>,
+    store: web::Data<dyn Store>,
+    verifier: web::Data<SignatureVerifier>,
+) -> impl Responder {
     let bytes = match payload {
         Some(v) => v,
         None => {
@@ -628,19 +775,38 @@ async fn referral_event(payload: Option<web::Bytes>) -> impl Responder {
 
     match serde_json::from_slice::<NewReferralEvent>(&bytes) {
         Ok(mut event) => {
+            if let Err(e) = verifier
+                .authenticate(
+                    &req,
+                    "referral_event",
+                    event.referred_wallet_address,
+                    &bytes,
+                )
+                .await
+            {
+                return auth_failed(e);
+            }
+
             let unsafe_address = event.referred_wallet_address;
             let safe_address = format_tx(&unsafe_address.to_owned());
             event.referred_wallet_address = &safe_address;
 
-            match create_referral_event(event) {
+            match store.create_referral_event(event) {
                 Ok(_) => HttpResponse::Ok().json(GenericResponse {
                     status: "success".to_string(),
                     message: "Event stored".to_string(),
                 }),
-                Err(_) => HttpResponse::BadRequest().json(GenericResponse {
+                Err(DbError::Query(_)) => HttpResponse::BadRequest().json(GenericResponse {
                     status: "bad_request".to_string(),
                     message: "Referal does not exist".to_string(),
                 }),
+                Err(e) => {
+                    println!("Failed storing referral event: {}", e);
+                    HttpResponse::InternalServerError().json(GenericResponse {
+                        status: "server_error".to_string(),
+                        message: "Failed storing event".to_string(),
+                    })
+                }
             }
         }
         Err(_) => HttpResponse::BadRequest().json(GenericResponse {
@@ -651,7 +817,12 @@ async fn referral_event(payload: Option<web::Bytes>) -> impl Responder {
 }
 
 #[post("/mainnet/insurance-event")]
-async fn insurance_event(payload: Option<web::Bytes>) -> impl Responder {
+async fn insurance_event(
+    req: HttpRequest,
+    payload: Option<web::Bytes>,
+    store: web::Data<dyn Store>,
+    verifier: web::Data<SignatureVerifier>,
+) -> impl Responder {
     let bytes = match payload {
         Some(v) => v,
         None => {
@@ -664,19 +835,45 @@ async fn insurance_event(payload: Option<web::Bytes>) -> impl Responder {
 
     match serde_json::from_slice::<InsuranceEvent>(&bytes) {
         Ok(mut event) => {
+            if !is_hex_address(event.user_address) {
+                return HttpResponse::BadRequest().json(GenericResponse {
+                    status: "bad_request".to_string(),
+                    message: "Invalid user address".to_string(),
+                });
+            }
+            if let Err(e) = decode_insurance_calldata(&event.calldata) {
+                return HttpResponse::BadRequest().json(GenericResponse {
+                    status: "bad_request".to_string(),
+                    message: format!("Invalid calldata: {}", e),
+                });
+            }
+            if let Err(e) = verifier
+                .authenticate(&req, "insurance_event", event.user_address, &bytes)
+                .await
+            {
+                return auth_failed(e);
+            }
+
             let unsafe_address = event.user_address;
             let safe_address = format_tx(&unsafe_address.to_owned());
             event.user_address = &safe_address;
 
-            match create_insurance_event(event) {
+            match store.create_insurance_event(event) {
                 Ok(_) => HttpResponse::Ok().json(GenericResponse {
                     status: "success".to_string(),
                     message: "Event stored".to_string(),
                 }),
-                Err(_) => HttpResponse::BadRequest().json(GenericResponse {
+                Err(DbError::Query(_)) => HttpResponse::BadRequest().json(GenericResponse {
                     status: "bad_request".to_string(),
                     message: "Failed storing event".to_string(),
                 }),
+                Err(e) => {
+                    println!("Failed storing insurance event: {}", e);
+                    HttpResponse::InternalServerError().json(GenericResponse {
+                        status: "server_error".to_string(),
+                        message: "Failed storing event".to_string(),
+                    })
+                }
             }
         }
         Err(_) => HttpResponse::BadRequest().json(GenericResponse {
@@ -921,18 +1118,72 @@ pub async fn braavos_proscore(data: web::Data<Arc<Mutex<AppState>>>) -> impl Res
 #[get("/mainnet/hedge")]
 pub async fn pail_token(
     opts: web::Query<std::collections::HashMap<String, String>>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let token_id = match opts.get("token_id").map(|id| id.parse::<u64>()) {
+        Some(Ok(token_id)) => token_id,
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest().body("Invalid token_id: must be a valid u64")
+        }
+        None => return HttpResponse::BadRequest().body("Missing token_id in query parameters"),
+    };
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    match app_state
+        .mainnet
+        .hedges
+        .iter()
+        .find(|hedge| hedge.token_id == token_id)
+    {
+        Some(hedge) => {
+            let image = format!(
+                "{}/api/v1/mainnet/hedge/{}/image.svg",
+                *PUBLIC_API_URL, token_id
+            );
+            HttpResponse::Ok().json(hedge_token_metadata(hedge, image))
+        }
+        None => HttpResponse::BadRequest().body("Unknown token_id: hedge not indexed yet"),
+    }
+}
+
+#[get("/mainnet/hedge/{token_id}/image.svg")]
+pub async fn pail_token_image(
+    path: web::Path<u64>,
+    data: web::Data<Arc<Mutex<AppState>>>,
 ) -> impl Responder {
-    match opts.get("token_id") {
-        Some(token_id_str) => match token_id_str.parse::<u64>() {
-            Ok(token_id) => HttpResponse::Ok().json(PailToken {
-                name: "PAIL token".to_string(),
-                description: format!("token {}", token_id),
-                token_id,
-                image: "https://app.carmine.finance/logo.png".to_string(),
-            }),
-            Err(_) => HttpResponse::BadRequest().body("Invalid token_id: must be a valid u64"),
-        },
-        None => HttpResponse::BadRequest().body("Missing token_id in query parameters"),
+    let token_id = path.into_inner();
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    match app_state
+        .mainnet
+        .hedges
+        .iter()
+        .find(|hedge| hedge.token_id == token_id)
+    {
+        Some(hedge) => HttpResponse::Ok()
+            .content_type("image/svg+xml")
+            .body(hedge_svg(hedge)),
+        None => HttpResponse::BadRequest().body("Unknown token_id: hedge not indexed yet"),
     }
 }
 
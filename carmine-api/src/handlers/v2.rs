@@ -1,11 +1,23 @@
-use crate::types::{DataResponse, GenericResponse};
+use crate::types::{
+    CandlesQueryOptions, DataResponse, GenericResponse, HedgesQueryOptions,
+    LeaderboardQueryOptions, PaginatedResponse, PriceAtQueryOptions, TimeRangeQueryOptions,
+    TwapQueryOptions, VolatilityPremiumQueryOptions,
+};
 use actix_web::{
     get,
     http::header::AcceptEncoding,
     web::{self},
     HttpResponse, Responder,
 };
-use carmine_api_core::types::AppState;
+use carmine_api_core::{
+    pail::HedgeStatus,
+    types::{
+        AppState, Hedge, LendingMarketStats, PriceCandle, ProposalSummary, ReferrerSummary,
+        VolatilityPremiumSeries,
+    },
+    utils::{canonical_address, is_hex_address},
+};
+use carmine_api_prices::history::{self, CANDLE_INTERVALS};
 use std::sync::{Arc, Mutex};
 
 #[get("/mainnet/{pool}/apy")]
@@ -45,3 +57,599 @@ pub async fn pool_apy(
         }
     }
 }
+
+const DEFAULT_CANDLE_INTERVAL: i64 = 3600;
+const DEFAULT_PAGE_SIZE: usize = 500;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[get("/mainnet/prices/{pair}/candles")]
+pub async fn price_candles(
+    path: web::Path<String>,
+    opts: web::Query<CandlesQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let pair_id = path.into_inner();
+
+    let interval = opts.interval.unwrap_or(DEFAULT_CANDLE_INTERVAL);
+    if !CANDLE_INTERVALS.contains(&interval) {
+        return HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: format!("interval must be one of {:?} seconds", CANDLE_INTERVALS),
+        });
+    }
+
+    let from = opts.from.unwrap_or(0);
+    let to = opts.to.unwrap_or(i64::MAX);
+    if from >= to {
+        return HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "to must be greater than from".to_string(),
+        });
+    }
+
+    let page = opts.page.unwrap_or(0);
+    let page_size = opts.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: format!("page_size must be between 1 and {}", MAX_PAGE_SIZE),
+        });
+    }
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let candles = match app_state
+        .mainnet
+        .price_candles
+        .get(&pair_id)
+        .and_then(|by_interval| by_interval.get(&interval))
+    {
+        Some(candles) => candles,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid token pair".to_string(),
+            });
+        }
+    };
+
+    let all_candles = history::candles_in_range(candles, interval, from, to);
+    let total = all_candles.len();
+    let page_data: Vec<&PriceCandle> = all_candles
+        .iter()
+        .skip(page.saturating_mul(page_size))
+        .take(page_size)
+        .collect();
+
+    HttpResponse::Ok()
+        .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+        .json(PaginatedResponse {
+            status: "success".to_string(),
+            data: page_data,
+            page,
+            page_size,
+            total,
+        })
+}
+
+#[get("/mainnet/prices/{pair}/twap")]
+pub async fn price_twap(
+    path: web::Path<String>,
+    opts: web::Query<TwapQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let pair_id = path.into_inner();
+
+    let (from, to) = match (opts.from, opts.to) {
+        (Some(from), Some(to)) if from < to => (from, to),
+        _ => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "from and to must be specified and to must be greater than from"
+                    .to_string(),
+            });
+        }
+    };
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let series = match app_state.mainnet.price_series.get(&pair_id) {
+        Some(series) => series,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid token pair".to_string(),
+            });
+        }
+    };
+
+    match history::twap(series, from, to) {
+        Some(twap) => HttpResponse::Ok().json(DataResponse {
+            status: "success".to_string(),
+            data: twap,
+        }),
+        None => HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "No prices in the given window".to_string(),
+        }),
+    }
+}
+
+#[get("/mainnet/prices/{pair}/at")]
+pub async fn price_at(
+    path: web::Path<String>,
+    opts: web::Query<PriceAtQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let pair_id = path.into_inner();
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let series = match app_state.mainnet.price_series.get(&pair_id) {
+        Some(series) => series,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid token pair".to_string(),
+            });
+        }
+    };
+
+    let point = match (opts.timestamp, opts.block_number) {
+        (Some(ts), None) => history::price_at_timestamp(series, ts),
+        (None, Some(block_number)) => history::price_at_block(series, block_number),
+        _ => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "specify either timestamp or block_number".to_string(),
+            });
+        }
+    };
+
+    match point {
+        Some(point) => HttpResponse::Ok().json(DataResponse {
+            status: "success".to_string(),
+            data: point,
+        }),
+        None => HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "No price before the given point".to_string(),
+        }),
+    }
+}
+
+#[get("/mainnet/token-prices/sources")]
+pub async fn token_price_sources(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: &app_state.token_price_sources,
+    })
+}
+
+#[get("/mainnet/oracle/health")]
+pub async fn oracle_health(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: &app_state.mainnet.oracle_health,
+    })
+}
+
+#[get("/mainnet/oracle/health/{pair}")]
+pub async fn oracle_pair_health(
+    path: web::Path<String>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let pair = path.into_inner();
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    match app_state
+        .mainnet
+        .oracle_health
+        .iter()
+        .find(|health| health.token_pair == pair)
+    {
+        Some(health) => HttpResponse::Ok().json(DataResponse {
+            status: "success".to_string(),
+            data: health,
+        }),
+        None => HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "Invalid token pair".to_string(),
+        }),
+    }
+}
+
+#[get("/mainnet/{pool}/volatility-premium")]
+pub async fn pool_volatility_premium(
+    path: web::Path<String>,
+    opts: web::Query<VolatilityPremiumQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let pool_id = path.into_inner();
+    let from = opts.from.unwrap_or(i64::MIN);
+    let to = opts.to.unwrap_or(i64::MAX);
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let premium = match app_state.mainnet.volatility_premium.get(&pool_id) {
+        Some(premium) => premium,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid pool".to_string(),
+            });
+        }
+    };
+
+    let series: Vec<VolatilityPremiumSeries> = premium
+        .iter()
+        .filter(|series| opts.maturity.is_none() || opts.maturity == Some(series.maturity))
+        .map(|series| {
+            // points are sorted by block number, timestamps follow
+            let start = series.points.partition_point(|p| p.timestamp < from);
+            let end = series.points.partition_point(|p| p.timestamp <= to);
+            VolatilityPremiumSeries {
+                maturity: series.maturity,
+                points: series.points[start..end.max(start)].to_vec(),
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok()
+        .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+        .json(DataResponse {
+            status: "success".to_string(),
+            data: series,
+        })
+}
+
+#[get("/lending/{protocol}/{market}")]
+pub async fn lending_market(
+    path: web::Path<(String, String)>,
+    opts: web::Query<TimeRangeQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let (protocol, market) = path.into_inner();
+    let from = opts.from.unwrap_or(i64::MIN);
+    let to = opts.to.unwrap_or(i64::MAX);
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let markets = match app_state.mainnet.lending.get(&protocol) {
+        Some(markets) => markets,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid protocol".to_string(),
+            });
+        }
+    };
+
+    let stats = match markets.get(&market.to_lowercase()) {
+        Some(stats) => stats,
+        None => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid market".to_string(),
+            });
+        }
+    };
+
+    // history is sorted by timestamp
+    let start = stats.history.partition_point(|p| p.timestamp < from);
+    let end = stats.history.partition_point(|p| p.timestamp <= to);
+
+    HttpResponse::Ok()
+        .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+        .json(DataResponse {
+            status: "success".to_string(),
+            data: LendingMarketStats {
+                history: stats.history[start..end.max(start)].to_vec(),
+                ..stats.clone()
+            },
+        })
+}
+
+#[get("/mainnet/proposals")]
+pub async fn proposals(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let summaries: Vec<&ProposalSummary> = app_state
+        .mainnet
+        .proposals
+        .iter()
+        .map(|proposal| &proposal.summary)
+        .collect();
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: summaries,
+    })
+}
+
+#[get("/mainnet/proposals/{id}")]
+pub async fn proposal(
+    path: web::Path<i64>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let prop_id = path.into_inner();
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    match app_state
+        .mainnet
+        .proposals
+        .iter()
+        .find(|proposal| proposal.summary.prop_id == prop_id)
+    {
+        Some(proposal) => HttpResponse::Ok().json(DataResponse {
+            status: "success".to_string(),
+            data: proposal,
+        }),
+        None => HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "Invalid proposal".to_string(),
+        }),
+    }
+}
+
+#[get("/mainnet/hedges")]
+pub async fn hedges(
+    opts: web::Query<HedgesQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let status = match opts.status.as_deref().map(HedgeStatus::parse) {
+        Some(None) => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid status, use \"open\", \"closed\" or \"settled\"".to_string(),
+            });
+        }
+        Some(Some(status)) => Some(status.as_str()),
+        None => None,
+    };
+    let address = match opts.address.as_deref() {
+        Some(address) if !is_hex_address(address) => {
+            return HttpResponse::BadRequest().json(GenericResponse {
+                status: "bad_request".to_string(),
+                message: "Invalid address".to_string(),
+            });
+        }
+        address => address.map(canonical_address),
+    };
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let hedges: Vec<&Hedge> = app_state
+        .mainnet
+        .hedges
+        .iter()
+        .filter(|hedge| {
+            address
+                .as_deref()
+                .map_or(true, |address| hedge.user == address)
+        })
+        .filter(|hedge| status.map_or(true, |status| hedge.status == status))
+        .collect();
+
+    HttpResponse::Ok()
+        .insert_header(AcceptEncoding(vec!["gzip".parse().unwrap()]))
+        .json(DataResponse {
+            status: "success".to_string(),
+            data: hedges,
+        })
+}
+
+#[get("/mainnet/hedges/stats")]
+pub async fn hedge_stats(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: &app_state.mainnet.pail_stats,
+    })
+}
+
+#[get("/mainnet/referrals/leaderboard")]
+pub async fn referral_leaderboard(
+    opts: web::Query<LeaderboardQueryOptions>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    let leaderboard: Vec<&ReferrerSummary> = app_state
+        .mainnet
+        .referrers
+        .iter()
+        .take(opts.limit.unwrap_or(usize::MAX))
+        .map(|referrer| &referrer.summary)
+        .collect();
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: leaderboard,
+    })
+}
+
+#[get("/mainnet/referrals/abuse")]
+pub async fn referral_abuse_report(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    HttpResponse::Ok().json(DataResponse {
+        status: "success".to_string(),
+        data: &app_state.mainnet.referral_abuse,
+    })
+}
+
+#[get("/mainnet/referrals/{address}")]
+pub async fn referrals(
+    path: web::Path<String>,
+    data: web::Data<Arc<Mutex<AppState>>>,
+) -> impl Responder {
+    let address = path.into_inner();
+    if !is_hex_address(&address) {
+        return HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "Invalid address".to_string(),
+        });
+    }
+    let address = canonical_address(&address);
+
+    let locked = &data.lock();
+    let app_state = match locked {
+        Ok(app_data) => app_data,
+        _ => {
+            return HttpResponse::InternalServerError().json(GenericResponse {
+                status: "server_error".to_string(),
+                message: "Failed to read AppState".to_string(),
+            });
+        }
+    };
+
+    match app_state
+        .mainnet
+        .referrers
+        .iter()
+        .find(|referrer| referrer.summary.address == address)
+    {
+        Some(referrer) => HttpResponse::Ok().json(DataResponse {
+            status: "success".to_string(),
+            data: referrer,
+        }),
+        None => HttpResponse::BadRequest().json(GenericResponse {
+            status: "bad_request".to_string(),
+            message: "Address has not referred anyone".to_string(),
+        }),
+    }
+}
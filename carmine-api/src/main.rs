@@ -1,3 +1,4 @@
+mod auth;
 mod handlers;
 mod types;
 
@@ -5,11 +6,13 @@ use actix_cors::Cors;
 use actix_web::middleware::{self, Logger};
 use actix_web::web::Data;
 use actix_web::{http::header, App, HttpServer};
+use auth::{AuthMode, SignatureVerifier, EXPIRY_HEADER, NONCE_HEADER, SIGNATURE_HEADER};
 use carmine_api_airdrop::merkle_tree::MerkleTree;
 use carmine_api_cache::Cache;
 use carmine_api_core::network::Network;
 use carmine_api_core::types::{AppState, TokenPrices};
-use carmine_api_core::utils::get_coingecko_prices;
+use carmine_api_db::{prepare_database, PgStore, Store};
+use carmine_api_prices::aggregator::PriceAggregator;
 use dotenvy::dotenv;
 use std::env;
 use std::sync::{Arc, Mutex};
@@ -43,6 +46,7 @@ fn ip_address() -> &'static str {
 fn startup_check() {
     let environment = env::var("ENVIRONMENT").expect("ENV \"ENVIRONMENT\" is not set");
     env::var("STARKSCAN_API_KEY").expect("ENV \"STARKSCAN_API_KEY\" is not set");
+    env::var("PUBLIC_API_URL").expect("ENV \"PUBLIC_API_URL\" is not set");
     if environment.as_str() != "local" {
         // only check those if not connecting to local DB
         env::var("DB_USER").expect("ENV \"DB_USER\" is not set");
@@ -55,6 +59,7 @@ fn startup_check() {
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     startup_check();
+    prepare_database(&Network::Mainnet).expect("Failed preparing DB");
 
     println!("👷 Starting server");
 
@@ -65,12 +70,17 @@ async fn main() -> std::io::Result<()> {
 
     println!("🛠️  Creating cache instances...");
 
-    let mut mainnet_cache = Cache::new(Network::Mainnet).await;
+    // nothing to serve without the initial data
+    let mut mainnet_cache = Cache::new(PgStore::new(Network::Mainnet))
+        .await
+        .expect("Failed creating Mainnet cache");
     // let mut testnet_cache = Cache::new(Network::Testnet).await;
 
     println!("🛠️  Getting data from DB...");
 
-    let mainnet = mainnet_cache.get_app_data();
+    let mainnet = mainnet_cache
+        .get_app_data()
+        .expect("Failed getting Mainnet data");
 
     println!("✨ Got Mainnet data");
 
@@ -84,27 +94,26 @@ async fn main() -> std::io::Result<()> {
 
     println!("🛠️  Creating app state...");
 
-    let token_prices = match get_coingecko_prices().await {
-        Ok(res) => TokenPrices {
-            eth: res.ethereum.usd,
-            usdc: res.usd_coin.usd,
-            strk: res.starknet.usd,
-            btc: res.bitcoin.usd,
-            ekubo: res.ekubo.usd,
-        },
-        Err(e) => {
-            println!("{:#?}", e);
-            panic!("Failed getting gecko prices")
-        }
-    };
+    let price_aggregator = PriceAggregator::default();
+    let aggregated_prices = price_aggregator.fetch().await;
+    // tokens no source could price are served as null until a later update prices them
+    let token_prices = aggregated_prices.token_prices(&TokenPrices::default());
 
     let app_state = Data::new(Arc::new(Mutex::new(AppState {
         mainnet,
         // testnet,
         airdrop,
         token_prices,
+        token_price_sources: aggregated_prices.prices,
     })));
 
+    // store for the handlers writing to the DB
+    let store: Arc<dyn Store> = Arc::new(PgStore::new(Network::Mainnet));
+
+    let signature_mode = AuthMode::from_env();
+    println!("🔏 Signature auth: {:?}", signature_mode);
+    let signature_verifier = Data::new(SignatureVerifier::new(signature_mode));
+
     println!("🛠️  Cloning app state...");
 
     println!("🛠️  Spawning app state updating thread...");
@@ -125,7 +134,13 @@ async fn main() -> std::io::Result<()> {
                 println!("Updating AppState");
                 mainnet_cache.update().await;
                 // testnet_cache.update().await;
-                let mainnet = mainnet_cache.get_app_data();
+                let mainnet = match mainnet_cache.get_app_data() {
+                    Ok(data) => data,
+                    Err(e) => {
+                        println!("Failed getting AppData, keeping the previous: {}", e);
+                        continue;
+                    }
+                };
                 // let testnet = testnet_cache.get_app_data();
 
                 let mut app_state_lock = app_state_clone.lock().unwrap();
@@ -150,28 +165,19 @@ async fn main() -> std::io::Result<()> {
                     sleep(Duration::from_secs(UPDATE_PRICES_INTERVAL)).await;
                 }
 
-                println!("Updating Gecko prices");
-
-                let token_prices_response = match get_coingecko_prices().await {
-                    Ok(res) => Ok(TokenPrices {
-                        eth: res.ethereum.usd,
-                        usdc: res.usd_coin.usd,
-                        strk: res.starknet.usd,
-                        btc: res.bitcoin.usd,
-                        ekubo: res.ekubo.usd,
-                    }),
-                    Err(e) => Err(e),
-                };
+                println!("Updating token prices");
+
+                let aggregated_prices = price_aggregator.fetch().await;
 
                 let mut app_state_lock = app_state_clone.lock().unwrap();
 
-                match token_prices_response {
-                    Ok(token_prices) => {
-                        app_state_lock.token_prices = token_prices;
-                        println!("Gecko prices updated");
-                    }
-                    Err(e) => println!("Failed updating Gecko prices: {:?}", e),
-                };
+                // tokens no source could price keep the previous value
+                app_state_lock.token_prices =
+                    aggregated_prices.token_prices(&app_state_lock.token_prices);
+                app_state_lock
+                    .token_price_sources
+                    .extend(aggregated_prices.prices);
+                println!("Token prices updated");
 
                 drop(app_state_lock);
             }
@@ -191,10 +197,13 @@ async fn main() -> std::io::Result<()> {
             .allowed_methods(vec!["GET", "POST", "OPTIONS"])
             .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
             .allowed_header(header::CONTENT_TYPE)
+            .allowed_headers(vec![SIGNATURE_HEADER, NONCE_HEADER, EXPIRY_HEADER])
             .supports_credentials()
             .max_age(3600);
         App::new()
             .app_data(app_state.clone())
+            .app_data(Data::from(store.clone()))
+            .app_data(signature_verifier.clone())
             .configure(handlers::config)
             .wrap(cors)
             .wrap(Logger::default())
@@ -13,6 +13,15 @@ pub struct DataResponse<T> {
     pub data: T,
 }
 
+#[derive(Serialize, Debug)]
+pub struct PaginatedResponse<T> {
+    pub status: String,
+    pub data: T,
+    pub page: usize,
+    pub page_size: usize,
+    pub total: usize,
+}
+
 #[derive(Serialize, Debug)]
 pub struct AllNonExpired<'a> {
     pub status: String,
@@ -47,4 +56,52 @@ pub struct QueryOptions {
 pub struct PoolStateQueryOptions {
     pub min_block_number: Option<i64>,
     pub max_block_number: Option<i64>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub resolution: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CandlesQueryOptions {
+    pub interval: Option<i64>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TwapQueryOptions {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeRangeQueryOptions {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct VolatilityPremiumQueryOptions {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub maturity: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct PriceAtQueryOptions {
+    pub timestamp: Option<i64>,
+    pub block_number: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardQueryOptions {
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct HedgesQueryOptions {
+    pub address: Option<String>,
+    pub status: Option<String>,
 }